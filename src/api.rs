@@ -0,0 +1,820 @@
+//! Anthropic API 数据结构与错误类型
+//!
+//! 定义消息、请求/响应结构，以及 API 调用失败时的结构化错误，
+//! 便于调用者区分处理。
+
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// ============== API 请求/响应结构 ==============
+
+/// API 请求格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApiFormat {
+    /// Anthropic Messages API
+    #[default]
+    Anthropic,
+    /// OpenAI 兼容的 chat-completions（Ollama、LM Studio、vLLM 等）
+    OpenAi,
+}
+
+impl ApiFormat {
+    /// 从配置值解析，未知值返回 `None`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "anthropic" => Some(ApiFormat::Anthropic),
+            "openai" => Some(ApiFormat::OpenAi),
+            _ => None,
+        }
+    }
+
+    /// 相对于 base_url 的请求路径
+    pub fn endpoint(self) -> &'static str {
+        match self {
+            ApiFormat::Anthropic => "/v1/messages",
+            ApiFormat::OpenAi => "/v1/chat/completions",
+        }
+    }
+}
+
+/// API 密钥放在哪个请求头中
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthScheme {
+    /// `x-api-key: <key>`（Anthropic 格式的默认值）
+    ApiKey,
+    /// `Authorization: Bearer <key>`（OpenAI 格式的默认值，部分代理和网关也要求此形式）
+    Bearer,
+}
+
+impl AuthScheme {
+    /// 从配置值解析，未知值返回 `None`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "x-api-key" => Some(AuthScheme::ApiKey),
+            "bearer" => Some(AuthScheme::Bearer),
+            _ => None,
+        }
+    }
+
+    /// 各 API 格式默认使用的方式
+    pub fn default_for(format: ApiFormat) -> Self {
+        match format {
+            ApiFormat::Anthropic => AuthScheme::ApiKey,
+            ApiFormat::OpenAi => AuthScheme::Bearer,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Message {
+    pub role: String,
+    pub content: MessageContent,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Blocks(Vec<Value>),
+}
+
+#[derive(Serialize)]
+pub struct AnthropicRequest {
+    pub model: String,
+    pub max_tokens: u32,
+    /// 系统提示（未配置时不发送）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<SystemPrompt>,
+    pub messages: Vec<Message>,
+    pub tools: Vec<Value>,
+    /// 扩展思考（未启用时不发送）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+    /// 采样温度（未配置时使用 API 默认值）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// nucleus 采样阈值（未配置时使用 API 默认值）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// 是否使用 SSE 流式响应
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+}
+
+impl AnthropicRequest {
+    /// 启用提示缓存：在系统提示和最后一个工具定义上设置缓存断点
+    ///
+    /// 断点之前的内容（工具定义、系统提示）在后续请求中可以命中缓存。
+    pub fn enable_prompt_caching(&mut self) {
+        if let Some(system) = &mut self.system {
+            system.cached = true;
+        }
+        if let Some(Value::Object(tool)) = self.tools.last_mut() {
+            tool.insert("cache_control".to_string(), cache_control());
+        }
+    }
+}
+
+/// token 计数接口（相对 base_url）
+pub const COUNT_TOKENS_ENDPOINT: &str = "/v1/messages/count_tokens";
+
+/// token 计数请求：与消息请求相同的上下文，不含输出相关的参数
+#[derive(Debug, Serialize)]
+pub struct CountTokensRequest {
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<SystemPrompt>,
+    pub messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+}
+
+/// token 计数响应
+#[derive(Debug, Deserialize)]
+pub struct CountTokensResponse {
+    pub input_tokens: u64,
+}
+
+/// 提示缓存断点
+fn cache_control() -> Value {
+    json!({"type": "ephemeral"})
+}
+
+/// 系统提示
+///
+/// 未启用缓存时序列化为字符串；启用后序列化为带 `cache_control` 的 text block 数组。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemPrompt {
+    pub text: String,
+    pub cached: bool,
+}
+
+impl From<String> for SystemPrompt {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            cached: false,
+        }
+    }
+}
+
+impl Serialize for SystemPrompt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.cached {
+            json!([{"type": "text", "text": self.text, "cache_control": cache_control()}])
+                .serialize(serializer)
+        } else {
+            serializer.serialize_str(&self.text)
+        }
+    }
+}
+
+/// 扩展思考的请求参数
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThinkingConfig {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    /// 思考可用的 token 数，计入 max_tokens
+    budget_tokens: u32,
+}
+
+impl ThinkingConfig {
+    pub fn enabled(budget_tokens: u32) -> Self {
+        Self {
+            kind: "enabled",
+            budget_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AnthropicResponse {
+    pub content: Vec<Value>,
+    pub stop_reason: Option<String>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+/// token 用量
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} in / {} out",
+            format_tokens(self.input_tokens),
+            format_tokens(self.output_tokens)
+        )
+    }
+}
+
+/// 以紧凑形式显示 token 数，如 `950`、`12.3k`、`1.2M`
+pub fn format_tokens(tokens: u64) -> String {
+    match tokens {
+        0..=999 => tokens.to_string(),
+        1_000..=999_999 => format!("{:.1}k", tokens as f64 / 1_000.0),
+        _ => format!("{:.1}M", tokens as f64 / 1_000_000.0),
+    }
+}
+
+// ============== Content Block 处理 ==============
+
+/// 从 Value 中提取 content block 类型和数据
+pub fn parse_content_block(block: &Value) -> Option<(&str, &Value)> {
+    let block_type = block.get("type")?.as_str()?;
+    Some((block_type, block))
+}
+
+/// 创建 tool_result block
+pub fn create_tool_result(tool_use_id: &str, content: &str) -> Value {
+    serde_json::json!({
+        "type": "tool_result",
+        "tool_use_id": tool_use_id,
+        "content": content
+    })
+}
+
+/// 创建附件 block：图片为 `image`，PDF 为 `document`，内容以 base64 内联
+pub fn create_attachment_block(media_type: &str, data: &str) -> Value {
+    let kind = if media_type.starts_with("image/") {
+        "image"
+    } else {
+        "document"
+    };
+    json!({
+        "type": kind,
+        "source": {
+            "type": "base64",
+            "media_type": media_type,
+            "data": data
+        }
+    })
+}
+
+/// 截断过大的工具输出，避免它随每轮请求反复发送并撑满上下文
+///
+/// 保留前 `max_bytes` 字节（不拆开 UTF-8 字符），并追加省略标记。
+pub fn truncate_tool_output(output: &str, max_bytes: usize) -> String {
+    if output.len() <= max_bytes {
+        return output.to_string();
+    }
+    let mut end = max_bytes;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n...[truncated, {} bytes omitted]...",
+        &output[..end],
+        output.len() - end
+    )
+}
+
+// ============== 错误类型 ==============
+
+/// 限流响应头：建议的重试等待秒数
+const RETRY_AFTER_HEADER: &str = "retry-after";
+
+/// Anthropic 限流响应头：各项配额恢复的时间（RFC 3339）
+const RATELIMIT_RESET_HEADERS: [&str; 4] = [
+    "anthropic-ratelimit-requests-reset",
+    "anthropic-ratelimit-tokens-reset",
+    "anthropic-ratelimit-input-tokens-reset",
+    "anthropic-ratelimit-output-tokens-reset",
+];
+
+/// 响应头给出的重置等待时间的上限，避免异常的响应头导致溢出或过长的等待
+const MAX_RESET_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// 指数退避的初始等待时间
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// 单次重试的最长等待时间
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// 可重试的 HTTP 状态码（限流、服务端瞬时错误、过载）
+const RETRYABLE_STATUSES: [u16; 5] = [429, 500, 502, 503, 529];
+
+/// API 调用错误
+#[derive(Debug)]
+pub enum ApiError {
+    /// 触发限流 (429)，且重试次数已用尽
+    RateLimited {
+        /// 已经进行的重试次数
+        retries: u32,
+        /// 距离限流重置的时间（来自响应头）
+        reset_after: Option<Duration>,
+    },
+    /// 用户按 Ctrl+C 中断了正在进行的请求
+    Interrupted,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::RateLimited {
+                retries,
+                reset_after,
+            } => {
+                write!(f, "Rate limited: exhausted {} retries", retries)?;
+                match reset_after {
+                    Some(reset) => write!(f, ", reset in {}s", reset.as_secs()),
+                    None => write!(f, ", reset time unknown"),
+                }
+            }
+            ApiError::Interrupted => write!(f, "Request interrupted by user"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    /// 面向用户的错误提示
+    pub fn user_message(&self) -> String {
+        match self {
+            ApiError::RateLimited {
+                retries,
+                reset_after,
+            } => match reset_after {
+                Some(reset) => format!(
+                    "请求过于频繁，已重试 {} 次仍被限流，约 {} 秒后可再次尝试",
+                    retries,
+                    reset.as_secs()
+                ),
+                None => format!("请求过于频繁，已重试 {} 次仍被限流，请稍后重试", retries),
+            },
+            ApiError::Interrupted => "已中断当前请求".to_string(),
+        }
+    }
+}
+
+/// 从响应头解析限流重置时间
+///
+/// 优先读取 `retry-after`（秒数，允许小数）；没有或无法解析时取
+/// `anthropic-ratelimit-*-reset` 中最晚的恢复时间。结果不超过 [`MAX_RESET_AFTER`]，都缺失或无法解析时返回 `None`。
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    parse_retry_after_at(headers, SystemTime::now())
+}
+
+fn parse_retry_after_at(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let retry_after = header(RETRY_AFTER_HEADER)
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|seconds| *seconds >= 0.0)
+        // 超出 Duration 范围（如 1e30、inf）时按上限处理；NaN 已被上面的比较排除
+        .map(|seconds| Duration::try_from_secs_f64(seconds).unwrap_or(MAX_RESET_AFTER));
+    let delay = match retry_after {
+        Some(delay) => delay,
+        None => RATELIMIT_RESET_HEADERS
+            .iter()
+            .filter_map(|name| parse_rfc3339(header(name)?.trim()))
+            .map(|reset| reset.duration_since(now).unwrap_or(Duration::ZERO))
+            .max()?,
+    };
+    Some(delay.min(MAX_RESET_AFTER))
+}
+
+/// 解析 RFC 3339 时间，如 `2025-01-01T00:00:30Z` 或 `2025-01-01T08:00:30.5+08:00`
+fn parse_rfc3339(value: &str) -> Option<SystemTime> {
+    // 各字段最多 4 位数字，避免异常的值在计算时溢出
+    let number = |text: &str| -> Option<i64> {
+        (text.len() <= 4 && text.bytes().all(|b| b.is_ascii_digit())).then(|| text.parse().ok())?
+    };
+    let (date, time) = value.split_once(['T', 't', ' '])?;
+    let mut date = date.splitn(3, '-');
+    let (year, month, day) = (
+        number(date.next()?)?,
+        number(date.next()?)?,
+        number(date.next()?)?,
+    );
+
+    let (time, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(i) => time.split_at(i),
+        None => return None,
+    };
+    let offset_seconds = match offset {
+        "Z" | "z" => 0,
+        _ => {
+            let (hours, minutes) = offset[1..].split_once(':')?;
+            let seconds = number(hours)? * 3600 + number(minutes)? * 60;
+            if offset.starts_with('-') {
+                -seconds
+            } else {
+                seconds
+            }
+        }
+    };
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':');
+    let (hour, minute, second) = (
+        number(time.next()?)?,
+        number(time.next()?)?,
+        number(time.next()?)?,
+    );
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    let nanos = match fraction {
+        "" => 0,
+        digits => {
+            if !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            format!("{:0<9}", &digits[..digits.len().min(9)])
+                .parse()
+                .ok()?
+        }
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second
+        - offset_seconds;
+    let seconds = u64::try_from(seconds).ok()?;
+    UNIX_EPOCH.checked_add(Duration::new(seconds, nanos))
+}
+
+/// 公历日期距 1970-01-01 的天数
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// 状态码是否属于可重试的瞬时错误
+pub fn is_retryable_status(status: u16) -> bool {
+    RETRYABLE_STATUSES.contains(&status)
+}
+
+/// 请求是否因连接被对端关闭而失败（例如代理断开了空闲连接）
+pub fn is_connection_closed(error: &reqwest::Error) -> bool {
+    let mut source: Option<&dyn std::error::Error> = Some(error);
+    while let Some(e) = source {
+        if let Some(io_error) = e.downcast_ref::<std::io::Error>() {
+            if matches!(
+                io_error.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            ) {
+                return true;
+            }
+        }
+        if e.to_string().contains("connection closed") {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// 计算第 `attempt` 次重试前的等待时间（从 0 开始计数）
+///
+/// 服务端给出 `retry-after` 时优先使用，否则按指数退避；两者都不超过上限。
+pub fn retry_delay(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    let delay = retry_after.unwrap_or_else(|| RETRY_BASE_DELAY * 2u32.saturating_pow(attempt));
+    delay.min(RETRY_MAX_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_truncate_tool_output() {
+        assert_eq!(truncate_tool_output("small", 100), "small");
+
+        let output = "x".repeat(250);
+        let truncated = truncate_tool_output(&output, 100);
+        assert!(truncated.starts_with(&"x".repeat(100)));
+        assert!(truncated.ends_with("\n...[truncated, 150 bytes omitted]..."));
+        assert!(!truncated.contains(&"x".repeat(101)));
+
+        // 不会拆开多字节字符
+        assert_eq!(
+            truncate_tool_output("中文", 4),
+            "中\n...[truncated, 3 bytes omitted]..."
+        );
+    }
+
+    #[test]
+    fn test_thinking_block_survives_history_round_trip() {
+        let response: AnthropicResponse = serde_json::from_str(
+            r#"{
+                "content": [
+                    {"type": "thinking", "thinking": "Check the manifest first.", "signature": "sig_abc"},
+                    {"type": "text", "text": "Reading it now."}
+                ],
+                "stop_reason": "end_turn"
+            }"#,
+        )
+        .unwrap();
+        let (block_type, block) = parse_content_block(&response.content[0]).unwrap();
+        assert_eq!(block_type, "thinking");
+        assert_eq!(block["thinking"], "Check the manifest first.");
+
+        // 原样放回历史，后续请求中签名不变
+        let message = Message {
+            role: "assistant".to_string(),
+            content: MessageContent::Blocks(response.content),
+        };
+        let sent = serde_json::to_value(&message).unwrap();
+        assert_eq!(sent["content"][0]["signature"], "sig_abc");
+        assert_eq!(sent["content"][1]["type"], "text");
+    }
+
+    #[test]
+    fn test_unrecognized_block_type_deserializes() {
+        let response: AnthropicResponse = serde_json::from_str(
+            r#"{
+                "content": [
+                    {"type": "redacted_thinking", "data": "opaque"},
+                    {"type": "text", "text": "Hi."}
+                ],
+                "stop_reason": "end_turn"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_content_block(&response.content[0]).map(|(t, _)| t),
+            Some("redacted_thinking")
+        );
+        assert_eq!(response.content[0]["data"], "opaque");
+        assert_eq!(response.content[1]["text"], "Hi.");
+    }
+
+    #[test]
+    fn test_sampling_params_serialized_only_when_set() {
+        let mut request = AnthropicRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            max_tokens: 1024,
+            system: None,
+            messages: Vec::new(),
+            tools: Vec::new(),
+            thinking: None,
+            temperature: None,
+            top_p: None,
+            stream: false,
+        };
+        let body = serde_json::to_value(&request).unwrap();
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("top_p").is_none());
+
+        request.temperature = Some(0.25);
+        request.top_p = Some(0.5);
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["temperature"], 0.25);
+        assert_eq!(body["top_p"], 0.5);
+    }
+
+    #[test]
+    fn test_prompt_caching_marks_system_and_last_tool() {
+        let mut request = AnthropicRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            max_tokens: 1024,
+            system: Some(SystemPrompt::from("Be terse.".to_string())),
+            messages: Vec::new(),
+            tools: vec![json!({"name": "read_file"}), json!({"name": "write_file"})],
+            thinking: None,
+            temperature: None,
+            top_p: None,
+            stream: false,
+        };
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["system"], "Be terse.");
+        assert!(body["tools"][1].get("cache_control").is_none());
+
+        request.enable_prompt_caching();
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            body["system"],
+            json!([{"type": "text", "text": "Be terse.", "cache_control": {"type": "ephemeral"}}])
+        );
+        assert!(body["tools"][0].get("cache_control").is_none());
+        assert_eq!(
+            body["tools"][1]["cache_control"],
+            json!({"type": "ephemeral"})
+        );
+    }
+
+    #[test]
+    fn test_image_block_round_trips_in_history() {
+        let message = Message {
+            role: "user".to_string(),
+            content: MessageContent::Blocks(vec![
+                create_attachment_block("image/png", "iVBORw0KGgo="),
+                json!({"type": "text", "text": "What is this error?"}),
+            ]),
+        };
+
+        let serialized = serde_json::to_value(&message).unwrap();
+        let image = &serialized["content"][0];
+        assert_eq!(image["type"], "image");
+        assert_eq!(
+            image["source"],
+            json!({"type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="})
+        );
+
+        let restored: Message = serde_json::from_value(serialized).unwrap();
+        let MessageContent::Blocks(blocks) = restored.content else {
+            panic!("expected blocks");
+        };
+        assert_eq!(blocks[0]["source"]["data"], "iVBORw0KGgo=");
+        assert_eq!(
+            create_attachment_block("application/pdf", "JVBERi0=")["type"],
+            "document"
+        );
+    }
+
+    #[test]
+    fn test_thinking_config_serialization() {
+        assert_eq!(
+            serde_json::to_value(ThinkingConfig::enabled(2048)).unwrap(),
+            serde_json::json!({"type": "enabled", "budget_tokens": 2048})
+        );
+    }
+
+    #[test]
+    fn test_rate_limited_message_includes_retries_and_reset() {
+        let error = ApiError::RateLimited {
+            retries: 3,
+            reset_after: Some(Duration::from_secs(42)),
+        };
+        let msg = error.to_string();
+        assert!(msg.contains("exhausted 3 retries"));
+        assert!(msg.contains("reset in 42s"));
+
+        let user_msg = error.user_message();
+        assert!(user_msg.contains("3 次"));
+        assert!(user_msg.contains("42 秒"));
+    }
+
+    #[test]
+    fn test_rate_limited_message_without_reset() {
+        let error = ApiError::RateLimited {
+            retries: 0,
+            reset_after: None,
+        };
+        assert!(error.to_string().contains("reset time unknown"));
+    }
+
+    #[test]
+    fn test_deserialize_response_with_usage() {
+        let json = r#"{
+            "content": [{"type": "text", "text": "hi"}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 12345, "output_tokens": 1100, "cache_read_input_tokens": 0}
+        }"#;
+        let response: AnthropicResponse = serde_json::from_str(json).unwrap();
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.input_tokens, 12345);
+        assert_eq!(usage.output_tokens, 1100);
+        assert_eq!(usage.to_string(), "12.3k in / 1.1k out");
+
+        let without: AnthropicResponse =
+            serde_json::from_str(r#"{"content": [], "stop_reason": null}"#).unwrap();
+        assert!(without.usage.is_none());
+    }
+
+    #[test]
+    fn test_usage_accumulates() {
+        let mut total = Usage::default();
+        total += Usage {
+            input_tokens: 500,
+            output_tokens: 20,
+        };
+        total += Usage {
+            input_tokens: 700,
+            output_tokens: 30,
+        };
+        assert_eq!(total.to_string(), "1.2k in / 50 out");
+    }
+
+    #[test]
+    fn test_is_retryable_status() {
+        for status in [429, 500, 502, 503, 529] {
+            assert!(is_retryable_status(status));
+        }
+        for status in [400, 401, 403, 404] {
+            assert!(!is_retryable_status(status));
+        }
+    }
+
+    #[test]
+    fn test_retry_delay_backoff() {
+        assert_eq!(retry_delay(0, None), Duration::from_millis(500));
+        assert_eq!(retry_delay(2, None), Duration::from_secs(2));
+        assert_eq!(retry_delay(20, None), RETRY_MAX_DELAY);
+        assert_eq!(
+            retry_delay(3, Some(Duration::from_secs(7))),
+            Duration::from_secs(7)
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER_HEADER, HeaderValue::from_static("30"));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(30)));
+
+        headers.insert(RETRY_AFTER_HEADER, HeaderValue::from_static("not-a-number"));
+        assert_eq!(parse_retry_after(&headers), None);
+
+        // 异常的大数值不会 panic，而是限制在上限内
+        for value in ["1e30", "1e400", "inf"] {
+            headers.insert(RETRY_AFTER_HEADER, HeaderValue::from_static(value));
+            assert_eq!(
+                parse_retry_after(&headers),
+                Some(MAX_RESET_AFTER),
+                "{}",
+                value
+            );
+        }
+        headers.insert(RETRY_AFTER_HEADER, HeaderValue::from_static("NaN"));
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_parse_ratelimit_reset_headers() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_735_689_600); // 2025-01-01T00:00:00Z
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "anthropic-ratelimit-requests-reset",
+            HeaderValue::from_static("2025-01-01T00:00:10Z"),
+        );
+        headers.insert(
+            "anthropic-ratelimit-tokens-reset",
+            HeaderValue::from_static("2025-01-01T08:00:42.5+08:00"),
+        );
+        // 取最晚恢复的配额
+        assert_eq!(
+            parse_retry_after_at(&headers, now),
+            Some(Duration::from_millis(42_500))
+        );
+
+        // retry-after 优先
+        headers.insert(RETRY_AFTER_HEADER, HeaderValue::from_static("5"));
+        assert_eq!(
+            parse_retry_after_at(&headers, now),
+            Some(Duration::from_secs(5))
+        );
+
+        // retry-after 无法解析时改用重置时间
+        for value in ["soon", "-1", "NaN", "Wed, 01 Jan 2025 00:00:30 GMT"] {
+            headers.insert(RETRY_AFTER_HEADER, HeaderValue::from_static(value));
+            assert_eq!(
+                parse_retry_after_at(&headers, now),
+                Some(Duration::from_millis(42_500)),
+                "{}",
+                value
+            );
+        }
+
+        // 已经过去的时间不需要等待，无法解析的值被忽略
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "anthropic-ratelimit-tokens-reset",
+            HeaderValue::from_static("2024-12-31T23:59:00Z"),
+        );
+        assert_eq!(parse_retry_after_at(&headers, now), Some(Duration::ZERO));
+        headers.insert(
+            "anthropic-ratelimit-tokens-reset",
+            HeaderValue::from_static("tomorrow"),
+        );
+        assert_eq!(parse_retry_after_at(&headers, now), None);
+        headers.insert(
+            "anthropic-ratelimit-tokens-reset",
+            HeaderValue::from_static("99999999999999999-01-01T00:00:00Z"),
+        );
+        assert_eq!(parse_retry_after_at(&headers, now), None);
+    }
+}
@@ -0,0 +1,383 @@
+//! 操作确认模块
+//!
+//! 修改文件的工具调用在执行前默认逐次征求用户同意，并展示 diff 或写入大小。
+//! 配置了阈值时只对有风险的调用（大文件写入、一次修改很多文件、删除）确认，
+//! 日常的小修改则自动放行。
+
+use crate::config::ConfirmSettings;
+use crate::labels::Labels;
+use crate::staging::render_unified_diff;
+use crate::tools::apply_edit;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+/// 需要确认的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalReason {
+    /// 单次写入超过字节阈值
+    LargeWrite {
+        file_path: String,
+        bytes: usize,
+        limit: usize,
+    },
+    /// 一轮回复中修改的文件数超过阈值
+    ManyFiles { files: Vec<String>, limit: usize },
+    /// 删除文件
+    Delete { file_path: String },
+    /// 运行命令
+    Command { command: String },
+}
+
+impl ApprovalReason {
+    /// 生成确认提示
+    pub fn prompt(&self) -> String {
+        match self {
+            ApprovalReason::LargeWrite {
+                file_path,
+                bytes,
+                limit,
+            } => format!(
+                "⚠️  即将写入 {} ({} 字节，超过阈值 {} 字节)，是否继续？",
+                file_path, bytes, limit
+            ),
+            ApprovalReason::ManyFiles { files, limit } => format!(
+                "⚠️  本轮将修改 {} 个文件（超过阈值 {}）:\n{}\n是否继续？",
+                files.len(),
+                limit,
+                files
+                    .iter()
+                    .map(|f| format!("  - {}", f))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            ApprovalReason::Delete { file_path } => {
+                format!("⚠️  即将删除 {}（移入回收站），是否继续？", file_path)
+            }
+            ApprovalReason::Command { command } => {
+                format!("⚠️  即将运行命令: {}，是否继续？", command)
+            }
+        }
+    }
+}
+
+/// 一次修改操作的目标
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mutation {
+    file_path: String,
+    /// 写入的字节数；删除操作为 `None`
+    bytes: Option<usize>,
+}
+
+/// 解析工具调用会修改的文件
+fn mutation_of(name: &str, input: &Value) -> Option<Mutation> {
+    let field = |key: &str| input.get(key).and_then(|v| v.as_str());
+    let file_path = field("file_path")?.to_string();
+
+    let bytes = match name {
+        "write_file" | "append_file" => Some(field("content").map_or(0, str::len)),
+        "edit_file" => Some(field("new_string").map_or(0, str::len)),
+        "delete_file" => None,
+        _ => return None,
+    };
+
+    Some(Mutation { file_path, bytes })
+}
+
+/// run_command 调用的确认原因，展示完整命令行
+fn command_reason(name: &str, input: &Value) -> Option<ApprovalReason> {
+    if name != "run_command" {
+        return None;
+    }
+    let mut parts = vec![input.get("command")?.as_str()?.to_string()];
+    parts.extend(
+        input
+            .get("args")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .map(|arg| match arg.as_str() {
+                Some(s) => s.to_string(),
+                None => arg.to_string(),
+            }),
+    );
+    Some(ApprovalReason::Command {
+        command: parts.join(" "),
+    })
+}
+
+/// 逐次确认时的提示：目标路径，加上修改的 diff 或写入大小
+///
+/// write_file / edit_file 基于磁盘上的当前内容生成 diff；
+/// 新文件或无法生成 diff 时只显示大小，其他工具显示原始输入。
+pub fn confirm_prompt(name: &str, input: &Value) -> String {
+    if let Some(reason) = command_reason(name, input) {
+        return reason.prompt();
+    }
+    let field = |key: &str| input.get(key).and_then(|v| v.as_str());
+    let Some(file_path) = field("file_path") else {
+        return format!("⚠️  即将执行 {}: {}，是否继续？", name, input);
+    };
+    if name == "delete_file" {
+        return ApprovalReason::Delete {
+            file_path: file_path.to_string(),
+        }
+        .prompt();
+    }
+    let original = fs::read_to_string(file_path).ok();
+
+    let modified = match name {
+        "write_file" => field("content").map(str::to_string),
+        "append_file" => field("content")
+            .map(|content| format!("{}{}", original.as_deref().unwrap_or(""), content)),
+        "edit_file" => original.as_deref().and_then(|content| {
+            apply_edit(
+                content,
+                field("old_string")?,
+                field("new_string")?,
+                input
+                    .get("expected_count")
+                    .and_then(|v| v.as_u64())
+                    .map(|n| n as u32),
+            )
+            .ok()
+            .map(|(modified, _)| modified)
+        }),
+        _ => None,
+    };
+
+    match (original, modified) {
+        (Some(original), Some(modified)) => format!(
+            "⚠️  即将修改 {}:\n{}是否继续？",
+            file_path,
+            render_unified_diff(&original, &modified, file_path)
+        ),
+        (None, Some(modified)) => format!(
+            "⚠️  即将创建 {} ({} 字节)，是否继续？",
+            file_path,
+            modified.len()
+        ),
+        _ => format!("⚠️  即将执行 {} ({})，是否继续？", name, file_path),
+    }
+}
+
+/// 确认策略：根据阈值判断哪些操作需要确认
+#[derive(Debug, Clone, Default)]
+pub struct ApprovalPolicy {
+    max_write_bytes: Option<usize>,
+    max_batch_files: Option<usize>,
+}
+
+impl ApprovalPolicy {
+    /// 从配置创建策略
+    pub fn from_settings(settings: &ConfirmSettings) -> Self {
+        Self {
+            max_write_bytes: settings.max_write_bytes,
+            max_batch_files: settings.max_batch_files,
+        }
+    }
+
+    /// 检查一轮回复中的全部工具调用是否修改了过多文件
+    ///
+    /// # Arguments
+    /// * `calls` - 本轮的 (工具名, 输入) 列表
+    pub fn check_batch<'a>(
+        &self,
+        calls: impl IntoIterator<Item = (&'a str, &'a Value)>,
+    ) -> Option<ApprovalReason> {
+        let limit = self.max_batch_files?;
+
+        let mut seen = HashSet::new();
+        let files: Vec<String> = calls
+            .into_iter()
+            .filter_map(|(name, input)| mutation_of(name, input))
+            .map(|m| m.file_path)
+            .filter(|path| seen.insert(path.clone()))
+            .collect();
+
+        (files.len() > limit).then_some(ApprovalReason::ManyFiles { files, limit })
+    }
+
+    /// 检查单个工具调用是否需要确认
+    ///
+    /// 运行命令不受阈值影响，始终需要确认。
+    pub fn check_call(&self, name: &str, input: &Value) -> Option<ApprovalReason> {
+        if let Some(reason) = command_reason(name, input) {
+            return Some(reason);
+        }
+        let mutation = mutation_of(name, input)?;
+
+        match mutation.bytes {
+            None => Some(ApprovalReason::Delete {
+                file_path: mutation.file_path,
+            }),
+            Some(bytes) => {
+                let limit = self.max_write_bytes?;
+                (bytes > limit).then_some(ApprovalReason::LargeWrite {
+                    file_path: mutation.file_path,
+                    bytes,
+                    limit,
+                })
+            }
+        }
+    }
+}
+
+/// 向用户征求确认
+pub trait Approver: Send {
+    /// 展示提示并返回用户是否同意
+    fn confirm(&mut self, prompt: &str) -> bool;
+}
+
+/// 从标准输入读取 y/N 的确认器
+pub struct StdinApprover {
+    labels: Labels,
+}
+
+impl StdinApprover {
+    pub fn new(labels: Labels) -> Self {
+        Self { labels }
+    }
+}
+
+impl Approver for StdinApprover {
+    fn confirm(&mut self, prompt: &str) -> bool {
+        print!("{} [y/N] ", self.labels.apply(prompt));
+        let _ = io::stdout().flush();
+
+        let mut answer = String::new();
+        if io::stdin().lock().read_line(&mut answer).is_err() {
+            return false;
+        }
+        matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+    }
+}
+
+/// 用户拒绝时返回给模型的 tool_result 内容
+pub fn rejected_output() -> String {
+    serde_json::json!({
+        "success": false,
+        "error": "Operation rejected by the user"
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_write_bytes: Option<usize>, max_batch_files: Option<usize>) -> ApprovalPolicy {
+        ApprovalPolicy {
+            max_write_bytes,
+            max_batch_files,
+        }
+    }
+
+    fn write_input(path: &str, bytes: usize) -> Value {
+        serde_json::json!({"file_path": path, "content": "x".repeat(bytes)})
+    }
+
+    #[test]
+    fn test_large_write_threshold_boundary() {
+        let policy = policy(Some(100), None);
+        assert_eq!(
+            policy.check_call("write_file", &write_input("a.txt", 100)),
+            None
+        );
+        assert!(matches!(
+            policy.check_call("write_file", &write_input("a.txt", 101)),
+            Some(ApprovalReason::LargeWrite { bytes: 101, .. })
+        ));
+    }
+
+    #[test]
+    fn test_no_write_threshold_never_prompts() {
+        let policy = policy(None, None);
+        assert_eq!(
+            policy.check_call("write_file", &write_input("a.txt", 1_000_000)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_read_never_prompts() {
+        let policy = policy(Some(0), Some(0));
+        let input = serde_json::json!({"file_path": "Cargo.toml"});
+        assert_eq!(policy.check_call("read_file", &input), None);
+    }
+
+    #[test]
+    fn test_delete_always_prompts() {
+        let policy = policy(None, None);
+        let input = serde_json::json!({"file_path": "old.txt"});
+        assert!(matches!(
+            policy.check_call("delete_file", &input),
+            Some(ApprovalReason::Delete { .. })
+        ));
+    }
+
+    #[test]
+    fn test_run_command_always_prompts() {
+        let policy = policy(Some(1024), None);
+        let input = serde_json::json!({"command": "cargo", "args": ["test", "--quiet"]});
+        let reason = policy.check_call("run_command", &input).unwrap();
+        assert_eq!(
+            reason,
+            ApprovalReason::Command {
+                command: "cargo test --quiet".to_string()
+            }
+        );
+        assert_eq!(confirm_prompt("run_command", &input), reason.prompt());
+    }
+
+    #[test]
+    fn test_confirm_prompt_shows_diff_or_size() {
+        let path = "target/test_confirm_prompt.txt";
+        let _ = fs::remove_file(path);
+
+        let prompt = confirm_prompt("write_file", &write_input(path, 12));
+        assert!(prompt.contains("即将创建"));
+        assert!(prompt.contains("12 字节"));
+
+        fs::create_dir_all("target").unwrap();
+        fs::write(path, "old line\n").unwrap();
+        let input = serde_json::json!({
+            "file_path": path,
+            "old_string": "old",
+            "new_string": "new"
+        });
+        let prompt = confirm_prompt("edit_file", &input);
+        assert!(prompt.contains("-old line"));
+        assert!(prompt.contains("+new line"));
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_batch_threshold_boundary() {
+        let policy = policy(None, Some(2));
+        let a = write_input("a.txt", 1);
+        let b = write_input("b.txt", 1);
+        let c = write_input("c.txt", 1);
+        let read = serde_json::json!({"file_path": "d.txt"});
+
+        // 两个文件 + 一次读取 + 对同一文件的重复写入：不超过阈值
+        let calls = vec![
+            ("write_file", &a),
+            ("write_file", &b),
+            ("read_file", &read),
+            ("write_file", &a),
+        ];
+        assert_eq!(policy.check_batch(calls), None);
+
+        let calls = vec![("write_file", &a), ("write_file", &b), ("edit_file", &c)];
+        match policy.check_batch(calls) {
+            Some(ApprovalReason::ManyFiles { files, limit }) => {
+                assert_eq!(files, vec!["a.txt", "b.txt", "c.txt"]);
+                assert_eq!(limit, 2);
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+}
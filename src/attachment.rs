@@ -0,0 +1,117 @@
+//! 消息附件
+//!
+//! `/attach` 读取图片或 PDF，以 base64 内联为 content block，随下一条用户消息发送。
+
+use crate::api::create_attachment_block;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// 图片附件的大小上限（API 限制为 5MB）
+pub const MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// PDF 附件的大小上限（API 限制为 32MB）
+pub const MAX_DOCUMENT_BYTES: u64 = 32 * 1024 * 1024;
+
+/// 支持的扩展名及对应的 media type
+const MEDIA_TYPES: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("pdf", "application/pdf"),
+];
+
+/// 附件错误
+#[derive(Debug)]
+pub enum AttachmentError {
+    /// 不支持的文件类型
+    Unsupported(String),
+    /// 文件超过大小上限
+    TooLarge { size: u64, max: u64 },
+    /// 读取文件失败
+    Io(String),
+}
+
+impl fmt::Display for AttachmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttachmentError::Unsupported(path) => write!(
+                f,
+                "不支持的附件类型: {}（支持 png、jpg、gif、webp、pdf）",
+                path
+            ),
+            AttachmentError::TooLarge { size, max } => {
+                write!(f, "附件过大: {} 字节，上限为 {} 字节", size, max)
+            }
+            AttachmentError::Io(msg) => write!(f, "无法读取附件: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AttachmentError {}
+
+/// 按扩展名（不区分大小写）判断 media type，不支持的类型返回 `None`
+pub fn media_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    MEDIA_TYPES
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, media_type)| *media_type)
+}
+
+/// 读取文件并生成附件 block；路径应已通过工作区校验
+pub fn load(path: &Path) -> Result<Value, AttachmentError> {
+    let media_type =
+        media_type(path).ok_or_else(|| AttachmentError::Unsupported(path.display().to_string()))?;
+    let max = if media_type.starts_with("image/") {
+        MAX_IMAGE_BYTES
+    } else {
+        MAX_DOCUMENT_BYTES
+    };
+    let size = fs::metadata(path)
+        .map_err(|e| AttachmentError::Io(e.to_string()))?
+        .len();
+    if size > max {
+        return Err(AttachmentError::TooLarge { size, max });
+    }
+
+    let bytes = fs::read(path).map_err(|e| AttachmentError::Io(e.to_string()))?;
+    Ok(create_attachment_block(media_type, &STANDARD.encode(bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_type_by_extension() {
+        assert_eq!(media_type(Path::new("shot.PNG")), Some("image/png"));
+        assert_eq!(media_type(Path::new("a/b.jpeg")), Some("image/jpeg"));
+        assert_eq!(media_type(Path::new("spec.pdf")), Some("application/pdf"));
+        assert_eq!(media_type(Path::new("notes.txt")), None);
+        assert_eq!(media_type(Path::new("Makefile")), None);
+    }
+
+    #[test]
+    fn test_load_encodes_base64() {
+        let path = Path::new("target/test_attachment.png");
+        fs::write(path, b"\x89PNG\r\n").unwrap();
+
+        let block = load(path).unwrap();
+        assert_eq!(block["type"], "image");
+        assert_eq!(block["source"]["type"], "base64");
+        assert_eq!(block["source"]["media_type"], "image/png");
+        assert_eq!(block["source"]["data"], "iVBORw0K");
+
+        assert!(matches!(
+            load(Path::new("Cargo.toml")),
+            Err(AttachmentError::Unsupported(_))
+        ));
+        let _ = fs::remove_file(path);
+    }
+}
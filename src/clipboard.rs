@@ -0,0 +1,137 @@
+//! 复制到系统剪贴板
+//!
+//! `/copy` 复制最近一条助手回复的文本，`/copy code` 只复制其中最后一个代码块，
+//! 避免从终端选择时被折行打乱。
+
+use crate::api::{Message, MessageContent};
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+
+/// 进程内共享的剪贴板
+///
+/// Linux 上剪贴板内容由持有者提供，释放 `Clipboard` 后内容随之消失，
+/// 因此首次使用后一直保留到程序退出。
+static CLIPBOARD: LazyLock<Mutex<Option<arboard::Clipboard>>> = LazyLock::new(|| Mutex::new(None));
+
+/// 剪贴板不可用（如通过 SSH 连接的无图形界面环境）
+#[derive(Debug)]
+pub struct ClipboardError(String);
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "剪贴板不可用: {}", self.0)
+    }
+}
+
+impl std::error::Error for ClipboardError {}
+
+/// 把文本写入系统剪贴板
+pub fn copy(text: &str) -> Result<(), ClipboardError> {
+    let mut clipboard = CLIPBOARD.lock().unwrap_or_else(|e| e.into_inner());
+    if clipboard.is_none() {
+        *clipboard = Some(arboard::Clipboard::new().map_err(|e| ClipboardError(e.to_string()))?);
+    }
+    clipboard
+        .as_mut()
+        .expect("clipboard initialized above")
+        .set_text(text)
+        .map_err(|e| ClipboardError(e.to_string()))
+}
+
+/// 最近一条包含文本的助手消息；只有工具调用的消息会被跳过
+pub fn last_assistant_text(messages: &[Message]) -> Option<String> {
+    messages
+        .iter()
+        .rev()
+        .filter(|message| message.role == "assistant")
+        .map(|message| match &message.content {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Blocks(blocks) => blocks
+                .iter()
+                .filter(|block| block["type"] == "text")
+                .filter_map(|block| block["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        })
+        .find(|text| !text.trim().is_empty())
+}
+
+/// 文本中最后一个完整的围栏代码块（``` 或 ~~~）的内容，不含围栏和语言标记
+pub fn last_code_block(text: &str) -> Option<String> {
+    let mut last = None;
+    // 当前打开的围栏（字符和长度）及已收集的行
+    let mut open: Option<(char, usize, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let fence = ['`', '~'].into_iter().find_map(|c| {
+            let len = trimmed.chars().take_while(|&ch| ch == c).count();
+            (len >= 3).then_some((c, len))
+        });
+        if let Some((c, len, lines)) = &mut open {
+            // 闭合围栏：同一字符、不短于开头，且之后没有其他内容
+            let closes = fence.is_some_and(|(fc, flen)| {
+                fc == *c && flen >= *len && trimmed[flen..].trim().is_empty()
+            });
+            if closes {
+                last = Some(lines.join("\n"));
+                open = None;
+            } else {
+                lines.push(line);
+            }
+        } else if let Some((c, len)) = fence {
+            open = Some((c, len, Vec::new()));
+        }
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_last_code_block() {
+        let text = "先这样：\n\n```rust\nfn a() {}\n```\n\n然后：\n\n```bash\ncargo test\n  --all\n```\n完成";
+        assert_eq!(last_code_block(text).unwrap(), "cargo test\n  --all");
+
+        // 长围栏中可以包含短围栏
+        let nested = "````markdown\n```rust\nfn a() {}\n```\n````";
+        assert_eq!(last_code_block(nested).unwrap(), "```rust\nfn a() {}\n```");
+
+        assert_eq!(last_code_block("~~~\n\n~~~").unwrap(), "");
+        assert!(last_code_block("no code here").is_none());
+        // 未闭合的代码块不算
+        assert!(last_code_block("```rust\nfn a() {}").is_none());
+    }
+
+    #[test]
+    fn test_last_assistant_text_skips_tool_calls() {
+        let messages = vec![
+            Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Text("first".to_string()),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Blocks(vec![
+                    json!({"type": "text", "text": "line 1"}),
+                    json!({"type": "text", "text": "line 2"}),
+                ]),
+            },
+            Message {
+                role: "user".to_string(),
+                content: MessageContent::Text("thanks".to_string()),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Blocks(vec![
+                    json!({"type": "tool_use", "id": "t1", "name": "glob", "input": {}}),
+                ]),
+            },
+        ];
+        assert_eq!(last_assistant_text(&messages).unwrap(), "line 1\nline 2");
+        assert!(last_assistant_text(&messages[2..]).is_none());
+    }
+}
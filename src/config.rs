@@ -6,10 +6,20 @@
 //! - 避免在错误信息中泄露敏感信息（如 API 密钥）
 //! - 支持配置验证
 
+use crate::api::{ApiFormat, AuthScheme};
+use crate::history::TrimStrategy;
+use crate::output::Verbosity;
+use crate::pricing::ModelPrice;
+use crate::tools::{is_root_name, SymlinkPolicy};
+use log::warn;
 use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
 use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 
 /// 配置错误类型
 #[derive(Debug)]
@@ -50,7 +60,7 @@ impl fmt::Display for ConfigError {
 impl std::error::Error for ConfigError {}
 
 /// 环境变量配置
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Default)]
 pub struct Env {
     #[serde(rename = "ANTHROPIC_AUTH_TOKEN")]
     pub api_key: String,
@@ -60,13 +70,319 @@ pub struct Env {
     pub https_proxy: Option<String>,
 }
 
+/// 当前的配置文件格式版本
+///
+/// 版本 0 即没有 `version` 字段的配置，其余格式与版本 1 相同。
+pub const CONFIG_VERSION: u32 = 1;
+
+/// 默认的输入历史文件
+const DEFAULT_HISTORY_PATH: &str = ".mentat/history.txt";
+
+/// 默认保留的输入历史条数
+const DEFAULT_HISTORY_MAX_ENTRIES: usize = 1000;
+
+/// 默认最大输出 token 数
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// 默认的 `anthropic-version` 请求头
+const DEFAULT_ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// temperature 和 top_p 的有效范围
+pub const SAMPLING_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+
+/// 扩展思考预算的下限（API 要求）
+const MIN_THINKING_BUDGET_TOKENS: u32 = 1024;
+
+/// 瞬时 API 错误的默认重试次数
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// 默认 HTTP 请求超时（秒）
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+/// 空闲连接在连接池中保留的默认秒数（与 reqwest 默认值一致）
+const DEFAULT_POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+
+/// 默认 TCP keep-alive 间隔（秒，与 reqwest 默认值一致）
+const DEFAULT_TCP_KEEPALIVE_SECS: u64 = 15;
+
+/// 发回 API 的工具结果默认保留的字节数
+const DEFAULT_MAX_TOOL_RESULT_BYTES: usize = 100 * 1024;
+
+/// 每轮默认允许的工具调用往返次数
+const DEFAULT_MAX_TOOL_ITERATIONS: u32 = 25;
+
+/// 工具调用日志中每条输出默认保留的字节数
+const DEFAULT_TOOL_LOG_MAX_BYTES: usize = 4096;
+
+/// run_command 的默认超时（秒）
+const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 120;
+
+/// 未知模型的最大输出 token 上限
+const FALLBACK_MAX_OUTPUT_TOKENS: u32 = 128_000;
+
+/// 已知模型的最大输出 token 数（按模型名前缀匹配，更具体的前缀在前）
+const MODEL_OUTPUT_LIMITS: &[(&str, u32)] = &[
+    ("claude-opus-4-5", 64_000),
+    ("claude-sonnet-4-5", 64_000),
+    ("claude-haiku-4-5", 64_000),
+    ("claude-opus-4-1", 32_000),
+    ("claude-opus-4", 32_000),
+    ("claude-sonnet-4", 64_000),
+    ("claude-3-7-sonnet", 64_000),
+    ("claude-3-5-haiku", 8_192),
+];
+
+/// 获取模型允许的最大输出 token 数
+pub fn max_output_tokens(model: &str) -> u32 {
+    MODEL_OUTPUT_LIMITS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, limit)| *limit)
+        .unwrap_or(FALLBACK_MAX_OUTPUT_TOKENS)
+}
+
+/// 模型的上下文窗口（当前所有 Claude 模型均为 200k）
+pub const CONTEXT_WINDOW_TOKENS: u64 = 200_000;
+
+/// 已知的 Anthropic 模型 ID（含带日期的版本和别名）
+pub const KNOWN_MODELS: &[&str] = &[
+    "claude-opus-4-5-20251101",
+    "claude-opus-4-5",
+    "claude-sonnet-4-5-20250929",
+    "claude-sonnet-4-5",
+    "claude-haiku-4-5-20251001",
+    "claude-haiku-4-5",
+    "claude-opus-4-1-20250805",
+    "claude-opus-4-1",
+    "claude-opus-4-20250514",
+    "claude-opus-4-0",
+    "claude-sonnet-4-20250514",
+    "claude-sonnet-4-0",
+    "claude-3-7-sonnet-20250219",
+    "claude-3-7-sonnet-latest",
+    "claude-3-5-haiku-20241022",
+    "claude-3-5-haiku-latest",
+];
+
+/// 是否为已知模型（代理或本地服务的模型名可能不在列表中）
+pub fn is_known_model(model: &str) -> bool {
+    KNOWN_MODELS.contains(&model)
+}
+
+/// 默认的工作区标记
+const DEFAULT_WORKSPACE_MARKERS: [&str; 2] = [".mentat", ".git"];
+
+/// 工作区配置
+#[derive(Deserialize, Clone, Default)]
+pub struct WorkspaceSettings {
+    /// 是否从当前目录向上查找标记来确定工作区根目录
+    #[serde(default)]
+    pub discover: bool,
+    /// 标记文件或目录名（可选，默认 `.mentat` 和 `.git`）
+    #[serde(default)]
+    pub markers: Option<Vec<String>>,
+}
+
+impl WorkspaceSettings {
+    /// 获取工作区标记，未配置时返回默认值
+    pub fn get_markers(&self) -> Vec<String> {
+        self.markers.clone().unwrap_or_else(|| {
+            DEFAULT_WORKSPACE_MARKERS
+                .iter()
+                .map(|m| m.to_string())
+                .collect()
+        })
+    }
+}
+
+/// 大型/批量操作确认配置
+///
+/// 配置此项后，删除操作总是需要确认；写入操作仅在超过阈值时确认。
+#[derive(Deserialize, Clone, Default)]
+pub struct ConfirmSettings {
+    /// 单次写入超过该字节数时确认
+    #[serde(default)]
+    pub max_write_bytes: Option<usize>,
+    /// 一轮回复修改的文件数超过该值时确认
+    #[serde(default)]
+    pub max_batch_files: Option<usize>,
+}
+
 /// 应用配置
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Default)]
 pub struct Settings {
+    /// 配置文件格式版本（可选），缺失视为 0，见 [`CONFIG_VERSION`]
+    #[serde(default)]
+    pub version: Option<u32>,
     pub env: Env,
     /// 模型名称（可选，默认使用 claude-sonnet-4-20250514）
     #[serde(default)]
     pub model: Option<String>,
+    /// 暂存模式：write_file 的修改先暂存，经 /apply 逐个审阅后再落盘
+    #[serde(default)]
+    pub staging: bool,
+    /// 工作区配置
+    #[serde(default)]
+    pub workspace: WorkspaceSettings,
+    /// 工作区根目录（可选），配置后优先于 `workspace.discover`
+    ///
+    /// 相对路径按启动时的当前目录解析；所有工具都以该目录为沙箱。
+    #[serde(default)]
+    pub workspace_root: Option<String>,
+    /// 系统提示（可选），作为请求的顶层 `system` 字段发送
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// 从文件读取系统提示（可选），与 `system_prompt` 二选一
+    #[serde(default)]
+    pub system_prompt_file: Option<String>,
+    /// 角色预设（可选）：名称 -> 追加在系统提示之后的片段，用 `/persona <名称>` 切换
+    #[serde(default)]
+    pub personas: Option<HashMap<String, String>>,
+    /// 流式输出：边生成边显示回复（默认关闭）
+    #[serde(default)]
+    pub stream: bool,
+    /// API 格式（可选）：`"anthropic"`（默认）或 `"openai"`
+    ///
+    /// `"openai"` 用于只提供 OpenAI 兼容接口的本地模型服务，此时暂不支持流式输出。
+    #[serde(default)]
+    pub api_format: Option<String>,
+    /// 携带 API 密钥的请求头（可选）：`"x-api-key"` 或 `"bearer"`（`Authorization: Bearer`）
+    ///
+    /// 未配置时 anthropic 格式使用 `x-api-key`，openai 格式使用 `bearer`。
+    #[serde(default)]
+    pub auth_scheme: Option<String>,
+    /// `anthropic-version` 请求头（可选，默认 `2023-06-01`）
+    #[serde(default)]
+    pub anthropic_version: Option<String>,
+    /// `anthropic-beta` 请求头中启用的 beta 功能（可选），多个值以逗号连接
+    #[serde(default)]
+    pub anthropic_beta: Option<Vec<String>>,
+    /// 单次回复的最大输出 token 数（可选，默认 4096）
+    ///
+    /// 回复达到该上限时 API 返回 `stop_reason == "max_tokens"`，
+    /// 此时回复是被截断的，REPL 应提示用户。
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// 采样温度（可选，0.0–1.0）：越低越确定，适合生成代码；越高越发散
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// nucleus 采样阈值（可选，0.0–1.0），通常只需调整 temperature
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    /// 扩展思考的 token 预算（可选，未配置时不启用）
+    ///
+    /// 至少 1024，且必须小于 `max_tokens`；思考内容会保留在对话历史中。
+    #[serde(default)]
+    pub thinking_budget_tokens: Option<u32>,
+    /// 瞬时 API 错误（429、5xx、连接失败）的最大重试次数（可选，默认 3）
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+    /// HTTP 请求超时秒数（可选，默认 120）
+    ///
+    /// 流式响应中该超时作用于每次读取，长回复不会因总时长超时而中断。
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// 空闲连接在连接池中保留的秒数（可选，默认 90）
+    ///
+    /// 代理会悄悄断开空闲连接时，调小该值可避免复用已失效的连接。
+    #[serde(default)]
+    pub pool_idle_timeout_secs: Option<u64>,
+    /// TCP keep-alive 间隔秒数（可选，默认 15）
+    #[serde(default)]
+    pub tcp_keepalive_secs: Option<u64>,
+    /// 空闲多少分钟后自动清除对话（可选，默认不清除）
+    ///
+    /// 启用后不再读写输入历史文件，清除时也会清空内存中的输入历史，
+    /// 以免下一个使用终端的人看到之前的输入。
+    #[serde(default)]
+    pub idle_clear_minutes: Option<u64>,
+    /// 允许写入的目录（可选，相对工作区根目录），未配置时整个工作区都可写入；读取不受限制
+    #[serde(default)]
+    pub writable_roots: Option<Vec<String>>,
+    /// 工作区之外可读取的命名根目录（可选），如 `{"shared": "../shared-lib"}`
+    ///
+    /// 工具通过 `shared:src/lib.rs` 形式的路径读取其中的文件；相对路径基于工作区根目录，
+    /// 这些目录只读，启动时必须存在。
+    #[serde(default)]
+    pub extra_roots: Option<HashMap<String, String>>,
+    /// read_file 返回内容前把密钥、私钥和 `*_KEY=` 之类的环境变量值替换为 `***REDACTED***`
+    #[serde(default)]
+    pub redact_secrets: bool,
+    /// 除 `.mentat/ignore` 外，也按工作区根目录的 `.gitignore` 忽略路径
+    #[serde(default)]
+    pub respect_gitignore: bool,
+    /// 路径中包含符号链接时的处理策略：`resolve`（默认，目标须在工作区内）或 `reject`
+    #[serde(default)]
+    pub symlink_policy: Option<String>,
+    /// REPL 输入历史文件（可选，默认 `.mentat/history.txt`），目录不存在时自动创建
+    #[serde(default)]
+    pub history_path: Option<String>,
+    /// 输入历史最多保留的条数（可选，默认 1000），超出时丢弃最早的记录
+    #[serde(default)]
+    pub history_max_entries: Option<usize>,
+    /// 允许使用的工具（可选，未配置时允许全部内置工具）
+    #[serde(default)]
+    pub enabled_tools: Option<Vec<String>>,
+    /// 禁用的工具（可选），优先级高于 `enabled_tools`
+    #[serde(default)]
+    pub disabled_tools: Option<Vec<String>>,
+    /// 按工具名配置的超时秒数（可选），超时后返回错误结果；未配置的工具不限时
+    #[serde(default)]
+    pub tool_timeouts: Option<HashMap<String, u64>>,
+    /// 对话历史的 token 预算（可选，按字符数估算）；超出时在每轮开始前裁剪最早的轮次
+    #[serde(default)]
+    pub history_token_budget: Option<usize>,
+    /// 超出预算时的裁剪策略：`drop_oldest`（默认）或 `summarize`
+    #[serde(default)]
+    pub history_trim_strategy: Option<String>,
+    /// 发回 API 的单个工具结果的最大字节数（可选，默认 100KB），超出部分被截断
+    #[serde(default)]
+    pub max_tool_result_bytes: Option<usize>,
+    /// 每轮最多执行几次工具调用往返（可选，默认 25），达到后要求模型停止调用工具并总结
+    #[serde(default)]
+    pub max_tool_iterations: Option<u32>,
+    /// 把每次工具调用追加记录到 `.mentat/tool_log.jsonl`（默认关闭）
+    #[serde(default)]
+    pub tool_log: Option<bool>,
+    /// 工具调用日志中每条输出保留的最大字节数（可选，默认 4096）
+    #[serde(default)]
+    pub tool_log_max_bytes: Option<usize>,
+    /// run_command 允许执行的程序名（可选，默认为空，即不允许运行任何程序）
+    #[serde(default)]
+    pub allowed_commands: Option<Vec<String>>,
+    /// run_command 的超时秒数（可选，默认 120）
+    #[serde(default)]
+    pub command_timeout_secs: Option<u64>,
+    /// 自动同意所有修改操作，不再逐次确认（用于非交互场景）
+    #[serde(default)]
+    pub auto_approve: bool,
+    /// dry-run 模式：write_file/edit_file 只报告将要进行的修改，不写入磁盘
+    #[serde(default)]
+    pub dry_run: bool,
+    /// 在终端中为助手回复的 Markdown 着色（代码块、标题），设置 `NO_COLOR` 时不生效
+    #[serde(default)]
+    pub render_markdown: bool,
+    /// 用 `[AI]`、`[tool]` 等 ASCII 标记代替 emoji；`TERM=dumb` 或输出不是终端时自动启用
+    #[serde(default)]
+    pub ascii_only: bool,
+    /// 启动时检查 base_url 和 API 密钥是否可用（同 `--check`），默认关闭
+    #[serde(default)]
+    pub preflight: bool,
+    /// 在系统提示和工具定义上设置缓存断点（Anthropic 提示缓存），降低长会话的费用和延迟
+    #[serde(default)]
+    pub prompt_caching: bool,
+    /// 每轮结束时在 token 用量后显示费用估算
+    #[serde(default)]
+    pub show_cost: bool,
+    /// 模型价格（可选，按完整模型名，美元 / 百万 token），覆盖或补充内置价格表
+    #[serde(default)]
+    pub pricing: Option<HashMap<String, ModelPrice>>,
+    /// 输出详细程度：`quiet`、`normal`（默认）或 `verbose`
+    #[serde(default)]
+    pub verbosity: Option<String>,
+    /// 确认阈值（可选）：配置后只确认超过阈值的操作，未配置时每次修改都确认
+    #[serde(default)]
+    pub confirm: Option<ConfirmSettings>,
 }
 
 impl Settings {
@@ -97,6 +413,307 @@ impl Settings {
             ));
         }
 
+        // 验证 API 格式
+        if let Some(format) = &self.api_format {
+            if ApiFormat::parse(format).is_none() {
+                return Err(ConfigError::ValidationError(format!(
+                    "api_format 无效: {}（可选值: anthropic, openai）",
+                    format
+                )));
+            }
+        }
+        if let Some(scheme) = &self.auth_scheme {
+            if AuthScheme::parse(scheme).is_none() {
+                return Err(ConfigError::ValidationError(format!(
+                    "auth_scheme 无效: {}（可选值: x-api-key, bearer）",
+                    scheme
+                )));
+            }
+        }
+
+        // 验证 Anthropic 请求头
+        if self
+            .anthropic_version
+            .as_deref()
+            .is_some_and(|v| v.trim().is_empty())
+        {
+            return Err(ConfigError::ValidationError(
+                "anthropic_version 不能为空字符串".to_string(),
+            ));
+        }
+        if self
+            .anthropic_beta
+            .iter()
+            .flatten()
+            .any(|b| b.trim().is_empty() || b.contains(','))
+        {
+            return Err(ConfigError::ValidationError(
+                "anthropic_beta 中的每一项都不能为空或包含逗号".to_string(),
+            ));
+        }
+
+        // 验证 max_tokens 范围
+        if let Some(max_tokens) = self.max_tokens {
+            let limit = max_output_tokens(&self.get_model());
+            if max_tokens == 0 || max_tokens > limit {
+                return Err(ConfigError::ValidationError(format!(
+                    "max_tokens 必须在 1 到 {} 之间（当前模型的上限）",
+                    limit
+                )));
+            }
+        }
+
+        // 验证采样参数
+        for (field, value) in [("temperature", self.temperature), ("top_p", self.top_p)] {
+            if value.is_some_and(|v| !SAMPLING_RANGE.contains(&v)) {
+                return Err(ConfigError::ValidationError(format!(
+                    "{} 必须在 0.0 到 1.0 之间",
+                    field
+                )));
+            }
+        }
+
+        // 验证扩展思考预算
+        if let Some(budget) = self.thinking_budget_tokens {
+            let max_tokens = self.get_max_tokens();
+            if budget < MIN_THINKING_BUDGET_TOKENS || budget >= max_tokens {
+                return Err(ConfigError::ValidationError(format!(
+                    "thinking_budget_tokens 必须不小于 {} 且小于 max_tokens ({})",
+                    MIN_THINKING_BUDGET_TOKENS, max_tokens
+                )));
+            }
+        }
+
+        // 验证请求超时
+        if self.timeout_secs == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "timeout_secs 必须大于 0".to_string(),
+            ));
+        }
+
+        // 验证连接池设置
+        if self.pool_idle_timeout_secs == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "pool_idle_timeout_secs 必须大于 0".to_string(),
+            ));
+        }
+        if self.tcp_keepalive_secs == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "tcp_keepalive_secs 必须大于 0".to_string(),
+            ));
+        }
+
+        // 验证历史裁剪配置
+        if self.history_token_budget == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "history_token_budget 必须大于 0".to_string(),
+            ));
+        }
+        if let Some(strategy) = &self.history_trim_strategy {
+            if TrimStrategy::parse(strategy).is_none() {
+                return Err(ConfigError::ValidationError(format!(
+                    "history_trim_strategy 无效: {}（可选值: drop_oldest, summarize）",
+                    strategy
+                )));
+            }
+        }
+
+        // 验证输出详细程度
+        if let Some(verbosity) = &self.verbosity {
+            if Verbosity::parse(verbosity).is_none() {
+                return Err(ConfigError::ValidationError(format!(
+                    "verbosity 无效: {}（可选值: quiet, normal, verbose）",
+                    verbosity
+                )));
+            }
+        }
+
+        // 验证符号链接策略
+        if let Some(policy) = &self.symlink_policy {
+            if SymlinkPolicy::parse(policy).is_none() {
+                return Err(ConfigError::ValidationError(format!(
+                    "symlink_policy 无效: {}（可选值: resolve, reject）",
+                    policy
+                )));
+            }
+        }
+
+        // 验证角色预设
+        for (name, prompt) in self.personas.iter().flatten() {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(ConfigError::ValidationError(format!(
+                    "personas 名称不能为空或包含空白: {:?}",
+                    name
+                )));
+            }
+            if prompt.trim().is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "personas 中 {} 的提示不能为空",
+                    name
+                )));
+            }
+        }
+
+        // 验证模型价格
+        for (model, price) in self.pricing.iter().flatten() {
+            let valid = |rate: f64| rate.is_finite() && rate >= 0.0;
+            if !valid(price.input_per_mtok) || !valid(price.output_per_mtok) {
+                return Err(ConfigError::ValidationError(format!(
+                    "pricing 中 {} 的价格必须是非负数",
+                    model
+                )));
+            }
+        }
+
+        // 验证工具结果上限
+        if self.max_tool_result_bytes == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "max_tool_result_bytes 必须大于 0".to_string(),
+            ));
+        }
+
+        // 验证工具调用往返上限
+        if self.max_tool_iterations == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "max_tool_iterations 必须大于 0".to_string(),
+            ));
+        }
+
+        // 验证工具调用日志的输出上限
+        if self.tool_log_max_bytes == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "tool_log_max_bytes 必须大于 0".to_string(),
+            ));
+        }
+
+        // 验证 run_command 配置
+        if self.command_timeout_secs == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "command_timeout_secs 必须大于 0".to_string(),
+            ));
+        }
+        if let Some(invalid) = self
+            .allowed_commands
+            .iter()
+            .flatten()
+            .find(|c| c.trim().is_empty() || c.contains(['/', '\\']))
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "allowed_commands 只能包含程序名，不能为空或包含路径: {:?}",
+                invalid
+            )));
+        }
+
+        // 验证输入历史
+        if self
+            .history_path
+            .as_deref()
+            .is_some_and(|path| path.trim().is_empty())
+        {
+            return Err(ConfigError::ValidationError(
+                "history_path 不能为空字符串".to_string(),
+            ));
+        }
+        if self.history_max_entries == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "history_max_entries 必须大于 0".to_string(),
+            ));
+        }
+
+        // 验证空闲清除时长
+        if self.idle_clear_minutes == Some(0) {
+            return Err(ConfigError::ValidationError(
+                "idle_clear_minutes 必须大于 0".to_string(),
+            ));
+        }
+
+        // 验证工具列表中的名称
+        let known = crate::tools::builtin_tool_names();
+        for (field, list) in [
+            ("enabled_tools", &self.enabled_tools),
+            ("disabled_tools", &self.disabled_tools),
+        ] {
+            if let Some(unknown) = list.iter().flatten().find(|n| !known.contains(&n.as_str())) {
+                return Err(ConfigError::ValidationError(format!(
+                    "{} 中包含未知工具: {}（可用工具: {}）",
+                    field,
+                    unknown,
+                    known.join(", ")
+                )));
+            }
+        }
+        for (name, secs) in self.tool_timeouts.iter().flatten() {
+            if !known.contains(&name.as_str()) {
+                return Err(ConfigError::ValidationError(format!(
+                    "tool_timeouts 中包含未知工具: {}（可用工具: {}）",
+                    name,
+                    known.join(", ")
+                )));
+            }
+            if *secs == 0 {
+                return Err(ConfigError::ValidationError(format!(
+                    "tool_timeouts 中 {} 的超时必须大于 0",
+                    name
+                )));
+            }
+        }
+
+        // 验证工作区根目录
+        if self
+            .workspace_root
+            .as_deref()
+            .is_some_and(|root| root.trim().is_empty())
+        {
+            return Err(ConfigError::ValidationError(
+                "workspace_root 不能为空字符串".to_string(),
+            ));
+        }
+
+        // 验证允许写入的目录
+        if let Some(invalid) = self.writable_roots.iter().flatten().find(|root| {
+            let path = Path::new(root.as_str());
+            root.trim().is_empty()
+                || path.is_absolute()
+                || path.components().any(|c| c == Component::ParentDir)
+        }) {
+            return Err(ConfigError::ValidationError(format!(
+                "writable_roots 只能包含工作区内的相对路径: {:?}",
+                invalid
+            )));
+        }
+
+        // 验证额外的根目录
+        for (name, root) in self.extra_roots.iter().flatten() {
+            if !is_root_name(name) {
+                return Err(ConfigError::ValidationError(format!(
+                    "extra_roots 名称只能包含字母、数字、_ 和 -: {:?}",
+                    name
+                )));
+            }
+            if root.trim().is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "extra_roots 中 {} 的路径不能为空",
+                    name
+                )));
+            }
+        }
+
+        // 验证系统提示
+        if self.system_prompt.is_some() && self.system_prompt_file.is_some() {
+            return Err(ConfigError::ValidationError(
+                "system_prompt 和 system_prompt_file 只能配置其中一个".to_string(),
+            ));
+        }
+
+        // 验证工作区标记
+        if let Some(markers) = &self.workspace.markers {
+            if markers.iter().any(|m| m.trim().is_empty()) {
+                return Err(ConfigError::ValidationError(
+                    "工作区标记 (workspace.markers) 不能为空字符串".to_string(),
+                ));
+            }
+        }
+
         // 验证代理 URL（如果存在）
         if let Some(proxy) = &self.env.https_proxy {
             if !proxy.is_empty()
@@ -110,289 +727,1347 @@ impl Settings {
             }
         }
 
-        Ok(())
+        if let Some(warning) = self.model_warning() {
+            warn!("{}", warning);
+        }
+        if let Some(hint) = self.version_hint() {
+            warn!("{}", hint);
+        }
+
+        Ok(())
+    }
+
+    /// 检查模型名：Anthropic 格式下不在 [`KNOWN_MODELS`] 中时返回警告
+    ///
+    /// 只警告不报错，代理服务可能使用自定义的模型名；OpenAI 兼容格式不检查。
+    pub fn model_warning(&self) -> Option<String> {
+        let model = self.get_model();
+        if self.get_api_format() != ApiFormat::Anthropic || is_known_model(&model) {
+            return None;
+        }
+        Some(format!(
+            "未知模型: {}，如使用代理的自定义模型名可忽略（已知模型: {}）",
+            model,
+            KNOWN_MODELS.join(", ")
+        ))
+    }
+
+    /// 检查配置文件版本：缺失或早于 [`CONFIG_VERSION`] 时返回升级提示
+    pub fn version_hint(&self) -> Option<String> {
+        let version = self.version.unwrap_or(0);
+        if version >= CONFIG_VERSION {
+            return None;
+        }
+        Some(format!(
+            "配置文件版本 {} 早于当前版本 {}，新增的配置项将使用默认值；运行 mentat --migrate-config 升级配置文件（mentat --init 生成的模板为当前格式）",
+            version, CONFIG_VERSION
+        ))
+    }
+
+    /// 获取模型名称，如果未配置则返回默认值
+    pub fn get_model(&self) -> String {
+        self.model
+            .clone()
+            .unwrap_or_else(|| "claude-opus-4-5-20251101".to_string())
+    }
+
+    /// 获取 API 格式，如果未配置则返回默认值
+    pub fn get_api_format(&self) -> ApiFormat {
+        self.api_format
+            .as_deref()
+            .and_then(ApiFormat::parse)
+            .unwrap_or_default()
+    }
+
+    /// 获取携带 API 密钥的方式，未配置时取决于 API 格式
+    pub fn get_auth_scheme(&self) -> AuthScheme {
+        self.auth_scheme
+            .as_deref()
+            .and_then(AuthScheme::parse)
+            .unwrap_or_else(|| AuthScheme::default_for(self.get_api_format()))
+    }
+
+    /// 获取 `anthropic-version` 请求头，如果未配置则返回默认值
+    pub fn get_anthropic_version(&self) -> String {
+        self.anthropic_version
+            .clone()
+            .unwrap_or_else(|| DEFAULT_ANTHROPIC_VERSION.to_string())
+    }
+
+    /// 获取最大输出 token 数，如果未配置则返回默认值
+    pub fn get_max_tokens(&self) -> u32 {
+        self.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)
+    }
+
+    /// 获取最大重试次数，如果未配置则返回默认值
+    pub fn get_max_retries(&self) -> u32 {
+        self.max_retries.unwrap_or(DEFAULT_MAX_RETRIES)
+    }
+
+    /// 获取空闲清除时长；未配置时返回 `None`（不清除）
+    pub fn get_idle_clear(&self) -> Option<Duration> {
+        self.idle_clear_minutes
+            .map(|minutes| Duration::from_secs(minutes.saturating_mul(60)))
+    }
+
+    /// 获取输入历史文件路径，如果未配置则返回默认值
+    pub fn get_history_path(&self) -> PathBuf {
+        PathBuf::from(self.history_path.as_deref().unwrap_or(DEFAULT_HISTORY_PATH))
+    }
+
+    /// 获取输入历史的条数上限，如果未配置则返回默认值
+    pub fn get_history_max_entries(&self) -> usize {
+        self.history_max_entries
+            .unwrap_or(DEFAULT_HISTORY_MAX_ENTRIES)
+    }
+
+    /// 获取 run_command 允许执行的程序，未配置时为空
+    ///
+    /// 白名单需要用户显式开启：cargo 会执行 build.rs，git 可以通过配置执行任意命令，
+    /// 加入白名单等同于允许模型在本机运行代码。
+    pub fn get_allowed_commands(&self) -> Vec<String> {
+        self.allowed_commands.clone().unwrap_or_default()
+    }
+
+    /// 获取允许写入的目录，未配置时为空（整个工作区都可写入）
+    pub fn get_writable_roots(&self) -> Vec<PathBuf> {
+        self.writable_roots
+            .iter()
+            .flatten()
+            .map(PathBuf::from)
+            .collect()
+    }
+
+    /// 获取额外的命名根目录，相对路径基于工作区根目录；未配置时为空
+    pub fn get_extra_roots(&self, workspace_root: &Path) -> HashMap<String, PathBuf> {
+        self.extra_roots
+            .iter()
+            .flatten()
+            .map(|(name, root)| (name.clone(), workspace_root.join(root)))
+            .collect()
+    }
+
+    /// 获取符号链接策略，如果未配置则返回默认值
+    pub fn get_symlink_policy(&self) -> SymlinkPolicy {
+        self.symlink_policy
+            .as_deref()
+            .and_then(SymlinkPolicy::parse)
+            .unwrap_or_default()
+    }
+
+    /// 获取角色预设，未配置时为空
+    pub fn get_personas(&self) -> HashMap<String, String> {
+        self.personas.clone().unwrap_or_default()
+    }
+
+    /// 获取配置的模型价格，未配置时为空
+    pub fn get_pricing(&self) -> HashMap<String, ModelPrice> {
+        self.pricing.clone().unwrap_or_default()
+    }
+
+    /// 获取按工具名配置的超时
+    pub fn get_tool_timeouts(&self) -> HashMap<String, Duration> {
+        self.tool_timeouts
+            .iter()
+            .flatten()
+            .map(|(name, secs)| (name.clone(), Duration::from_secs(*secs)))
+            .collect()
+    }
+
+    /// 获取 run_command 的超时时长
+    pub fn get_command_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.command_timeout_secs
+                .unwrap_or(DEFAULT_COMMAND_TIMEOUT_SECS),
+        )
+    }
+
+    /// 获取系统提示：优先使用 `system_prompt`，否则读取 `system_prompt_file`
+    pub fn load_system_prompt(&self) -> Result<Option<String>, ConfigError> {
+        if let Some(prompt) = &self.system_prompt {
+            return Ok(Some(prompt.clone()));
+        }
+        match &self.system_prompt_file {
+            Some(path) => fs::read_to_string(path).map(Some).map_err(|e| {
+                ConfigError::ReadError(format!("system_prompt_file {} ({})", path, e))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// 获取历史裁剪策略，如果未配置则返回默认值
+    pub fn get_history_trim_strategy(&self) -> TrimStrategy {
+        self.history_trim_strategy
+            .as_deref()
+            .and_then(TrimStrategy::parse)
+            .unwrap_or_default()
+    }
+
+    /// 获取输出详细程度，如果未配置则返回默认值
+    pub fn get_verbosity(&self) -> Verbosity {
+        self.verbosity
+            .as_deref()
+            .and_then(Verbosity::parse)
+            .unwrap_or_default()
+    }
+
+    /// 获取工具结果的字节上限，如果未配置则返回默认值
+    pub fn get_max_tool_result_bytes(&self) -> usize {
+        self.max_tool_result_bytes
+            .unwrap_or(DEFAULT_MAX_TOOL_RESULT_BYTES)
+    }
+
+    /// 获取每轮工具调用往返的上限，如果未配置则返回默认值
+    pub fn get_max_tool_iterations(&self) -> u32 {
+        self.max_tool_iterations
+            .unwrap_or(DEFAULT_MAX_TOOL_ITERATIONS)
+    }
+
+    /// 是否启用工具调用日志
+    pub fn tool_log_enabled(&self) -> bool {
+        self.tool_log.unwrap_or(false)
+    }
+
+    /// 获取工具调用日志的输出字节上限，如果未配置则返回默认值
+    pub fn get_tool_log_max_bytes(&self) -> usize {
+        self.tool_log_max_bytes
+            .unwrap_or(DEFAULT_TOOL_LOG_MAX_BYTES)
+    }
+
+    /// 获取 HTTP 请求超时，如果未配置则返回默认值
+    pub fn get_timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS))
+    }
+
+    /// 获取构建 HTTP 客户端所需的超时、连接池和代理设置
+    pub fn get_http_client_options(&self) -> HttpClientOptions {
+        HttpClientOptions {
+            timeout: self.get_timeout(),
+            pool_idle_timeout: Duration::from_secs(
+                self.pool_idle_timeout_secs
+                    .unwrap_or(DEFAULT_POOL_IDLE_TIMEOUT_SECS),
+            ),
+            tcp_keepalive: Duration::from_secs(
+                self.tcp_keepalive_secs
+                    .unwrap_or(DEFAULT_TCP_KEEPALIVE_SECS),
+            ),
+            proxy: self
+                .env
+                .https_proxy
+                .clone()
+                .filter(|proxy| !proxy.is_empty()),
+        }
+    }
+}
+
+/// HTTP 客户端设置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpClientOptions {
+    /// 单次请求超时
+    pub timeout: Duration,
+    /// 空闲连接在连接池中保留的时长
+    pub pool_idle_timeout: Duration,
+    /// TCP keep-alive 间隔
+    pub tcp_keepalive: Duration,
+    /// HTTPS 代理（未配置或为空时为 `None`）
+    pub proxy: Option<String>,
+}
+
+/// 默认的 API 地址（与配置模板一致）
+pub const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+
+/// 默认配置文件路径
+pub const DEFAULT_CONFIG_PATH: &str = ".mentat/settings.json";
+
+/// 加载配置文件
+///
+/// 按以下顺序搜索配置文件（同一目录下 settings.json 优先于 settings.toml）：
+/// 1. 当前目录下的 .mentat/settings.json
+/// 2. 用户配置目录下的 mentat/settings.json（如 ~/.config/mentat/settings.json）
+///
+/// # 返回
+/// - `Ok(Settings)` - 成功加载并验证的配置
+/// - `Err(ConfigError)` - 加载或验证失败
+#[allow(dead_code)]
+pub fn load_settings() -> Result<Settings, ConfigError> {
+    load_settings_from_path(None)
+}
+
+/// 从指定路径加载配置文件
+///
+/// # 参数
+/// - `custom_path` - 自定义配置文件路径，如果为 None 则使用默认搜索路径
+pub fn load_settings_from_path(custom_path: Option<&str>) -> Result<Settings, ConfigError> {
+    load_and_validate(find_config_path(custom_path)?)
+}
+
+/// 确定配置文件路径：优先使用自定义路径，否则按搜索顺序取第一个存在的文件
+pub fn find_config_path(custom_path: Option<&str>) -> Result<PathBuf, ConfigError> {
+    // 如果指定了自定义路径，直接使用
+    if let Some(path) = custom_path {
+        return Ok(PathBuf::from(path));
+    }
+
+    // 搜索配置文件
+    get_config_search_paths()
+        .into_iter()
+        .find(|path| path.exists())
+        // 没有找到配置文件
+        .ok_or_else(|| ConfigError::NotFound(PathBuf::from(DEFAULT_CONFIG_PATH)))
+}
+
+/// 获取配置文件搜索路径列表
+fn get_config_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    // 1. 当前目录下的配置文件
+    paths.push(PathBuf::from(DEFAULT_CONFIG_PATH));
+    paths.push(PathBuf::from(DEFAULT_CONFIG_PATH).with_extension("toml"));
+
+    // 2. 用户配置目录（跨平台）
+    if let Some(config_dir) = dirs_config_dir() {
+        paths.push(config_dir.join("mentat/settings.json"));
+        paths.push(config_dir.join("mentat/settings.toml"));
+    }
+
+    // 3. 用户主目录下的 .mentat
+    if let Some(home_dir) = dirs_home_dir() {
+        paths.push(home_dir.join(".mentat/settings.json"));
+        paths.push(home_dir.join(".mentat/settings.toml"));
+    }
+
+    paths
+}
+
+/// 配置文件是否为 TOML 格式（按扩展名判断，其余按 JSON 解析）
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "toml")
+}
+
+/// 解析 TOML 配置；错误信息与 JSON 一样给出行列位置
+fn from_toml<T: serde::de::DeserializeOwned>(content: &str) -> Result<T, ConfigError> {
+    toml::from_str(content).map_err(|e| {
+        let (line, column) = e.span().map_or((0, 0), |span| {
+            let before = &content[..span.start];
+            let line = before.matches('\n').count() + 1;
+            let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+            (line, column)
+        });
+        ConfigError::ParseError(format!(
+            "{}: 第 {} 行，第 {} 列\n   提示: 请检查 TOML 格式是否正确，特别是引号、表名和键值",
+            e.message(),
+            line,
+            column
+        ))
+    })
+}
+
+/// 获取用户配置目录（简化实现，不依赖 dirs crate）
+fn dirs_config_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        dirs_home_dir().map(|h| h.join("Library/Application Support"))
+    }
+    #[cfg(target_os = "linux")]
+    {
+        std::env::var("XDG_CONFIG_HOME")
+            .ok()
+            .map(PathBuf::from)
+            .or_else(|| dirs_home_dir().map(|h| h.join(".config")))
+    }
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var("APPDATA").ok().map(PathBuf::from)
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+    {
+        None
+    }
+}
+
+/// 获取用户主目录
+fn dirs_home_dir() -> Option<PathBuf> {
+    std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .ok()
+        .map(PathBuf::from)
+}
+
+/// 加载并验证配置文件
+fn load_and_validate(path: PathBuf) -> Result<Settings, ConfigError> {
+    // 读取文件
+    let content = fs::read_to_string(&path).map_err(|e| {
+        // 提供有用的错误信息，但不暴露敏感内容
+        let hint = match e.kind() {
+            std::io::ErrorKind::NotFound => "文件不存在",
+            std::io::ErrorKind::PermissionDenied => "权限不足",
+            _ => "读取失败",
+        };
+        ConfigError::ReadError(format!("{} ({})", hint, path.display()))
+    })?;
+
+    let settings: Settings = if is_toml(&path) {
+        from_toml(&content)?
+    } else {
+        parse_json(&content)?
+    };
+
+    // 验证配置
+    settings.validate()?;
+
+    Ok(settings)
+}
+
+/// 解析 JSON 配置
+fn parse_json(content: &str) -> Result<Settings, ConfigError> {
+    serde_json::from_str(content).map_err(|e| {
+        // 提供详细的解析错误信息以帮助调试
+        let error_type = match e.classify() {
+            serde_json::error::Category::Io => "IO 错误",
+            serde_json::error::Category::Syntax => "语法错误",
+            serde_json::error::Category::Data => "数据类型错误",
+            serde_json::error::Category::Eof => "文件意外结束",
+        };
+        ConfigError::ParseError(format!(
+            "{}: 第 {} 行，第 {} 列\n   提示: 请检查 JSON 格式是否正确，特别是引号、逗号和括号",
+            error_type,
+            e.line(),
+            e.column()
+        ))
+    })
+}
+
+/// 把旧版本的配置升级为当前格式，保留已有的配置值
+///
+/// 版本 0 到 1 只新增 `version` 字段，其余内容原样保留；
+/// 已是当前（或更新）版本的配置原样返回。
+pub fn migrate(mut config: Value) -> Result<Value, ConfigError> {
+    let Some(object) = config.as_object_mut() else {
+        return Err(ConfigError::ParseError(
+            "配置文件必须是 JSON 对象".to_string(),
+        ));
+    };
+    let version = object.get("version").and_then(Value::as_u64).unwrap_or(0);
+
+    if version < u64::from(CONFIG_VERSION) {
+        object.insert("version".to_string(), Value::from(CONFIG_VERSION));
+    }
+    Ok(config)
+}
+
+/// 升级配置文件并写回，原文件备份为 `<文件>.bak`
+///
+/// 返回升级前的版本；已是当前版本时不做修改。
+pub fn migrate_file(path: &Path) -> Result<u32, ConfigError> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| ConfigError::ReadError(format!("{} ({})", e, path.display())))?;
+    let config: Value = if is_toml(path) {
+        from_toml(&content)?
+    } else {
+        serde_json::from_str(&content).map_err(|e| ConfigError::ParseError(e.to_string()))?
+    };
+    let version = config.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if version >= CONFIG_VERSION {
+        return Ok(version);
+    }
+
+    let migrated = migrate(config)?;
+    // 先序列化，失败时不备份也不改动原文件
+    let content = if is_toml(path) {
+        toml::to_string_pretty(&migrated).map_err(|e| ConfigError::ParseError(e.to_string()))?
+    } else {
+        serde_json::to_string_pretty(&migrated)
+            .map_err(|e| ConfigError::ParseError(e.to_string()))?
+            + "\n"
+    };
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    fs::copy(path, &backup)
+        .map_err(|e| ConfigError::ReadError(format!("无法备份配置文件: {}", e)))?;
+    fs::write(path, content)
+        .map_err(|e| ConfigError::ReadError(format!("无法写入配置文件: {}", e)))?;
+    Ok(version)
+}
+
+/// `--init` 中用户的回答；留空的字段使用模板中的默认值
+#[derive(Debug, Default)]
+pub struct InitAnswers {
+    pub api_key: String,
+    pub base_url: String,
+    pub model: String,
+}
+
+/// 在默认模板上填入回答，生成通过验证的配置文件内容
+pub fn build_config(answers: &InitAnswers) -> Result<String, ConfigError> {
+    let mut config: Value = serde_json::from_str(DEFAULT_CONFIG_TEMPLATE)
+        .map_err(|e| ConfigError::ParseError(e.to_string()))?;
+    config["env"]["ANTHROPIC_AUTH_TOKEN"] = Value::from(answers.api_key.trim());
+    let base_url = answers.base_url.trim().trim_end_matches('/');
+    if !base_url.is_empty() {
+        config["env"]["ANTHROPIC_BASE_URL"] = Value::from(base_url);
+    }
+    let model = answers.model.trim();
+    if !model.is_empty() {
+        config["model"] = Value::from(model);
+    }
+
+    let settings: Settings = serde_json::from_value(config.clone())
+        .map_err(|e| ConfigError::ParseError(e.to_string()))?;
+    settings.validate()?;
+    Ok(serde_json::to_string_pretty(&config).unwrap_or_default() + "\n")
+}
+
+/// 按回答创建配置文件（默认路径），目录不存在时自动创建
+pub fn create_default_config(answers: &InitAnswers) -> Result<PathBuf, ConfigError> {
+    let content = build_config(answers)?;
+    let config_path = PathBuf::from(DEFAULT_CONFIG_PATH);
+
+    // 确保目录存在
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| ConfigError::ReadError(format!("无法创建配置目录: {}", e)))?;
+    }
+
+    fs::write(&config_path, content)
+        .map_err(|e| ConfigError::ReadError(format!("无法写入配置文件: {}", e)))?;
+
+    Ok(config_path)
+}
+
+/// 默认配置文件模板（当前格式版本）
+const DEFAULT_CONFIG_TEMPLATE: &str = r#"{
+  "version": 1,
+  "env": {
+    "ANTHROPIC_AUTH_TOKEN": "your-api-key-here",
+    "ANTHROPIC_BASE_URL": "https://api.anthropic.com",
+    "HTTPS_PROXY": null
+  },
+  "model": "claude-opus-4-5-20251101"
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_empty_api_key() {
+        let settings = Settings {
+            env: Env {
+                api_key: "".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            model: None,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_short_api_key() {
+        let settings = Settings {
+            env: Env {
+                api_key: "short".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            model: None,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_empty_base_url() {
+        let settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "".to_string(),
+                https_proxy: None,
+            },
+            model: None,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_invalid_base_url() {
+        let settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "not-a-url".to_string(),
+                https_proxy: None,
+            },
+            model: None,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_invalid_proxy() {
+        let settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: Some("invalid-proxy".to_string()),
+            },
+            model: None,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_valid_settings() {
+        let settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            model: None,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_valid_settings_with_proxy() {
+        let settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: Some("http://proxy.example.com:8080".to_string()),
+            },
+            model: None,
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_get_model_default() {
+        let settings = Settings {
+            env: Env {
+                api_key: "test".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            model: None,
+            ..Default::default()
+        };
+        assert_eq!(settings.get_model(), "claude-opus-4-5-20251101");
+    }
+
+    #[test]
+    fn test_get_model_custom() {
+        let settings = Settings {
+            env: Env {
+                api_key: "test".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            model: Some("claude-opus-4-5-20251101".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(settings.get_model(), "claude-opus-4-5-20251101");
+    }
+
+    #[test]
+    fn test_get_max_tokens_default() {
+        let settings = Settings::default();
+        assert_eq!(settings.get_max_tokens(), 4096);
+    }
+
+    #[test]
+    fn test_validate_max_tokens_range() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            max_tokens: Some(0),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+
+        settings.max_tokens = Some(64_000);
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.get_max_tokens(), 64_000);
+
+        settings.max_tokens = Some(64_001);
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_timeout_default_and_validation() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            ..Default::default()
+        };
+        assert_eq!(settings.get_timeout(), Duration::from_secs(120));
+
+        settings.timeout_secs = Some(0);
+        assert!(settings.validate().is_err());
+
+        settings.timeout_secs = Some(5);
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.get_timeout(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_http_client_options() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: Some(String::new()),
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.get_http_client_options(),
+            HttpClientOptions {
+                timeout: Duration::from_secs(120),
+                pool_idle_timeout: Duration::from_secs(90),
+                tcp_keepalive: Duration::from_secs(15),
+                proxy: None,
+            }
+        );
+
+        settings.pool_idle_timeout_secs = Some(0);
+        assert!(settings.validate().is_err());
+        settings.pool_idle_timeout_secs = Some(20);
+        settings.tcp_keepalive_secs = Some(0);
+        assert!(settings.validate().is_err());
+        settings.tcp_keepalive_secs = Some(30);
+        settings.env.https_proxy = Some("http://127.0.0.1:8080".to_string());
+        assert!(settings.validate().is_ok());
+
+        let options = settings.get_http_client_options();
+        assert_eq!(options.pool_idle_timeout, Duration::from_secs(20));
+        assert_eq!(options.tcp_keepalive, Duration::from_secs(30));
+        assert_eq!(options.proxy.as_deref(), Some("http://127.0.0.1:8080"));
+    }
+
+    #[test]
+    fn test_idle_clear_disabled_by_default() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            ..Default::default()
+        };
+        assert_eq!(settings.get_idle_clear(), None);
+
+        settings.idle_clear_minutes = Some(0);
+        assert!(settings.validate().is_err());
+
+        settings.idle_clear_minutes = Some(15);
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.get_idle_clear(), Some(Duration::from_secs(900)));
+
+        // 过大的值不会溢出
+        settings.idle_clear_minutes = Some(u64::MAX);
+        assert_eq!(
+            settings.get_idle_clear(),
+            Some(Duration::from_secs(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn test_api_format_default_and_validation() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "http://localhost:11434".to_string(),
+                https_proxy: None,
+            },
+            ..Default::default()
+        };
+        assert_eq!(settings.get_api_format(), ApiFormat::Anthropic);
+
+        settings.api_format = Some("openai".to_string());
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.get_api_format(), ApiFormat::OpenAi);
+
+        settings.api_format = Some("gemini".to_string());
+        let err = settings.validate().unwrap_err().to_string();
+        assert!(err.contains("api_format"));
+    }
+
+    #[test]
+    fn test_auth_scheme_default_and_validation() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://gateway.example.com".to_string(),
+                https_proxy: None,
+            },
+            ..Default::default()
+        };
+        assert_eq!(settings.get_auth_scheme(), AuthScheme::ApiKey);
+        settings.api_format = Some("openai".to_string());
+        assert_eq!(settings.get_auth_scheme(), AuthScheme::Bearer);
+
+        settings.api_format = None;
+        settings.auth_scheme = Some("bearer".to_string());
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.get_auth_scheme(), AuthScheme::Bearer);
+        settings.auth_scheme = Some("x-api-key".to_string());
+        assert_eq!(settings.get_auth_scheme(), AuthScheme::ApiKey);
+
+        settings.auth_scheme = Some("basic".to_string());
+        let err = settings.validate().unwrap_err().to_string();
+        assert!(err.contains("auth_scheme"), "{}", err);
+    }
+
+    #[test]
+    fn test_system_prompt_from_setting_or_file() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            ..Default::default()
+        };
+        assert_eq!(settings.load_system_prompt().unwrap(), None);
+
+        let path = "target/test_system_prompt.md";
+        fs::create_dir_all("target").unwrap();
+        fs::write(path, "Always use 4-space indent.").unwrap();
+        settings.system_prompt_file = Some(path.to_string());
+        assert_eq!(
+            settings.load_system_prompt().unwrap().as_deref(),
+            Some("Always use 4-space indent.")
+        );
+
+        settings.system_prompt = Some("inline".to_string());
+        assert!(settings
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("system_prompt"));
+
+        settings.system_prompt_file = Some("target/missing_system_prompt.md".to_string());
+        settings.system_prompt = None;
+        assert!(settings.load_system_prompt().is_err());
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_validate_unknown_tool_names() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            enabled_tools: Some(vec!["read_file".to_string()]),
+            disabled_tools: Some(vec!["write_file".to_string()]),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+
+        settings.disabled_tools = Some(vec!["wirte_file".to_string()]);
+        let err = settings.validate().unwrap_err().to_string();
+        assert!(err.contains("disabled_tools"));
+        assert!(err.contains("wirte_file"));
     }
 
-    /// 获取模型名称，如果未配置则返回默认值
-    pub fn get_model(&self) -> String {
-        self.model
-            .clone()
-            .unwrap_or_else(|| "claude-opus-4-5-20251101".to_string())
+    #[test]
+    fn test_max_output_tokens_by_model() {
+        assert_eq!(max_output_tokens("claude-opus-4-1-20250805"), 32_000);
+        assert_eq!(max_output_tokens("claude-3-5-haiku-20241022"), 8_192);
+        assert_eq!(max_output_tokens("my-local-model"), 128_000);
     }
-}
 
-/// 默认配置文件路径
-const DEFAULT_CONFIG_PATH: &str = ".mentat/settings.json";
+    #[test]
+    fn test_is_known_model() {
+        assert!(is_known_model("claude-sonnet-4-5-20250929"));
+        assert!(is_known_model("claude-3-5-haiku-latest"));
+        assert!(!is_known_model("llama3.1"));
+        assert!(!is_known_model("claude-opus-4.5"));
+    }
 
-/// 加载配置文件
-///
-/// 按以下顺序搜索配置文件：
-/// 1. 当前目录下的 .mentat/settings.json
-/// 2. 用户配置目录下的 mentat/settings.json（如 ~/.config/mentat/settings.json）
-///
-/// # 返回
-/// - `Ok(Settings)` - 成功加载并验证的配置
-/// - `Err(ConfigError)` - 加载或验证失败
-pub fn load_settings() -> Result<Settings, ConfigError> {
-    load_settings_from_path(None)
-}
+    #[test]
+    fn test_history_settings() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.get_history_path(),
+            PathBuf::from(".mentat/history.txt")
+        );
+        assert_eq!(settings.get_history_max_entries(), 1000);
 
-/// 从指定路径加载配置文件
-///
-/// # 参数
-/// - `custom_path` - 自定义配置文件路径，如果为 None 则使用默认搜索路径
-pub fn load_settings_from_path(custom_path: Option<&str>) -> Result<Settings, ConfigError> {
-    // 如果指定了自定义路径，直接使用
-    if let Some(path) = custom_path {
-        return load_and_validate(PathBuf::from(path));
+        settings.history_path = Some("target/mentat_history.txt".to_string());
+        settings.history_max_entries = Some(50);
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.get_history_max_entries(), 50);
+
+        settings.history_max_entries = Some(0);
+        assert!(settings
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("history_max_entries"));
     }
 
-    // 搜索配置文件
-    let search_paths = get_config_search_paths();
+    #[test]
+    fn test_validate_personas() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            personas: Some(HashMap::from([(
+                "reviewer".to_string(),
+                "Review the code critically.".to_string(),
+            )])),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+        assert_eq!(
+            settings.get_personas()["reviewer"],
+            "Review the code critically."
+        );
 
-    for path in &search_paths {
-        if path.exists() {
-            return load_and_validate(path.clone());
+        for (name, prompt) in [("", "x"), ("code reviewer", "x"), ("reviewer", "  ")] {
+            settings.personas = Some(HashMap::from([(name.to_string(), prompt.to_string())]));
+            let err = settings.validate().unwrap_err().to_string();
+            assert!(err.contains("personas"), "{}", err);
         }
     }
 
-    // 没有找到配置文件
-    Err(ConfigError::NotFound(PathBuf::from(DEFAULT_CONFIG_PATH)))
-}
+    #[test]
+    fn test_pricing_override_parsed_and_validated() {
+        let json = r#"{
+            "env": {"ANTHROPIC_AUTH_TOKEN": "valid-api-key-12345", "ANTHROPIC_BASE_URL": "https://api.anthropic.com"},
+            "pricing": {"claude-sonnet-4-5": {"input_per_mtok": 1.5, "output_per_mtok": 7.5}}
+        }"#;
+        let mut settings: Settings = serde_json::from_str(json).unwrap();
+        assert!(settings.validate().is_ok());
+        assert_eq!(
+            settings.get_pricing()["claude-sonnet-4-5"].output_per_mtok,
+            7.5
+        );
 
-/// 获取配置文件搜索路径列表
-fn get_config_search_paths() -> Vec<PathBuf> {
-    let mut paths = Vec::new();
+        settings
+            .pricing
+            .as_mut()
+            .unwrap()
+            .get_mut("claude-sonnet-4-5")
+            .unwrap()
+            .input_per_mtok = -1.0;
+        assert!(settings
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("pricing"));
+    }
 
-    // 1. 当前目录下的配置文件
-    paths.push(PathBuf::from(DEFAULT_CONFIG_PATH));
+    #[test]
+    fn test_tool_timeouts_validated() {
+        let mut settings = Settings {
+            env: Env {
+                api_key: "valid-api-key-12345".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
+                https_proxy: None,
+            },
+            tool_timeouts: Some(HashMap::from([("search".to_string(), 30)])),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+        assert_eq!(
+            settings.get_tool_timeouts()["search"],
+            Duration::from_secs(30)
+        );
 
-    // 2. 用户配置目录（跨平台）
-    if let Some(config_dir) = dirs_config_dir() {
-        paths.push(config_dir.join("mentat/settings.json"));
+        settings.tool_timeouts = Some(HashMap::from([("serach".to_string(), 30)]));
+        assert!(settings.validate().is_err());
+        settings.tool_timeouts = Some(HashMap::from([("search".to_string(), 0)]));
+        assert!(settings.validate().is_err());
     }
 
-    // 3. 用户主目录下的 .mentat
-    if let Some(home_dir) = dirs_home_dir() {
-        paths.push(home_dir.join(".mentat/settings.json"));
-    }
+    #[test]
+    fn test_migrate_v0_config_to_current_version() {
+        let v0 = serde_json::json!({
+            "env": {
+                "ANTHROPIC_AUTH_TOKEN": "valid-api-key-12345",
+                "ANTHROPIC_BASE_URL": "https://api.anthropic.com"
+            },
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 2048,
+            "staging": true
+        });
+        // 没有 version 字段视为版本 0
+        assert!(Settings::default().version_hint().is_some());
 
-    paths
-}
+        let migrated = migrate(v0.clone()).unwrap();
+        assert_eq!(migrated["version"], CONFIG_VERSION);
+        // 除 version 外不改动任何内容
+        let mut without_version = migrated.clone();
+        without_version.as_object_mut().unwrap().remove("version");
+        assert_eq!(without_version, v0);
+        let settings: Settings = serde_json::from_value(migrated.clone()).unwrap();
+        assert!(settings.validate().is_ok());
+        assert!(settings.version_hint().is_none());
+        assert_eq!(settings.env.api_key, "valid-api-key-12345");
+        assert_eq!(settings.get_model(), "claude-sonnet-4-5");
+        assert_eq!(settings.get_max_tokens(), 2048);
+        assert!(settings.staging);
 
-/// 获取用户配置目录（简化实现，不依赖 dirs crate）
-fn dirs_config_dir() -> Option<PathBuf> {
-    #[cfg(target_os = "macos")]
-    {
-        dirs_home_dir().map(|h| h.join("Library/Application Support"))
-    }
-    #[cfg(target_os = "linux")]
-    {
-        std::env::var("XDG_CONFIG_HOME")
-            .ok()
-            .map(PathBuf::from)
-            .or_else(|| dirs_home_dir().map(|h| h.join(".config")))
-    }
-    #[cfg(target_os = "windows")]
-    {
-        std::env::var("APPDATA").ok().map(PathBuf::from)
-    }
-    #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
-    {
-        None
+        // 已是当前版本时不再修改
+        assert_eq!(migrate(migrated.clone()).unwrap(), migrated);
+        assert!(migrate(serde_json::json!([])).is_err());
     }
-}
 
-/// 获取用户主目录
-fn dirs_home_dir() -> Option<PathBuf> {
-    std::env::var("HOME")
-        .or_else(|_| std::env::var("USERPROFILE"))
-        .ok()
-        .map(PathBuf::from)
-}
+    #[test]
+    fn test_toml_config_matches_json() {
+        let dir = std::env::temp_dir().join("mentat_test_toml_config");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let json_path = dir.join("settings.json");
+        fs::write(
+            &json_path,
+            r#"{
+                "version": 1,
+                "env": {
+                    "ANTHROPIC_AUTH_TOKEN": "valid-api-key-12345",
+                    "ANTHROPIC_BASE_URL": "https://api.anthropic.com",
+                    "HTTPS_PROXY": null
+                },
+                "model": "claude-sonnet-4-5",
+                "max_tokens": 2048,
+                "staging": true,
+                "writable_roots": ["src", "docs"],
+                "tool_timeouts": {"search": 30},
+                "workspace": {"discover": true}
+            }"#,
+        )
+        .unwrap();
+        let toml_path = dir.join("settings.toml");
+        fs::write(
+            &toml_path,
+            r#"
+version = 1
+model = "claude-sonnet-4-5"
+max_tokens = 2048
+staging = true
+writable_roots = ["src", "docs"]
 
-/// 加载并验证配置文件
-fn load_and_validate(path: PathBuf) -> Result<Settings, ConfigError> {
-    // 读取文件
-    let content = fs::read_to_string(&path).map_err(|e| {
-        // 提供有用的错误信息，但不暴露敏感内容
-        let hint = match e.kind() {
-            std::io::ErrorKind::NotFound => "文件不存在",
-            std::io::ErrorKind::PermissionDenied => "权限不足",
-            _ => "读取失败",
-        };
-        ConfigError::ReadError(format!("{} ({})", hint, path.display()))
-    })?;
+[env]
+ANTHROPIC_AUTH_TOKEN = "valid-api-key-12345"
+ANTHROPIC_BASE_URL = "https://api.anthropic.com"
 
-    // 解析 JSON
-    let settings: Settings = serde_json::from_str(&content).map_err(|e| {
-        // 提供详细的解析错误信息以帮助调试
-        let error_type = match e.classify() {
-            serde_json::error::Category::Io => "IO 错误",
-            serde_json::error::Category::Syntax => "语法错误",
-            serde_json::error::Category::Data => "数据类型错误",
-            serde_json::error::Category::Eof => "文件意外结束",
-        };
-        ConfigError::ParseError(format!(
-            "{}: 第 {} 行，第 {} 列\n   提示: 请检查 JSON 格式是否正确，特别是引号、逗号和括号",
-            error_type,
-            e.line(),
-            e.column()
-        ))
-    })?;
+[tool_timeouts]
+search = 30
 
-    // 验证配置
-    settings.validate()?;
+[workspace]
+discover = true
+"#,
+        )
+        .unwrap();
 
-    Ok(settings)
-}
+        let from_json = load_and_validate(json_path).unwrap();
+        let from_toml = load_and_validate(toml_path.clone()).unwrap();
+        assert_eq!(from_toml.version, from_json.version);
+        assert_eq!(from_toml.env.api_key, from_json.env.api_key);
+        assert_eq!(from_toml.env.base_url, from_json.env.base_url);
+        assert_eq!(from_toml.env.https_proxy, from_json.env.https_proxy);
+        assert_eq!(from_toml.get_model(), from_json.get_model());
+        assert_eq!(from_toml.get_max_tokens(), from_json.get_max_tokens());
+        assert_eq!(from_toml.staging, from_json.staging);
+        assert_eq!(
+            from_toml.get_writable_roots(),
+            from_json.get_writable_roots()
+        );
+        assert_eq!(from_toml.get_tool_timeouts(), from_json.get_tool_timeouts());
+        assert_eq!(from_toml.workspace.discover, from_json.workspace.discover);
 
-/// 创建默认配置文件模板
-pub fn create_default_config() -> Result<PathBuf, ConfigError> {
-    let config_path = PathBuf::from(DEFAULT_CONFIG_PATH);
+        // 语法错误给出行号
+        fs::write(&toml_path, "model = \"a\"\nmax_tokens = \n").unwrap();
+        let Err(err) = load_and_validate(toml_path) else {
+            panic!("invalid TOML should fail to load");
+        };
+        let err = err.to_string();
+        assert!(err.contains("第 2 行"), "{}", err);
+        let _ = fs::remove_dir_all(&dir);
+    }
 
-    // 确保目录存在
-    if let Some(parent) = config_path.parent() {
-        fs::create_dir_all(parent)
-            .map_err(|e| ConfigError::ReadError(format!("无法创建配置目录: {}", e)))?;
+    #[test]
+    fn test_config_search_paths_include_toml() {
+        let paths = get_config_search_paths();
+        assert_eq!(paths[0], PathBuf::from(".mentat/settings.json"));
+        assert_eq!(paths[1], PathBuf::from(".mentat/settings.toml"));
+        assert!(paths.iter().all(|p| p.extension().is_some()));
     }
 
-    // 创建模板配置
-    let template = r#"{
-  "env": {
-    "ANTHROPIC_AUTH_TOKEN": "your-api-key-here",
-    "ANTHROPIC_BASE_URL": "https://api.anthropic.com",
-    "HTTPS_PROXY": null
-  },
-  "model": "claude-opus-4-5-20251101"
-}
-"#;
+    #[test]
+    fn test_migrate_file_keeps_backup() {
+        let dir = std::env::temp_dir().join("mentat_test_migrate_config");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        let original = r#"{"env": {"ANTHROPIC_AUTH_TOKEN": "valid-api-key-12345", "ANTHROPIC_BASE_URL": "https://api.anthropic.com"}}"#;
+        fs::write(&path, original).unwrap();
 
-    fs::write(&config_path, template)
-        .map_err(|e| ConfigError::ReadError(format!("无法写入配置文件: {}", e)))?;
+        assert_eq!(migrate_file(&path).unwrap(), 0);
+        assert_eq!(
+            fs::read_to_string(dir.join("settings.json.bak")).unwrap(),
+            original
+        );
+        let settings = load_and_validate(path.clone()).unwrap();
+        assert_eq!(settings.version, Some(CONFIG_VERSION));
+        assert_eq!(migrate_file(&path).unwrap(), CONFIG_VERSION);
+        let _ = fs::remove_dir_all(&dir);
+    }
 
-    Ok(config_path)
-}
+    #[test]
+    fn test_build_config_from_answers() {
+        let answers = InitAnswers {
+            api_key: " valid-api-key-12345 ".to_string(),
+            base_url: "http://localhost:8080/".to_string(),
+            model: "claude-sonnet-4-5".to_string(),
+        };
+        let settings: Settings = serde_json::from_str(&build_config(&answers).unwrap()).unwrap();
+        assert_eq!(settings.version, Some(CONFIG_VERSION));
+        assert_eq!(settings.env.api_key, "valid-api-key-12345");
+        assert_eq!(settings.env.base_url, "http://localhost:8080");
+        assert_eq!(settings.get_model(), "claude-sonnet-4-5");
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // 留空的地址和模型使用默认值
+        let answers = InitAnswers {
+            api_key: "valid-api-key-12345".to_string(),
+            ..Default::default()
+        };
+        let settings: Settings = serde_json::from_str(&build_config(&answers).unwrap()).unwrap();
+        assert_eq!(settings.env.base_url, DEFAULT_BASE_URL);
+        assert_eq!(settings.get_model(), Settings::default().get_model());
 
-    #[test]
-    fn test_validate_empty_api_key() {
-        let settings = Settings {
-            env: Env {
-                api_key: "".to_string(),
-                base_url: "https://api.anthropic.com".to_string(),
-                https_proxy: None,
-            },
-            model: None,
+        // 生成的配置必须通过验证
+        assert!(matches!(
+            build_config(&InitAnswers::default()),
+            Err(ConfigError::ValidationError(_))
+        ));
+        let answers = InitAnswers {
+            api_key: "valid-api-key-12345".to_string(),
+            base_url: "localhost".to_string(),
+            ..Default::default()
         };
-        assert!(settings.validate().is_err());
+        assert!(build_config(&answers).is_err());
     }
 
     #[test]
-    fn test_validate_short_api_key() {
-        let settings = Settings {
-            env: Env {
-                api_key: "short".to_string(),
-                base_url: "https://api.anthropic.com".to_string(),
-                https_proxy: None,
-            },
-            model: None,
-        };
-        assert!(settings.validate().is_err());
+    fn test_default_template_is_current_version() {
+        let settings: Settings = serde_json::from_str(DEFAULT_CONFIG_TEMPLATE).unwrap();
+        assert_eq!(settings.version, Some(CONFIG_VERSION));
     }
 
     #[test]
-    fn test_validate_empty_base_url() {
-        let settings = Settings {
+    fn test_symlink_policy_setting() {
+        let mut settings = Settings {
             env: Env {
                 api_key: "valid-api-key-12345".to_string(),
-                base_url: "".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
                 https_proxy: None,
             },
-            model: None,
+            ..Default::default()
         };
-        assert!(settings.validate().is_err());
+        assert_eq!(settings.get_symlink_policy(), SymlinkPolicy::Resolve);
+
+        settings.symlink_policy = Some("reject".to_string());
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.get_symlink_policy(), SymlinkPolicy::Reject);
+
+        settings.symlink_policy = Some("follow".to_string());
+        assert!(settings
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("symlink_policy"));
     }
 
     #[test]
-    fn test_validate_invalid_base_url() {
-        let settings = Settings {
+    fn test_validate_writable_roots() {
+        let mut settings = Settings {
             env: Env {
                 api_key: "valid-api-key-12345".to_string(),
-                base_url: "not-a-url".to_string(),
+                base_url: "https://api.anthropic.com".to_string(),
                 https_proxy: None,
             },
-            model: None,
+            writable_roots: Some(vec!["src".to_string(), "tests".to_string()]),
+            ..Default::default()
         };
-        assert!(settings.validate().is_err());
+        assert!(settings.validate().is_ok());
+        assert_eq!(
+            settings.get_writable_roots(),
+            vec![PathBuf::from("src"), PathBuf::from("tests")]
+        );
+
+        for invalid in ["", "/etc", "../outside"] {
+            settings.writable_roots = Some(vec![invalid.to_string()]);
+            let err = settings.validate().unwrap_err().to_string();
+            assert!(err.contains("writable_roots"), "{}", err);
+        }
+
+        settings.writable_roots = None;
+        assert!(settings.get_writable_roots().is_empty());
     }
 
     #[test]
-    fn test_validate_invalid_proxy() {
-        let settings = Settings {
+    fn test_validate_extra_roots() {
+        let mut settings = Settings {
             env: Env {
                 api_key: "valid-api-key-12345".to_string(),
                 base_url: "https://api.anthropic.com".to_string(),
-                https_proxy: Some("invalid-proxy".to_string()),
+                https_proxy: None,
             },
-            model: None,
+            extra_roots: Some(HashMap::from([
+                ("shared".to_string(), "../shared".to_string()),
+                ("vendor_2".to_string(), "/opt/vendor".to_string()),
+            ])),
+            ..Default::default()
         };
-        assert!(settings.validate().is_err());
+        assert!(settings.validate().is_ok());
+        let roots = settings.get_extra_roots(Path::new("/work/app"));
+        assert_eq!(roots["shared"], PathBuf::from("/work/app/../shared"));
+        assert_eq!(roots["vendor_2"], PathBuf::from("/opt/vendor"));
+
+        for (name, root) in [
+            ("", "../x"),
+            ("a:b", "../x"),
+            ("has space", "../x"),
+            ("ok", " "),
+        ] {
+            settings.extra_roots = Some(HashMap::from([(name.to_string(), root.to_string())]));
+            let err = settings.validate().unwrap_err().to_string();
+            assert!(err.contains("extra_roots"), "{}", err);
+        }
+
+        settings.extra_roots = None;
+        assert!(settings.get_extra_roots(Path::new("/work")).is_empty());
     }
 
     #[test]
-    fn test_validate_valid_settings() {
-        let settings = Settings {
+    fn test_validate_sampling_ranges() {
+        let mut settings = Settings {
             env: Env {
                 api_key: "valid-api-key-12345".to_string(),
                 base_url: "https://api.anthropic.com".to_string(),
                 https_proxy: None,
             },
-            model: None,
+            temperature: Some(0.0),
+            top_p: Some(1.0),
+            ..Default::default()
         };
         assert!(settings.validate().is_ok());
+
+        settings.temperature = Some(1.5);
+        assert!(settings
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("temperature"));
+
+        settings.temperature = None;
+        settings.top_p = Some(-0.1);
+        assert!(settings
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("top_p"));
     }
 
     #[test]
-    fn test_validate_valid_settings_with_proxy() {
-        let settings = Settings {
+    fn test_anthropic_headers_default_and_validation() {
+        let mut settings = Settings {
             env: Env {
                 api_key: "valid-api-key-12345".to_string(),
                 base_url: "https://api.anthropic.com".to_string(),
-                https_proxy: Some("http://proxy.example.com:8080".to_string()),
+                https_proxy: None,
             },
-            model: None,
+            ..Default::default()
         };
+        assert_eq!(settings.get_anthropic_version(), "2023-06-01");
+
+        settings.anthropic_version = Some(" ".to_string());
+        assert!(settings.validate().is_err());
+
+        settings.anthropic_version = Some("2025-01-01".to_string());
+        settings.anthropic_beta = Some(vec!["a,b".to_string()]);
+        assert!(settings.validate().is_err());
+
+        settings.anthropic_beta = Some(vec!["context-1m-2025-08-07".to_string()]);
         assert!(settings.validate().is_ok());
+        assert_eq!(settings.get_anthropic_version(), "2025-01-01");
     }
 
     #[test]
-    fn test_get_model_default() {
-        let settings = Settings {
+    fn test_unknown_model_warns_without_failing() {
+        let mut settings = Settings {
             env: Env {
-                api_key: "test".to_string(),
+                api_key: "valid-api-key-12345".to_string(),
                 base_url: "https://api.anthropic.com".to_string(),
                 https_proxy: None,
             },
-            model: None,
+            model: Some("claude-sonnet-4-5".to_string()),
+            ..Default::default()
         };
-        assert_eq!(settings.get_model(), "claude-opus-4-5-20251101");
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.model_warning(), None);
+
+        settings.model = Some("claude-opus-4.5".to_string());
+        assert!(settings.validate().is_ok());
+        assert!(settings
+            .model_warning()
+            .is_some_and(|w| w.contains("claude-opus-4.5")));
+
+        // 本地模型不检查
+        settings.api_format = Some("openai".to_string());
+        assert_eq!(settings.model_warning(), None);
     }
 
     #[test]
-    fn test_get_model_custom() {
+    fn test_workspace_markers_default() {
+        let workspace = WorkspaceSettings::default();
+        assert_eq!(workspace.get_markers(), vec![".mentat", ".git"]);
+    }
+
+    #[test]
+    fn test_validate_empty_workspace_marker() {
         let settings = Settings {
             env: Env {
-                api_key: "test".to_string(),
+                api_key: "valid-api-key-12345".to_string(),
                 base_url: "https://api.anthropic.com".to_string(),
                 https_proxy: None,
             },
-            model: Some("claude-opus-4-5-20251101".to_string()),
+            workspace: WorkspaceSettings {
+                discover: true,
+                markers: Some(vec!["".to_string()]),
+            },
+            ..Default::default()
         };
-        assert_eq!(settings.get_model(), "claude-opus-4-5-20251101");
+        assert!(settings.validate().is_err());
     }
 
     #[test]
@@ -0,0 +1,138 @@
+//! 把对话导出为 Markdown
+//!
+//! 用户消息作为标题，助手文本作为正文，工具调用以 JSON 代码块展示，
+//! 工具结果折叠在 `<details>` 中，便于分享或归档。
+
+use crate::api::{parse_content_block, Message, MessageContent};
+use serde_json::Value;
+
+/// 把对话历史渲染为 Markdown 文档
+pub fn render_markdown(messages: &[Message]) -> String {
+    let mut out = String::from("# Mentat 对话记录\n");
+    let mut turn = 0;
+    for message in messages {
+        let blocks = match &message.content {
+            MessageContent::Text(text) => {
+                if message.role == "user" {
+                    turn += 1;
+                    out.push_str(&format!("\n## 用户（第 {} 轮）\n\n{}\n", turn, text));
+                } else {
+                    out.push_str(&format!("\n{}\n", text));
+                }
+                continue;
+            }
+            MessageContent::Blocks(blocks) => blocks,
+        };
+
+        // 只包含工具结果的 user 消息属于上一轮，不另起标题
+        let has_text = blocks.iter().any(|b| b["type"] == "text");
+        if message.role == "user" && has_text {
+            turn += 1;
+            out.push_str(&format!("\n## 用户（第 {} 轮）\n", turn));
+        }
+        for block in blocks {
+            render_block(&mut out, block);
+        }
+    }
+    out
+}
+
+/// 渲染单个内容块，thinking 等其他块不导出
+fn render_block(out: &mut String, block: &Value) {
+    match parse_content_block(block) {
+        Some(("text", data)) => {
+            let text = data.get("text").and_then(|v| v.as_str()).unwrap_or("");
+            out.push_str(&format!("\n{}\n", text));
+        }
+        Some(("tool_use", data)) => {
+            let name = data.get("name").and_then(|v| v.as_str()).unwrap_or("");
+            let input = data.get("input").unwrap_or(&Value::Null);
+            let json = serde_json::to_string_pretty(input).unwrap_or_default();
+            out.push_str(&format!("\n**🔧 {}**\n\n{}\n", name, fenced("json", &json)));
+        }
+        Some(("image" | "document", data)) => {
+            let media_type = data["source"]["media_type"].as_str().unwrap_or("");
+            out.push_str(&format!("\n📎 附件（{}）\n", media_type));
+        }
+        Some(("tool_result", data)) => {
+            let content = match data.get("content") {
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+                None => String::new(),
+            };
+            let id = data
+                .get("tool_use_id")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            out.push_str(&format!(
+                "\n<details>\n<summary>工具结果 {}</summary>\n\n{}\n</details>\n",
+                id,
+                fenced("", &content)
+            ));
+        }
+        _ => {}
+    }
+}
+
+/// 代码块，围栏比内容中最长的连续反引号多一个，避免内容提前结束代码块
+fn fenced(lang: &str, content: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}{}\n{}\n{}", fence, lang, content, fence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::create_tool_result;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_conversation() {
+        let messages = vec![
+            Message {
+                role: "user".to_string(),
+                content: MessageContent::Text("read Cargo.toml".to_string()),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Blocks(vec![
+                    json!({"type": "thinking", "thinking": "hidden"}),
+                    json!({"type": "text", "text": "Reading it."}),
+                    json!({"type": "tool_use", "id": "toolu_1", "name": "read_file", "input": {"file_path": "Cargo.toml"}}),
+                ]),
+            },
+            Message {
+                role: "user".to_string(),
+                content: MessageContent::Blocks(vec![create_tool_result(
+                    "toolu_1",
+                    r#"{"success":true}"#,
+                )]),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Text("It is a Rust crate.".to_string()),
+            },
+        ];
+
+        let markdown = render_markdown(&messages);
+
+        assert!(markdown.starts_with("# Mentat 对话记录\n"));
+        assert_eq!(markdown.matches("## 用户").count(), 1);
+        assert!(markdown.contains("## 用户（第 1 轮）\n\nread Cargo.toml\n"));
+        assert!(markdown.contains("\nReading it.\n"));
+        assert!(!markdown.contains("hidden"));
+        assert!(markdown
+            .contains("**🔧 read_file**\n\n```json\n{\n  \"file_path\": \"Cargo.toml\"\n}\n```"));
+        assert!(markdown.contains(
+            "<details>\n<summary>工具结果 toolu_1</summary>\n\n```\n{\"success\":true}\n```\n</details>"
+        ));
+        assert!(markdown.ends_with("\nIt is a Rust crate.\n"));
+    }
+
+    #[test]
+    fn test_fence_longer_than_content_backticks() {
+        assert_eq!(fenced("", "a ```rust b"), "````\na ```rust b\n````");
+        assert_eq!(fenced("json", "{}"), "```json\n{}\n```");
+    }
+}
@@ -0,0 +1,381 @@
+//! 对话历史操作
+//!
+//! 回退（rewind）对话时只能截断在完整轮次的边界上：
+//! 截断后的最后一条消息必须是不含 `tool_use` 的助手回复，
+//! 否则会留下没有 `tool_result` 的工具调用，或与下一条用户消息连续出现两条用户消息。
+//!
+//! 按 token 预算裁剪历史时同样只在轮次边界上丢弃最早的消息。
+
+use crate::api::{Message, MessageContent};
+use serde_json::Value;
+use std::fmt;
+
+/// 回退失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RewindError {
+    /// 索引超出对话长度
+    OutOfRange { index: usize, len: usize },
+    /// 索引不是完整轮次的边界
+    InvalidCutPoint { index: usize, valid: Vec<usize> },
+    /// 要撤销的轮数超过已有的轮数
+    NotEnoughTurns { requested: usize, available: usize },
+}
+
+impl fmt::Display for RewindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RewindError::OutOfRange { index, len } => {
+                write!(f, "索引 {} 超出范围（当前共 {} 条消息）", index, len)
+            }
+            RewindError::InvalidCutPoint { index, valid } => {
+                let valid = valid
+                    .iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    f,
+                    "索引 {} 不在完整轮次的边界上，可用的位置: {}",
+                    index, valid
+                )
+            }
+            RewindError::NotEnoughTurns {
+                requested,
+                available,
+            } => write!(
+                f,
+                "无法撤销 {} 轮，当前只有 {} 轮对话",
+                requested, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RewindError {}
+
+/// 粗略估算 token 数时每个 token 对应的字符数
+const CHARS_PER_TOKEN: usize = 4;
+
+/// 超出 token 预算时的裁剪策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrimStrategy {
+    /// 直接丢弃最早的轮次
+    #[default]
+    DropOldest,
+    /// 让模型把最早的轮次总结为一段摘要
+    Summarize,
+}
+
+impl TrimStrategy {
+    /// 解析配置中的策略名称
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "drop_oldest" => Some(TrimStrategy::DropOldest),
+            "summarize" => Some(TrimStrategy::Summarize),
+            _ => None,
+        }
+    }
+}
+
+/// 按字符数粗略估算消息的 token 数（约 4 个字符一个 token）
+pub fn estimate_tokens(messages: &[Message]) -> usize {
+    let chars: usize = messages
+        .iter()
+        .map(|message| match &message.content {
+            MessageContent::Text(text) => text.chars().count(),
+            MessageContent::Blocks(blocks) => blocks.iter().map(block_chars).sum(),
+        })
+        .sum();
+    chars.div_ceil(CHARS_PER_TOKEN)
+}
+
+/// 按字符数粗略估算一段文本的 token 数
+pub fn estimate_text_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// 附件按固定大小估算，base64 数据的长度与实际 token 数无关
+const ATTACHMENT_CHARS: usize = 1600 * CHARS_PER_TOKEN;
+
+/// 单个 content block 的估算字符数
+fn block_chars(block: &Value) -> usize {
+    match block.get("type").and_then(|t| t.as_str()) {
+        Some("image" | "document") => ATTACHMENT_CHARS,
+        _ => block.to_string().chars().count(),
+    }
+}
+
+/// 为满足 token 预算需要从开头丢弃的消息数；无需裁剪时返回 0
+///
+/// 只在轮次边界上裁剪，保留的部分总是从一条用户消息开始，角色交替不被破坏。
+/// 最近一轮（含最新的用户消息）始终保留，即使它本身已超出预算。
+pub fn trim_point(messages: &[Message], budget: usize) -> usize {
+    if estimate_tokens(messages) <= budget {
+        return 0;
+    }
+    let candidates: Vec<usize> = valid_cut_points(messages)
+        .into_iter()
+        .filter(|&i| i > 0 && i < messages.len())
+        .collect();
+    candidates
+        .iter()
+        .copied()
+        .find(|&i| estimate_tokens(&messages[i..]) <= budget)
+        .or(candidates.last().copied())
+        .unwrap_or(0)
+}
+
+/// 消息是否包含 `tool_use` block
+fn has_tool_use(message: &Message) -> bool {
+    match &message.content {
+        MessageContent::Text(_) => false,
+        MessageContent::Blocks(blocks) => blocks
+            .iter()
+            .any(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_use")),
+    }
+}
+
+/// 保留前 `index` 条消息后，对话是否仍然完整
+pub fn is_valid_cut_point(messages: &[Message], index: usize) -> bool {
+    if index == 0 {
+        return true;
+    }
+    match messages.get(index - 1) {
+        Some(last) => last.role == "assistant" && !has_tool_use(last),
+        None => false,
+    }
+}
+
+/// 所有可用的截断位置（升序，包含 0）
+pub fn valid_cut_points(messages: &[Message]) -> Vec<usize> {
+    (0..=messages.len())
+        .filter(|&i| is_valid_cut_point(messages, i))
+        .collect()
+}
+
+/// 把对话截断为前 `index` 条消息
+///
+/// # Returns
+/// 被丢弃的消息数
+pub fn rewind(messages: &mut Vec<Message>, index: usize) -> Result<usize, RewindError> {
+    let len = messages.len();
+    if index > len {
+        return Err(RewindError::OutOfRange { index, len });
+    }
+    if !is_valid_cut_point(messages, index) {
+        return Err(RewindError::InvalidCutPoint {
+            index,
+            valid: valid_cut_points(messages),
+        });
+    }
+
+    messages.truncate(index);
+    Ok(len - index)
+}
+
+/// 撤销最近的 `turns` 轮对话
+///
+/// 一轮从用户输入开始，包括其间所有的 tool_use / tool_result 往返；
+/// 撤销后对话仍以用户消息开始并保持角色交替。
+///
+/// # Returns
+/// 被丢弃的消息数
+pub fn rewind_turns(messages: &mut Vec<Message>, turns: usize) -> Result<usize, RewindError> {
+    // 每轮的起点：完整轮次边界上的用户消息
+    let starts: Vec<usize> = valid_cut_points(messages)
+        .into_iter()
+        .filter(|&i| messages.get(i).is_some_and(|m| m.role == "user"))
+        .collect();
+    if turns == 0 || turns > starts.len() {
+        return Err(RewindError::NotEnoughTurns {
+            requested: turns,
+            available: starts.len(),
+        });
+    }
+
+    let index = starts[starts.len() - turns];
+    let dropped = messages.len() - index;
+    messages.truncate(index);
+    Ok(dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn text(role: &str, text: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: MessageContent::Text(text.to_string()),
+        }
+    }
+
+    fn blocks(role: &str, blocks: Vec<serde_json::Value>) -> Message {
+        Message {
+            role: role.to_string(),
+            content: MessageContent::Blocks(blocks),
+        }
+    }
+
+    /// 两轮对话，第二轮包含一次工具调用
+    fn conversation() -> Vec<Message> {
+        vec![
+            text("user", "hi"),
+            blocks("assistant", vec![json!({"type": "text", "text": "hello"})]),
+            text("user", "read Cargo.toml"),
+            blocks(
+                "assistant",
+                vec![json!({"type": "tool_use", "id": "t1", "name": "read_file", "input": {}})],
+            ),
+            blocks(
+                "user",
+                vec![json!({"type": "tool_result", "tool_use_id": "t1", "content": "ok"})],
+            ),
+            blocks("assistant", vec![json!({"type": "text", "text": "done"})]),
+        ]
+    }
+
+    #[test]
+    fn test_valid_cut_points_are_turn_boundaries() {
+        assert_eq!(valid_cut_points(&conversation()), vec![0, 2, 6]);
+    }
+
+    #[test]
+    fn test_rewind_truncates_at_turn_boundary() {
+        let mut messages = conversation();
+        assert_eq!(rewind(&mut messages, 2), Ok(4));
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].role, "assistant");
+    }
+
+    #[test]
+    fn test_rewind_rejects_dangling_tool_use() {
+        let mut messages = conversation();
+        assert_eq!(
+            rewind(&mut messages, 4),
+            Err(RewindError::InvalidCutPoint {
+                index: 4,
+                valid: vec![0, 2, 6]
+            })
+        );
+        assert_eq!(messages.len(), 6);
+    }
+
+    #[test]
+    fn test_rewind_rejects_trailing_user_message() {
+        let mut messages = conversation();
+        assert!(rewind(&mut messages, 1).is_err());
+        assert!(rewind(&mut messages, 5).is_err());
+    }
+
+    #[test]
+    fn test_rewind_out_of_range() {
+        let mut messages = conversation();
+        assert_eq!(
+            rewind(&mut messages, 7),
+            Err(RewindError::OutOfRange { index: 7, len: 6 })
+        );
+    }
+
+    /// 检查角色交替且以用户消息开始
+    fn assert_alternating(messages: &[Message]) {
+        assert_eq!(messages[0].role, "user");
+        assert!(matches!(messages[0].content, MessageContent::Text(_)));
+        for pair in messages.windows(2) {
+            assert_ne!(pair[0].role, pair[1].role);
+        }
+    }
+
+    #[test]
+    fn test_estimate_tokens_by_chars() {
+        assert_eq!(estimate_tokens(&[]), 0);
+        assert_eq!(estimate_tokens(&[text("user", "abcdefgh")]), 2);
+        assert_eq!(estimate_tokens(&[text("user", "abcdefghi")]), 3);
+
+        // 附件按固定大小估算，与 base64 数据长度无关
+        let image = Message {
+            role: "user".to_string(),
+            content: MessageContent::Blocks(vec![crate::api::create_attachment_block(
+                "image/png",
+                &"A".repeat(100_000),
+            )]),
+        };
+        assert_eq!(estimate_tokens(&[image]), 1600);
+    }
+
+    #[test]
+    fn test_trim_point_keeps_alternation_and_latest_turn() {
+        let mut messages = conversation();
+        messages.push(text("user", "next question"));
+        let total = estimate_tokens(&messages);
+
+        // 预算足够时不裁剪
+        assert_eq!(trim_point(&messages, total), 0);
+
+        // 只丢弃第一轮
+        let without_first = estimate_tokens(&messages[2..]);
+        assert_eq!(trim_point(&messages, without_first), 2);
+        assert_alternating(&messages[2..]);
+
+        // 预算再小也保留最新的用户消息
+        let cut = trim_point(&messages, 1);
+        assert_eq!(cut, 6);
+        assert_alternating(&messages[cut..]);
+    }
+
+    #[test]
+    fn test_trim_point_mid_turn_keeps_pending_tool_calls() {
+        // 最后一轮仍在进行工具调用，不能从中间截断
+        let messages = conversation()[..5].to_vec();
+        let cut = trim_point(&messages, 1);
+        assert_eq!(cut, 2);
+        assert_alternating(&messages[cut..]);
+    }
+
+    #[test]
+    fn test_trim_strategy_parse() {
+        assert_eq!(
+            TrimStrategy::parse("drop_oldest"),
+            Some(TrimStrategy::DropOldest)
+        );
+        assert_eq!(
+            TrimStrategy::parse("summarize"),
+            Some(TrimStrategy::Summarize)
+        );
+        assert_eq!(TrimStrategy::parse("random"), None);
+    }
+
+    #[test]
+    fn test_rewind_turn_removes_whole_tool_exchange() {
+        let mut messages = conversation();
+        assert_eq!(rewind_turns(&mut messages, 1), Ok(4));
+        assert_eq!(messages.len(), 2);
+        assert_alternating(&messages);
+        assert!(!messages.iter().any(has_tool_use));
+    }
+
+    #[test]
+    fn test_rewind_multiple_turns() {
+        let mut messages = conversation();
+        assert_eq!(
+            rewind_turns(&mut messages, 3),
+            Err(RewindError::NotEnoughTurns {
+                requested: 3,
+                available: 2
+            })
+        );
+        assert_eq!(messages.len(), 6);
+
+        assert_eq!(rewind_turns(&mut messages, 2), Ok(6));
+        assert!(messages.is_empty());
+        assert!(rewind_turns(&mut messages, 1).is_err());
+    }
+
+    #[test]
+    fn test_rewind_to_start_clears_everything() {
+        let mut messages = conversation();
+        assert_eq!(rewind(&mut messages, 0), Ok(6));
+        assert!(messages.is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+//! 空闲自动清除
+//!
+//! 用于共享终端：距离上次输入超过设定时长后，下一次输入前清除对话，
+//! 避免离开的用户的上下文暴露给下一个人。进程本身不退出。
+
+use std::time::{Duration, Instant};
+
+/// 空闲计时器
+#[derive(Debug)]
+pub struct IdleTimer {
+    limit: Duration,
+    last_input: Instant,
+}
+
+impl IdleTimer {
+    /// 创建计时器，从 `now` 开始计时
+    pub fn new(limit: Duration, now: Instant) -> Self {
+        Self {
+            limit,
+            last_input: now,
+        }
+    }
+
+    /// 记录一次输入，返回距上次输入是否已超过空闲时长
+    pub fn record_input(&mut self, now: Instant) -> bool {
+        let expired = now.saturating_duration_since(self.last_input) >= self.limit;
+        self.last_input = now;
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_within_limit_does_not_expire() {
+        let start = Instant::now();
+        let mut timer = IdleTimer::new(Duration::from_secs(60), start);
+        assert!(!timer.record_input(start + Duration::from_secs(59)));
+        // 每次输入都会重新计时
+        assert!(!timer.record_input(start + Duration::from_secs(118)));
+    }
+
+    #[test]
+    fn test_input_after_limit_expires_once() {
+        let start = Instant::now();
+        let mut timer = IdleTimer::new(Duration::from_secs(60), start);
+        let late = start + Duration::from_secs(60);
+        assert!(timer.record_input(late));
+        assert!(!timer.record_input(late + Duration::from_secs(1)));
+    }
+}
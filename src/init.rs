@@ -0,0 +1,84 @@
+//! `--init`：交互式创建配置文件
+//!
+//! 依次询问 API 密钥（输入时隐藏）、API 地址和模型，生成的配置经过验证后
+//! 写入默认路径；配置文件已存在时先确认是否覆盖。
+
+use crate::config::{self, InitAnswers, DEFAULT_BASE_URL, DEFAULT_CONFIG_PATH};
+use rustyline::completion::Completer;
+use rustyline::config::Configurer;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{ColorMode, Editor, Helper};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use unicode_width::UnicodeWidthStr;
+
+/// 输入 API 密钥时把已输入的内容显示为 `*`
+struct MaskingHelper {
+    masking: bool,
+}
+
+impl Highlighter for MaskingHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if self.masking {
+            Cow::Owned("*".repeat(line.width()))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+        self.masking && kind != CmdKind::MoveCursor
+    }
+}
+
+impl Completer for MaskingHelper {
+    type Candidate = String;
+}
+
+impl Hinter for MaskingHelper {
+    type Hint = String;
+}
+
+impl Validator for MaskingHelper {}
+
+impl Helper for MaskingHelper {}
+
+/// 运行交互式初始化；用户拒绝覆盖已有配置时返回 `None`
+pub fn run() -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let mut rl: Editor<MaskingHelper, DefaultHistory> = Editor::new()?;
+    rl.set_helper(Some(MaskingHelper { masking: false }));
+
+    if Path::new(DEFAULT_CONFIG_PATH).exists() {
+        let answer = rl.readline(&format!(
+            "配置文件已存在: {}，是否覆盖？[y/N] ",
+            DEFAULT_CONFIG_PATH
+        ))?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            return Ok(None);
+        }
+    }
+
+    if let Some(helper) = rl.helper_mut() {
+        helper.masking = true;
+    }
+    rl.set_color_mode(ColorMode::Forced);
+    let api_key = rl.readline("API 密钥: ")?;
+    if let Some(helper) = rl.helper_mut() {
+        helper.masking = false;
+    }
+    rl.set_color_mode(ColorMode::Enabled);
+
+    let base_url = rl.readline(&format!("API 地址 [{}]: ", DEFAULT_BASE_URL))?;
+    let default_model = config::Settings::default().get_model();
+    let model = rl.readline(&format!("模型 [{}]: ", default_model))?;
+
+    let answers = InitAnswers {
+        api_key,
+        base_url,
+        model,
+    };
+    Ok(Some(config::create_default_config(&answers)?))
+}
@@ -0,0 +1,100 @@
+//! REPL 输入历史的持久化
+//!
+//! 输入历史保存在配置的文件中（默认 `.mentat/history.txt`），条数有上限，
+//! 超出时丢弃最早的记录，避免文件无限增长。
+
+use rustyline::history::History;
+use rustyline::DefaultEditor;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// 输入历史文件
+#[derive(Debug, Clone)]
+pub struct InputHistory {
+    path: PathBuf,
+    max_entries: usize,
+}
+
+impl InputHistory {
+    pub fn new(path: PathBuf, max_entries: usize) -> Self {
+        Self { path, max_entries }
+    }
+
+    /// 设置条数上限并加载历史文件（文件不存在时忽略）
+    pub fn load(&self, rl: &mut DefaultEditor) {
+        let _ = trim(rl, self.max_entries);
+        let _ = rl.load_history(&self.path);
+    }
+
+    /// 裁剪到条数上限后保存，目录不存在时自动创建
+    pub fn save(&self, rl: &mut DefaultEditor) -> rustyline::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        trim(rl, self.max_entries)?;
+        rl.save_history(&self.path)
+    }
+
+    /// 清空内存中的输入历史并删除历史文件
+    pub fn clear(&self, rl: &mut DefaultEditor) -> io::Result<()> {
+        let _ = rl.clear_history();
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 只保留最近的 `max_entries` 条输入
+pub fn trim(rl: &mut DefaultEditor, max_entries: usize) -> rustyline::Result<()> {
+    rl.history_mut().set_max_len(max_entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(rl: &DefaultEditor) -> Vec<String> {
+        rl.history().iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_trim_keeps_most_recent_entries() {
+        let mut rl = DefaultEditor::new().unwrap();
+        for i in 0..5 {
+            rl.add_history_entry(format!("input {}", i)).unwrap();
+        }
+
+        trim(&mut rl, 2).unwrap();
+        assert_eq!(entries(&rl), vec!["input 3", "input 4"]);
+    }
+
+    #[test]
+    fn test_save_creates_dir_and_caps_file() {
+        let root = std::env::temp_dir().join("mentat_test_input_history");
+        let _ = fs::remove_dir_all(&root);
+        let history = InputHistory::new(root.join("nested/history.txt"), 3);
+
+        let mut rl = DefaultEditor::new().unwrap();
+        history.load(&mut rl);
+        for i in 0..10 {
+            rl.add_history_entry(format!("input {}", i)).unwrap();
+        }
+        history.save(&mut rl).unwrap();
+
+        let mut reloaded = DefaultEditor::new().unwrap();
+        history.load(&mut reloaded);
+        assert_eq!(entries(&reloaded), vec!["input 7", "input 8", "input 9"]);
+
+        history.clear(&mut reloaded).unwrap();
+        assert!(entries(&reloaded).is_empty());
+        assert!(!root.join("nested/history.txt").exists());
+        // 文件已不存在时再次清除不报错
+        history.clear(&mut reloaded).unwrap();
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
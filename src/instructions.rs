@@ -0,0 +1,73 @@
+//! 项目级指令
+//!
+//! 工作区根目录下的 `MENTAT.md`（或 `.mentat/instructions.md`）会在启动时
+//! 自动读取，放在系统提示的最前面，用于注入每个仓库自己的约定。
+
+use crate::tools::ToolContext;
+use log::{debug, warn};
+use std::fs;
+
+/// 按优先级排列的项目指令文件（相对于工作区根目录）
+pub const INSTRUCTION_FILES: [&str; 2] = ["MENTAT.md", ".mentat/instructions.md"];
+
+/// 读取第一个存在的项目指令文件；都不存在时返回 `None`
+///
+/// 路径经过工具沙箱同样的校验，指向工作区外的符号链接会被忽略。
+pub fn load(ctx: &ToolContext) -> Option<String> {
+    for name in INSTRUCTION_FILES {
+        let Ok(path) = ctx.validator().validate_for_read(name) else {
+            continue;
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                debug!("已加载项目指令: {}", name);
+                return Some(content);
+            }
+            Err(e) => warn!("无法读取项目指令 {}: {}", name, e),
+        }
+    }
+    None
+}
+
+/// 组合系统提示：项目指令在前，配置中的系统提示在后
+pub fn compose_system_prompt(
+    instructions: Option<String>,
+    system_prompt: Option<String>,
+) -> Option<String> {
+    match (instructions, system_prompt) {
+        (Some(instructions), Some(prompt)) => Some(format!("{}\n\n{}", instructions, prompt)),
+        (instructions, prompt) => instructions.or(prompt),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_falls_back_to_dot_mentat_and_ignores_missing() {
+        let root = std::env::temp_dir().join("mentat_test_instructions");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(".mentat")).unwrap();
+        let ctx = ToolContext::new(root.clone());
+
+        assert_eq!(load(&ctx), None);
+
+        fs::write(root.join(".mentat/instructions.md"), "Use tabs.").unwrap();
+        assert_eq!(load(&ctx).as_deref(), Some("Use tabs."));
+
+        fs::write(root.join("MENTAT.md"), "Use spaces.").unwrap();
+        assert_eq!(load(&ctx).as_deref(), Some("Use spaces."));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_compose_system_prompt() {
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(compose_system_prompt(None, None), None);
+        assert_eq!(compose_system_prompt(some("a"), None), some("a"));
+        assert_eq!(compose_system_prompt(None, some("b")), some("b"));
+        assert_eq!(compose_system_prompt(some("a"), some("b")), some("a\n\nb"));
+    }
+}
@@ -0,0 +1,209 @@
+//! 终端提示中的装饰符号
+//!
+//! 默认使用 emoji 前缀（🤖、🔧 等）；在不支持 emoji 的终端、CI 日志或 Windows 控制台中
+//! 改用 `[AI]`、`[tool]` 这样的 ASCII 标记。启用 `ascii_only`、`TERM=dumb` 或标准输出
+//! 不是终端时自动切换。
+
+use crate::spinner;
+use std::borrow::Cow;
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// emoji 与对应的 ASCII 标记
+const EMOJI_LABELS: &[(&str, &str)] = &[
+    ("🤖", "[AI]"),
+    ("🔧", "[tool]"),
+    ("📤", "[output]"),
+    ("💭", "[thinking]"),
+    ("📊", "[usage]"),
+    ("❌", "[error]"),
+    ("⚠", "[warn]"),
+    ("⛔", "[stopped]"),
+    ("ℹ", "[info]"),
+    ("✅", "[ok]"),
+    ("💡", "[hint]"),
+    ("❓", "[?]"),
+    ("📝", "[note]"),
+    ("🆕", "[new]"),
+    ("💾", "[saved]"),
+    ("📂", "[session]"),
+    ("⏪", "[rewind]"),
+    ("🗜", "[compact]"),
+    ("🧠", "[model]"),
+    ("🌡", "[temp]"),
+    ("🧪", "[dry-run]"),
+    ("📜", "[system]"),
+    ("🧹", "[clear]"),
+    ("🔢", "[tokens]"),
+    ("📎", "[attach]"),
+    ("📋", "[clipboard]"),
+    ("🗑", "[trash]"),
+    ("♻", "[restored]"),
+    ("📦", "[staged]"),
+    ("📭", "[empty]"),
+    ("📄", "[file]"),
+    ("⏭", "[skip]"),
+    ("⏳", "[wait]"),
+    ("📚", "[help]"),
+    ("🔒", "[locked]"),
+    ("🎭", "[persona]"),
+    ("👋", "[bye]"),
+    ("❯", ">"),
+];
+
+/// emoji 之后的变体选择符（如 `⚠️` 中的 U+FE0F）
+const VARIATION_SELECTOR: char = '\u{fe0f}';
+
+/// 终端提示使用的装饰符号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Labels {
+    ascii: bool,
+}
+
+impl Labels {
+    pub fn new(ascii: bool) -> Self {
+        Self { ascii }
+    }
+
+    /// 按配置和当前终端决定是否使用 ASCII 标记
+    pub fn detect(ascii_only: bool) -> Self {
+        let term = env::var("TERM").ok();
+        Self::new(use_ascii(
+            ascii_only,
+            term.as_deref(),
+            io::stdout().is_terminal(),
+        ))
+    }
+
+    /// 助手回复的前缀
+    pub fn assistant(&self) -> &'static str {
+        self.pick("🤖")
+    }
+
+    /// 工具调用的前缀
+    pub fn tool(&self) -> &'static str {
+        self.pick("🔧")
+    }
+
+    /// 工具输出的前缀
+    pub fn tool_output(&self) -> &'static str {
+        self.pick("📤")
+    }
+
+    /// 思考过程的前缀
+    pub fn thinking(&self) -> &'static str {
+        self.pick("💭")
+    }
+
+    /// token 用量的前缀
+    pub fn usage(&self) -> &'static str {
+        self.pick("📊")
+    }
+
+    /// 输入提示符
+    pub fn prompt(&self) -> &'static str {
+        self.pick("❯")
+    }
+
+    /// 进度动画帧
+    pub fn spinner_frames(&self) -> &'static [char] {
+        if self.ascii {
+            spinner::ASCII_FRAMES
+        } else {
+            spinner::FRAMES
+        }
+    }
+
+    /// 把文本中的装饰 emoji 替换为 ASCII 标记；emoji 模式下原样返回
+    ///
+    /// emoji 后为对齐而多加的空格（如 `⚠️  `）同时压缩为一个。
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !self.ascii || text.is_ascii() {
+            return Cow::Borrowed(text);
+        }
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            match EMOJI_LABELS
+                .iter()
+                .find(|(emoji, _)| rest.starts_with(emoji))
+            {
+                Some((emoji, label)) => {
+                    result.push_str(label);
+                    rest = rest[emoji.len()..].trim_start_matches(VARIATION_SELECTOR);
+                    if rest.starts_with("  ") {
+                        rest = &rest[1..];
+                    }
+                }
+                None => {
+                    result.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        Cow::Owned(result)
+    }
+
+    /// 表中 emoji 对应的符号
+    fn pick(&self, emoji: &'static str) -> &'static str {
+        if !self.ascii {
+            return emoji;
+        }
+        EMOJI_LABELS
+            .iter()
+            .find(|(e, _)| *e == emoji)
+            .map_or(emoji, |(_, label)| label)
+    }
+}
+
+/// 配置了 `ascii_only`、`TERM=dumb` 或标准输出不是终端时使用 ASCII 标记
+pub fn use_ascii(ascii_only: bool, term: Option<&str>, is_tty: bool) -> bool {
+    ascii_only || term == Some("dumb") || !is_tty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_labels() {
+        let ascii = Labels::new(true);
+        assert_eq!(ascii.assistant(), "[AI]");
+        assert_eq!(ascii.tool(), "[tool]");
+        assert_eq!(ascii.prompt(), ">");
+        assert!(ascii.spinner_frames().iter().all(char::is_ascii));
+
+        let emoji = Labels::new(false);
+        assert_eq!(emoji.assistant(), "🤖");
+        assert_eq!(emoji.tool(), "🔧");
+        assert_eq!(emoji.prompt(), "❯");
+    }
+
+    #[test]
+    fn test_apply_replaces_emoji() {
+        let ascii = Labels::new(true);
+        assert_eq!(ascii.apply("👋 再见！"), "[bye] 再见！");
+        assert_eq!(
+            ascii.apply("⚠️  回复被截断 (max_tokens)"),
+            "[warn] 回复被截断 (max_tokens)"
+        );
+        assert_eq!(
+            ascii.apply("  ♻️  已恢复 a.txt"),
+            "  [restored] 已恢复 a.txt"
+        );
+        assert_eq!(ascii.apply("中文 ✓ 不变"), "中文 ✓ 不变");
+        assert!(ascii.apply("plain text").is_ascii());
+
+        let emoji = Labels::new(false);
+        assert_eq!(emoji.apply("👋 再见！"), "👋 再见！");
+    }
+
+    #[test]
+    fn test_use_ascii_detection() {
+        assert!(!use_ascii(false, Some("xterm-256color"), true));
+        assert!(!use_ascii(false, None, true));
+        assert!(use_ascii(true, Some("xterm-256color"), true));
+        assert!(use_ascii(false, Some("dumb"), true));
+        assert!(use_ascii(false, Some("xterm"), false));
+    }
+}
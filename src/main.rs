@@ -1,17 +1,69 @@
+mod api;
+mod approval;
+mod attachment;
+mod clipboard;
 mod config;
+mod export;
+mod history;
+mod idle;
+mod init;
+mod input_history;
+mod instructions;
+mod labels;
+mod multiline;
+mod openai;
+mod output;
+mod preflight;
+mod pricing;
+mod render;
+mod session;
+mod spinner;
+mod staging;
+mod stream;
+#[cfg(test)]
+mod test_support;
+mod tool_cache;
+mod tool_log;
 mod tools;
+mod trace;
+mod workspace;
 
+use api::{
+    create_tool_result, parse_content_block, AnthropicRequest, AnthropicResponse, ApiError,
+    ApiFormat, AuthScheme, CountTokensRequest, CountTokensResponse, Message, MessageContent,
+    SystemPrompt, ThinkingConfig, Usage,
+};
+use approval::{ApprovalPolicy, Approver, StdinApprover};
 use clap::Parser;
-use config::Settings;
+use config::{HttpClientOptions, Settings};
+use history::TrimStrategy;
+use input_history::InputHistory;
+use labels::Labels;
 use log::{debug, error, info, warn};
-use reqwest::blocking::Client;
+use multiline::MultilineBuffer;
+use output::{JsonOutput, OutputSink, PrettyOutput, StdoutOutput};
+use pricing::{CostEstimate, ModelPrice};
+use reqwest::blocking::{Client, RequestBuilder, Response};
 use rustyline::error::ReadlineError;
 use rustyline::{DefaultEditor, Result as RlResult};
-use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use spinner::Spinner;
+use staging::{ReviewChoice, StagedChange, StagingArea};
+use std::collections::HashMap;
 use std::fs;
+use std::io::{self, BufReader, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process;
-use tools::ToolRegistry;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use stream::StreamError;
+use tool_cache::ToolCallCache;
+use tool_log::ToolLog;
+use tools::{IgnoreRules, ToolContext, ToolRegistry};
+use trace::TraceRecorder;
 
 // ============== CLI 参数定义 ==============
 
@@ -25,6 +77,10 @@ struct Cli {
     #[arg(short, long, value_name = "FILE")]
     config: Option<String>,
 
+    /// 启动前先切换到该目录，相当于先 cd 再运行（同 `git -C`）
+    #[arg(short = 'C', long, value_name = "DIR")]
+    workdir: Option<PathBuf>,
+
     /// 启用调试模式（显示详细日志）
     #[arg(short, long)]
     debug: bool,
@@ -33,200 +89,681 @@ struct Cli {
     #[arg(long, default_value = "info")]
     log_level: String,
 
-    /// 直接执行单条命令后退出（非交互模式）
-    #[arg(short, long, value_name = "PROMPT")]
+    /// 直接执行单条命令后退出（非交互模式）；标准输入不是终端时从中读取提示
+    #[arg(
+        short,
+        long,
+        short_alias = 'p',
+        alias = "prompt",
+        value_name = "PROMPT"
+    )]
     execute: Option<String>,
 
-    /// 初始化配置文件
+    /// 交互式初始化配置文件（API 密钥、地址和模型）
     #[arg(long)]
     init: bool,
-}
 
-// ============== API 请求/响应结构 ==============
+    /// 把配置文件升级为当前格式版本（原文件备份为 .bak）
+    #[arg(long)]
+    migrate_config: bool,
 
-#[derive(Serialize, Clone, Debug)]
-struct Message {
-    role: String,
-    content: MessageContent,
-}
+    /// 启动时检查 base_url 和 API 密钥是否可用
+    #[arg(long)]
+    check: bool,
 
-#[derive(Serialize, Clone, Debug)]
-#[serde(untagged)]
-enum MessageContent {
-    Text(String),
-    Blocks(Vec<Value>),
+    /// 自动同意所有文件修改，不再逐次确认
+    #[arg(short = 'y', long)]
+    yes: bool,
+
+    /// 安静模式：只显示助手回复，不显示工具调用和 token 用量
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// 详细模式：显示完整的工具输入和工具输出
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// 单命令模式下每轮输出一个 JSON 对象（回复文本、工具调用、用量、stop_reason）
+    #[arg(long)]
+    json: bool,
 }
 
-#[derive(Serialize)]
-struct AnthropicRequest {
-    model: String,
-    max_tokens: u32,
-    messages: Vec<Message>,
-    tools: Vec<Value>,
+/// 运行模式
+#[derive(Debug, PartialEq)]
+enum RunMode {
+    /// 交互式 REPL
+    Repl,
+    /// 执行单条提示后退出
+    OneShot(String),
 }
 
-#[derive(Deserialize, Debug)]
-struct AnthropicResponse {
-    content: Vec<Value>,
-    #[allow(dead_code)]
-    stop_reason: Option<String>,
+/// 选择运行模式：命令行给出提示时直接执行；否则标准输入不是终端（如管道）时
+/// 读取其全部内容作为提示；都没有时进入 REPL
+fn select_run_mode(
+    execute: Option<String>,
+    stdin_is_tty: bool,
+    mut stdin: impl io::Read,
+) -> io::Result<RunMode> {
+    if let Some(prompt) = execute {
+        return Ok(RunMode::OneShot(prompt));
+    }
+    if stdin_is_tty {
+        return Ok(RunMode::Repl);
+    }
+    let mut prompt = String::new();
+    stdin.read_to_string(&mut prompt)?;
+    if prompt.trim().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "标准输入中没有提示内容",
+        ));
+    }
+    Ok(RunMode::OneShot(prompt.trim_end().to_string()))
 }
 
-// ============== Content Block 处理 ==============
+// ============== Chat Client ==============
+
+/// 收到 `pause_turn` 时自动续传的最大次数
+const MAX_PAUSE_CONTINUATIONS: u32 = 5;
+
+/// 工具调用往返达到上限时附在最后一批工具结果之后的提示
+const TOOL_LIMIT_NOTE: &str = "You have reached the tool call limit for this turn. Do not call any more tools. Summarize what you have done so far and what remains, so the user can decide how to continue.";
+
+/// 达到上限后模型仍调用工具时，这些调用不执行，返回该结果
+const TOOL_LIMIT_RESULT: &str =
+    r#"{"success": false, "error": "Tool call limit reached for this turn; the tool was not run"}"#;
+
+/// `/continue` 发送给模型的续写提示
+const CONTINUE_PROMPT: &str =
+    "Your previous response was cut off. Continue exactly where you left off.";
+
+/// 裁剪历史时请模型总结的提示
+const SUMMARY_PROMPT: &str = "Summarize the conversation so far in a few short paragraphs. Keep file paths, decisions and open tasks; omit pleasantries.";
 
-/// 从 Value 中提取 content block 类型和数据
-fn parse_content_block(block: &Value) -> Option<(&str, &Value)> {
-    let block_type = block.get("type")?.as_str()?;
-    Some((block_type, block))
+/// 摘要消息的开头
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:";
+
+/// 摘要消息后的助手确认，保持角色交替
+const SUMMARY_ACK: &str = "Understood, I'll continue from this summary.";
+
+/// 通过 `ChatClient` 的输出打印一行，用法同 `println!`
+macro_rules! outln {
+    ($client:expr) => {
+        $client.output.message("")
+    };
+    ($client:expr, $($arg:tt)*) => {
+        $client.output.message(&format!($($arg)*))
+    };
 }
 
-/// 创建 tool_result block
-fn create_tool_result(tool_use_id: &str, content: &str) -> Value {
-    serde_json::json!({
-        "type": "tool_result",
-        "tool_use_id": tool_use_id,
-        "content": content
-    })
+/// 创建纯文本消息
+fn text_message(role: &str, text: &str) -> Message {
+    Message {
+        role: role.to_string(),
+        content: MessageContent::Text(text.to_string()),
+    }
 }
 
-// ============== Chat Client ==============
+/// 根据 stop_reason 生成需要提示用户的警告
+fn stop_reason_warning(stop_reason: Option<&str>) -> Option<String> {
+    match stop_reason {
+        Some("max_tokens") => Some(
+            "⚠️  回复被截断 (max_tokens)，输入 /continue 继续生成，或调大配置中的 max_tokens"
+                .to_string(),
+        ),
+        _ => None,
+    }
+}
 
-struct ChatClient {
+/// 等待请求线程时检查中断标记的间隔
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 可以跨线程传递的错误
+type SendError = Box<dyn std::error::Error + Send + Sync>;
+
+/// API 连接参数
+///
+/// 可以廉价地克隆到请求线程中，使阻塞的 HTTP 请求能够被 Ctrl+C 中断。
+#[derive(Clone)]
+struct ApiConnection {
     client: Client,
+    /// 配置中的 base_url
+    base_url: String,
     url: String,
     api_key: String,
+    /// 请求/响应格式
+    api_format: ApiFormat,
+    /// 携带 API 密钥的请求头
+    auth_scheme: AuthScheme,
+    /// `anthropic-version` 请求头
+    anthropic_version: String,
+    /// `anthropic-beta` 请求头（为空时不发送）
+    anthropic_beta: Vec<String>,
+    /// 瞬时错误的最大重试次数
+    max_retries: u32,
+}
+
+/// 上下文的 token 数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TokenCount {
+    tokens: u64,
+    /// 由本地按字符数估算（count_tokens 接口不可用）
+    estimated: bool,
+}
+
+/// 上下文用量达到窗口的该比例时提示
+const CONTEXT_WARNING_RATIO: f64 = 0.8;
+
+/// 请求线程发回主线程的事件
+enum RequestEvent {
+    /// 重试、超时等提示，由主线程经输出通道显示
+    Notice(String),
+    /// 请求的最终结果
+    Reply(Result<ApiReply, SendError>),
+}
+
+/// 请求线程取回的响应
+enum ApiReply {
+    /// 流式响应，由主线程边读边显示
+    Stream(Response),
+    /// 非流式响应的完整正文
+    Text(String),
+}
+
+impl ApiConnection {
+    /// 按配置的方式附加 API 密钥
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.auth_scheme {
+            AuthScheme::ApiKey => request.header("x-api-key", &self.api_key),
+            AuthScheme::Bearer => request.bearer_auth(&self.api_key),
+        }
+    }
+
+    /// 连通性检查：请求一次模型列表，确认 base_url 和 API 密钥可用
+    fn preflight(&self) -> Result<(), preflight::PreflightError> {
+        let url = format!("{}{}", self.base_url, preflight::MODELS_ENDPOINT);
+        debug!("连通性检查: {}", url);
+
+        let request = self.authorize(self.client.get(&url).timeout(preflight::PREFLIGHT_TIMEOUT));
+        let request = match self.api_format {
+            ApiFormat::Anthropic => request.header("anthropic-version", &self.anthropic_version),
+            ApiFormat::OpenAi => request,
+        };
+        let response = request.send().map_err(|e| preflight::classify_error(&e))?;
+        preflight::check_status(response.status().as_u16(), &url)
+    }
+
+    /// 调用 count_tokens 接口计算请求上下文的 token 数（仅 anthropic 格式）
+    fn count_tokens(
+        &self,
+        request_body: &CountTokensRequest,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        if self.api_format != ApiFormat::Anthropic {
+            return Err("count_tokens is only available for the anthropic API format".into());
+        }
+        let url = format!("{}{}", self.base_url, api::COUNT_TOKENS_ENDPOINT);
+        debug!("计算 token 数: {}", url);
+
+        let request = self
+            .authorize(self.client.post(&url))
+            .header("content-type", "application/json")
+            .header("anthropic-version", &self.anthropic_version);
+        let request = if self.anthropic_beta.is_empty() {
+            request
+        } else {
+            request.header("anthropic-beta", self.anthropic_beta.join(","))
+        };
+        let response = request.json(request_body).send()?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("count_tokens returned HTTP {}", status.as_u16()).into());
+        }
+        Ok(response.json::<CountTokensResponse>()?.input_tokens)
+    }
+
+    /// 发送 API 请求，对瞬时错误按指数退避重试
+    ///
+    /// 429/500/502/503/529 和连接失败会重试，最多 `max_retries` 次，
+    /// 优先使用响应头 `retry-after` 指定的等待时间；其他错误立即返回。
+    /// 重试只重发同一个请求体，不会改动对话历史。
+    ///
+    /// 首次请求时连接被对端关闭（代理断开了连接池中的空闲连接）会立即重连一次，
+    /// 不计入重试次数。重试和失败提示交给 `notify`，由调用方决定如何显示。
+    fn send_with_retry(
+        &self,
+        request_body: &AnthropicRequest,
+        notify: &dyn Fn(String),
+    ) -> Result<Response, SendError> {
+        let mut attempt = 0;
+        let mut reconnected = false;
+
+        loop {
+            debug!("发送 API 请求到: {}", self.url);
+
+            let request = self
+                .authorize(self.client.post(&self.url))
+                .header("content-type", "application/json");
+            let request = match self.api_format {
+                ApiFormat::Anthropic => {
+                    let request = request.header("anthropic-version", &self.anthropic_version);
+                    let request = if self.anthropic_beta.is_empty() {
+                        request
+                    } else {
+                        request.header("anthropic-beta", self.anthropic_beta.join(","))
+                    };
+                    request.json(request_body)
+                }
+                ApiFormat::OpenAi => request.json(&openai::to_request(request_body)),
+            };
+            let outcome = request.send();
+
+            let delay = match outcome {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    let retry_after = api::parse_retry_after(response.headers());
+                    if !api::is_retryable_status(status) || attempt >= self.max_retries {
+                        return Err(status_error(response, attempt, notify));
+                    }
+                    warn!("API 请求失败 [{}]，准备重试", status);
+                    api::retry_delay(attempt, retry_after)
+                }
+                Err(e) if attempt == 0 && !reconnected && api::is_connection_closed(&e) => {
+                    warn!("连接已被关闭，重新连接: {}", e);
+                    reconnected = true;
+                    continue;
+                }
+                Err(e) if e.is_connect() && attempt < self.max_retries => {
+                    warn!("连接失败，准备重试: {}", e);
+                    api::retry_delay(attempt, None)
+                }
+                Err(e) if e.is_timeout() => {
+                    error!("API 请求超时: {}", e);
+                    notify("❌ 请求超时，请检查网络连接、代理或 base_url 设置".to_string());
+                    return Err(e.into());
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            attempt += 1;
+            notify(format!(
+                "⏳ 请求失败，{:.1} 秒后重试 ({}/{})",
+                delay.as_secs_f64(),
+                attempt,
+                self.max_retries
+            ));
+            thread::sleep(delay);
+        }
+    }
+}
+
+struct ChatClient {
+    connection: ApiConnection,
     tool_registry: ToolRegistry,
     messages: Vec<Message>,
     model: String,
+    /// 单次回复的最大输出 token 数
+    max_tokens: u32,
+    /// 系统提示（未配置时为 `None`），不含角色预设
+    system_prompt: Option<String>,
+    /// 配置的角色预设：名称 -> 追加在系统提示之后的片段
+    personas: HashMap<String, String>,
+    /// 当前启用的角色预设
+    persona: Option<String>,
+    /// 扩展思考（未启用时为 `None`）
+    thinking: Option<ThinkingConfig>,
+    /// 采样温度（可通过 `/temp` 调整）
+    temperature: Option<f32>,
+    /// nucleus 采样阈值
+    top_p: Option<f32>,
+    /// 发回 API 的单个工具结果的最大字节数
+    max_tool_result_bytes: usize,
+    /// 每轮最多执行的工具调用往返次数
+    max_tool_iterations: u32,
+    /// 对话历史的 token 预算（未配置时不裁剪）
+    history_budget: Option<usize>,
+    /// 超出预算时的裁剪策略
+    trim_strategy: TrimStrategy,
+    /// 最近一次响应的 stop_reason
+    last_stop_reason: Option<String>,
+    /// 最近一次发送的用户输入，供 `/retry` 重新发送
+    last_input: Option<String>,
+    /// `/attach` 添加的附件，随下一条用户消息发送；发送失败时保留，供 `/retry` 使用
+    attachments: Vec<Value>,
+    /// 暂存区（仅在暂存模式启用时存在）
+    staging: Option<StagingArea>,
+    /// 确认阈值策略（未配置时每次修改都确认）
+    approval: Option<ApprovalPolicy>,
+    /// 自动同意所有修改操作
+    auto_approve: bool,
+    /// 征求用户确认的方式
+    approver: Box<dyn Approver>,
+    /// 是否使用流式响应
+    stream: bool,
+    /// 回复、工具调用等的输出方式
+    output: Box<dyn OutputSink>,
+    /// 本次会话累计的 token 用量
+    usage: Usage,
+    /// 本次会话累计的费用估算
+    cost: CostEstimate,
+    /// 配置中覆盖的模型价格
+    pricing: HashMap<String, ModelPrice>,
+    /// 每轮结束时显示费用估算
+    show_cost: bool,
+    /// 在系统提示和工具定义上设置缓存断点
+    prompt_caching: bool,
+    /// 工具调用链追踪
+    trace: TraceRecorder,
+    /// 中断标记，由 Ctrl+C 处理器置位
+    interrupted: Arc<AtomicBool>,
+    /// 等待响应时显示进度提示（仅当标准输出是终端）
+    spinner: bool,
+    /// emoji 或 ASCII 提示前缀
+    labels: Labels,
 }
 
 impl ChatClient {
     fn new(settings: &Settings) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut client_builder = Client::builder();
+        let client = build_http_client(&settings.get_http_client_options())?;
+        let labels = Labels::detect(settings.ascii_only);
 
-        // 配置代理（如果存在且非空）
-        if let Some(proxy_url) = &settings.env.https_proxy {
-            if !proxy_url.is_empty() {
-                let proxy = reqwest::Proxy::all(proxy_url)?;
-                client_builder = client_builder.proxy(proxy);
-            }
+        // 工作区根目录在创建时确定，之后切换当前目录不会移动工具沙箱
+        let workspace_root = match &settings.workspace_root {
+            Some(root) => PathBuf::from(root),
+            None => std::env::current_dir()?,
+        };
+
+        let extra_roots = resolve_extra_roots(settings, &workspace_root)?;
+        let roots_note = extra_roots_note(&extra_roots);
+        let context = ToolContext::new(workspace_root.clone())
+            .with_writable_roots(settings.get_writable_roots())
+            .with_extra_roots(extra_roots)
+            .with_symlink_policy(settings.get_symlink_policy())
+            .with_redact_secrets(settings.redact_secrets)
+            .with_ignore_rules(IgnoreRules::load(
+                &workspace_root,
+                settings.respect_gitignore,
+            ));
+        // 项目指令（MENTAT.md）与配置中的系统提示组合使用
+        let system_prompt = instructions::compose_system_prompt(
+            instructions::load(&context),
+            settings.load_system_prompt()?,
+        );
+        let system_prompt = instructions::compose_system_prompt(system_prompt, roots_note);
+
+        let mut tool_registry = ToolRegistry::with_builtins_filtered(settings, context);
+        tool_registry.set_dry_run(settings.dry_run);
+        tool_registry.set_timeouts(settings.get_tool_timeouts());
+        if settings.tool_log_enabled() {
+            tool_registry.set_log(ToolLog::new(
+                workspace_root.join(tool_log::TOOL_LOG_PATH),
+                settings.get_tool_log_max_bytes(),
+            ));
+        }
+
+        let api_format = settings.get_api_format();
+        if settings.stream && api_format == ApiFormat::OpenAi {
+            warn!("OpenAI 兼容格式暂不支持流式输出，已改为非流式");
         }
-        let client = client_builder.build()?;
+
+        let stream = settings.stream && api_format == ApiFormat::Anthropic;
 
         Ok(Self {
-            client,
-            url: format!("{}/v1/messages", settings.env.base_url),
-            api_key: settings.env.api_key.clone(),
-            tool_registry: ToolRegistry::with_builtins(),
+            connection: ApiConnection {
+                client,
+                base_url: settings.env.base_url.clone(),
+                url: format!("{}{}", settings.env.base_url, api_format.endpoint()),
+                api_key: settings.env.api_key.clone(),
+                api_format,
+                auth_scheme: settings.get_auth_scheme(),
+                anthropic_version: settings.get_anthropic_version(),
+                anthropic_beta: settings.anthropic_beta.clone().unwrap_or_default(),
+                max_retries: settings.get_max_retries(),
+            },
+            tool_registry,
             messages: Vec::new(),
             model: settings.get_model(),
+            max_tokens: settings.get_max_tokens(),
+            system_prompt,
+            personas: settings.get_personas(),
+            persona: None,
+            thinking: settings
+                .thinking_budget_tokens
+                .filter(|_| api_format == ApiFormat::Anthropic)
+                .map(ThinkingConfig::enabled),
+            temperature: settings.temperature,
+            top_p: settings.top_p,
+            max_tool_result_bytes: settings.get_max_tool_result_bytes(),
+            max_tool_iterations: settings.get_max_tool_iterations(),
+            history_budget: settings.history_token_budget,
+            trim_strategy: settings.get_history_trim_strategy(),
+            last_stop_reason: None,
+            last_input: None,
+            attachments: Vec::new(),
+            staging: settings.staging.then(StagingArea::new),
+            approval: settings.confirm.as_ref().map(ApprovalPolicy::from_settings),
+            auto_approve: settings.auto_approve,
+            approver: Box::new(StdinApprover::new(labels)),
+            stream,
+            output: Box::new(
+                PrettyOutput::new(
+                    StdoutOutput,
+                    settings.render_markdown,
+                    stream,
+                    settings.get_verbosity(),
+                )
+                .with_labels(labels),
+            ),
+            usage: Usage::default(),
+            cost: CostEstimate::default(),
+            pricing: settings.get_pricing(),
+            show_cost: settings.show_cost,
+            prompt_caching: settings.prompt_caching,
+            trace: TraceRecorder::new(vec![settings.env.api_key.clone()]),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            spinner: io::stdout().is_terminal(),
+            labels,
         })
     }
 
     fn send_message(&mut self, user_input: &str) -> Result<(), Box<dyn std::error::Error>> {
-        // 添加用户消息
-        self.messages.push(Message {
-            role: "user".to_string(),
-            content: MessageContent::Text(user_input.to_string()),
-        });
+        self.last_input = Some(user_input.to_string());
+        let started = Instant::now();
+        self.trace.begin_turn(user_input);
+
+        // 工作区目录在会话中途被删除：工具调用都会失败，提示用户重新启动
+        let validator = self.tool_registry.validator();
+        if !validator.workspace_exists() {
+            outln!(
+                self,
+                "⚠️  工作区目录已不存在: {}，文件工具将无法使用；请 cd 到存在的目录后重新启动",
+                validator.root().display()
+            );
+        }
+
+        let result = self.run_turn(user_input);
+        if result.is_ok() {
+            self.attachments.clear();
+        }
+
+        self.trace.end_turn(
+            started.elapsed(),
+            result.as_ref().err().map(|e| e.to_string()),
+        );
+        result
+    }
+
+    /// 执行一轮对话：发送用户输入并循环处理工具调用，直到模型结束回复
+    fn run_turn(&mut self, user_input: &str) -> Result<(), Box<dyn std::error::Error>> {
+        // 清除上一轮残留的中断标记
+        self.interrupted.store(false, Ordering::SeqCst);
+
+        // 本轮开始前的完整历史，出错时整轮回滚，避免残留不成对的消息；
+        // 在裁剪之前保存，回滚时被裁剪掉的消息也一并恢复
+        let history = self.messages.clone();
+
+        // 添加用户消息，附件放在文本之前
+        if self.attachments.is_empty() {
+            self.messages.push(text_message("user", user_input));
+        } else {
+            let mut blocks = self.attachments.clone();
+            blocks.push(serde_json::json!({"type": "text", "text": user_input}));
+            self.messages.push(Message {
+                role: "user".to_string(),
+                content: MessageContent::Blocks(blocks),
+            });
+        }
+        self.trim_history();
+
+        // 裁剪后本轮用户消息之前的历史长度
+        let checkpoint = self.messages.len() - 1;
+
+        // 服务端长时间操作触发 pause_turn 后已自动续传的次数
+        let mut pause_continuations = 0;
+        // 本轮所有 API 往返的 token 用量
+        let mut turn_usage = Usage::default();
+        // 本轮已执行的工具调用，相同的重复调用直接返回之前的结果
+        let mut tool_cache = ToolCallCache::default();
+        // 本轮已完成的工具调用往返次数
+        let mut tool_rounds = 0;
 
         // Tool Use 循环
         loop {
-            let request_body = AnthropicRequest {
+            let mut request_body = AnthropicRequest {
                 model: self.model.clone(),
-                max_tokens: 4096,
+                max_tokens: self.max_tokens,
+                system: self.active_system_prompt().map(SystemPrompt::from),
                 messages: self.messages.clone(),
                 tools: self.tool_registry.definitions(),
+                thinking: self.thinking,
+                temperature: self.temperature,
+                top_p: self.top_p,
+                stream: self.stream,
             };
+            if self.prompt_caching {
+                request_body.enable_prompt_caching();
+            }
 
-            debug!("发送 API 请求到: {}", self.url);
-
-            let response = self
-                .client
-                .post(&self.url)
-                .header("x-api-key", &self.api_key)
-                .header("anthropic-version", "2023-06-01")
-                .header("content-type", "application/json")
-                .json(&request_body)
-                .send()?;
-
-            let status = response.status();
-
-            if !status.is_success() {
-                let error_text = response.text()?;
-                error!("API 请求失败 [{}]", status);
-
-                // 记录详细错误日志
-                debug!("API 错误详情: {}", error_text);
-
-                // 用户友好的错误提示
-                let user_message = match status.as_u16() {
-                    401 => "认证失败，请检查 API 密钥是否正确",
-                    403 => "访问被拒绝，请检查 API 权限",
-                    429 => "请求过于频繁，请稍后重试",
-                    500..=599 => "服务器错误，请稍后重试",
-                    _ => "请求失败，请检查网络连接",
-                };
-
-                eprintln!("❌ {}", user_message);
-                self.messages.pop();
+            let request_started = Instant::now();
+            let reply = match self.send_interruptible(request_body) {
+                Ok(reply) => reply,
+                Err(e) => {
+                    if matches!(e.downcast_ref::<ApiError>(), Some(ApiError::Interrupted)) {
+                        self.output.notice("\n⛔ 已中断当前请求");
+                    }
+                    self.messages = history;
+                    return Err(e);
+                }
+            };
 
-                // 返回错误而不是 Ok(())，让调用者知道发生了错误
-                return Err(format!("API Error [{}]: {}", status, user_message).into());
-            }
+            let result: AnthropicResponse = match reply {
+                ApiReply::Stream(response) => {
+                    let reader = BufReader::new(response);
+                    let output = &mut self.output;
+                    match stream::read_stream(reader, &self.interrupted, |event| {
+                        output.stream_text(event)
+                    }) {
+                        Ok(r) => r,
+                        Err(e) => {
+                            match &e {
+                                StreamError::Interrupted => {
+                                    self.output.notice("\n⛔ 已中断当前请求")
+                                }
+                                _ => {
+                                    error!("流式响应处理失败: {}", e);
+                                    self.output.notice("\n❌ 流式响应中断，请稍后重试");
+                                }
+                            }
+                            self.messages = history;
+                            return Err(Box::new(e));
+                        }
+                    }
+                }
+                ApiReply::Text(response_text) => {
+                    debug!("收到响应，长度: {} 字节", response_text.len());
 
-            // 先获取原始文本，便于调试
-            let response_text = response.text()?;
-            debug!("收到响应，长度: {} 字节", response_text.len());
+                    match self.parse_response_text(&response_text) {
+                        Ok(r) => r,
+                        Err(e) => {
+                            error!("JSON 解析失败: {}", e);
 
-            let result: AnthropicResponse = match serde_json::from_str(&response_text) {
-                Ok(r) => r,
-                Err(e) => {
-                    error!("JSON 解析失败: {}", e);
+                            // 记录详细的解析错误信息
+                            debug!("解析错误位置: 行 {}, 列 {}", e.line(), e.column());
+                            debug!("错误类型: {:?}", e.classify());
 
-                    // 记录详细的解析错误信息
-                    debug!("解析错误位置: 行 {}, 列 {}", e.line(), e.column());
-                    debug!("错误类型: {:?}", e.classify());
+                            // 安全地截取响应内容用于调试
+                            let preview_len = response_text.len().min(500);
+                            let preview = &response_text[..preview_len];
+                            debug!("响应预览: {}", preview);
 
-                    // 安全地截取响应内容用于调试
-                    let preview_len = response_text.len().min(500);
-                    let preview = &response_text[..preview_len];
-                    debug!("响应预览: {}", preview);
+                            // 检查是否是 HTML 响应（可能是代理或防火墙拦截）
+                            if response_text.trim_start().starts_with('<') {
+                                warn!("收到 HTML 响应，可能是代理或防火墙拦截");
+                                self.output
+                                    .notice("❌ 收到非预期的响应格式，请检查网络代理设置");
+                            } else {
+                                self.output.notice("❌ 响应解析失败，请稍后重试");
+                            }
 
-                    // 检查是否是 HTML 响应（可能是代理或防火墙拦截）
-                    if response_text.trim_start().starts_with('<') {
-                        warn!("收到 HTML 响应，可能是代理或防火墙拦截");
-                        eprintln!("❌ 收到非预期的响应格式，请检查网络代理设置");
-                    } else {
-                        eprintln!("❌ 响应解析失败，请稍后重试");
+                            self.messages = history;
+                            return Err(format!("JSON parse error: {}", e).into());
+                        }
                     }
-
-                    self.messages.pop();
-                    return Err(format!("JSON parse error: {}", e).into());
                 }
             };
 
+            self.trace
+                .record_step(request_started.elapsed(), result.stop_reason.as_deref());
+            if let Some(usage) = result.usage {
+                turn_usage += usage;
+                self.record_usage(usage);
+            }
+
+            // 空响应：不把空的 assistant 消息写入历史（下一次请求会被拒绝）。
+            // 本轮已执行的工具调用可能已经修改了文件，保留在历史中，使历史与工作区一致；
+            // 还没有执行工具时只撤销本条用户消息。返回错误以保留附件，供 /retry 使用。
+            if result.content.is_empty() {
+                let stop_reason = result.stop_reason.as_deref().unwrap_or("unknown");
+                warn!("模型返回了空响应，stop_reason: {}", stop_reason);
+                let message = if self.messages.len() == checkpoint + 1 {
+                    self.messages = history;
+                    format!(
+                        "⚠️  模型返回了空响应 (stop_reason: {})，本条消息已撤销",
+                        stop_reason
+                    )
+                } else {
+                    format!(
+                        "⚠️  模型返回了空响应 (stop_reason: {})，本轮已执行的工具调用保留在历史中",
+                        stop_reason
+                    )
+                };
+                self.output.notice(&message);
+                self.last_stop_reason = result.stop_reason.clone();
+                return Err(
+                    format!("empty response from model (stop_reason: {})", stop_reason).into(),
+                );
+            }
+
             // 处理响应内容
             let mut tool_results: Vec<Value> = Vec::new();
             let mut has_tool_use = false;
+            // 达到上限后的工具调用不会执行，无需确认
+            let batch_approved =
+                tool_rounds >= self.max_tool_iterations || self.confirm_batch(&result.content);
+            // 已并行执行完的只读工具调用：内容块下标 -> (输出, 耗时)
+            let mut prefetched: HashMap<usize, (String, Duration)> = HashMap::new();
 
-            for block in &result.content {
+            for (index, block) in result.content.iter().enumerate() {
                 if let Some((block_type, data)) = parse_content_block(block) {
                     match block_type {
                         "text" => {
                             if let Some(text) = data.get("text").and_then(|t| t.as_str()) {
-                                println!("\n🤖 {}\n", text);
+                                self.output.assistant_text(text);
                             }
                         }
                         "thinking" => {
                             if let Some(thinking) = data.get("thinking").and_then(|t| t.as_str()) {
-                                // 截取前 200 字符显示
-                                let display = if thinking.len() > 200 {
-                                    format!("{}...", &thinking[..200])
-                                } else {
-                                    thinking.to_string()
-                                };
-                                println!("\n💭 [思考中...] {}\n", display);
+                                self.output.thinking(thinking);
                             }
                         }
                         "tool_use" => {
@@ -235,13 +772,62 @@ impl ChatClient {
                             let name = data.get("name").and_then(|v| v.as_str()).unwrap_or("");
                             let input = data.get("input").unwrap_or(&Value::Null);
 
-                            println!("  🔧 [{}] {}", name, serde_json::to_string(input)?);
+                            self.output.tool_call(name, input);
 
-                            let tool_output = self.tool_registry.execute(name, input);
-                            tool_results.push(create_tool_result(id, &tool_output));
+                            let mut tool_started = Instant::now();
+                            let mut tool_elapsed = None;
+                            // 修改类工具每次都真正执行，只有只读工具复用之前的结果
+                            let mutating = self.tool_registry.is_mutating(name);
+                            let cached = if mutating {
+                                None
+                            } else {
+                                tool_cache.get(name, input)
+                            };
+                            let tool_output = match cached {
+                                _ if tool_rounds >= self.max_tool_iterations => {
+                                    warn!("已达到工具调用往返上限，不执行 {}", name);
+                                    TOOL_LIMIT_RESULT.to_string()
+                                }
+                                Some(cached) => {
+                                    warn!("本轮重复的工具调用 {}，返回之前的结果", name);
+                                    cached.to_string()
+                                }
+                                None => {
+                                    if !mutating && !prefetched.contains_key(&index) {
+                                        prefetched = self.run_read_only_batch(
+                                            &result.content,
+                                            index,
+                                            &tool_cache,
+                                        );
+                                        tool_started = Instant::now();
+                                    }
+                                    let output = match prefetched.remove(&index) {
+                                        Some((output, elapsed)) => {
+                                            tool_elapsed = Some(elapsed);
+                                            output
+                                        }
+                                        None => self.run_tool(name, input, batch_approved),
+                                    };
+                                    tool_cache.record(name, input, &output, mutating);
+                                    output
+                                }
+                            };
+                            self.output.tool_result(name, &tool_output);
+                            self.trace.record_tool_call(
+                                id,
+                                name,
+                                input,
+                                &tool_output,
+                                tool_elapsed.unwrap_or_else(|| tool_started.elapsed()),
+                            );
+                            let content =
+                                api::truncate_tool_output(&tool_output, self.max_tool_result_bytes);
+                            tool_results.push(create_tool_result(id, &content));
                         }
-                        _ => {
-                            // 忽略其他未知类型
+                        other => {
+                            // 未知类型（如 redacted_thinking、server_tool_use）不显示，
+                            // 但会原样保留在历史中
+                            debug!("跳过 {} 内容块", other);
                         }
                     }
                 }
@@ -253,11 +839,58 @@ impl ChatClient {
                 content: MessageContent::Blocks(result.content.clone()),
             });
 
+            self.last_stop_reason = result.stop_reason.clone();
+            if let Some(warning) = stop_reason_warning(result.stop_reason.as_deref()) {
+                warn!("回复未正常结束: {:?}", result.stop_reason);
+                self.output.notice(&warning);
+            }
+
+            // pause_turn：服务端工具尚未完成，原样重发对话让模型继续
+            if result.stop_reason.as_deref() == Some("pause_turn") {
+                if pause_continuations >= MAX_PAUSE_CONTINUATIONS {
+                    warn!("pause_turn 续传次数达到上限 ({})", MAX_PAUSE_CONTINUATIONS);
+                    self.output
+                        .notice("⚠️  模型多次暂停仍未完成，已停止自动续传");
+                    break;
+                }
+                pause_continuations += 1;
+                debug!("收到 pause_turn，第 {} 次续传", pause_continuations);
+                continue;
+            }
+
             // 检查是否需要继续循环
             if !has_tool_use {
                 break;
             }
 
+            // 已要求模型停止调用工具后仍在调用：补上结果并以一条说明结束本轮，
+            // 保持历史以不含 tool_use 的助手回复结尾
+            if tool_rounds >= self.max_tool_iterations {
+                self.messages.push(Message {
+                    role: "user".to_string(),
+                    content: MessageContent::Blocks(tool_results),
+                });
+                self.messages.push(text_message(
+                    "assistant",
+                    "Stopped: the tool call limit for this turn was reached.",
+                ));
+                break;
+            }
+
+            tool_rounds += 1;
+            self.output
+                .tool_round(tool_rounds, self.max_tool_iterations);
+
+            // 达到上限：随工具结果提示模型停止调用工具，再请求一次让它总结
+            if tool_rounds >= self.max_tool_iterations {
+                warn!("工具调用往返达到上限 ({})", self.max_tool_iterations);
+                self.output.notice(&format!(
+                    "⚠️  本轮工具调用已达到上限 ({} 次往返)，已要求模型停止调用工具并总结",
+                    self.max_tool_iterations
+                ));
+                tool_results.push(serde_json::json!({"type": "text", "text": TOOL_LIMIT_NOTE}));
+            }
+
             // 添加 tool_result 消息
             self.messages.push(Message {
                 role: "user".to_string(),
@@ -265,209 +898,3500 @@ impl ChatClient {
             });
         }
 
+        if let Some(staging) = self.staging.as_ref().filter(|s| !s.is_empty()) {
+            let message = format!("📦 {} 个修改已暂存，输入 /apply 审阅", staging.len());
+            self.output.notice(&message);
+        }
+
+        let turn_cost = self
+            .price()
+            .filter(|_| self.show_cost)
+            .map(|price| price.cost(turn_usage));
+        self.output
+            .end_turn(turn_usage, turn_cost, self.last_stop_reason.as_deref());
+
         Ok(())
     }
 
-    fn clear_history(&mut self) {
-        self.messages.clear();
-        println!("📝 对话历史已清除\n");
+    /// 当前模型的价格（未知时为 `None`）
+    fn price(&self) -> Option<ModelPrice> {
+        pricing::price_for(&self.model, &self.pricing)
     }
 
-    fn tool_count(&self) -> usize {
-        self.tool_registry.len()
+    /// 累计会话的 token 用量和费用估算
+    fn record_usage(&mut self, usage: Usage) {
+        self.usage += usage;
+        self.cost.add(usage, self.price());
     }
-}
-
-// ============== REPL 命令处理 ==============
 
-fn handle_command(cmd: &str, client: &mut ChatClient) -> bool {
-    match cmd.trim() {
-        "/exit" | "/quit" | "/q" => {
-            println!("👋 再见！");
-            return true;
-        }
-        "/clear" | "/c" => {
-            client.clear_history();
-        }
-        "/tools" | "/t" => {
-            println!("\n🔧 已注册的工具 ({}):", client.tool_count());
-            for name in client.tool_registry.tool_names() {
-                println!("  - {}", name);
+    /// 按当前 API 格式解析非流式响应
+    fn parse_response_text(&self, text: &str) -> Result<AnthropicResponse, serde_json::Error> {
+        match self.connection.api_format {
+            ApiFormat::Anthropic => serde_json::from_str(text),
+            ApiFormat::OpenAi => {
+                serde_json::from_str(text).and_then(|body| openai::parse_response(&body))
             }
-            println!();
         }
-        "/help" | "/h" | "/?" => {
-            println!(
-                r#"
-📚 可用命令:
-  /exit, /quit, /q  - 退出程序
-  /clear, /c        - 清除对话历史
-  /tools, /t        - 显示已注册的工具
-  /help, /h, /?     - 显示此帮助
+    }
 
-💡 提示:
-  - 直接输入问题即可与 AI 对话
-  - AI 可以使用已注册的工具操作本地文件
-  - 按 Ctrl+C 中断当前请求
-  - 按 Ctrl+D 退出
-"#
-            );
+    /// 对话超出 token 预算时裁剪最早的轮次
+    ///
+    /// `summarize` 策略下先请模型总结被裁掉的部分，失败时退回直接丢弃。
+    fn trim_history(&mut self) {
+        let Some(budget) = self.history_budget else {
+            return;
+        };
+        let cut = history::trim_point(&self.messages, budget);
+        if cut == 0 {
+            return;
         }
-        _ => {
-            println!("❓ 未知命令: {}，输入 /help 查看帮助", cmd);
+        let dropped: Vec<Message> = self.messages.drain(..cut).collect();
+
+        if self.trim_strategy == TrimStrategy::Summarize {
+            match self.summarize(&dropped) {
+                Ok(summary) => {
+                    let summary = [
+                        text_message("user", &format!("{}\n\n{}", SUMMARY_PREFIX, summary)),
+                        text_message("assistant", SUMMARY_ACK),
+                    ];
+                    self.messages.splice(0..0, summary);
+                    self.output.notice(&format!(
+                        "🗜️  对话超出 token 预算，已将最早的 {} 条消息总结为摘要",
+                        cut
+                    ));
+                    return;
+                }
+                Err(e) => warn!("总结对话历史失败，改为直接丢弃: {}", e),
+            }
         }
+        self.output.notice(&format!(
+            "🗜️  对话超出 token 预算，已丢弃最早的 {} 条消息",
+            cut
+        ));
     }
-    false
-}
 
-// ============== 日志初始化 ==============
+    /// 请模型把一段对话总结为摘要
+    fn summarize(&mut self, messages: &[Message]) -> Result<String, Box<dyn std::error::Error>> {
+        let mut messages = messages.to_vec();
+        messages.push(text_message("user", SUMMARY_PROMPT));
+        let mut request_body = AnthropicRequest {
+            model: self.model.clone(),
+            max_tokens: self.max_tokens,
+            system: self.active_system_prompt().map(SystemPrompt::from),
+            messages,
+            // 历史中可能包含 tool_use，需要带上工具定义
+            tools: self.tool_registry.definitions(),
+            thinking: None,
+            temperature: None,
+            top_p: None,
+            stream: false,
+        };
+        if self.prompt_caching {
+            request_body.enable_prompt_caching();
+        }
 
-fn init_logger(cli: &Cli) {
-    let log_level = if cli.debug { "debug" } else { &cli.log_level };
+        let ApiReply::Text(text) = self.send_interruptible(request_body)? else {
+            return Err("unexpected streaming reply".into());
+        };
+        let response = self.parse_response_text(&text)?;
+        if let Some(usage) = response.usage {
+            self.record_usage(usage);
+        }
+        let summary: Vec<&str> = response
+            .content
+            .iter()
+            .filter(|b| b["type"] == "text")
+            .filter_map(|b| b["text"].as_str())
+            .collect();
+        if summary.is_empty() {
+            return Err("empty summary".into());
+        }
+        Ok(summary.join("\n"))
+    }
 
-    let env = env_logger::Env::default().default_filter_or(log_level);
-    env_logger::Builder::from_env(env)
-        .format_timestamp(Some(env_logger::TimestampPrecision::Seconds))
-        .init();
+    /// 在请求线程中发送请求，等待期间响应 Ctrl+C
+    ///
+    /// 非流式响应的正文也在请求线程中读取。中断时立即返回
+    /// [`ApiError::Interrupted`]，被放弃的请求线程在收到响应或超时后自行结束。
+    /// 等待期间显示进度提示，返回前清除。
+    fn send_interruptible(
+        &mut self,
+        request_body: AnthropicRequest,
+    ) -> Result<ApiReply, Box<dyn std::error::Error>> {
+        if self.interrupted.load(Ordering::SeqCst) {
+            return Err(Box::new(ApiError::Interrupted));
+        }
 
-    debug!("日志系统初始化完成，级别: {}", log_level);
-}
+        let connection = self.connection.clone();
+        let stream = request_body.stream;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let notices = tx.clone();
+            let notify = move |notice: String| {
+                let _ = notices.send(RequestEvent::Notice(notice));
+            };
+            let reply = connection
+                .send_with_retry(&request_body, &notify)
+                .and_then(|response| {
+                    if stream {
+                        Ok(ApiReply::Stream(response))
+                    } else {
+                        // 先获取原始文本，便于调试
+                        Ok(ApiReply::Text(response.text()?))
+                    }
+                });
+            let _ = tx.send(RequestEvent::Reply(reply));
+        });
 
-// ============== 主函数 ==============
+        let frames = self.labels.spinner_frames();
+        let _spinner = self.spinner.then(|| Spinner::start("思考中", frames));
+        loop {
+            match rx.recv_timeout(INTERRUPT_POLL_INTERVAL) {
+                Ok(RequestEvent::Notice(notice)) => self.output.notice(&notice),
+                Ok(RequestEvent::Reply(reply)) => {
+                    return reply.map_err(|e| e as Box<dyn std::error::Error>)
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.interrupted.load(Ordering::SeqCst) {
+                        return Err(Box::new(ApiError::Interrupted));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("API request thread exited unexpectedly".into())
+                }
+            }
+        }
+    }
 
-fn main() -> RlResult<()> {
-    // 解析命令行参数
-    let cli = Cli::parse();
+    /// 一轮回复修改的文件过多时，整体征求一次确认
+    ///
+    /// 返回本轮的修改操作是否被允许。
+    fn confirm_batch(&mut self, content: &[Value]) -> bool {
+        // 暂存模式下修改不会立即落盘，审阅在 /apply 时进行
+        // dry-run 模式下不会写入磁盘，无需确认
+        if self.staging.is_some() || self.auto_approve || self.tool_registry.is_dry_run() {
+            return true;
+        }
+        let Some(policy) = &self.approval else {
+            return true;
+        };
 
-    // 初始化日志系统
-    init_logger(&cli);
+        let calls = content
+            .iter()
+            .filter_map(|block| match parse_content_block(block) {
+                Some(("tool_use", data)) => Some((
+                    data.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+                    data.get("input").unwrap_or(&Value::Null),
+                )),
+                _ => None,
+            });
 
-    info!("Mentat Code v{} 启动", env!("CARGO_PKG_VERSION"));
+        match policy.check_batch(calls) {
+            Some(reason) => self.approver.confirm(&reason.prompt()),
+            None => true,
+        }
+    }
 
-    // 处理 --init 参数
-    if cli.init {
-        match config::create_default_config() {
-            Ok(path) => {
-                println!("✅ 配置文件已创建: {}", path.display());
-                println!("   请编辑配置文件并填入您的 API 密钥");
-                return Ok(());
+    /// 并行执行从 `start` 开始的一段只读工具调用，直到遇到修改类工具为止
+    ///
+    /// 修改类工具之后的读取可能依赖修改的结果，因此不会提前执行；修改类工具
+    /// 仍由调用方按顺序逐个执行。已缓存或在本段中重复的调用不会执行，
+    /// 由调用方通过缓存取得结果。返回内容块下标到 (输出, 耗时) 的映射。
+    fn run_read_only_batch(
+        &self,
+        content: &[Value],
+        start: usize,
+        cache: &ToolCallCache,
+    ) -> HashMap<usize, (String, Duration)> {
+        let mut calls: Vec<(usize, &str, &Value)> = Vec::new();
+        for (index, block) in content.iter().enumerate().skip(start) {
+            let Some(("tool_use", data)) = parse_content_block(block) else {
+                continue;
+            };
+            let name = data.get("name").and_then(|v| v.as_str()).unwrap_or("");
+            let input = data.get("input").unwrap_or(&Value::Null);
+            if self.tool_registry.is_mutating(name) {
+                break;
             }
-            Err(e) => {
-                error!("创建配置文件失败: {}", e);
-                process::exit(1);
+            let duplicate = calls.iter().any(|(_, n, i)| *n == name && *i == input);
+            if !duplicate && cache.get(name, input).is_none() {
+                calls.push((index, name, input));
             }
         }
-    }
 
-    // 加载配置（使用新的配置模块）
-    let settings = match config::load_settings_from_path(cli.config.as_deref()) {
-        Ok(s) => {
-            info!("配置加载成功");
-            debug!("使用模型: {}", s.get_model());
-            s
-        }
-        Err(e) => {
-            error!("{}", e);
-            process::exit(1);
+        let registry = &self.tool_registry;
+        let run = move |name: &str, input: &Value| {
+            let started = Instant::now();
+            let output = registry.execute(name, input);
+            (output, started.elapsed())
+        };
+        if let [(index, name, input)] = calls[..] {
+            return HashMap::from([(index, run(name, input))]);
         }
-    };
+        debug!("并行执行 {} 个只读工具调用", calls.len());
+        thread::scope(|scope| {
+            let handles: Vec<_> = calls
+                .iter()
+                .map(|&(index, name, input)| (index, scope.spawn(move || run(name, input))))
+                .collect();
+            handles
+                .into_iter()
+                .map(|(index, handle)| {
+                    let result = handle.join().unwrap_or_else(|_| {
+                        let error = r#"{"success":false,"error":"Tool panicked"}"#;
+                        (error.to_string(), Duration::ZERO)
+                    });
+                    (index, result)
+                })
+                .collect()
+        })
+    }
 
-    // 创建 ChatClient
-    let mut client = match ChatClient::new(&settings) {
-        Ok(c) => {
-            info!("客户端创建成功");
-            c
+    /// 执行单个工具调用：暂存模式下进入暂存区，修改类工具先征求确认
+    fn run_tool(&mut self, name: &str, input: &Value, batch_approved: bool) -> String {
+        if let Some(output) = self.staging.as_mut().and_then(|staging| {
+            staging.stage_tool_call(name, input, self.tool_registry.validator())
+        }) {
+            return output;
         }
-        Err(e) => {
-            // 避免在错误信息中泄露敏感信息
-            error!("创建客户端失败: 请检查网络连接和配置");
-            // 仅在调试模式下显示详细错误
-            if cli.debug {
-                debug!("详细错误: {}", e);
+
+        if !self.auto_approve
+            && !self.tool_registry.is_dry_run()
+            && self.tool_registry.is_mutating(name)
+        {
+            let approved = if !batch_approved {
+                false
+            } else {
+                match &self.approval {
+                    // 配置了阈值：只确认超过阈值的操作
+                    Some(policy) => match policy.check_call(name, input) {
+                        Some(reason) => self.approver.confirm(&reason.prompt()),
+                        None => true,
+                    },
+                    // 默认：每次修改都展示 diff 或大小并确认
+                    None => self
+                        .approver
+                        .confirm(&approval::confirm_prompt(name, input)),
+                }
+            };
+
+            if !approved {
+                self.output.notice("  ⛔ 已拒绝");
+                return approval::rejected_output();
             }
-            process::exit(1);
         }
-    };
 
-    // 处理 --execute 参数（非交互模式）
-    if let Some(prompt) = cli.execute {
-        info!("执行单条命令模式");
-        if let Err(e) = client.send_message(&prompt) {
-            error!("执行失败: {}", e);
-            process::exit(1);
+        self.tool_registry.execute(name, input)
+    }
+
+    /// 重新发送最近一次的用户输入（失败的轮次已从历史中回滚）
+    fn retry_last(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(input) = self.last_input.clone() else {
+            outln!(self, "ℹ️  还没有发送过消息，无法重试\n");
+            return Ok(());
+        };
+        self.send_message(&input)
+    }
+
+    /// 在回复被截断后请求模型继续生成
+    fn continue_truncated(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.last_stop_reason.as_deref() != Some("max_tokens") {
+            outln!(self, "ℹ️  上一条回复没有被截断，无需继续\n");
+            return Ok(());
         }
-        return Ok(());
+        self.send_message(CONTINUE_PROMPT)
     }
 
-    // 创建 REPL 编辑器
-    let mut rl = DefaultEditor::new()?;
+    /// 旁路提问：使用独立的空历史发送一次性问题
+    ///
+    /// 问答结束后（无论成功与否）恢复主对话的历史、`/retry` 用的输入、stop_reason
+    /// 和待发送的附件，回答不会进入主对话，附件也留给主对话的下一条消息。
+    fn ask_isolated(&mut self, question: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let main_history = std::mem::take(&mut self.messages);
+        let attachments = std::mem::take(&mut self.attachments);
+        let last_input = self.last_input.take();
+        let last_stop_reason = self.last_stop_reason.take();
 
-    // 加载历史记录
-    let history_path = ".mentat/history.txt";
-    let _ = rl.load_history(history_path);
-    debug!("历史记录加载完成");
+        let result = self.send_message(question);
 
-    println!(
-        r#"
-╔══════════════════════════════════════════════════════════╗
-║                  🧠 Mentat Code v{}                   ║
-║                                                          ║
-║  输入问题与 AI 对话，输入 /help 查看帮助                 ║
-║  已加载 {} 个工具                                         ║
-╚══════════════════════════════════════════════════════════╝
-"#,
-        env!("CARGO_PKG_VERSION"),
-        client.tool_count()
-    );
+        self.messages = main_history;
+        self.attachments = attachments;
+        self.last_input = last_input;
+        self.last_stop_reason = last_stop_reason;
+        result
+    }
 
-    loop {
-        let readline = rl.readline("❯ ");
-        match readline {
-            Ok(line) => {
-                let input = line.trim();
-                if input.is_empty() {
-                    continue;
-                }
+    fn clear_history(&mut self) {
+        self.messages.clear();
+        self.attachments.clear();
+        outln!(self, "📝 对话历史已清除\n");
+    }
 
-                // 添加到历史
-                let _ = rl.add_history_entry(input);
+    /// 归档当前对话后开始新对话；归档失败时保留当前对话
+    fn new_session(&mut self, dir: &Path) -> Result<(), session::SessionError> {
+        if self.messages.is_empty() {
+            outln!(self, "📝 当前对话为空，无需归档");
+        } else {
+            let (name, _) = session::archive(dir, &self.messages, self.persona.as_deref())?;
+            outln!(
+                self,
+                "💾 当前对话已归档为 {}，可用 /load {} 恢复",
+                name,
+                name
+            );
+        }
+        self.messages.clear();
+        self.attachments.clear();
+        self.last_stop_reason = None;
+        outln!(self, "🆕 已开始新对话\n");
+        Ok(())
+    }
 
-                // 处理命令
-                if input.starts_with('/') {
-                    if handle_command(input, &mut client) {
-                        break;
+    /// 清除对话及所有可能包含对话内容的状态（空闲清除时使用）
+    fn reset_session(&mut self) {
+        self.messages.clear();
+        self.attachments.clear();
+        self.last_stop_reason = None;
+        if let Some(staging) = self.staging.as_mut() {
+            staging.take();
+        }
+        self.trace.clear();
+    }
+
+    /// 撤销最近的 `turns` 轮对话
+    fn rewind_turns(&mut self, turns: usize) {
+        match history::rewind_turns(&mut self.messages, turns) {
+            Ok(dropped) => {
+                self.last_stop_reason = None;
+                outln!(
+                    self,
+                    "⏪ 已撤销最近 {} 轮对话，丢弃了 {} 条消息\n",
+                    turns,
+                    dropped
+                );
+            }
+            Err(e) => outln!(self, "❌ {}\n", e),
+        }
+    }
+
+    /// 回退对话：只保留前 `index` 条消息
+    fn rewind(&mut self, index: usize) {
+        match history::rewind(&mut self.messages, index) {
+            Ok(dropped) => {
+                self.last_stop_reason = None;
+                outln!(
+                    self,
+                    "⏪ 已回退到第 {} 条消息，丢弃了 {} 条\n",
+                    index,
+                    dropped
+                );
+            }
+            Err(e) => outln!(self, "❌ {}\n", e),
+        }
+    }
+
+    /// 当前上下文（对话历史、工具定义和系统提示）的 token 数
+    ///
+    /// 优先调用 count_tokens 接口；接口不可用（如代理不支持、openai 格式）或对话为空时
+    /// 退回按字符数的本地估算。
+    fn count_tokens(&self) -> TokenCount {
+        let request = CountTokensRequest {
+            model: self.model.clone(),
+            system: self.active_system_prompt().map(SystemPrompt::from),
+            messages: self.messages.clone(),
+            tools: self.tool_registry.definitions(),
+            thinking: self.thinking,
+        };
+        if !request.messages.is_empty() {
+            match self.connection.count_tokens(&request) {
+                Ok(tokens) => {
+                    return TokenCount {
+                        tokens,
+                        estimated: false,
                     }
-                    continue;
                 }
+                Err(e) => warn!("count_tokens 不可用，使用本地估算: {}", e),
+            }
+        }
+
+        let definitions = serde_json::to_string(&request.tools).unwrap_or_default();
+        let system = self.active_system_prompt().unwrap_or_default();
+        let tokens = history::estimate_tokens(&request.messages)
+            + history::estimate_text_tokens(&definitions)
+            + history::estimate_text_tokens(&system);
+        TokenCount {
+            tokens: tokens as u64,
+            estimated: true,
+        }
+    }
+
+    /// 读取图片或 PDF 作为附件，随下一条消息发送；路径须通过工具的读取校验
+    fn attach(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let validated = self.tool_registry.validator().validate_for_read(path)?;
+        self.attachments.push(attachment::load(&validated)?);
+        Ok(())
+    }
+
+    /// 把当前对话导出为 Markdown 文件，路径须通过工具的写入校验
+    fn export_markdown(&self, path: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let target = self.tool_registry.validator().validate_for_write(path)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, export::render_markdown(&self.messages))?;
+        Ok(target)
+    }
+
+    /// 导出本次会话的工具调用链，路径须通过工具的写入校验
+    fn export_trace(&self, path: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let target = self.tool_registry.validator().validate_for_write(path)?;
+        self.trace.export(&target, &self.model)?;
+        Ok(target)
+    }
+
+    /// 把当前对话保存为会话
+    fn save_session(&self, dir: &Path, name: &str) -> Result<PathBuf, session::SessionError> {
+        session::save(dir, name, &self.messages, self.persona.as_deref())
+    }
+
+    /// 加载会话并替换当前对话
+    ///
+    /// `spec` 为 `<name>` 或 `<name>@<index>`；带索引时只保留前 `index` 条消息，
+    /// 索引必须位于完整轮次的边界上。加载失败时当前对话保持不变。
+    fn load_session(&mut self, dir: &Path, spec: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let (name, index) = match spec.split_once('@') {
+            Some((name, index)) => (
+                name,
+                Some(
+                    index
+                        .parse::<usize>()
+                        .map_err(|_| format!("无效的消息索引: {}", index))?,
+                ),
+            ),
+            None => (spec, None),
+        };
+
+        let session = session::load(dir, name)?;
+        let mut messages = session.messages;
+        let total = messages.len();
+        if let Some(index) = index {
+            history::rewind(&mut messages, index)?;
+        }
+
+        // 会话中用过但已不存在的工具：历史照常保留，模型无法再调用它们
+        let missing = session::missing_tools(&messages, &self.tool_registry.tool_names());
+        if !missing.is_empty() {
+            outln!(
+                self,
+                "⚠️  会话中使用过的工具已不可用: {}",
+                missing.join(", ")
+            );
+        }
+
+        outln!(
+            self,
+            "📂 已加载会话 {}（{} / {} 条消息）\n",
+            name,
+            messages.len(),
+            total
+        );
+        self.messages = messages;
+        self.last_stop_reason = None;
+
+        // 恢复保存时的角色预设；配置中已没有该角色时不启用
+        self.persona = match session.persona {
+            Some(persona) if self.personas.contains_key(&persona) => {
+                outln!(self, "🎭 已恢复角色: {}\n", persona);
+                Some(persona)
+            }
+            Some(persona) => {
+                outln!(
+                    self,
+                    "⚠️  会话使用的角色 {} 已不在配置中，未启用\n",
+                    persona
+                );
+                None
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// 实际发送的系统提示：基础系统提示加上当前角色预设的片段
+    fn active_system_prompt(&self) -> Option<String> {
+        let persona = self
+            .persona
+            .as_ref()
+            .and_then(|name| self.personas.get(name))
+            .cloned();
+        instructions::compose_system_prompt(self.system_prompt.clone(), persona)
+    }
+
+    /// 启用角色预设，`None` 取消；名称未配置时返回错误且不改变当前角色
+    fn set_persona(&mut self, name: Option<&str>) -> Result<(), String> {
+        match name {
+            Some(name) if !self.personas.contains_key(name) => Err(format!(
+                "未知角色: {}，输入 /persona 查看已配置的角色",
+                name
+            )),
+            name => {
+                self.persona = name.map(str::to_string);
+                Ok(())
+            }
+        }
+    }
+
+    /// 获取中断标记，供 Ctrl+C 处理器使用
+    fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupted)
+    }
+
+    /// 切换模型，保留对话历史
+    ///
+    /// 输出 token 上限超过新模型允许的范围时随之下调。
+    fn set_model(&mut self, model: &str) {
+        self.model = model.to_string();
+        self.max_tokens = self.max_tokens.min(config::max_output_tokens(model));
+    }
+
+    fn tool_count(&self) -> usize {
+        self.tool_registry.len()
+    }
+}
+
+/// 把失败的 HTTP 响应转换为错误，并通过 `notify` 给出友好提示
+fn status_error(response: Response, retries: u32, notify: &dyn Fn(String)) -> SendError {
+    let status = response.status();
+
+    if status.as_u16() == 429 {
+        let rate_limited = ApiError::RateLimited {
+            retries,
+            reset_after: api::parse_retry_after(response.headers()),
+        };
+        error!("API 请求被限流: {}", rate_limited);
+        notify(format!("❌ {}", rate_limited.user_message()));
+        return Box::new(rate_limited);
+    }
+
+    let error_text = response.text().unwrap_or_default();
+    error!("API 请求失败 [{}]", status);
+
+    // 记录详细错误日志
+    debug!("API 错误详情: {}", error_text);
+
+    // 用户友好的错误提示
+    let user_message = match status.as_u16() {
+        401 => "认证失败，请检查 API 密钥是否正确",
+        403 => "访问被拒绝，请检查 API 权限",
+        500..=599 => "服务器错误，请稍后重试",
+        _ => "请求失败，请检查网络连接",
+    };
+
+    notify(format!("❌ {}", user_message));
+
+    // 返回错误而不是 Ok(())，让调用者知道发生了错误
+    format!("API Error [{}]: {}", status, user_message).into()
+}
+
+/// 格式化工具列表：每个工具一行，附简短描述
+fn format_tool_listing(registry: &ToolRegistry) -> String {
+    let mut listing = format!("\n🔧 已注册的工具 ({}):\n", registry.len());
+    for (name, description) in registry.summaries() {
+        listing.push_str(&format!("  - {:<12} {}\n", name, description));
+    }
+    listing
+}
+
+// ============== REPL 命令处理 ==============
+
+/// REPL 命令名（含别名）
+const COMMANDS: &[&str] = &[
+    "/exit",
+    "/quit",
+    "/q",
+    "/clear",
+    "/c",
+    "/new",
+    "/tools",
+    "/t",
+    "/apply",
+    "/continue",
+    "/retry",
+    "/ask",
+    "/rewind",
+    "/save",
+    "/load",
+    "/sessions",
+    "/trace",
+    "/export",
+    "/attach",
+    "/copy",
+    "/restore",
+    "/model",
+    "/paste",
+    "/dryrun",
+    "/temp",
+    "/system",
+    "/persona",
+    "/usage",
+    "/tokens",
+    "/history",
+    "/help",
+    "/h",
+    "/?",
+];
+
+/// 一行输入的类型
+#[derive(Debug, PartialEq)]
+enum InputKind<'a> {
+    /// REPL 命令（可能是未知命令）
+    Command(&'a str),
+    /// 发送给模型的消息
+    Message(&'a str),
+}
 
-                // 发送消息
-                debug!("发送消息: {}", input);
-                if let Err(e) = client.send_message(input) {
-                    error!("发送消息失败: {}", e);
+/// 区分命令和消息
+///
+/// `//` 开头的输入去掉一个 `/` 后作为消息发送；不是已知命令、且第一个词
+/// 像路径（如 `/usr/lib/...`）的输入也按消息处理，其余 `/` 开头的输入都是命令。
+fn classify_input(input: &str) -> InputKind<'_> {
+    if input.starts_with("//") {
+        return InputKind::Message(&input[1..]);
+    }
+    if !input.starts_with('/') {
+        return InputKind::Message(input);
+    }
+    let (name, _) = split_command(input);
+    if !COMMANDS.contains(&name) && name[1..].contains('/') {
+        return InputKind::Message(input);
+    }
+    InputKind::Command(input)
+}
+
+/// 拆分命令名和参数，如 `/ask what is X` -> (`/ask`, `what is X`)
+fn split_command(cmd: &str) -> (&str, &str) {
+    let cmd = cmd.trim();
+    match cmd.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (cmd, ""),
+    }
+}
+
+fn handle_command(
+    cmd: &str,
+    client: &mut ChatClient,
+    rl: &mut DefaultEditor,
+    history: Option<&InputHistory>,
+) -> bool {
+    let (name, args) = split_command(cmd);
+    match name {
+        "/exit" | "/quit" | "/q" => {
+            outln!(client, "👋 再见！");
+            return true;
+        }
+        "/clear" | "/c" => {
+            client.clear_history();
+        }
+        "/new" => {
+            if let Err(e) = client.new_session(Path::new(session::SESSIONS_DIR)) {
+                outln!(client, "❌ {}，当前对话未清除\n", e);
+            }
+        }
+        "/tools" | "/t" => {
+            if args.is_empty() {
+                outln!(client, "{}", format_tool_listing(&client.tool_registry));
+            } else {
+                match client.tool_registry.definition(args) {
+                    Some(definition) => outln!(
+                        client,
+                        "{}\n",
+                        serde_json::to_string_pretty(&definition).unwrap_or_default()
+                    ),
+                    None => outln!(client, "❓ 未知工具: {}，输入 /tools 查看全部工具\n", args),
                 }
             }
-            Err(ReadlineError::Interrupted) => {
-                println!("^C");
-                warn!("用户中断");
-                continue;
+        }
+        "/apply" => {
+            review_staged_changes(client, rl);
+        }
+        "/continue" => {
+            if let Err(e) = client.continue_truncated() {
+                error!("继续生成失败: {}", e);
             }
-            Err(ReadlineError::Eof) => {
-                println!("👋 再见！");
-                info!("用户退出");
-                break;
+        }
+        "/retry" => {
+            if let Err(e) = client.retry_last() {
+                error!("重试失败: {}", e);
+            }
+        }
+        "/ask" => {
+            if args.is_empty() {
+                outln!(client, "用法: /ask <问题>\n");
+            } else if let Err(e) = client.ask_isolated(args) {
+                error!("旁路提问失败: {}", e);
+            }
+        }
+        "/rewind" => {
+            let (turns, index) = match split_command(args) {
+                ("to", index) => (None, index.parse::<usize>().ok()),
+                ("", _) => (Some(1), None),
+                _ => (args.parse::<usize>().ok().filter(|n| *n > 0), None),
+            };
+            match (turns, index) {
+                (Some(turns), _) => client.rewind_turns(turns),
+                (_, Some(index)) => client.rewind(index),
+                _ => {
+                    let points = history::valid_cut_points(&client.messages)
+                        .iter()
+                        .map(|i| i.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    outln!(
+                        client,
+                        "用法: /rewind [轮数] 或 /rewind to <索引>\n可用的位置: {}\n",
+                        points
+                    );
+                }
+            }
+        }
+        "/save" => {
+            if args.is_empty() {
+                outln!(client, "用法: /save <名称>\n");
+            } else {
+                match client.save_session(Path::new(session::SESSIONS_DIR), args) {
+                    Ok(path) => outln!(client, "💾 会话已保存: {}\n", path.display()),
+                    Err(e) => outln!(client, "❌ {}\n", e),
+                }
+            }
+        }
+        "/load" => {
+            if args.is_empty() {
+                outln!(client, "用法: /load <名称>[@<索引>]\n");
+            } else if let Err(e) = client.load_session(Path::new(session::SESSIONS_DIR), args) {
+                outln!(client, "❌ {}\n", e);
+            }
+        }
+        "/sessions" => {
+            let sessions = session::list(Path::new(session::SESSIONS_DIR));
+            if sessions.is_empty() {
+                outln!(client, "📂 没有已保存的会话\n");
+            } else {
+                outln!(client, "📂 已保存的会话（从新到旧）:");
+                for name in sessions {
+                    outln!(client, "  - {}", name);
+                }
+                outln!(client, "用法: /load <名称>\n");
+            }
+        }
+        "/trace" => match split_command(args) {
+            ("export", path) if !path.is_empty() => {
+                match client.export_trace(path) {
+                    Ok(target) => outln!(
+                        client,
+                        "📊 已导出 {} 轮调用链到 {}\n",
+                        client.trace.len(),
+                        target.display()
+                    ),
+                    Err(e) => outln!(client, "❌ 导出失败: {}\n", e),
+                }
+            }
+            _ => outln!(
+                client,
+                "用法: /trace export <文件>\n已记录 {} 轮对话\n",
+                client.trace.len()
+            ),
+        },
+        "/attach" => {
+            if args.is_empty() {
+                outln!(
+                    client,
+                    "用法: /attach <文件>（png、jpg、gif、webp、pdf）\n待发送的附件: {} 个\n",
+                    client.attachments.len()
+                );
+            } else {
+                match client.attach(args) {
+                    Ok(()) => outln!(client, "📎 已附加 {}，将随下一条消息发送\n", args),
+                    Err(e) => outln!(client, "❌ {}\n", e),
+                }
+            }
+        }
+        "/copy" => {
+            let text = clipboard::last_assistant_text(&client.messages);
+            let (text, what) = match args {
+                "" => (text, "回复"),
+                "code" => (text.as_deref().and_then(clipboard::last_code_block), "代码块"),
+                _ => {
+                    outln!(client, "用法: /copy [code]\n");
+                    return false;
+                }
+            };
+            match text {
+                None => outln!(client, "没有可复制的{}\n", what),
+                Some(text) => match clipboard::copy(&text) {
+                    Ok(()) => outln!(
+                        client,
+                        "📋 已复制最近的{}（{} 行）\n",
+                        what,
+                        text.lines().count()
+                    ),
+                    Err(e) => outln!(client, "❌ {}，可改用 /export 导出对话\n", e),
+                },
+            }
+        }
+        "/export" => {
+            if args.is_empty() {
+                outln!(client, "用法: /export <文件.md>\n");
+            } else {
+                match client.export_markdown(args) {
+                    Ok(path) => outln!(client, "📝 对话已导出: {}\n", path.display()),
+                    Err(e) => outln!(client, "❌ 导出失败: {}\n", e),
+                }
+            }
+        }
+        "/restore" => {
+            if args.is_empty() {
+                let batches = tools::trash_batches(client.tool_registry.workspace_root());
+                if batches.is_empty() {
+                    outln!(client, "🗑️  回收站为空\n");
+                } else {
+                    outln!(client, "🗑️  回收站中的删除记录（从新到旧）:");
+                    for batch in batches {
+                        outln!(client, "  - {}", batch);
+                    }
+                    outln!(client, "用法: /restore <时间戳>\n");
+                }
+            } else {
+                match tools::restore_batch(client.tool_registry.workspace_root(), args) {
+                    Ok(files) => {
+                        for file in files {
+                            outln!(client, "  ♻️  已恢复 {}", file);
+                        }
+                        outln!(client);
+                    }
+                    Err(e) => outln!(client, "❌ {}\n", e),
+                }
+            }
+        }
+        "/model" => {
+            if args.is_empty() {
+                outln!(client, "🧠 当前模型: {}\n", client.model);
+            } else {
+                if !config::is_known_model(args) {
+                    warn!("未知模型: {}", args);
+                    outln!(
+                        client,
+                        "⚠️  {} 不在已知模型列表中，如使用代理或本地模型可忽略",
+                        args
+                    );
+                }
+                client.set_model(args);
+                outln!(client, "🧠 已切换到 {}，对话历史保留\n", client.model);
+            }
+        }
+        "/temp" => match args {
+            "" => match client.temperature {
+                Some(temperature) => outln!(client, "🌡️  当前 temperature: {}\n", temperature),
+                None => outln!(client, "🌡️  未设置 temperature，使用 API 默认值\n"),
+            },
+            "default" => {
+                client.temperature = None;
+                outln!(client, "🌡️  已恢复 API 默认的 temperature\n");
+            }
+            _ => match args.parse::<f32>() {
+                Ok(value) if config::SAMPLING_RANGE.contains(&value) => {
+                    client.temperature = Some(value);
+                    outln!(client, "🌡️  temperature 已设为 {}\n", value);
+                }
+                _ => outln!(client, "用法: /temp <0.0-1.0>|default\n"),
+            },
+        },
+        "/dryrun" => match args {
+            "on" => {
+                client.tool_registry.set_dry_run(true);
+                outln!(client, "🧪 dry-run 已开启：写入和编辑只报告将要进行的修改\n");
+            }
+            "off" => {
+                client.tool_registry.set_dry_run(false);
+                outln!(client, "🧪 dry-run 已关闭\n");
+            }
+            "" => outln!(
+                client,
+                "🧪 dry-run: {}\n用法: /dryrun on|off\n",
+                if client.tool_registry.is_dry_run() {
+                    "开启"
+                } else {
+                    "关闭"
+                }
+            ),
+            _ => outln!(client, "用法: /dryrun on|off\n"),
+        },
+        "/system" => match client.active_system_prompt() {
+            Some(prompt) => outln!(client, "📜 当前系统提示:\n{}\n", prompt),
+            None => outln!(
+                client,
+                "📜 未配置系统提示（可在配置中设置 system_prompt 或 system_prompt_file，或在工作区放置 MENTAT.md）\n"
+            ),
+        },
+        "/persona" => match args {
+            "" => {
+                let mut names: Vec<&String> = client.personas.keys().collect();
+                names.sort();
+                if names.is_empty() {
+                    outln!(client, "🎭 未配置角色预设（可在配置的 personas 中添加）\n");
+                } else {
+                    outln!(client, "🎭 可用的角色:");
+                    for name in names {
+                        let marker = if client.persona.as_ref() == Some(name) {
+                            "*"
+                        } else {
+                            " "
+                        };
+                        outln!(client, "  {} {}", marker, name);
+                    }
+                    outln!(client, "用法: /persona <名称> 切换，/persona off 取消\n");
+                }
+            }
+            "off" => {
+                let _ = client.set_persona(None);
+                outln!(client, "🎭 已取消角色预设\n");
+            }
+            name => match client.set_persona(Some(name)) {
+                Ok(()) => outln!(client, "🎭 已切换到角色: {}（对话历史保留）\n", name),
+                Err(e) => outln!(client, "❌ {}\n", e),
+            },
+        },
+        "/history" => match (args, history) {
+            ("clear", Some(history)) => match history.clear(rl) {
+                Ok(()) => outln!(client, "🧹 输入历史已清除\n"),
+                Err(e) => outln!(client, "❌ 删除历史文件失败: {}\n", e),
+            },
+            ("clear", None) => {
+                let _ = rl.clear_history();
+                outln!(client, "🧹 输入历史已清除（当前未保存历史文件）\n");
+            }
+            _ => outln!(client, "用法: /history clear\n"),
+        },
+        "/tokens" => {
+            let count = client.count_tokens();
+            let ratio = count.tokens as f64 / config::CONTEXT_WINDOW_TOKENS as f64;
+            outln!(
+                client,
+                "🔢 当前上下文: {} tokens，占上下文窗口的 {:.0}%{}",
+                api::format_tokens(count.tokens),
+                ratio * 100.0,
+                if count.estimated {
+                    "（本地估算）"
+                } else {
+                    ""
+                }
+            );
+            if ratio >= CONTEXT_WARNING_RATIO {
+                outln!(
+                    client,
+                    "⚠️  已接近模型的上下文上限，可用 /rewind 撤销或 /clear 清除对话"
+                );
+            }
+            outln!(client);
+        }
+        "/usage" => {
+            outln!(
+                client,
+                "📊 本次会话累计: {}，{}\n",
+                client.usage,
+                client.cost
+            );
+        }
+        "/help" | "/h" | "/?" => {
+            outln!(
+                client,
+                r#"
+📚 可用命令:
+  /exit, /quit, /q  - 退出程序
+  /clear, /c        - 清除对话历史
+  /new              - 归档当前对话后开始新对话
+  /tools, /t [名称] - 显示已注册的工具；指定名称时显示完整 schema
+  /apply            - 逐个审阅并应用暂存的修改（暂存模式）
+  /ask <问题>       - 旁路提问：不带对话历史，回答不计入主对话
+  /continue         - 回复被截断后让模型继续生成
+  /retry            - 重新发送上一条消息（如因网络或 API 错误失败）
+  /rewind [轮数]    - 撤销最近一轮（或 N 轮）对话，包括其中的工具调用
+  /rewind to <索引> - 回退对话，只保留前 N 条消息
+  /save <名称>      - 保存当前对话为会话
+  /load <名称>[@N]  - 加载会话（可选只保留前 N 条消息）
+  /sessions         - 列出已保存和自动归档的会话
+  /trace export <文件> - 导出本次会话的工具调用链（JSON）
+  /export <文件.md> - 把对话导出为 Markdown 文件
+  /attach <文件>    - 附加图片或 PDF，随下一条消息发送
+  /copy [code]      - 复制最近的回复（或其中最后一个代码块）到剪贴板
+  /usage            - 显示本次会话累计的 token 用量和费用估算
+  /tokens           - 计算当前上下文的 token 数（接口不可用时本地估算）
+  /history clear    - 清除输入历史（包括历史文件）
+  /model [名称]     - 显示或切换当前模型（保留对话历史）
+  /restore [时间戳] - 列出回收站，或恢复被 delete_file 删除的文件
+  /paste            - 粘贴模式：多行输入，单独一行 . 或 /end 结束
+  /dryrun [on|off]  - 查看或切换 dry-run（只报告修改，不写入文件）
+  /temp [值|default] - 查看或设置采样温度（0.0-1.0），default 恢复 API 默认值
+  /system           - 显示当前的系统提示
+  /persona [名称|off] - 列出、切换或取消角色预设（追加在系统提示之后）
+  /help, /h, /?     - 显示此帮助
+
+💡 提示:
+  - 直接输入问题即可与 AI 对话
+  - 行尾输入 \ 可以续行，多行内容作为一条消息发送
+  - 以 // 开头的输入会去掉一个 / 后作为消息发送，如 //usr/lib 是什么
+  - AI 可以使用已注册的工具操作本地文件
+  - 按 Ctrl+C 中断当前请求
+  - 按 Ctrl+D 退出
+"#
+            );
+        }
+        _ => {
+            outln!(
+                client,
+                "❓ 未知命令: {}，输入 /help 查看帮助（以 / 开头的消息请写成 //）",
+                cmd
+            );
+        }
+    }
+    false
+}
+
+/// 读取多行输入的后续行，返回拼接后的文本
+///
+/// Ctrl+C 只放弃当前缓冲区并回到主提示符，不退出程序；Ctrl+D 同样放弃。
+fn read_multiline(
+    client: &mut ChatClient,
+    rl: &mut DefaultEditor,
+    mut buffer: MultilineBuffer,
+) -> Option<String> {
+    loop {
+        match rl.readline(buffer.prompt()) {
+            Ok(line) => {
+                if let Some(text) = buffer.push(&line) {
+                    return Some(text);
+                }
+            }
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
+                outln!(client, "^C 已放弃多行输入\n");
+                return None;
             }
             Err(err) => {
                 error!("读取错误: {:?}", err);
-                break;
+                return None;
             }
         }
     }
+}
 
-    // 保存历史记录
-    let _ = fs::create_dir_all(".mentat");
-    let _ = rl.save_history(history_path);
-    debug!("历史记录已保存");
+// ============== 暂存修改审阅 ==============
 
-    info!("Mentat Code 退出");
-    Ok(())
+/// 逐个展示暂存修改的 diff，由用户决定保留、跳过或编辑
+///
+/// 所有选择完成后才统一应用被保留的修改；中途按 Ctrl+C 会中止全部，
+/// 此时不应用任何修改，暂存内容原样放回暂存区。
+fn review_staged_changes(client: &mut ChatClient, rl: &mut DefaultEditor) {
+    let changes = match client.staging.as_mut() {
+        Some(staging) => staging.take(),
+        None => {
+            outln!(
+                client,
+                "ℹ️  暂存模式未启用，请在配置中设置 \"staging\": true\n"
+            );
+            return;
+        }
+    };
+
+    if changes.is_empty() {
+        outln!(client, "📭 暂存区为空\n");
+        return;
+    }
+
+    let total = changes.len();
+    let mut approved: Vec<StagedChange> = Vec::new();
+
+    for (index, change) in changes.iter().enumerate() {
+        let mut change = change.clone();
+        outln!(
+            client,
+            "\n📄 [{}/{}] {}",
+            index + 1,
+            total,
+            change.file_path
+        );
+        outln!(
+            client,
+            "{}",
+            change.unified_diff(client.tool_registry.validator())
+        );
+
+        loop {
+            let prompt = format!("   [k]eep / [s]kip / [e]dit {} ", client.labels.prompt());
+            match rl.readline(&prompt) {
+                Ok(line) => match staging::parse_review_choice(&line) {
+                    Some(ReviewChoice::Keep) => {
+                        approved.push(change);
+                        break;
+                    }
+                    Some(ReviewChoice::Skip) => {
+                        outln!(client, "   ⏭️  已跳过");
+                        break;
+                    }
+                    Some(ReviewChoice::Edit) => match edit_in_editor(&change.content) {
+                        Ok(content) => {
+                            change.content = content;
+                            outln!(
+                                client,
+                                "{}",
+                                change.unified_diff(client.tool_registry.validator())
+                            );
+                        }
+                        Err(e) => {
+                            error!("打开编辑器失败: {}", e);
+                            outln!(client, "   ❌ 无法打开编辑器，请设置 $EDITOR");
+                        }
+                    },
+                    None => outln!(client, "   请输入 k / s / e"),
+                },
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
+                    outln!(
+                        client,
+                        "\n⛔ 已中止，未应用任何修改（修改仍保留在暂存区）\n"
+                    );
+                    if let Some(staging) = client.staging.as_mut() {
+                        for change in &changes {
+                            staging.stage(&change.file_path, &change.content);
+                        }
+                    }
+                    return;
+                }
+                Err(err) => {
+                    error!("读取错误: {:?}", err);
+                    return;
+                }
+            }
+        }
+    }
+
+    let mut applied = 0;
+    for change in &approved {
+        let output = client
+            .tool_registry
+            .execute("write_file", &change.to_tool_input());
+        let result: Value = serde_json::from_str(&output).unwrap_or(Value::Null);
+        if result.get("success").and_then(|v| v.as_bool()) == Some(true) {
+            applied += 1;
+            outln!(client, "   ✅ {}", change.file_path);
+        } else {
+            let reason = result
+                .get("error")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown error");
+            outln!(client, "   ❌ {}: {}", change.file_path, reason);
+        }
+    }
+
+    outln!(client, "\n📝 已应用 {}/{} 个修改\n", applied, total);
+}
+
+/// 在外部编辑器中编辑内容，返回编辑后的结果
+fn edit_in_editor(content: &str) -> std::io::Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let (temp_path, mut file) = create_temp_file("mentat-edit")?;
+    file.write_all(content.as_bytes())?;
+    drop(file);
+    let status = process::Command::new(&editor).arg(&temp_path).status();
+    let edited = fs::read_to_string(&temp_path);
+    let _ = fs::remove_file(&temp_path);
+
+    if !status?.success() {
+        return Err(std::io::Error::other(format!(
+            "{} exited with error",
+            editor
+        )));
+    }
+    edited
+}
+
+/// 在临时目录中新建一个之前不存在的文件，避免使用可预测的文件名覆盖或读取他人的文件
+fn create_temp_file(prefix: &str) -> std::io::Result<(PathBuf, fs::File)> {
+    let dir = std::env::temp_dir();
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    for attempt in 0..100u32 {
+        let path = dir.join(format!(
+            "{}-{}-{}-{}.tmp",
+            prefix,
+            process::id(),
+            nanos,
+            attempt
+        ));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "无法创建临时文件",
+    ))
+}
+
+// ============== 日志初始化 ==============
+
+fn init_logger(cli: &Cli) {
+    let log_level = if cli.debug { "debug" } else { &cli.log_level };
+
+    let env = env_logger::Env::default().default_filter_or(log_level);
+    env_logger::Builder::from_env(env)
+        .format_timestamp(Some(env_logger::TimestampPrecision::Seconds))
+        .init();
+
+    debug!("日志系统初始化完成，级别: {}", log_level);
+}
+
+// ============== 工作区 ==============
+
+/// 按配置构建 HTTP 客户端（超时、连接池、keep-alive 和代理）
+fn build_http_client(options: &HttpClientOptions) -> reqwest::Result<Client> {
+    let mut builder = Client::builder()
+        .timeout(options.timeout)
+        .pool_idle_timeout(options.pool_idle_timeout)
+        .tcp_keepalive(options.tcp_keepalive);
+    if let Some(proxy_url) = &options.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy_url)?);
+    }
+    builder.build()
+}
+
+/// 解析配置的额外根目录，每个都必须是已存在的目录
+fn resolve_extra_roots(
+    settings: &Settings,
+    workspace_root: &Path,
+) -> Result<HashMap<String, PathBuf>, Box<dyn std::error::Error>> {
+    settings
+        .get_extra_roots(workspace_root)
+        .into_iter()
+        .map(|(name, root)| match root.canonicalize() {
+            Ok(root) if root.is_dir() => Ok((name, root)),
+            Ok(root) => Err(format!("extra_roots.{}: {} 不是目录", name, root.display()).into()),
+            Err(e) => Err(format!("extra_roots.{}: {}: {}", name, root.display(), e).into()),
+        })
+        .collect()
+}
+
+/// 告诉模型可以用 `名称:路径` 读取额外的根目录
+fn extra_roots_note(roots: &HashMap<String, PathBuf>) -> Option<String> {
+    if roots.is_empty() {
+        return None;
+    }
+    let mut names: Vec<&String> = roots.keys().collect();
+    names.sort();
+    let lines: Vec<String> = names
+        .iter()
+        .map(|name| format!("- {}: {}", name, roots[*name].display()))
+        .collect();
+    Some(format!(
+        "Read-only directories outside the workspace can be accessed with `name:relative/path` (e.g. `{}:src/lib.rs`):\n{}",
+        names[0],
+        lines.join("\n")
+    ))
+}
+
+/// 切换进程工作目录到 `-C` 指定的目录，返回规范化后的路径
+fn change_workdir(dir: &Path) -> io::Result<PathBuf> {
+    let path = dir.canonicalize().map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("{} 不存在或无法访问: {}", dir.display(), e),
+        )
+    })?;
+    if !path.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotADirectory,
+            format!("{} 不是目录", dir.display()),
+        ));
+    }
+    std::env::set_current_dir(&path)?;
+    Ok(path)
+}
+
+/// 确定工作区根目录
+///
+/// 配置了 `workspace_root` 时直接使用该目录；否则在启用 `workspace.discover` 时
+/// 从当前目录向上查找标记，未找到时保持当前目录不变。确定后切换进程工作目录，
+/// 使会话、回收站等相对路径也落在工作区内。
+fn resolve_workspace_root(settings: &Settings) -> std::io::Result<PathBuf> {
+    let cwd = std::env::current_dir()?;
+    if let Some(root) = &settings.workspace_root {
+        let root = cwd.join(root).canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} 不是目录", root.display()),
+            ));
+        }
+        std::env::set_current_dir(&root)?;
+        return Ok(root);
+    }
+    if !settings.workspace.discover {
+        return Ok(cwd);
+    }
+
+    let root = workspace::resolve_root(&cwd, &settings.workspace.get_markers());
+    if root != cwd {
+        std::env::set_current_dir(&root)?;
+        debug!("从 {} 向上发现工作区根目录", cwd.display());
+    }
+    Ok(root)
+}
+
+// ============== 主函数 ==============
+
+fn main() -> RlResult<()> {
+    // 解析命令行参数
+    let cli = Cli::parse();
+
+    // 初始化日志系统
+    init_logger(&cli);
+
+    info!("Mentat Code v{} 启动", env!("CARGO_PKG_VERSION"));
+
+    // 处理 -C 参数：在读取配置和确定工作区之前切换目录
+    if let Some(dir) = &cli.workdir {
+        match change_workdir(dir) {
+            Ok(path) => debug!("工作目录: {}", path.display()),
+            Err(e) => {
+                error!("无法切换工作目录: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // 处理 --init 参数
+    if cli.init {
+        match init::run() {
+            Ok(Some(path)) => {
+                println!("✅ 配置文件已创建: {}", path.display());
+                return Ok(());
+            }
+            Ok(None) => {
+                println!("已取消，保留现有配置文件");
+                return Ok(());
+            }
+            Err(e) => {
+                error!("创建配置文件失败: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // 处理 --migrate-config 参数
+    if cli.migrate_config {
+        let result = config::find_config_path(cli.config.as_deref())
+            .and_then(|path| config::migrate_file(&path).map(|version| (path, version)));
+        match result {
+            Ok((path, version)) if version >= config::CONFIG_VERSION => {
+                println!("✅ 配置文件已是当前版本: {}", path.display());
+                return Ok(());
+            }
+            Ok((path, version)) => {
+                println!(
+                    "✅ 配置文件已从版本 {} 升级到 {}: {}",
+                    version,
+                    config::CONFIG_VERSION,
+                    path.display()
+                );
+                return Ok(());
+            }
+            Err(e) => {
+                error!("升级配置文件失败: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // 加载配置（使用新的配置模块）
+    let mut settings = match config::load_settings_from_path(cli.config.as_deref()) {
+        Ok(s) => {
+            info!("配置加载成功");
+            debug!("使用模型: {}", s.get_model());
+            s
+        }
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    if cli.yes {
+        settings.auto_approve = true;
+    }
+    if cli.quiet {
+        settings.verbosity = Some("quiet".to_string());
+    } else if cli.verbose {
+        settings.verbosity = Some("verbose".to_string());
+    }
+
+    // 确定工作区根目录（所有工具都以此为沙箱）
+    let workspace_root = match resolve_workspace_root(&settings) {
+        Ok(root) => root,
+        Err(e) => {
+            error!("无法切换到工作区根目录: {}", e);
+            process::exit(1);
+        }
+    };
+    info!("工作区: {}", workspace_root.display());
+    settings.workspace_root = Some(workspace_root.to_string_lossy().into_owned());
+
+    // 创建 ChatClient
+    let mut client = match ChatClient::new(&settings) {
+        Ok(c) => {
+            info!("客户端创建成功");
+            c
+        }
+        Err(e) => {
+            // 避免在错误信息中泄露敏感信息
+            error!("创建客户端失败: 请检查网络连接和配置");
+            // 仅在调试模式下显示详细错误
+            if cli.debug {
+                debug!("详细错误: {}", e);
+            }
+            process::exit(1);
+        }
+    };
+
+    // 连通性检查（默认关闭，避免拖慢启动）
+    if cli.check || settings.preflight {
+        match client.connection.preflight() {
+            Ok(()) => info!("连通性检查通过: {}", settings.env.base_url),
+            Err(e) => {
+                error!("连通性检查失败: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // Ctrl+C 中断正在进行的请求而不是退出程序（输入提示处由 rustyline 自行处理）
+    let flag = client.interrupt_flag();
+    if let Err(e) = ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst)) {
+        warn!("无法注册 Ctrl+C 处理器: {}", e);
+    }
+
+    // 处理 --execute 参数或管道输入（非交互模式）
+    let mode = match select_run_mode(cli.execute, io::stdin().is_terminal(), io::stdin()) {
+        Ok(mode) => mode,
+        Err(e) => {
+            error!("读取提示失败: {}", e);
+            process::exit(1);
+        }
+    };
+    if cli.json {
+        if mode == RunMode::Repl {
+            error!("--json 只能用于单命令模式（-e/-p 或管道输入）");
+            process::exit(1);
+        }
+        client.output = Box::new(JsonOutput::new(StdoutOutput));
+        client.spinner = false;
+    }
+    if let RunMode::OneShot(prompt) = mode {
+        info!("执行单条命令模式");
+        if let Err(e) = client.send_message(&prompt) {
+            error!("执行失败: {}", e);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // 创建 REPL 编辑器
+    let mut rl = DefaultEditor::new()?;
+
+    // 空闲清除启用时不持久化输入历史，避免在共享终端上留下记录
+    let mut idle_timer = settings
+        .get_idle_clear()
+        .map(|limit| idle::IdleTimer::new(limit, Instant::now()));
+    let history = idle_timer.is_none().then(|| {
+        InputHistory::new(
+            settings.get_history_path(),
+            settings.get_history_max_entries(),
+        )
+    });
+
+    // 加载历史记录
+    if let Some(history) = &history {
+        history.load(&mut rl);
+        debug!("历史记录加载完成");
+    }
+
+    let banner = format!(
+        r#"
+╔══════════════════════════════════════════════════════════╗
+║                  🧠 Mentat Code v{}                   ║
+║                                                          ║
+║  输入问题与 AI 对话，输入 /help 查看帮助                 ║
+║  已加载 {} 个工具                                         ║
+║  工作区: {}
+╚══════════════════════════════════════════════════════════╝
+"#,
+        env!("CARGO_PKG_VERSION"),
+        client.tool_count(),
+        workspace_root.display()
+    );
+    outln!(client, "{}", banner);
+
+    loop {
+        let prompt = format!("{} ", client.labels.prompt());
+        let readline = rl.readline(&prompt);
+        match readline {
+            Ok(line) => {
+                let input = line.trim();
+
+                // 空闲超时：先清除上一位用户的对话，再处理本次输入
+                if let Some(timer) = idle_timer.as_mut() {
+                    if timer.record_input(Instant::now()) {
+                        client.reset_session();
+                        let _ = rl.clear_history();
+                        outln!(client, "🔒 空闲时间过长，之前的对话已清除\n");
+                        info!("空闲超时，对话已清除");
+                    }
+                }
+
+                if input.is_empty() {
+                    continue;
+                }
+
+                // 多行输入：/paste 粘贴模式或行尾 `\` 续行
+                let buffer = if input == "/paste" {
+                    outln!(client, "📋 粘贴模式：单独一行 . 或 /end 结束，Ctrl+C 放弃");
+                    Some(MultilineBuffer::paste())
+                } else if multiline::is_continued(input) {
+                    Some(MultilineBuffer::continuation(input))
+                } else {
+                    None
+                };
+                if let Some(buffer) = buffer {
+                    let Some(text) = read_multiline(&mut client, &mut rl, buffer) else {
+                        continue;
+                    };
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+                    let _ = rl.add_history_entry(text.as_str());
+                    debug!("发送多行消息: {} 字节", text.len());
+                    if let Err(e) = client.send_message(&text) {
+                        error!("发送消息失败: {}", e);
+                        outln!(client, "💡 输入 /retry 重新发送\n");
+                    }
+                    continue;
+                }
+
+                // 添加到历史
+                let _ = rl.add_history_entry(input);
+
+                match classify_input(input) {
+                    // 处理命令
+                    InputKind::Command(cmd) => {
+                        if handle_command(cmd, &mut client, &mut rl, history.as_ref()) {
+                            break;
+                        }
+                    }
+                    // 发送消息
+                    InputKind::Message(message) => {
+                        debug!("发送消息: {}", message);
+                        if let Err(e) = client.send_message(message) {
+                            error!("发送消息失败: {}", e);
+                            outln!(client, "💡 输入 /retry 重新发送\n");
+                        }
+                    }
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                outln!(client, "^C");
+                warn!("用户中断");
+                continue;
+            }
+            Err(ReadlineError::Eof) => {
+                outln!(client, "👋 再见！");
+                info!("用户退出");
+                break;
+            }
+            Err(err) => {
+                error!("读取错误: {:?}", err);
+                break;
+            }
+        }
+    }
+
+    // 保存历史记录
+    if let Some(history) = &history {
+        match history.save(&mut rl) {
+            Ok(()) => debug!("历史记录已保存"),
+            Err(e) => warn!("保存历史记录失败: {}", e),
+        }
+    }
+
+    info!("Mentat Code 退出");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use output::{BufferOutput, Verbosity};
+    use test_support::{MockResponse, MockServer};
+
+    /// 指向不可达地址的配置，请求会立即失败
+    fn unreachable_settings() -> Settings {
+        Settings {
+            env: config::Env {
+                api_key: "test-api-key-12345".to_string(),
+                base_url: "http://127.0.0.1:1".to_string(),
+                https_proxy: None,
+            },
+            // 连接必然失败，不必等待重试
+            max_retries: Some(0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_client_uses_configured_model() {
+        let settings = Settings {
+            model: Some("claude-sonnet-4-5-20250929".to_string()),
+            ..unreachable_settings()
+        };
+        let client = ChatClient::new(&settings).unwrap();
+        assert_eq!(client.model, "claude-sonnet-4-5-20250929");
+    }
+
+    fn mock_settings(server: &MockServer) -> Settings {
+        Settings {
+            env: config::Env {
+                api_key: "test-api-key-12345".to_string(),
+                base_url: server.url.clone(),
+                https_proxy: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn text_response(text: &str, stop_reason: &str) -> MockResponse {
+        MockResponse::json(serde_json::json!({
+            "content": [{"type": "text", "text": text}],
+            "stop_reason": stop_reason
+        }))
+    }
+
+    #[test]
+    fn test_pause_turn_continues_conversation() {
+        let server = MockServer::start(vec![
+            text_response("searching...", "pause_turn"),
+            text_response("done", "end_turn"),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+
+        client.send_message("search the web").unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        // 续传请求原样带上暂停前的 assistant 消息，不追加新的 user 消息
+        let resent = requests[1].json();
+        assert_eq!(resent["messages"].as_array().unwrap().len(), 2);
+        assert_eq!(resent["messages"][1]["role"], "assistant");
+        assert_eq!(client.messages.len(), 3);
+    }
+
+    #[test]
+    fn test_empty_response_rolls_back_turn() {
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({"content": [], "stop_reason": "end_turn"})),
+            text_response("hello", "end_turn"),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client.attachments = vec![serde_json::json!({"type": "text", "text": "attached"})];
+
+        // 返回错误，附件保留给 /retry
+        assert!(client.send_message("hi").is_err());
+        assert!(client.messages.is_empty());
+        assert_eq!(client.last_stop_reason.as_deref(), Some("end_turn"));
+        assert_eq!(client.attachments.len(), 1);
+
+        // 重试时不会带上空的 assistant 消息
+        client.retry_last().unwrap();
+        let resent = server.requests()[1].json();
+        assert_eq!(resent["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(resent["messages"][0]["content"][0]["text"], "attached");
+        assert_eq!(client.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_empty_response_keeps_completed_tool_rounds() {
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "content": [{
+                    "type": "tool_use",
+                    "id": "toolu_1",
+                    "name": "stat",
+                    "input": {"path": "Cargo.toml"}
+                }],
+                "stop_reason": "tool_use"
+            })),
+            MockResponse::json(serde_json::json!({"content": [], "stop_reason": "end_turn"})),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+
+        assert!(client.send_message("check").is_err());
+        // 用户消息、工具调用和工具结果都保留，空的 assistant 消息不写入
+        assert_eq!(client.messages.len(), 3);
+        assert_eq!(client.messages[1].role, "assistant");
+        assert_eq!(client.messages[2].role, "user");
+    }
+
+    #[test]
+    fn test_pause_turn_stops_at_limit() {
+        let server = MockServer::start(vec![text_response("still working", "pause_turn")]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+
+        client.send_message("long task").unwrap();
+
+        let expected = 1 + MAX_PAUSE_CONTINUATIONS as usize;
+        assert_eq!(server.requests().len(), expected);
+    }
+
+    #[test]
+    fn test_tool_loop_stops_at_iteration_limit() {
+        // 模型每次都继续调用工具
+        let server = MockServer::start(vec![MockResponse::json(serde_json::json!({
+            "content": [{
+                "type": "tool_use",
+                "id": "toolu_1",
+                "name": "stat",
+                "input": {"path": "Cargo.toml"}
+            }],
+            "stop_reason": "tool_use"
+        }))]);
+        let mut settings = mock_settings(&server);
+        settings.max_tool_iterations = Some(3);
+        let mut client = ChatClient::new(&settings).unwrap();
+
+        client.send_message("look around").unwrap();
+
+        // 3 次往返后再请求一次让模型总结，之后不再继续
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        let last = requests[3].json();
+        let blocks = last["messages"].as_array().unwrap().last().unwrap()["content"].clone();
+        assert_eq!(blocks[0]["type"], "tool_result");
+        assert_eq!(blocks[1]["text"], TOOL_LIMIT_NOTE);
+
+        // 达到上限后的工具调用不执行，历史以助手回复结尾
+        let results = match &client.messages[client.messages.len() - 2].content {
+            MessageContent::Blocks(blocks) => blocks.clone(),
+            other => panic!("unexpected content: {:?}", other),
+        };
+        assert_eq!(results[0]["content"], TOOL_LIMIT_RESULT);
+        let last = client.messages.last().unwrap();
+        assert_eq!(last.role, "assistant");
+        assert!(matches!(last.content, MessageContent::Text(_)));
+    }
+
+    /// 记录提示并返回固定答复的确认器
+    struct ScriptedApprover {
+        answer: bool,
+        prompts: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl Approver for ScriptedApprover {
+        fn confirm(&mut self, prompt: &str) -> bool {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            self.answer
+        }
+    }
+
+    fn write_tool_response(file_path: &str, content: &str) -> MockResponse {
+        MockResponse::json(serde_json::json!({
+            "content": [{
+                "type": "tool_use",
+                "id": "toolu_1",
+                "name": "write_file",
+                "input": {"file_path": file_path, "content": content}
+            }],
+            "stop_reason": "tool_use"
+        }))
+    }
+
+    /// 统计执行次数的只读工具
+    struct CountingTool(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl tools::Tool for CountingTool {
+        fn name(&self) -> &'static str {
+            "count"
+        }
+
+        fn definition(&self) -> Value {
+            serde_json::json!({"name": "count", "description": "Count calls", "input_schema": {"type": "object"}})
+        }
+
+        fn execute(&self, _ctx: &ToolContext, _input: &Value) -> String {
+            let calls = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            format!(r#"{{"success":true,"calls":{}}}"#, calls)
+        }
+    }
+
+    fn count_tool_response(id: &str) -> MockResponse {
+        MockResponse::json(serde_json::json!({
+            "content": [{"type": "tool_use", "id": id, "name": "count", "input": {"path": "a"}}],
+            "stop_reason": "tool_use"
+        }))
+    }
+
+    #[test]
+    fn test_identical_tool_calls_run_once_per_turn() {
+        let server = MockServer::start(vec![
+            count_tool_response("toolu_1"),
+            count_tool_response("toolu_2"),
+            text_response("done", "end_turn"),
+            count_tool_response("toolu_3"),
+            text_response("done", "end_turn"),
+        ]);
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client
+            .tool_registry
+            .register(Box::new(CountingTool(Arc::clone(&calls))));
+
+        client.send_message("count twice").unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // 重复调用得到与第一次相同的结果
+        let result = &server.requests()[2].json()["messages"][4]["content"][0];
+        assert_eq!(result["tool_use_id"], "toolu_2");
+        assert!(result["content"].as_str().unwrap().contains(r#""calls":1"#));
+
+        // 新的一轮不使用上一轮的结果
+        client.send_message("count again").unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_identical_mutating_calls_run_each_time() {
+        let path = "target/test_append_twice.txt";
+        let _ = fs::remove_file(path);
+        let append = || {
+            MockResponse::json(serde_json::json!({
+                "content": [{
+                    "type": "tool_use",
+                    "id": "toolu_1",
+                    "name": "append_file",
+                    "input": {"file_path": path, "content": "line\n"}
+                }],
+                "stop_reason": "tool_use"
+            }))
+        };
+        let server = MockServer::start(vec![append(), append(), text_response("done", "end_turn")]);
+        let mut settings = mock_settings(&server);
+        settings.auto_approve = true;
+        let mut client = ChatClient::new(&settings).unwrap();
+
+        client.send_message("append twice").unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "line\nline\n");
+        let _ = fs::remove_file(path);
+    }
+
+    /// 休眠一段时间后返回输入的只读工具
+    struct SleepTool;
+
+    impl tools::Tool for SleepTool {
+        fn name(&self) -> &'static str {
+            "sleep"
+        }
+
+        fn definition(&self) -> Value {
+            serde_json::json!({"name": "sleep", "description": "Sleep", "input_schema": {"type": "object"}})
+        }
+
+        fn execute(&self, _ctx: &ToolContext, input: &Value) -> String {
+            thread::sleep(Duration::from_millis(300));
+            format!(r#"{{"success":true,"id":{}}}"#, input["id"])
+        }
+    }
+
+    #[test]
+    fn test_read_only_tool_calls_run_in_parallel_in_order() {
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "content": [
+                    {"type": "tool_use", "id": "toolu_1", "name": "sleep", "input": {"id": 1}},
+                    {"type": "tool_use", "id": "toolu_2", "name": "sleep", "input": {"id": 2}},
+                    {"type": "tool_use", "id": "toolu_3", "name": "sleep", "input": {"id": 3}}
+                ],
+                "stop_reason": "tool_use"
+            })),
+            text_response("done", "end_turn"),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client.tool_registry.register(Box::new(SleepTool));
+
+        let started = Instant::now();
+        client.send_message("sleep three times").unwrap();
+        assert!(started.elapsed() < Duration::from_millis(800));
+
+        let results = server.requests()[1].json()["messages"][2]["content"].clone();
+        for (i, result) in results.as_array().unwrap().iter().enumerate() {
+            assert_eq!(result["tool_use_id"], format!("toolu_{}", i + 1));
+            let expected = format!(r#""id":{}"#, i + 1);
+            assert!(result["content"].as_str().unwrap().contains(&expected));
+        }
+    }
+
+    #[test]
+    fn test_timed_out_tool_still_returns_tool_result() {
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "content": [{"type": "tool_use", "id": "toolu_1", "name": "sleep", "input": {"id": 1}}],
+                "stop_reason": "tool_use"
+            })),
+            text_response("done", "end_turn"),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client.tool_registry.register(Box::new(SleepTool));
+        client.tool_registry.set_timeouts(HashMap::from([(
+            "sleep".to_string(),
+            Duration::from_millis(50),
+        )]));
+
+        client.send_message("sleep").unwrap();
+
+        let result = &server.requests()[1].json()["messages"][2]["content"][0];
+        assert_eq!(result["type"], "tool_result");
+        assert_eq!(result["tool_use_id"], "toolu_1");
+        assert!(result["content"].as_str().unwrap().contains("ToolTimeout"));
+    }
+
+    #[test]
+    fn test_read_after_write_sees_new_content() {
+        let path = "target/test_parallel_read_after_write.txt";
+        fs::write(path, "old").unwrap();
+        let read = serde_json::json!({"file_path": path});
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "content": [
+                    {"type": "tool_use", "id": "toolu_1", "name": "read_file", "input": read},
+                    {"type": "tool_use", "id": "toolu_2", "name": "write_file", "input": {"file_path": path, "content": "new"}},
+                    {"type": "tool_use", "id": "toolu_3", "name": "read_file", "input": read}
+                ],
+                "stop_reason": "tool_use"
+            })),
+            text_response("done", "end_turn"),
+        ]);
+        let settings = Settings {
+            auto_approve: true,
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+
+        client.send_message("read, write, read").unwrap();
+
+        let results = server.requests()[1].json()["messages"][2]["content"].clone();
+        assert!(results[0]["content"].as_str().unwrap().contains("old"));
+        assert!(results[2]["content"].as_str().unwrap().contains("new"));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_large_write_rejected_by_user() {
+        let path = "target/test_confirm_large_write.txt";
+        let _ = fs::remove_file(path);
+        let server = MockServer::start(vec![
+            write_tool_response(path, &"x".repeat(20)),
+            text_response("ok", "end_turn"),
+        ]);
+        let settings = Settings {
+            confirm: Some(config::ConfirmSettings {
+                max_write_bytes: Some(10),
+                max_batch_files: None,
+            }),
+            ..mock_settings(&server)
+        };
+        let prompts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.approver = Box::new(ScriptedApprover {
+            answer: false,
+            prompts: Arc::clone(&prompts),
+        });
+
+        client.send_message("write a big file").unwrap();
+
+        assert_eq!(prompts.lock().unwrap().len(), 1);
+        assert!(!std::path::Path::new(path).exists());
+        let tool_result = &server.requests()[1].json()["messages"][2]["content"][0];
+        assert!(tool_result["content"]
+            .as_str()
+            .unwrap()
+            .contains("rejected"));
+    }
+
+    #[test]
+    fn test_small_write_proceeds_without_prompt() {
+        let path = "target/test_confirm_small_write.txt";
+        let server = MockServer::start(vec![
+            write_tool_response(path, "tiny"),
+            text_response("ok", "end_turn"),
+        ]);
+        let settings = Settings {
+            confirm: Some(config::ConfirmSettings {
+                max_write_bytes: Some(10),
+                max_batch_files: Some(3),
+            }),
+            ..mock_settings(&server)
+        };
+        let prompts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.approver = Box::new(ScriptedApprover {
+            answer: false,
+            prompts: Arc::clone(&prompts),
+        });
+
+        client.send_message("write a small file").unwrap();
+
+        assert!(prompts.lock().unwrap().is_empty());
+        assert_eq!(fs::read_to_string(path).unwrap(), "tiny");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_write_prompts_by_default() {
+        let path = "target/test_confirm_default.txt";
+        let _ = fs::remove_file(path);
+        let server = MockServer::start(vec![
+            write_tool_response(path, "hello"),
+            text_response("ok", "end_turn"),
+        ]);
+        let prompts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client.approver = Box::new(ScriptedApprover {
+            answer: true,
+            prompts: Arc::clone(&prompts),
+        });
+
+        client.send_message("write a file").unwrap();
+
+        let prompts = prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert!(prompts[0].contains(path));
+        assert!(prompts[0].contains("5 字节"));
+        assert_eq!(fs::read_to_string(path).unwrap(), "hello");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_auto_approve_skips_prompt() {
+        let path = "target/test_confirm_auto_approve.txt";
+        let server = MockServer::start(vec![
+            write_tool_response(path, "hello"),
+            text_response("ok", "end_turn"),
+        ]);
+        let settings = Settings {
+            auto_approve: true,
+            ..mock_settings(&server)
+        };
+        let prompts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.approver = Box::new(ScriptedApprover {
+            answer: false,
+            prompts: Arc::clone(&prompts),
+        });
+
+        client.send_message("write a file").unwrap();
+
+        assert!(prompts.lock().unwrap().is_empty());
+        assert_eq!(fs::read_to_string(path).unwrap(), "hello");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_openai_format_runs_tool_loop() {
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "choices": [{
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call_1",
+                            "type": "function",
+                            "function": {"name": "read_file", "arguments": "{\"file_path\":\"Cargo.toml\"}"}
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }]
+            })),
+            MockResponse::json(serde_json::json!({
+                "choices": [{
+                    "message": {"role": "assistant", "content": "done"},
+                    "finish_reason": "stop"
+                }],
+                "usage": {"prompt_tokens": 50, "completion_tokens": 5}
+            })),
+        ]);
+        let settings = Settings {
+            api_format: Some("openai".to_string()),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+
+        client.send_message("read Cargo.toml").unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/v1/chat/completions");
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some("Bearer test-api-key-12345")
+        );
+        assert_eq!(requests[0].json()["tools"][0]["type"], "function");
+        // 第二次请求带上了工具结果
+        let messages = requests[1].json()["messages"].clone();
+        assert_eq!(messages[1]["tool_calls"][0]["id"], "call_1");
+        assert_eq!(messages[2]["role"], "tool");
+        assert!(messages[2]["content"]
+            .as_str()
+            .unwrap()
+            .contains("[package]"));
+
+        assert_eq!(client.last_stop_reason.as_deref(), Some("end_turn"));
+        assert_eq!(client.usage.output_tokens, 5);
+    }
+
+    #[test]
+    fn test_usage_accumulates_across_turns() {
+        let response = MockResponse::json(serde_json::json!({
+            "content": [{"type": "text", "text": "hi"}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 100, "output_tokens": 10}
+        }));
+        let server = MockServer::start(vec![response]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+
+        client.send_message("one").unwrap();
+        client.send_message("two").unwrap();
+
+        assert_eq!(
+            client.usage,
+            Usage {
+                input_tokens: 200,
+                output_tokens: 20
+            }
+        );
+    }
+
+    #[test]
+    fn test_stop_reason_warning() {
+        assert!(stop_reason_warning(Some("max_tokens"))
+            .unwrap()
+            .contains("max_tokens"));
+        assert_eq!(stop_reason_warning(Some("end_turn")), None);
+        assert_eq!(stop_reason_warning(None), None);
+    }
+
+    #[test]
+    fn test_truncated_response_enables_continue() {
+        let server = MockServer::start(vec![
+            text_response("part one", "max_tokens"),
+            text_response("part two", "end_turn"),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+
+        client.send_message("write a long essay").unwrap();
+        assert_eq!(client.last_stop_reason.as_deref(), Some("max_tokens"));
+
+        client.continue_truncated().unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1].json()["messages"][2]["content"],
+            CONTINUE_PROMPT
+        );
+        assert_eq!(client.last_stop_reason.as_deref(), Some("end_turn"));
+
+        // 未被截断时 /continue 不发送请求
+        client.continue_truncated().unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_retry_command_resends_failed_input() {
+        let server = MockServer::start(vec![
+            MockResponse::with_status(500, "{}"),
+            text_response("hello", "end_turn"),
+        ]);
+        let settings = Settings {
+            max_retries: Some(0),
+            ..mock_settings(&server)
+        };
+        let buffer = BufferOutput::default();
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.output = Box::new(PrettyOutput::new(
+            buffer.clone(),
+            false,
+            false,
+            Verbosity::Normal,
+        ));
+
+        // 没有发送过消息时只提示
+        client.retry_last().unwrap();
+        assert!(buffer.contents().contains("无法重试"));
+        assert!(server.requests().is_empty());
+
+        assert!(client.send_message("explain main.rs").is_err());
+        assert!(client.messages.is_empty());
+
+        client.retry_last().unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1].json()["messages"][0]["content"],
+            "explain main.rs"
+        );
+        assert_eq!(client.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_retry_after_rate_limit_then_success() {
+        let server = MockServer::start(vec![
+            MockResponse::with_status(429, "{}").header("retry-after", "0"),
+            text_response("hello", "end_turn"),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+
+        client.send_message("hi").unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        // 重试不会重复添加用户消息
+        assert_eq!(requests[1].json()["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(client.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_retry_notice_goes_through_output() {
+        let responses = || {
+            vec![
+                MockResponse::with_status(503, "{}").header("retry-after", "0"),
+                text_response("hello", "end_turn"),
+            ]
+        };
+
+        let server = MockServer::start(responses());
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        let buffer = BufferOutput::default();
+        client.output = Box::new(PrettyOutput::new(
+            buffer.clone(),
+            false,
+            false,
+            Verbosity::Normal,
+        ));
+        client.send_message("hi").unwrap();
+        assert!(
+            buffer.contents().contains("秒后重试 (1/"),
+            "{}",
+            buffer.contents()
+        );
+
+        // JSON 模式下重试提示不能破坏唯一的 JSON 输出
+        let server = MockServer::start(responses());
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        let buffer = BufferOutput::default();
+        client.output = Box::new(JsonOutput::new(buffer.clone()));
+        client.send_message("hi").unwrap();
+        let output = buffer.contents();
+        assert_eq!(output.lines().count(), 1, "{}", output);
+        let record: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(record["text"], "hello");
+    }
+
+    #[test]
+    fn test_count_tokens_uses_endpoint() {
+        let server = MockServer::start(vec![MockResponse::json(
+            serde_json::json!({"input_tokens": 1234}),
+        )]);
+        let settings = Settings {
+            system_prompt: Some("be brief".to_string()),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.messages = vec![text_message("user", "hi")];
+
+        assert_eq!(
+            client.count_tokens(),
+            TokenCount {
+                tokens: 1234,
+                estimated: false
+            }
+        );
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v1/messages/count_tokens");
+        assert_eq!(requests[0].header("x-api-key"), Some("test-api-key-12345"));
+        let body = requests[0].json();
+        assert_eq!(body["model"], client.model.as_str());
+        assert_eq!(body["system"], "be brief");
+        assert_eq!(body["messages"][0]["content"], "hi");
+        assert!(!body["tools"].as_array().unwrap().is_empty());
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("stream").is_none());
+    }
+
+    #[test]
+    fn test_count_tokens_falls_back_to_estimate() {
+        let server = MockServer::start(vec![MockResponse::with_status(404, "{}")]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client.messages = vec![text_message("user", &"a".repeat(4000))];
+
+        let count = client.count_tokens();
+        assert!(count.estimated);
+        assert!(count.tokens >= 1000, "{:?}", count);
+        assert_eq!(server.requests().len(), 1);
+
+        // 对话为空时直接本地估算，不请求接口
+        client.messages.clear();
+        assert!(client.count_tokens().estimated);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_closed_connection_reconnects_once() {
+        let server = MockServer::start(vec![
+            MockResponse::dropped(),
+            text_response("hello", "end_turn"),
+        ]);
+        let settings = Settings {
+            max_retries: Some(0),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+
+        client.send_message("hi").unwrap();
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(client.messages.len(), 2);
+
+        // 只自动重连一次
+        let server = MockServer::start(vec![MockResponse::dropped()]);
+        let settings = Settings {
+            max_retries: Some(0),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        assert!(client.send_message("hi").is_err());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_rate_limit_exhausts_retries() {
+        let server = MockServer::start(vec![
+            MockResponse::with_status(429, "{}").header("retry-after", "0")
+        ]);
+        let settings = Settings {
+            max_retries: Some(2),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+
+        let err = client.send_message("hi").unwrap_err();
+
+        assert_eq!(server.requests().len(), 3);
+        assert!(matches!(
+            err.downcast_ref::<ApiError>(),
+            Some(ApiError::RateLimited { retries: 2, .. })
+        ));
+        assert!(client.messages.is_empty());
+    }
+
+    #[test]
+    fn test_system_prompt_sent_only_when_configured() {
+        let server = MockServer::start(vec![text_response("ok", "end_turn")]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client.send_message("hi").unwrap();
+
+        let settings = Settings {
+            system_prompt: Some("You are reviewing Rust code.".to_string()),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.send_message("hi").unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0].json().get("system").is_none());
+        assert_eq!(requests[1].json()["system"], "You are reviewing Rust code.");
+    }
+
+    #[test]
+    fn test_persona_changes_system_prompt() {
+        let server = MockServer::start(vec![
+            text_response("ok", "end_turn"),
+            text_response("ok", "end_turn"),
+            text_response("ok", "end_turn"),
+        ]);
+        let settings = Settings {
+            system_prompt: Some("You are helpful.".to_string()),
+            personas: Some(HashMap::from([(
+                "reviewer".to_string(),
+                "Review the code critically.".to_string(),
+            )])),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+
+        client.send_message("hi").unwrap();
+        assert!(client.set_persona(Some("tester")).is_err());
+        client.set_persona(Some("reviewer")).unwrap();
+        client.send_message("look at this").unwrap();
+        client.set_persona(None).unwrap();
+        client.send_message("thanks").unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].json()["system"], "You are helpful.");
+        assert_eq!(
+            requests[1].json()["system"],
+            "You are helpful.\n\nReview the code critically."
+        );
+        assert_eq!(requests[2].json()["system"], "You are helpful.");
+    }
+
+    #[test]
+    fn test_persona_saved_with_session() {
+        let dir = std::env::current_dir()
+            .unwrap()
+            .join("target/test_client_persona_session");
+        let _ = fs::remove_dir_all(&dir);
+        let settings = Settings {
+            personas: Some(HashMap::from([(
+                "teacher".to_string(),
+                "Explain like I'm new.".to_string(),
+            )])),
+            ..unreachable_settings()
+        };
+
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.messages = tool_call_conversation();
+        client.set_persona(Some("teacher")).unwrap();
+        client.save_session(&dir, "learning").unwrap();
+
+        let mut restored = ChatClient::new(&settings).unwrap();
+        restored.load_session(&dir, "learning").unwrap();
+        assert_eq!(restored.persona.as_deref(), Some("teacher"));
+        assert_eq!(
+            restored.active_system_prompt().as_deref(),
+            Some("Explain like I'm new.")
+        );
+
+        // 配置中已没有该角色时不启用
+        let mut other = ChatClient::new(&unreachable_settings()).unwrap();
+        other.load_session(&dir, "learning").unwrap();
+        assert!(other.persona.is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prompt_caching_marks_system_and_tools() {
+        let server = MockServer::start(vec![text_response("ok", "end_turn")]);
+        let settings = Settings {
+            system_prompt: Some("You are reviewing Rust code.".to_string()),
+            prompt_caching: true,
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.send_message("hi").unwrap();
+
+        let body = server.requests()[0].json();
+        let ephemeral = serde_json::json!({"type": "ephemeral"});
+        assert_eq!(body["system"][0]["text"], "You are reviewing Rust code.");
+        assert_eq!(body["system"][0]["cache_control"], ephemeral);
+        let tools = body["tools"].as_array().unwrap();
+        assert_eq!(tools.last().unwrap()["cache_control"], ephemeral);
+        assert!(tools[..tools.len() - 1]
+            .iter()
+            .all(|tool| tool.get("cache_control").is_none()));
+    }
+
+    #[test]
+    fn test_preflight_reports_bad_api_key() {
+        let server = MockServer::start(vec![
+            MockResponse::with_status(401, r#"{"type":"error"}"#),
+            MockResponse::json(serde_json::json!({"data": []})),
+        ]);
+        let client = ChatClient::new(&mock_settings(&server)).unwrap();
+
+        assert_eq!(
+            client.connection.preflight(),
+            Err(preflight::PreflightError::Unauthorized)
+        );
+        assert!(client.connection.preflight().is_ok());
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v1/models");
+        assert!(requests[0].header("x-api-key").is_some());
+    }
+
+    #[test]
+    fn test_auth_scheme_selects_header() {
+        let server = MockServer::start(vec![
+            text_response("ok", "end_turn"),
+            text_response("ok", "end_turn"),
+        ]);
+
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client.send_message("hi").unwrap();
+
+        let settings = Settings {
+            auth_scheme: Some("bearer".to_string()),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.send_message("hi").unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("x-api-key"), Some("test-api-key-12345"));
+        assert!(requests[0].header("authorization").is_none());
+        assert_eq!(
+            requests[1].header("authorization"),
+            Some("Bearer test-api-key-12345")
+        );
+        assert!(requests[1].header("x-api-key").is_none());
+        // 其余 Anthropic 请求头不受影响
+        assert!(requests[1].header("anthropic-version").is_some());
+    }
+
+    #[test]
+    fn test_anthropic_headers_follow_config() {
+        let server = MockServer::start(vec![text_response("ok", "end_turn")]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client.send_message("hi").unwrap();
+
+        let settings = Settings {
+            anthropic_version: Some("2025-01-01".to_string()),
+            anthropic_beta: Some(vec![
+                "interleaved-thinking-2025-05-14".to_string(),
+                "context-1m-2025-08-07".to_string(),
+            ]),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.send_message("hi").unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("anthropic-version"), Some("2023-06-01"));
+        assert_eq!(requests[0].header("anthropic-beta"), None);
+        assert_eq!(requests[1].header("anthropic-version"), Some("2025-01-01"));
+        assert_eq!(
+            requests[1].header("anthropic-beta"),
+            Some("interleaved-thinking-2025-05-14,context-1m-2025-08-07")
+        );
+    }
+
+    #[test]
+    fn test_thinking_enabled_and_preserved_across_tool_loop() {
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "content": [
+                    {
+                        "type": "thinking",
+                        "thinking": "I should read the manifest.\nThen answer.",
+                        "signature": "sig_abc"
+                    },
+                    {
+                        "type": "tool_use",
+                        "id": "toolu_1",
+                        "name": "read_file",
+                        "input": {"file_path": "Cargo.toml"}
+                    }
+                ],
+                "stop_reason": "tool_use"
+            })),
+            text_response("It is a Rust crate.", "end_turn"),
+        ]);
+        let settings = Settings {
+            max_tokens: Some(4096),
+            thinking_budget_tokens: Some(2048),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        let buffer = BufferOutput::default();
+        client.output = Box::new(PrettyOutput::new(
+            buffer.clone(),
+            false,
+            false,
+            Verbosity::Normal,
+        ));
+        client.send_message("what is this project?").unwrap();
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].json()["thinking"],
+            serde_json::json!({"type": "enabled", "budget_tokens": 2048})
+        );
+        // 后续请求中原样带回思考内容和签名
+        let assistant = &requests[1].json()["messages"][1]["content"][0];
+        assert_eq!(assistant["type"], "thinking");
+        assert_eq!(assistant["signature"], "sig_abc");
+        // 默认折叠显示
+        let output = buffer.contents();
+        assert!(output.contains("💭 I should read the manifest. (已折叠"));
+        assert!(!output.contains("Then answer."));
+    }
+
+    #[test]
+    fn test_unknown_blocks_passed_back_unchanged() {
+        let unknown = serde_json::json!({"type": "redacted_thinking", "data": "opaque"});
+        let server_tool = serde_json::json!({
+            "type": "server_tool_use",
+            "id": "srvtoolu_1",
+            "name": "web_search",
+            "input": {"query": "rust"}
+        });
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "content": [
+                    unknown,
+                    server_tool,
+                    {
+                        "type": "tool_use",
+                        "id": "toolu_1",
+                        "name": "read_file",
+                        "input": {"file_path": "Cargo.toml"}
+                    }
+                ],
+                "stop_reason": "tool_use"
+            })),
+            text_response("done", "end_turn"),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client.send_message("search and read").unwrap();
+
+        let sent = server.requests()[1].json();
+        let assistant = &sent["messages"][1]["content"];
+        assert_eq!(assistant[0], unknown);
+        assert_eq!(assistant[1], server_tool);
+        // 只有客户端工具产生 tool_result
+        let results = sent["messages"][2]["content"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["tool_use_id"], "toolu_1");
+    }
+
+    #[test]
+    fn test_project_instructions_prepended_to_system_prompt() {
+        let root = std::env::temp_dir().join("mentat_test_project_instructions");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("MENTAT.md"), "Always use 4-space indent.").unwrap();
+
+        let server = MockServer::start(vec![text_response("ok", "end_turn")]);
+        let settings = Settings {
+            workspace_root: Some(root.to_string_lossy().to_string()),
+            system_prompt: Some("You are reviewing Rust code.".to_string()),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.send_message("hi").unwrap();
+
+        assert_eq!(
+            server.requests()[0].json()["system"],
+            "Always use 4-space indent.\n\nYou are reviewing Rust code."
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_json_output_is_parseable() {
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "content": [{
+                    "type": "tool_use",
+                    "id": "toolu_1",
+                    "name": "read_file",
+                    "input": {"file_path": "Cargo.toml"}
+                }],
+                "stop_reason": "tool_use",
+                "usage": {"input_tokens": 10, "output_tokens": 5}
+            })),
+            MockResponse::json(serde_json::json!({
+                "content": [{"type": "text", "text": "It is a Rust crate."}],
+                "stop_reason": "end_turn",
+                "usage": {"input_tokens": 20, "output_tokens": 7}
+            })),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        let buffer = BufferOutput::default();
+        client.output = Box::new(JsonOutput::new(buffer.clone()));
+
+        client.send_message("what is this project?").unwrap();
+
+        let output = buffer.contents();
+        assert_eq!(output.lines().count(), 1);
+        let record: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(record["text"], "It is a Rust crate.");
+        assert_eq!(record["tool_calls"][0]["name"], "read_file");
+        assert_eq!(record["tool_calls"][0]["input"]["file_path"], "Cargo.toml");
+        assert_eq!(record["usage"]["input_tokens"], 30);
+        assert_eq!(record["usage"]["output_tokens"], 12);
+        assert_eq!(record["stop_reason"], "end_turn");
+        // 未启用 show_cost 时不输出费用
+        assert!(record.get("cost_usd").is_none());
+    }
+
+    #[test]
+    fn test_show_cost_after_turn() {
+        let server = MockServer::start(vec![MockResponse::json(serde_json::json!({
+            "content": [{"type": "text", "text": "hi"}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 1000, "output_tokens": 1000}
+        }))]);
+        let settings = Settings {
+            model: Some("claude-sonnet-4-5".to_string()),
+            show_cost: true,
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        let buffer = BufferOutput::default();
+        client.output = Box::new(PrettyOutput::new(
+            buffer.clone(),
+            false,
+            false,
+            Verbosity::Normal,
+        ));
+
+        client.send_message("hello").unwrap();
+
+        // 1000 * $3 + 1000 * $15（每百万 token）
+        assert!(buffer
+            .contents()
+            .contains("📊 1.0k in / 1.0k out · $0.0180"));
+        assert!((client.cost.total - 0.018).abs() < 1e-9);
+        assert!(!client.cost.unpriced);
+    }
+
+    #[test]
+    fn test_turn_output_written_to_buffer() {
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "content": [{
+                    "type": "tool_use",
+                    "id": "toolu_1",
+                    "name": "read_file",
+                    "input": {"file_path": "Cargo.toml"}
+                }],
+                "stop_reason": "tool_use"
+            })),
+            MockResponse::json(serde_json::json!({
+                "content": [{"type": "text", "text": "It is a Rust crate."}],
+                "stop_reason": "end_turn",
+                "usage": {"input_tokens": 20, "output_tokens": 7}
+            })),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        let buffer = BufferOutput::default();
+        client.output = Box::new(PrettyOutput::new(
+            buffer.clone(),
+            false,
+            false,
+            Verbosity::Normal,
+        ));
+
+        client.send_message("what is this project?").unwrap();
+
+        let output = buffer.contents();
+        let lines: Vec<&str> = output.lines().filter(|l| !l.is_empty()).collect();
+        assert_eq!(
+            lines,
+            [
+                r#"  🔧 [read_file] {"file_path":"Cargo.toml"}"#,
+                "🤖 It is a Rust crate.",
+                "📊 20 in / 7 out",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ascii_labels_replace_emoji_in_output() {
+        let server = MockServer::start(vec![text_response("Done 🎉", "max_tokens")]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        let buffer = BufferOutput::default();
+        client.output = Box::new(
+            PrettyOutput::new(buffer.clone(), false, false, Verbosity::Normal)
+                .with_labels(Labels::new(true)),
+        );
+
+        client.send_message("hi").unwrap();
+        outln!(client, "👋 再见！");
+
+        let output = buffer.contents();
+        // 回复内容原样保留，只替换提示的前缀
+        assert!(output.contains("[AI] Done 🎉"), "{}", output);
+        assert!(output.contains("[warn] 回复被截断"), "{}", output);
+        assert!(output.contains("[bye] 再见！"), "{}", output);
+    }
+
+    #[test]
+    fn test_quiet_mode_hides_tool_calls_and_verbose_shows_results() {
+        let run = |verbosity| {
+            let server = MockServer::start(vec![
+                MockResponse::json(serde_json::json!({
+                    "content": [{
+                        "type": "tool_use",
+                        "id": "toolu_1",
+                        "name": "read_file",
+                        "input": {"file_path": "Cargo.toml"}
+                    }],
+                    "stop_reason": "tool_use"
+                })),
+                MockResponse::json(serde_json::json!({
+                    "content": [{"type": "text", "text": "Done."}],
+                    "stop_reason": "end_turn",
+                    "usage": {"input_tokens": 20, "output_tokens": 7}
+                })),
+            ]);
+            let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+            let buffer = BufferOutput::default();
+            client.output = Box::new(PrettyOutput::new(buffer.clone(), false, false, verbosity));
+            client.send_message("read the manifest").unwrap();
+            buffer.contents()
+        };
+
+        let quiet = run(Verbosity::Quiet);
+        assert!(!quiet.contains("🔧"));
+        assert!(!quiet.contains("📊"));
+        assert!(quiet.contains("🤖 Done."));
+
+        let verbose = run(Verbosity::Verbose);
+        assert!(verbose.contains(r#"🔧 [read_file] {"file_path":"Cargo.toml"}"#));
+        assert!(verbose.contains("📤 "));
+        assert!(verbose.contains("[package]"));
+    }
+
+    #[test]
+    fn test_large_tool_result_truncated_before_sending() {
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "content": [{
+                    "type": "tool_use",
+                    "id": "toolu_1",
+                    "name": "read_file",
+                    "input": {"file_path": "Cargo.toml"}
+                }],
+                "stop_reason": "tool_use"
+            })),
+            text_response("done", "end_turn"),
+        ]);
+        let settings = Settings {
+            max_tool_result_bytes: Some(50),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.send_message("read the manifest").unwrap();
+
+        let sent = server.requests()[1].json();
+        let content = sent["messages"][2]["content"][0]["content"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let (kept, marker) = content.split_once("\n...[truncated, ").unwrap();
+        assert_eq!(kept.len(), 50);
+        assert!(marker.ends_with(" bytes omitted]..."));
+    }
+
+    #[test]
+    fn test_history_summarized_when_over_budget() {
+        let server = MockServer::start(vec![
+            text_response("User greeted; assistant said hello.", "end_turn"),
+            text_response("answer", "end_turn"),
+        ]);
+        let settings = Settings {
+            history_token_budget: Some(5),
+            history_trim_strategy: Some("summarize".to_string()),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        client
+            .messages
+            .push(text_message("user", "hi there, how are you?"));
+        client
+            .messages
+            .push(text_message("assistant", "hello, I am fine thanks!"));
+
+        client.send_message("next question").unwrap();
+
+        let requests = server.requests();
+        let summary_request = requests[0].json();
+        let sent = summary_request["messages"].as_array().unwrap();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[2]["content"], SUMMARY_PROMPT);
+
+        // 摘要替换最早的轮次，角色仍然交替且以用户消息开始
+        let roles: Vec<&str> = client.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant", "user", "assistant"]);
+        let sent = requests[1].json();
+        assert!(sent["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .ends_with("User greeted; assistant said hello."));
+        assert_eq!(sent["messages"][2]["content"], "next question");
+    }
+
+    #[test]
+    fn test_history_drop_oldest_when_over_budget() {
+        let server = MockServer::start(vec![text_response("answer", "end_turn")]);
+        let settings = Settings {
+            history_token_budget: Some(5),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        client
+            .messages
+            .push(text_message("user", "hi there, how are you?"));
+        client
+            .messages
+            .push(text_message("assistant", "hello, I am fine thanks!"));
+
+        client.send_message("next question").unwrap();
+
+        let sent = server.requests()[0].json();
+        assert_eq!(sent["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(client.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_failed_turn_restores_trimmed_history() {
+        let server = MockServer::start(vec![MockResponse::with_status(401, "{}")]);
+        let settings = Settings {
+            history_token_budget: Some(5),
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        let history = vec![
+            text_message("user", "hi there, how are you?"),
+            text_message("assistant", "hello, I am fine thanks!"),
+        ];
+        client.messages = history.clone();
+
+        assert!(client.send_message("next question").is_err());
+
+        // 请求时已裁剪，失败后恢复为本轮之前的完整历史
+        let sent = server.requests()[0].json();
+        assert_eq!(sent["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            serde_json::to_value(&client.messages).unwrap(),
+            serde_json::to_value(&history).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_interrupt_mid_turn_rolls_back_history() {
+        let server = MockServer::start(vec![
+            MockResponse::json(serde_json::json!({
+                "content": [{
+                    "type": "tool_use",
+                    "id": "toolu_1",
+                    "name": "read_file",
+                    "input": {"file_path": "Cargo.toml"}
+                }],
+                "stop_reason": "tool_use"
+            })),
+            text_response("too late", "end_turn").delayed(Duration::from_secs(3)),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client
+            .messages
+            .push(text_message("user", "earlier question"));
+        client
+            .messages
+            .push(text_message("assistant", "earlier answer"));
+
+        // 第一次往返和工具调用完成后，在第二个请求等待响应时按下 Ctrl+C
+        let flag = client.interrupt_flag();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(500));
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        let started = Instant::now();
+        let err = client.send_message("read the manifest").unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(matches!(
+            err.downcast_ref::<ApiError>(),
+            Some(ApiError::Interrupted)
+        ));
+        assert_eq!(server.requests().len(), 2);
+        // 本轮的用户消息、tool_use 和 tool_result 全部回滚，之前的对话保留
+        assert_eq!(client.messages.len(), 2);
+        assert_eq!(client.messages[1].role, "assistant");
+    }
+
+    #[test]
+    fn test_non_retryable_error_fails_immediately() {
+        let server = MockServer::start(vec![MockResponse::with_status(401, "{}")]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+
+        assert!(client.send_message("hi").is_err());
+        assert_eq!(server.requests().len(), 1);
+        assert!(client.messages.is_empty());
+    }
+
+    #[test]
+    fn test_request_times_out_instead_of_blocking() {
+        // 接受连接但从不响应的服务端
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let settings = Settings {
+            env: config::Env {
+                api_key: "test-api-key-12345".to_string(),
+                base_url,
+                https_proxy: None,
+            },
+            timeout_secs: Some(1),
+            max_retries: Some(0),
+            ..Default::default()
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+
+        let started = std::time::Instant::now();
+        let err = client.send_message("hi").unwrap_err();
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        assert!(err
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(reqwest::Error::is_timeout));
+        assert!(client.messages.is_empty());
+        drop(listener);
+    }
+
+    #[test]
+    fn test_reset_session_clears_conversation_and_staging() {
+        let settings = Settings {
+            staging: true,
+            ..unreachable_settings()
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+        client.messages.push(text_message("user", "secret"));
+        client.last_stop_reason = Some("max_tokens".to_string());
+        client
+            .staging
+            .as_mut()
+            .unwrap()
+            .stage("notes.txt", "secret");
+
+        client.reset_session();
+
+        assert!(client.messages.is_empty());
+        assert!(client.last_stop_reason.is_none());
+        assert!(client.staging.as_ref().unwrap().is_empty());
+    }
+
+    fn tool_call_conversation() -> Vec<Message> {
+        vec![
+            text_message("user", "read it"),
+            Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Blocks(vec![serde_json::json!({
+                    "type": "tool_use", "id": "t1", "name": "removed_tool", "input": {}
+                })]),
+            },
+            Message {
+                role: "user".to_string(),
+                content: MessageContent::Blocks(vec![create_tool_result("t1", "ok")]),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Blocks(vec![
+                    serde_json::json!({"type": "text", "text": "done"}),
+                ]),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_session_save_and_load_restores_messages() {
+        let dir = std::env::current_dir()
+            .unwrap()
+            .join("target/test_client_sessions");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut client = ChatClient::new(&unreachable_settings()).unwrap();
+        client.messages = tool_call_conversation();
+        client.save_session(&dir, "work").unwrap();
+
+        // 加载到新客户端：引用已不存在的工具也能正常恢复
+        let mut restored = ChatClient::new(&unreachable_settings()).unwrap();
+        restored.load_session(&dir, "work").unwrap();
+        assert_eq!(restored.messages.len(), 4);
+
+        // 带索引加载：只保留到指定轮次边界
+        restored.load_session(&dir, "work@0").unwrap();
+        assert!(restored.messages.is_empty());
+
+        // 无效的截断位置不会覆盖当前对话
+        restored.load_session(&dir, "work").unwrap();
+        assert!(restored.load_session(&dir, "work@2").is_err());
+        assert_eq!(restored.messages.len(), 4);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_new_session_archives_and_clears() {
+        let dir = std::env::current_dir()
+            .unwrap()
+            .join("target/test_client_new_session");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut client = ChatClient::new(&unreachable_settings()).unwrap();
+        client.messages = tool_call_conversation();
+        client.new_session(&dir).unwrap();
+        assert!(client.messages.is_empty());
+
+        let archived = session::list(&dir);
+        assert_eq!(archived.len(), 1);
+        assert!(archived[0].starts_with(session::ARCHIVE_PREFIX));
+        client.load_session(&dir, &archived[0]).unwrap();
+        assert_eq!(client.messages.len(), 4);
+
+        // 空对话不产生归档
+        client.messages.clear();
+        client.new_session(&dir).unwrap();
+        assert_eq!(session::list(&dir).len(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_trace_records_tool_chain() {
+        let server = MockServer::start(vec![
+            write_tool_response("target/test_trace_chain.txt", "hi"),
+            text_response("done", "end_turn"),
+        ]);
+        let settings = Settings {
+            auto_approve: true,
+            ..mock_settings(&server)
+        };
+        let mut client = ChatClient::new(&settings).unwrap();
+
+        client.send_message("write it").unwrap();
+
+        assert_eq!(client.trace.len(), 1);
+        let path = client.export_trace("target/test_trace_chain.json").unwrap();
+        // 与 /export 一样只能写入工作区内允许写入的位置
+        assert!(client.export_trace("../test_trace_chain.json").is_err());
+        assert!(client.export_trace("/tmp/test_trace_chain.json").is_err());
+        let document: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let steps = &document["turns"][0]["steps"];
+        assert_eq!(steps.as_array().unwrap().len(), 2);
+        assert_eq!(steps[0]["tool_calls"][0]["name"], "write_file");
+        assert_eq!(steps[1]["stop_reason"], "end_turn");
+        // API 密钥不会出现在导出的文件中
+        assert!(!document.to_string().contains("test-api-key-12345"));
+
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file("target/test_trace_chain.txt");
+    }
+
+    #[test]
+    fn test_tool_listing_includes_builtin_tools() {
+        let listing = format_tool_listing(&ToolRegistry::with_builtins(tools::test_context()));
+        assert!(listing.contains("read_file"));
+        assert!(listing.contains("write_file"));
+        assert!(listing.contains("Read the contents of a file"));
+    }
+
+    #[test]
+    fn test_cli_config_flag() {
+        let cli = Cli::try_parse_from(["mentat", "--config", "work.json"]).unwrap();
+        assert_eq!(cli.config.as_deref(), Some("work.json"));
+
+        let cli = Cli::try_parse_from(["mentat", "-c", "personal.json", "-y"]).unwrap();
+        assert_eq!(cli.config.as_deref(), Some("personal.json"));
+        assert!(cli.yes);
+
+        // 未指定时按默认顺序搜索配置文件
+        let cli = Cli::try_parse_from(["mentat"]).unwrap();
+        assert!(cli.config.is_none());
+
+        assert!(Cli::try_parse_from(["mentat", "--config"]).is_err());
+    }
+
+    #[test]
+    fn test_create_temp_file_is_unique() {
+        let (first, _) = create_temp_file("mentat-test").unwrap();
+        let (second, _) = create_temp_file("mentat-test").unwrap();
+        assert_ne!(first, second);
+        let _ = fs::remove_file(first);
+        let _ = fs::remove_file(second);
+    }
+
+    #[test]
+    fn test_cli_workdir_flag() {
+        let cli = Cli::try_parse_from(["mentat", "-C", "../foo", "-c", "work.json"]).unwrap();
+        assert_eq!(cli.workdir.as_deref(), Some(Path::new("../foo")));
+        assert_eq!(cli.config.as_deref(), Some("work.json"));
+
+        let cli = Cli::try_parse_from(["mentat", "--workdir", "/tmp"]).unwrap();
+        assert_eq!(cli.workdir.as_deref(), Some(Path::new("/tmp")));
+
+        assert!(Cli::try_parse_from(["mentat"]).unwrap().workdir.is_none());
+        assert!(Cli::try_parse_from(["mentat", "-C"]).is_err());
+    }
+
+    #[test]
+    fn test_change_workdir_rejects_invalid_paths() {
+        let cwd = std::env::current_dir().unwrap();
+
+        let err = change_workdir(Path::new("target/no_such_workdir")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("target/no_such_workdir"));
+
+        let err = change_workdir(Path::new("Cargo.toml")).unwrap_err();
+        assert!(err.to_string().contains("不是目录"));
+
+        // 失败时不改变当前目录
+        assert_eq!(std::env::current_dir().unwrap(), cwd);
+    }
+
+    #[test]
+    fn test_select_run_mode() {
+        let empty = io::empty();
+        assert_eq!(select_run_mode(None, true, empty).unwrap(), RunMode::Repl);
+
+        // 命令行提示优先于标准输入
+        let cli = Cli::try_parse_from(["mentat", "-p", "explain this error"]).unwrap();
+        assert_eq!(
+            select_run_mode(cli.execute, false, "ignored".as_bytes()).unwrap(),
+            RunMode::OneShot("explain this error".to_string())
+        );
+
+        // 管道输入作为提示
+        assert_eq!(
+            select_run_mode(None, false, "fix the build\n".as_bytes()).unwrap(),
+            RunMode::OneShot("fix the build".to_string())
+        );
+        assert!(select_run_mode(None, false, " \n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_cli_version_and_help_exit_before_repl() {
+        use clap::error::ErrorKind;
+
+        let err = Cli::try_parse_from(["mentat", "--version"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::DisplayVersion);
+        assert!(err.to_string().contains(env!("CARGO_PKG_VERSION")));
+
+        let err = Cli::try_parse_from(["mentat", "--help"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::DisplayHelp);
+        assert!(err.to_string().contains("--config"));
+    }
+
+    #[test]
+    fn test_rewind_to_matches_whole_word() {
+        let mut client = ChatClient::new(&unreachable_settings()).unwrap();
+        let buffer = BufferOutput::default();
+        client.output = Box::new(PrettyOutput::new(
+            buffer.clone(),
+            false,
+            false,
+            Verbosity::Normal,
+        ));
+        client.messages = vec![
+            text_message("user", "one"),
+            text_message("assistant", "1"),
+            text_message("user", "two"),
+            text_message("assistant", "2"),
+        ];
+        let mut rl = DefaultEditor::new().unwrap();
+
+        for cmd in ["/rewind tomorrow", "/rewind top", "/rewind to"] {
+            assert!(!handle_command(cmd, &mut client, &mut rl, None));
+            assert_eq!(client.messages.len(), 4, "{}", cmd);
+        }
+        assert_eq!(buffer.contents().matches("用法: /rewind").count(), 3);
+
+        assert!(!handle_command("/rewind to 2", &mut client, &mut rl, None));
+        assert_eq!(client.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_model_command_switches_model_and_keeps_history() {
+        let mut client = ChatClient::new(&unreachable_settings()).unwrap();
+        client.messages = vec![text_message("user", "hi")];
+        let mut rl = DefaultEditor::new().unwrap();
+
+        assert!(!handle_command("/model foo", &mut client, &mut rl, None));
+        assert_eq!(client.model, "foo");
+        assert_eq!(client.messages.len(), 1);
+
+        // 输出上限随模型下调
+        client.max_tokens = 32_000;
+        client.set_model("claude-3-5-haiku-latest");
+        assert_eq!(client.max_tokens, 8_192);
+    }
+
+    #[test]
+    fn test_export_command_writes_inside_workspace() {
+        let path = "target/test_export/session.md";
+        let _ = fs::remove_dir_all("target/test_export");
+        let mut client = ChatClient::new(&unreachable_settings()).unwrap();
+        client.messages = vec![text_message("user", "hi")];
+        let mut rl = DefaultEditor::new().unwrap();
+
+        assert!(!handle_command(
+            &format!("/export {}", path),
+            &mut client,
+            &mut rl,
+            None
+        ));
+        let markdown = fs::read_to_string(path).unwrap();
+        assert!(markdown.contains("## 用户（第 1 轮）\n\nhi\n"));
+
+        assert!(client.export_markdown("../outside.md").is_err());
+        assert!(!Path::new("../outside.md").exists());
+        let _ = fs::remove_dir_all("target/test_export");
+    }
+
+    #[test]
+    fn test_attachment_sent_with_next_message() {
+        let path = "target/test_attach_screenshot.png";
+        fs::write(path, b"\x89PNG\r\n").unwrap();
+        let server = MockServer::start(vec![
+            text_response("a stack trace", "end_turn"),
+            text_response("ok", "end_turn"),
+        ]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        let mut rl = DefaultEditor::new().unwrap();
+
+        assert!(!handle_command(
+            &format!("/attach {}", path),
+            &mut client,
+            &mut rl,
+            None
+        ));
+        assert!(client.attach("Cargo.toml").is_err());
+        assert!(client.attach("../outside.png").is_err());
+        client.send_message("what is this error?").unwrap();
+        client.send_message("thanks").unwrap();
+
+        let requests = server.requests();
+        let content = &requests[0].json()["messages"][0]["content"];
+        assert_eq!(content[0]["type"], "image");
+        assert_eq!(content[0]["source"]["data"], "iVBORw0K");
+        assert_eq!(content[1]["text"], "what is this error?");
+        // 附件只随下一条消息发送一次，但保留在历史中
+        let second = requests[1].json();
+        assert_eq!(second["messages"][0]["content"][0]["type"], "image");
+        assert_eq!(second["messages"][2]["content"], "thanks");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(split_command("/help"), ("/help", ""));
+        assert_eq!(split_command("/ask  what is X? "), ("/ask", "what is X?"));
+    }
+
+    #[test]
+    fn test_classify_input() {
+        assert_eq!(classify_input("/help"), InputKind::Command("/help"));
+        assert_eq!(classify_input("/rewind 2"), InputKind::Command("/rewind 2"));
+        assert_eq!(
+            classify_input("//literal message"),
+            InputKind::Message("/literal message")
+        );
+        // 未知命令仍按命令处理，以便提示拼写错误
+        assert_eq!(
+            classify_input("/unknown thing"),
+            InputKind::Command("/unknown thing")
+        );
+        // 像路径的输入按消息处理
+        assert_eq!(
+            classify_input("/usr/lib/libfoo.so is missing"),
+            InputKind::Message("/usr/lib/libfoo.so is missing")
+        );
+        assert_eq!(classify_input("hello"), InputKind::Message("hello"));
+    }
+
+    #[test]
+    fn test_ask_isolated_restores_main_history() {
+        let mut client = ChatClient::new(&unreachable_settings()).unwrap();
+        client.messages.push(text_message("user", "main question"));
+        client
+            .messages
+            .push(text_message("assistant", "main answer"));
+        client.last_input = Some("main question".to_string());
+        client.last_stop_reason = Some("max_tokens".to_string());
+        client.attachments = vec![serde_json::json!({"type": "text", "text": "attached"})];
+
+        // 请求失败也必须恢复主对话
+        assert!(client.ask_isolated("side question").is_err());
+        assert_eq!(client.messages.len(), 2);
+        assert!(matches!(
+            &client.messages[0].content,
+            MessageContent::Text(text) if text == "main question"
+        ));
+        // /retry 和 /continue 仍然针对主对话，附件留给主对话
+        assert_eq!(client.last_input.as_deref(), Some("main question"));
+        assert_eq!(client.last_stop_reason.as_deref(), Some("max_tokens"));
+        assert_eq!(client.attachments.len(), 1);
+    }
+
+    #[test]
+    fn test_ask_isolated_success_keeps_main_state() {
+        let server = MockServer::start(vec![text_response("side answer", "end_turn")]);
+        let mut client = ChatClient::new(&mock_settings(&server)).unwrap();
+        client.last_input = Some("main question".to_string());
+        client.attachments = vec![serde_json::json!({"type": "text", "text": "attached"})];
+
+        client.ask_isolated("side question").unwrap();
+        assert!(client.messages.is_empty());
+        assert_eq!(client.last_input.as_deref(), Some("main question"));
+        assert_eq!(client.attachments.len(), 1);
+
+        // 旁路问题不带主对话的附件
+        let sent = server.requests()[0].json();
+        assert_eq!(sent["messages"][0]["content"], "side question");
+    }
+
+    #[test]
+    fn test_extra_roots_resolved_and_described() {
+        let base = std::env::temp_dir().join("mentat_test_main_extra_roots");
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("app")).unwrap();
+        fs::create_dir_all(base.join("shared")).unwrap();
+        let workspace = base.join("app");
+
+        let mut settings = Settings {
+            extra_roots: Some(HashMap::from([(
+                "shared".to_string(),
+                "../shared".to_string(),
+            )])),
+            ..Default::default()
+        };
+        let roots = resolve_extra_roots(&settings, &workspace).unwrap();
+        assert_eq!(roots["shared"], base.join("shared").canonicalize().unwrap());
+        let note = extra_roots_note(&roots).unwrap();
+        assert!(note.contains("`shared:src/lib.rs`"), "{}", note);
+        assert!(extra_roots_note(&HashMap::new()).is_none());
+
+        // 不存在的目录在启动时报错
+        settings.extra_roots = Some(HashMap::from([(
+            "gone".to_string(),
+            "../missing".to_string(),
+        )]));
+        let err = resolve_extra_roots(&settings, &workspace).unwrap_err();
+        assert!(err.to_string().contains("extra_roots.gone"), "{}", err);
+        let _ = fs::remove_dir_all(&base);
+    }
 }
@@ -0,0 +1,116 @@
+//! REPL 多行输入
+//!
+//! 两种方式：
+//! - `/paste` 进入粘贴模式，逐行累积，直到单独一行 `.` 或 `/end`；
+//! - 行尾 `\` 表示续行，直到某一行不以 `\` 结尾。
+
+/// 粘贴模式的结束标记
+const PASTE_TERMINATORS: &[&str] = &[".", "/end"];
+
+/// 多行输入的累积方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Paste,
+    Continuation,
+}
+
+/// 多行输入缓冲区
+#[derive(Debug)]
+pub struct MultilineBuffer {
+    mode: Mode,
+    lines: Vec<String>,
+}
+
+/// 行尾是否带续行符 `\`
+pub fn is_continued(line: &str) -> bool {
+    line.trim_end().ends_with('\\')
+}
+
+/// 去掉行尾的续行符
+fn strip_continuation(line: &str) -> &str {
+    let line = line.trim_end();
+    line.strip_suffix('\\').unwrap_or(line)
+}
+
+impl MultilineBuffer {
+    /// 进入粘贴模式（`/paste` 本身不计入内容）
+    pub fn paste() -> Self {
+        Self {
+            mode: Mode::Paste,
+            lines: Vec::new(),
+        }
+    }
+
+    /// 以一行带续行符的输入开始续行模式
+    pub fn continuation(first_line: &str) -> Self {
+        Self {
+            mode: Mode::Continuation,
+            lines: vec![strip_continuation(first_line).to_string()],
+        }
+    }
+
+    /// 续行时使用的提示符
+    pub fn prompt(&self) -> &'static str {
+        match self.mode {
+            Mode::Paste => "… ",
+            Mode::Continuation => "\\ ",
+        }
+    }
+
+    /// 追加一行，输入结束时返回拼接后的完整文本
+    pub fn push(&mut self, line: &str) -> Option<String> {
+        match self.mode {
+            Mode::Paste => {
+                if PASTE_TERMINATORS.contains(&line.trim()) {
+                    return Some(self.lines.join("\n"));
+                }
+                self.lines.push(line.to_string());
+                None
+            }
+            Mode::Continuation => {
+                if is_continued(line) {
+                    self.lines.push(strip_continuation(line).to_string());
+                    None
+                } else {
+                    self.lines.push(line.to_string());
+                    Some(self.lines.join("\n"))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paste_accumulates_until_terminator() {
+        let mut buffer = MultilineBuffer::paste();
+        assert_eq!(buffer.push("fn main() {"), None);
+        assert_eq!(buffer.push("    println!(\"hi\");"), None);
+        assert_eq!(buffer.push(""), None);
+        assert_eq!(buffer.push("}"), None);
+        assert_eq!(
+            buffer.push("."),
+            Some("fn main() {\n    println!(\"hi\");\n\n}".to_string())
+        );
+
+        let mut buffer = MultilineBuffer::paste();
+        assert_eq!(buffer.push("a.b"), None);
+        assert_eq!(buffer.push(" /end "), Some("a.b".to_string()));
+    }
+
+    #[test]
+    fn test_continuation_until_line_without_backslash() {
+        assert!(is_continued("first \\"));
+        assert!(!is_continued("no continuation"));
+
+        let mut buffer = MultilineBuffer::continuation("first \\");
+        assert_eq!(buffer.push("second\\"), None);
+        assert_eq!(
+            buffer.push("third"),
+            Some("first \nsecond\nthird".to_string())
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! OpenAI chat-completions 格式转换
+//!
+//! 本地模型服务（Ollama、LM Studio、vLLM 等）通常只提供 OpenAI 兼容的
+//! `/v1/chat/completions` 接口。对话历史和工具调用循环仍以 Anthropic 的
+//! 消息结构为准，这里只负责请求和响应在两种格式之间的转换。
+
+use crate::api::{AnthropicRequest, AnthropicResponse, Message, MessageContent, Usage};
+use serde::de::Error as _;
+use serde_json::{json, Value};
+
+/// 把 Anthropic 请求转换为 OpenAI chat-completions 请求体
+///
+/// 系统提示转为第一条 `system` 角色消息；流式响应暂不支持，始终以非流式发送。
+pub fn to_request(request: &AnthropicRequest) -> Value {
+    let system = request
+        .system
+        .as_ref()
+        .map(|system| json!({"role": "system", "content": system.text}));
+    let messages: Vec<Value> = system
+        .into_iter()
+        .chain(request.messages.iter().flat_map(to_messages))
+        .collect();
+    let mut body = json!({
+        "model": request.model,
+        "max_tokens": request.max_tokens,
+        "messages": messages,
+    });
+    if !request.tools.is_empty() {
+        body["tools"] = request.tools.iter().map(to_tool).collect();
+    }
+    if let Some(temperature) = request.temperature {
+        body["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = request.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    body
+}
+
+/// 工具定义：`{name, description, input_schema}` -> `{type: function, function: {...}}`
+fn to_tool(definition: &Value) -> Value {
+    json!({
+        "type": "function",
+        "function": {
+            "name": definition["name"],
+            "description": definition["description"],
+            "parameters": definition["input_schema"],
+        }
+    })
+}
+
+/// 转换一条消息
+///
+/// OpenAI 中工具结果是独立的 `tool` 角色消息，因此一条包含多个
+/// tool_result 的 user 消息会展开为多条；thinking 等其他 block 被丢弃。
+fn to_messages(message: &Message) -> Vec<Value> {
+    let blocks = match &message.content {
+        MessageContent::Text(text) => {
+            return vec![json!({"role": message.role, "content": text})];
+        }
+        MessageContent::Blocks(blocks) => blocks,
+    };
+
+    let text: Vec<&str> = blocks
+        .iter()
+        .filter(|b| b["type"] == "text")
+        .filter_map(|b| b["text"].as_str())
+        .collect();
+    let text = text.join("\n");
+
+    if message.role == "assistant" {
+        let tool_calls: Vec<Value> = blocks
+            .iter()
+            .filter(|b| b["type"] == "tool_use")
+            .map(|b| {
+                json!({
+                    "id": b["id"],
+                    "type": "function",
+                    "function": {
+                        "name": b["name"],
+                        "arguments": b["input"].to_string(),
+                    }
+                })
+            })
+            .collect();
+
+        let mut assistant = json!({
+            "role": "assistant",
+            "content": if text.is_empty() { Value::Null } else { Value::String(text) },
+        });
+        if !tool_calls.is_empty() {
+            assistant["tool_calls"] = Value::Array(tool_calls);
+        }
+        return vec![assistant];
+    }
+
+    let mut messages: Vec<Value> = blocks
+        .iter()
+        .filter(|b| b["type"] == "tool_result")
+        .map(|b| {
+            let content = match &b["content"] {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            json!({
+                "role": "tool",
+                "tool_call_id": b["tool_use_id"],
+                "content": content,
+            })
+        })
+        .collect();
+    if !text.is_empty() {
+        messages.push(json!({"role": message.role, "content": text}));
+    }
+    messages
+}
+
+/// 把 OpenAI 的 `finish_reason` 映射为 Anthropic 的 `stop_reason`
+fn to_stop_reason(finish_reason: &str) -> String {
+    match finish_reason {
+        "stop" => "end_turn",
+        "tool_calls" | "function_call" => "tool_use",
+        "length" => "max_tokens",
+        other => other,
+    }
+    .to_string()
+}
+
+/// 把 OpenAI chat-completions 响应解析为 Anthropic 响应
+///
+/// 取 `choices[0].message`：文本内容转为 text block，`tool_calls` 转为
+/// tool_use block（`arguments` 无法解析为 JSON 时作为空对象）。
+pub fn parse_response(body: &Value) -> Result<AnthropicResponse, serde_json::Error> {
+    let choice = body["choices"]
+        .get(0)
+        .ok_or_else(|| serde_json::Error::custom("response has no choices"))?;
+    let message = &choice["message"];
+
+    let mut content = Vec::new();
+    if let Some(text) = message["content"].as_str().filter(|t| !t.is_empty()) {
+        content.push(json!({"type": "text", "text": text}));
+    }
+    for call in message["tool_calls"].as_array().into_iter().flatten() {
+        let function = &call["function"];
+        let input = function["arguments"]
+            .as_str()
+            .and_then(|args| serde_json::from_str(args).ok())
+            .unwrap_or_else(|| json!({}));
+        content.push(json!({
+            "type": "tool_use",
+            "id": call["id"],
+            "name": function["name"],
+            "input": input,
+        }));
+    }
+
+    let usage = body.get("usage").map(|usage| Usage {
+        input_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+        output_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+    });
+
+    Ok(AnthropicResponse {
+        content,
+        stop_reason: choice["finish_reason"].as_str().map(to_stop_reason),
+        usage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::create_tool_result;
+
+    fn request(messages: Vec<Message>, tools: Vec<Value>) -> AnthropicRequest {
+        AnthropicRequest {
+            model: "llama3.1".to_string(),
+            max_tokens: 1024,
+            system: None,
+            messages,
+            tools,
+            thinking: None,
+            temperature: None,
+            top_p: None,
+            stream: false,
+        }
+    }
+
+    #[test]
+    fn test_request_translates_tool_conversation() {
+        let messages = vec![
+            Message {
+                role: "user".to_string(),
+                content: MessageContent::Text("read Cargo.toml".to_string()),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Blocks(vec![
+                    json!({"type": "thinking", "thinking": "..."}),
+                    json!({"type": "text", "text": "Reading it."}),
+                    json!({"type": "tool_use", "id": "call_1", "name": "read_file", "input": {"file_path": "Cargo.toml"}}),
+                ]),
+            },
+            Message {
+                role: "user".to_string(),
+                content: MessageContent::Blocks(vec![create_tool_result(
+                    "call_1",
+                    r#"{"success":true}"#,
+                )]),
+            },
+        ];
+        let tools = vec![json!({
+            "name": "read_file",
+            "description": "Read a file",
+            "input_schema": {"type": "object", "properties": {}}
+        })];
+
+        let body = to_request(&request(messages, tools));
+
+        assert_eq!(body["model"], "llama3.1");
+        assert_eq!(body["tools"][0]["type"], "function");
+        assert_eq!(body["tools"][0]["function"]["name"], "read_file");
+        assert_eq!(body["tools"][0]["function"]["parameters"]["type"], "object");
+
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[0],
+            json!({"role": "user", "content": "read Cargo.toml"})
+        );
+        assert_eq!(messages[1]["content"], "Reading it.");
+        let call = &messages[1]["tool_calls"][0];
+        assert_eq!(call["id"], "call_1");
+        assert_eq!(call["function"]["name"], "read_file");
+        assert_eq!(
+            call["function"]["arguments"],
+            r#"{"file_path":"Cargo.toml"}"#
+        );
+        assert_eq!(messages[2]["role"], "tool");
+        assert_eq!(messages[2]["tool_call_id"], "call_1");
+        assert_eq!(messages[2]["content"], r#"{"success":true}"#);
+    }
+
+    #[test]
+    fn test_system_prompt_becomes_first_message() {
+        let mut request = request(
+            vec![Message {
+                role: "user".to_string(),
+                content: MessageContent::Text("hi".to_string()),
+            }],
+            Vec::new(),
+        );
+        request.system = Some("Be terse.".to_string().into());
+
+        let body = to_request(&request);
+        assert_eq!(
+            body["messages"][0],
+            json!({"role": "system", "content": "Be terse."})
+        );
+        assert_eq!(body["messages"][1]["content"], "hi");
+    }
+
+    #[test]
+    fn test_request_without_tools_omits_field() {
+        let body = to_request(&request(Vec::new(), Vec::new()));
+        assert!(body.get("tools").is_none());
+        assert!(body.get("stream").is_none());
+    }
+
+    #[test]
+    fn test_parse_tool_call_response() {
+        let body = json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_9",
+                        "type": "function",
+                        "function": {"name": "write_file", "arguments": "{\"file_path\":\"a.txt\",\"content\":\"x\"}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": {"prompt_tokens": 120, "completion_tokens": 15}
+        });
+
+        let response = parse_response(&body).unwrap();
+        assert_eq!(response.stop_reason.as_deref(), Some("tool_use"));
+        assert_eq!(response.content.len(), 1);
+        let block = &response.content[0];
+        assert_eq!(block["type"], "tool_use");
+        assert_eq!(block["id"], "call_9");
+        assert_eq!(block["name"], "write_file");
+        assert_eq!(block["input"]["file_path"], "a.txt");
+        assert_eq!(
+            response.usage,
+            Some(Usage {
+                input_tokens: 120,
+                output_tokens: 15
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_text_response() {
+        let body = json!({
+            "choices": [{
+                "message": {"role": "assistant", "content": "Hello!"},
+                "finish_reason": "length"
+            }]
+        });
+
+        let response = parse_response(&body).unwrap();
+        assert_eq!(
+            response.content,
+            vec![json!({"type": "text", "text": "Hello!"})]
+        );
+        assert_eq!(response.stop_reason.as_deref(), Some("max_tokens"));
+        assert!(response.usage.is_none());
+    }
+
+    #[test]
+    fn test_parse_response_without_choices_fails() {
+        assert!(parse_response(&json!({"error": "bad"})).is_err());
+    }
+}
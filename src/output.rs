@@ -0,0 +1,344 @@
+//! 对话输出
+//!
+//! 一轮对话中产生的回复文本、工具调用、token 用量等通过 [`OutputSink`] 输出：
+//! [`PrettyOutput`] 面向终端显示，[`JsonOutput`] 在每轮结束时输出一个 JSON 对象，
+//! 便于脚本和 CI 解析。两者都写入 [`Output`]，测试中可换成内存缓冲区。
+
+use crate::api::Usage;
+use crate::labels::Labels;
+use crate::pricing;
+use crate::render;
+use crate::stream::TextEvent;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, Write};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+/// normal 模式下工具输入显示的最大字符数
+const TOOL_INPUT_PREVIEW_CHARS: usize = 120;
+
+/// normal 模式下折叠显示的思考过程的最大字符数
+const THINKING_PREVIEW_CHARS: usize = 200;
+
+/// verbose 模式下工具输出显示的最大字符数
+const TOOL_OUTPUT_PREVIEW_CHARS: usize = 2000;
+
+/// 终端输出的详细程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// 只显示助手回复和必要的提示
+    Quiet,
+    /// 显示工具调用（输入过长时截断）和 token 用量
+    #[default]
+    Normal,
+    /// 显示完整的工具输入和截断后的工具输出
+    Verbose,
+}
+
+impl Verbosity {
+    /// 解析配置中的名称
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "quiet" => Some(Verbosity::Quiet),
+            "normal" => Some(Verbosity::Normal),
+            "verbose" => Some(Verbosity::Verbose),
+            _ => None,
+        }
+    }
+}
+
+/// 文本输出的目的地
+pub trait Output {
+    fn write_str(&mut self, text: &str);
+}
+
+/// 输出到标准输出
+pub struct StdoutOutput;
+
+impl Output for StdoutOutput {
+    fn write_str(&mut self, text: &str) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(text.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+/// 输出到内存缓冲区，克隆后共享同一份内容
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct BufferOutput(Arc<Mutex<String>>);
+
+#[cfg(test)]
+impl BufferOutput {
+    /// 已写入的内容
+    pub fn contents(&self) -> String {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Output for BufferOutput {
+    fn write_str(&mut self, text: &str) {
+        self.0.lock().unwrap().push_str(text);
+    }
+}
+
+/// 对话输出的接收端
+pub trait OutputSink {
+    /// 流式回复的文本片段（实时到达）
+    fn stream_text(&mut self, event: TextEvent);
+
+    /// 一个完整的回复文本块
+    fn assistant_text(&mut self, text: &str);
+
+    /// 模型的思考过程
+    fn thinking(&mut self, thinking: &str);
+
+    /// 即将执行的工具调用
+    fn tool_call(&mut self, name: &str, input: &Value);
+
+    /// 工具调用的结果
+    fn tool_result(&mut self, name: &str, output: &str);
+
+    /// 本轮第 `round` 次工具调用往返已完成，`max` 为上限
+    fn tool_round(&mut self, round: u32, max: u32);
+
+    /// 提示信息，如回复被截断、历史被裁剪、操作被拒绝
+    fn notice(&mut self, message: &str);
+
+    /// 一轮对话结束；`cost` 为本轮的费用估算（未启用或价格未知时为 `None`）
+    fn end_turn(&mut self, usage: Usage, cost: Option<f64>, stop_reason: Option<&str>);
+
+    /// 普通的一行输出，如命令的执行结果
+    fn message(&mut self, text: &str);
+}
+
+/// 终端输出：带 emoji 前缀，按终端宽度折行
+pub struct PrettyOutput {
+    out: Box<dyn Output>,
+    /// 是否为 Markdown 着色
+    markdown: bool,
+    /// 回复文本是否已通过流式事件实时输出
+    streamed: bool,
+    verbosity: Verbosity,
+    /// emoji 或 ASCII 前缀
+    labels: Labels,
+}
+
+impl PrettyOutput {
+    pub fn new(
+        out: impl Output + 'static,
+        markdown: bool,
+        streamed: bool,
+        verbosity: Verbosity,
+    ) -> Self {
+        Self {
+            out: Box::new(out),
+            markdown,
+            streamed,
+            verbosity,
+            labels: Labels::default(),
+        }
+    }
+
+    /// 设置提示使用的前缀
+    pub fn with_labels(mut self, labels: Labels) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// 原样输出一行（回复和工具内容中的 emoji 不做替换）
+    fn write_line(&mut self, text: &str) {
+        self.out.write_str(text);
+        self.out.write_str("\n");
+    }
+}
+
+impl OutputSink for PrettyOutput {
+    fn stream_text(&mut self, event: TextEvent) {
+        match event {
+            TextEvent::Start => {
+                let prefix = format!("\n{} ", self.labels.assistant());
+                self.out.write_str(&prefix)
+            }
+            TextEvent::Delta(text) => self.out.write_str(&text),
+            TextEvent::Stop => self.out.write_str("\n\n"),
+        }
+    }
+
+    fn assistant_text(&mut self, text: &str) {
+        // 流式模式下文本已经实时输出
+        if !self.streamed {
+            let text = render::assistant_text(text, self.markdown);
+            let line = format!("\n{} {}\n", self.labels.assistant(), text);
+            self.write_line(&line);
+        }
+    }
+
+    fn thinking(&mut self, thinking: &str) {
+        // 思考过程通常很长：normal 模式下只显示第一行的开头，verbose 模式下完整显示
+        let display = match self.verbosity {
+            Verbosity::Quiet => return,
+            Verbosity::Normal => {
+                let first_line = thinking.lines().next().unwrap_or("");
+                let collapsed: String = first_line.chars().take(THINKING_PREVIEW_CHARS).collect();
+                if collapsed == thinking {
+                    collapsed
+                } else {
+                    format!(
+                        "{} (已折叠，共 {} 字符，使用 -v 查看全部)",
+                        collapsed,
+                        thinking.chars().count()
+                    )
+                }
+            }
+            Verbosity::Verbose => thinking.to_string(),
+        };
+        let line = format!(
+            "\n{} {}\n",
+            self.labels.thinking(),
+            render::dimmed(&display)
+        );
+        self.write_line(&line);
+    }
+
+    fn tool_call(&mut self, name: &str, input: &Value) {
+        let input = input.to_string();
+        let input = match self.verbosity {
+            Verbosity::Quiet => return,
+            Verbosity::Normal => preview(&input, TOOL_INPUT_PREVIEW_CHARS),
+            Verbosity::Verbose => input,
+        };
+        let line = format!("  {} [{}] {}", self.labels.tool(), name, input);
+        self.write_line(&line);
+    }
+
+    fn tool_result(&mut self, _name: &str, output: &str) {
+        if self.verbosity == Verbosity::Verbose {
+            let output = preview(output, TOOL_OUTPUT_PREVIEW_CHARS);
+            let line = format!("  {} {}", self.labels.tool_output(), output);
+            self.write_line(&line);
+        }
+    }
+
+    fn tool_round(&mut self, round: u32, max: u32) {
+        if self.verbosity == Verbosity::Verbose {
+            let line = format!("  {} 工具调用往返 {}/{}", self.labels.tool(), round, max);
+            self.write_line(&line);
+        }
+    }
+
+    fn notice(&mut self, message: &str) {
+        self.message(&format!("{}\n", message));
+    }
+
+    fn end_turn(&mut self, usage: Usage, cost: Option<f64>, _stop_reason: Option<&str>) {
+        if self.verbosity != Verbosity::Quiet && usage != Usage::default() {
+            let line = match cost {
+                Some(cost) => format!(
+                    "{} {} · {}\n",
+                    self.labels.usage(),
+                    usage,
+                    pricing::format_usd(cost)
+                ),
+                None => format!("{} {}\n", self.labels.usage(), usage),
+            };
+            self.write_line(&line);
+        }
+    }
+
+    fn message(&mut self, text: &str) {
+        let text = self.labels.apply(text).into_owned();
+        self.write_line(&text);
+    }
+}
+
+/// JSON 输出中的一次工具调用
+#[derive(Debug, Serialize)]
+struct ToolCall {
+    name: String,
+    input: Value,
+}
+
+/// JSON 输出中的一轮对话
+#[derive(Debug, Serialize)]
+struct TurnRecord<'a> {
+    text: String,
+    tool_calls: &'a [ToolCall],
+    usage: Usage,
+    /// 费用估算（美元），未启用 `show_cost` 或价格未知时省略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cost_usd: Option<f64>,
+    stop_reason: Option<&'a str>,
+}
+
+/// JSON 输出：收集一轮中的内容，结束时写出一行 JSON
+///
+/// 提示信息不写入 JSON，调用方可以从 `stop_reason` 判断回复是否完整。
+pub struct JsonOutput {
+    out: Box<dyn Output>,
+    text: Vec<String>,
+    tool_calls: Vec<ToolCall>,
+}
+
+impl JsonOutput {
+    pub fn new(out: impl Output + 'static) -> Self {
+        Self {
+            out: Box::new(out),
+            text: Vec::new(),
+            tool_calls: Vec::new(),
+        }
+    }
+}
+
+impl OutputSink for JsonOutput {
+    fn stream_text(&mut self, _event: TextEvent) {
+        // 完整文本随回复内容块一起到达
+    }
+
+    fn assistant_text(&mut self, text: &str) {
+        self.text.push(text.to_string());
+    }
+
+    fn thinking(&mut self, _thinking: &str) {}
+
+    fn tool_call(&mut self, name: &str, input: &Value) {
+        self.tool_calls.push(ToolCall {
+            name: name.to_string(),
+            input: input.clone(),
+        });
+    }
+
+    fn tool_result(&mut self, _name: &str, _output: &str) {}
+
+    fn tool_round(&mut self, _round: u32, _max: u32) {}
+
+    fn notice(&mut self, _message: &str) {}
+
+    fn end_turn(&mut self, usage: Usage, cost: Option<f64>, stop_reason: Option<&str>) {
+        let record = TurnRecord {
+            text: self.text.join("\n\n"),
+            tool_calls: &self.tool_calls,
+            usage,
+            cost_usd: cost,
+            stop_reason,
+        };
+        let line = serde_json::to_string(&record).unwrap_or_else(|e| {
+            serde_json::json!({"error": format!("Failed to serialize turn: {}", e)}).to_string()
+        });
+        self.out.write_str(&format!("{}\n", line));
+        self.text.clear();
+        self.tool_calls.clear();
+    }
+
+    fn message(&mut self, _text: &str) {}
+}
+
+/// 截取前 `max_chars` 个字符，超出时追加 `...`
+fn preview(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
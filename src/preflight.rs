@@ -0,0 +1,149 @@
+//! 启动时的连通性检查
+//!
+//! 错误的 base_url 或 API 密钥通常要到发送第一条消息时才暴露，且报错难以理解。
+//! 启用检查后在进入 REPL 前请求一次模型列表接口（不消耗 token），
+//! 并把失败归类为 DNS、TLS、连接、认证或路径问题。
+
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// 检查使用的接口（Anthropic 与 OpenAI 兼容服务都提供）
+pub const MODELS_ENDPOINT: &str = "/v1/models";
+
+/// 检查请求的超时时间，不使用对话请求的长超时
+pub const PREFLIGHT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 连通性检查失败的原因
+#[derive(Debug, PartialEq, Eq)]
+pub enum PreflightError {
+    /// 域名解析失败
+    Dns(String),
+    /// TLS 握手或证书校验失败
+    Tls(String),
+    /// 无法建立连接（端口未监听、被拒绝、代理不可用等）
+    Connect(String),
+    /// 请求超时
+    Timeout,
+    /// 401：API 密钥无效
+    Unauthorized,
+    /// 404：base_url 的路径不对
+    NotFound(String),
+    /// 其他非成功状态码
+    Status(u16),
+}
+
+impl fmt::Display for PreflightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreflightError::Dns(host) => {
+                write!(
+                    f,
+                    "无法解析域名 {}，请检查 ANTHROPIC_BASE_URL 是否拼写正确",
+                    host
+                )
+            }
+            PreflightError::Tls(detail) => {
+                write!(
+                    f,
+                    "TLS 连接失败（{}），请检查证书、代理或 URL 协议（http/https）",
+                    detail
+                )
+            }
+            PreflightError::Connect(detail) => {
+                write!(f, "无法连接到服务器（{}），请检查网络、代理和端口", detail)
+            }
+            PreflightError::Timeout => write!(f, "连接超时，请检查网络连接或代理设置"),
+            PreflightError::Unauthorized => {
+                write!(f, "认证失败 (401)，请检查 ANTHROPIC_AUTH_TOKEN 是否正确")
+            }
+            PreflightError::NotFound(url) => write!(
+                f,
+                "接口不存在 (404): {}，请检查 ANTHROPIC_BASE_URL（不要包含 /v1 等路径）",
+                url
+            ),
+            PreflightError::Status(status) => write!(f, "服务器返回异常状态码 {}", status),
+        }
+    }
+}
+
+impl Error for PreflightError {}
+
+/// 按状态码判断检查结果
+pub fn check_status(status: u16, url: &str) -> Result<(), PreflightError> {
+    match status {
+        200..=299 => Ok(()),
+        401 => Err(PreflightError::Unauthorized),
+        404 => Err(PreflightError::NotFound(url.to_string())),
+        _ => Err(PreflightError::Status(status)),
+    }
+}
+
+/// 归类请求错误
+///
+/// reqwest 不区分 DNS 与 TLS 错误，只能根据错误链中的描述判断。
+pub fn classify_error(error: &reqwest::Error) -> PreflightError {
+    if error.is_timeout() {
+        return PreflightError::Timeout;
+    }
+
+    let mut chain = Vec::new();
+    let mut source: Option<&dyn Error> = Some(error);
+    while let Some(e) = source {
+        chain.push(e.to_string());
+        source = e.source();
+    }
+    let detail = chain.last().cloned().unwrap_or_default();
+    let description = chain.join(": ").to_lowercase();
+    let host = error
+        .url()
+        .and_then(|url| url.host_str())
+        .unwrap_or_default()
+        .to_string();
+
+    if [
+        "dns error",
+        "failed to lookup",
+        "name or service not known",
+        "no such host",
+    ]
+    .iter()
+    .any(|pattern| description.contains(pattern))
+    {
+        PreflightError::Dns(host)
+    } else if ["certificate", "tls", "ssl", "handshake"]
+        .iter()
+        .any(|pattern| description.contains(pattern))
+    {
+        PreflightError::Tls(detail)
+    } else {
+        PreflightError::Connect(detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_status() {
+        assert_eq!(check_status(200, "u"), Ok(()));
+        assert_eq!(check_status(401, "u"), Err(PreflightError::Unauthorized));
+        assert_eq!(
+            check_status(404, "http://x/v1/models"),
+            Err(PreflightError::NotFound("http://x/v1/models".to_string()))
+        );
+        assert_eq!(check_status(500, "u"), Err(PreflightError::Status(500)));
+    }
+
+    #[test]
+    fn test_refused_connection_is_connect_error() {
+        // 绑定后立即释放端口，之后的连接会被拒绝
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let error = reqwest::blocking::get(format!("http://{}/v1/models", addr)).unwrap_err();
+        assert!(matches!(classify_error(&error), PreflightError::Connect(_)));
+    }
+}
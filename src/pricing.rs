@@ -0,0 +1,156 @@
+//! 按模型价格估算费用
+//!
+//! 内置常见 Claude 模型的公开价格（美元 / 百万 token），可通过配置中的
+//! `pricing` 覆盖或补充，例如使用代理或折扣价格时。估算不含缓存等折扣。
+
+use crate::api::Usage;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// 模型价格（美元 / 百万 token）
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+impl ModelPrice {
+    const fn new(input_per_mtok: f64, output_per_mtok: f64) -> Self {
+        Self {
+            input_per_mtok,
+            output_per_mtok,
+        }
+    }
+
+    /// 计算给定用量的费用（美元）
+    pub fn cost(&self, usage: Usage) -> f64 {
+        (usage.input_tokens as f64 * self.input_per_mtok
+            + usage.output_tokens as f64 * self.output_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+/// 内置价格表，按模型名前缀匹配
+///
+/// 更具体的前缀必须排在前面（如 `claude-opus-4-5` 在 `claude-opus-4` 之前）。
+pub const MODEL_PRICES: &[(&str, ModelPrice)] = &[
+    ("claude-opus-4-5", ModelPrice::new(5.0, 25.0)),
+    ("claude-sonnet-4-5", ModelPrice::new(3.0, 15.0)),
+    ("claude-haiku-4-5", ModelPrice::new(1.0, 5.0)),
+    ("claude-opus-4-1", ModelPrice::new(15.0, 75.0)),
+    ("claude-opus-4", ModelPrice::new(15.0, 75.0)),
+    ("claude-sonnet-4", ModelPrice::new(3.0, 15.0)),
+    ("claude-3-7-sonnet", ModelPrice::new(3.0, 15.0)),
+    ("claude-3-5-haiku", ModelPrice::new(0.8, 4.0)),
+];
+
+/// 查找模型价格：配置中的覆盖（按完整模型名）优先，其次是内置价格表
+pub fn price_for(model: &str, overrides: &HashMap<String, ModelPrice>) -> Option<ModelPrice> {
+    overrides.get(model).copied().or_else(|| {
+        MODEL_PRICES
+            .iter()
+            .find(|(prefix, _)| model.starts_with(prefix))
+            .map(|(_, price)| *price)
+    })
+}
+
+/// 累计的费用估算
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CostEstimate {
+    /// 已知价格部分的费用（美元）
+    pub total: f64,
+    /// 是否有用量因模型价格未知而未计入
+    pub unpriced: bool,
+}
+
+impl CostEstimate {
+    /// 累加一次用量，价格未知时只做标记
+    pub fn add(&mut self, usage: Usage, price: Option<ModelPrice>) {
+        match price {
+            Some(price) => self.total += price.cost(usage),
+            None if usage != Usage::default() => self.unpriced = true,
+            None => {}
+        }
+    }
+}
+
+impl fmt::Display for CostEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.total > 0.0, self.unpriced) {
+            (_, false) => write!(f, "约 {}", format_usd(self.total)),
+            (true, true) => write!(f, "约 {}（部分用量的模型价格未知）", format_usd(self.total)),
+            (false, true) => write!(f, "价格未知"),
+        }
+    }
+}
+
+/// 以美元显示费用，小额时保留更多小数位
+pub fn format_usd(cost: f64) -> String {
+    if cost < 1.0 {
+        format!("${:.4}", cost)
+    } else {
+        format!("${:.2}", cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> Usage {
+        Usage {
+            input_tokens,
+            output_tokens,
+        }
+    }
+
+    #[test]
+    fn test_cost_for_known_model() {
+        let price = price_for("claude-sonnet-4-5-20250929", &HashMap::new()).unwrap();
+        assert_eq!(price, ModelPrice::new(3.0, 15.0));
+        let cost = price.cost(usage(1_000_000, 100_000));
+        assert!((cost - 4.5).abs() < 1e-9);
+
+        // 更具体的前缀优先
+        assert_eq!(
+            price_for("claude-opus-4-5", &HashMap::new()),
+            Some(ModelPrice::new(5.0, 25.0))
+        );
+        assert_eq!(
+            price_for("claude-opus-4-20250514", &HashMap::new()),
+            Some(ModelPrice::new(15.0, 75.0))
+        );
+    }
+
+    #[test]
+    fn test_configured_override() {
+        let overrides = HashMap::from([
+            ("claude-sonnet-4-5".to_string(), ModelPrice::new(1.5, 7.5)),
+            ("llama3.1".to_string(), ModelPrice::new(0.0, 0.0)),
+        ]);
+        let price = price_for("claude-sonnet-4-5", &overrides).unwrap();
+        assert!((price.cost(usage(2_000_000, 0)) - 3.0).abs() < 1e-9);
+        assert_eq!(
+            price_for("llama3.1", &overrides)
+                .unwrap()
+                .cost(usage(10, 10)),
+            0.0
+        );
+        assert_eq!(price_for("llama3.1", &HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_estimate_display() {
+        let mut estimate = CostEstimate::default();
+        estimate.add(usage(100, 10), None);
+        assert_eq!(estimate.to_string(), "价格未知");
+
+        estimate.add(usage(1_000_000, 0), Some(ModelPrice::new(3.0, 15.0)));
+        assert_eq!(estimate.to_string(), "约 $3.00（部分用量的模型价格未知）");
+
+        let mut estimate = CostEstimate::default();
+        estimate.add(usage(1_000, 1_000), Some(ModelPrice::new(3.0, 15.0)));
+        assert_eq!(estimate.to_string(), "约 $0.0180");
+    }
+}
@@ -0,0 +1,240 @@
+//! 助手回复的终端渲染
+//!
+//! 按终端宽度折行普通段落，围栏代码块（```）原样保留；启用 `render_markdown`
+//! 时再用 ANSI 转义给代码块着色、把标题加粗。
+//! 输出不是终端（如被管道重定向）时不做任何处理，设置了 `NO_COLOR` 时不着色。
+
+use std::env;
+use std::ffi::OsStr;
+use std::io::{self, IsTerminal};
+use terminal_size::{terminal_size, Width};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// 无法获取终端宽度时使用的默认值
+const DEFAULT_WIDTH: usize = 80;
+
+/// 回复前缀 `🤖 ` 占用的列数
+const REPLY_PREFIX_WIDTH: usize = 3;
+
+/// 标题样式：加粗
+const STYLE_HEADER: &str = "\x1b[1m";
+/// 代码块样式：青色
+const STYLE_CODE: &str = "\x1b[36m";
+/// 暗色：围栏行和思考过程
+const STYLE_DIM: &str = "\x1b[2m";
+/// 清除样式
+const STYLE_RESET: &str = "\x1b[0m";
+
+/// 渲染一条助手回复用于终端显示
+///
+/// `markdown` 为 true 时在终端中为 Markdown 着色。
+pub fn assistant_text(text: &str, markdown: bool) -> String {
+    let is_tty = io::stdout().is_terminal();
+    let width = terminal_width(is_tty).map(|w| w.saturating_sub(REPLY_PREFIX_WIDTH));
+    let color = markdown && color_enabled(env::var_os("NO_COLOR").as_deref(), is_tty);
+    render(text, width, color)
+}
+
+/// 以暗色显示文本（用于思考过程）；不输出颜色时原样返回
+pub fn dimmed(text: &str) -> String {
+    let is_tty = io::stdout().is_terminal();
+    if color_enabled(env::var_os("NO_COLOR").as_deref(), is_tty) {
+        format!("{}{}{}", STYLE_DIM, text, STYLE_RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// 是否输出颜色：必须是终端，且没有设置 `NO_COLOR`（任意非空值）
+fn color_enabled(no_color: Option<&OsStr>, is_tty: bool) -> bool {
+    is_tty && no_color.is_none_or(OsStr::is_empty)
+}
+
+/// 获取终端宽度：优先查询终端，其次读取 `$COLUMNS`
+///
+/// stdout 不是终端时返回 `None`。
+fn terminal_width(is_tty: bool) -> Option<usize> {
+    if !is_tty {
+        return None;
+    }
+    if let Some((Width(width), _)) = terminal_size() {
+        return Some(width as usize);
+    }
+    let columns = env::var("COLUMNS").ok().and_then(|c| c.trim().parse().ok());
+    Some(columns.filter(|w| *w > 0).unwrap_or(DEFAULT_WIDTH))
+}
+
+/// 渲染文本：按 `width` 折行（`None` 时不折行），`color` 为 true 时输出 ANSI 样式
+///
+/// 围栏代码块内的行不折行。逐行处理以保留 Markdown 的列表、标题等结构，
+/// 续行沿用原行的缩进；宽度按 Unicode 显示宽度计算，中文等宽字符算两列。
+fn render(text: &str, width: Option<usize>, color: bool) -> String {
+    let style = |style: &str, line: &str| {
+        if color {
+            format!("{}{}{}", style, line, STYLE_RESET)
+        } else {
+            line.to_string()
+        }
+    };
+    let mut in_fence = false;
+    let mut lines = Vec::new();
+
+    for line in text.split('\n') {
+        if is_fence(line) {
+            in_fence = !in_fence;
+            lines.push(style(STYLE_DIM, line));
+        } else if in_fence {
+            lines.push(style(STYLE_CODE, line));
+        } else {
+            let wrapped = match width {
+                Some(width) => wrap_line(line, width.max(1)),
+                None => vec![line.to_string()],
+            };
+            if is_header(line) {
+                lines.extend(wrapped.iter().map(|l| style(STYLE_HEADER, l)));
+            } else {
+                lines.extend(wrapped);
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// 是否为 ATX 标题行（`#` 到 `######` 后跟空格）
+fn is_header(line: &str) -> bool {
+    let hashes = line.len() - line.trim_start_matches('#').len();
+    (1..=6).contains(&hashes) && line[hashes..].starts_with(' ')
+}
+
+/// 是否为代码块的围栏行
+fn is_fence(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+/// 折行单行文本
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    if line.width() <= width {
+        return vec![line.to_string()];
+    }
+
+    let content = line.trim_start();
+    let indent = &line[..line.len() - content.len()];
+    // 缩进过深时不再保留，避免每行只能放下一个字符
+    let indent = if indent.width() * 2 > width {
+        ""
+    } else {
+        indent
+    };
+    let available = width - indent.width();
+
+    let mut lines = Vec::new();
+    let mut current = indent.to_string();
+    let mut current_width = 0;
+    for word in content.split_whitespace() {
+        for piece in split_word(word, available) {
+            let piece_width = piece.width();
+            if current_width > 0 && current_width + 1 + piece_width > available {
+                lines.push(std::mem::replace(&mut current, indent.to_string()));
+                current_width = 0;
+            }
+            if current_width > 0 {
+                current.push(' ');
+                current_width += 1;
+            }
+            current.push_str(piece);
+            current_width += piece_width;
+        }
+    }
+    if current_width > 0 {
+        lines.push(current);
+    }
+    lines
+}
+
+/// 把超过宽度的单词（如没有空格的中文句子、长 URL）按字符切开
+fn split_word(word: &str, width: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut piece_width = 0;
+    for (i, c) in word.char_indices() {
+        let w = c.width().unwrap_or(0);
+        if piece_width + w > width && i > start {
+            pieces.push(&word[start..i]);
+            start = i;
+            piece_width = 0;
+        }
+        piece_width += w;
+    }
+    pieces.push(&word[start..]);
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(text: &str, width: usize) -> String {
+        render(text, Some(width), false)
+    }
+
+    #[test]
+    fn test_wrap_plain_paragraph() {
+        let text = "the quick brown fox jumps over the lazy dog";
+        assert_eq!(
+            wrap(text, 16),
+            "the quick brown\nfox jumps over\nthe lazy dog"
+        );
+        // 不超过宽度的行保持原样
+        assert_eq!(wrap("short line", 16), "short line");
+    }
+
+    #[test]
+    fn test_wrap_leaves_code_fences_untouched() {
+        let code = "    let value = some_function(argument_one, argument_two);";
+        let text = format!(
+            "Here is a fairly long sentence before the code.\n```rust\n{}\n```\nDone.",
+            code
+        );
+        let wrapped = wrap(&text, 20);
+        let lines: Vec<&str> = wrapped.lines().collect();
+        assert_eq!(
+            &lines[..3],
+            ["Here is a fairly", "long sentence before", "the code."]
+        );
+        assert_eq!(&lines[3..], ["```rust", code, "```", "Done."]);
+    }
+
+    #[test]
+    fn test_markdown_code_fence_and_header_styled() {
+        let text = "## Usage\n```rust\nlet x = 1;\n```\nplain #not-a-header";
+        assert_eq!(
+            render(text, None, true),
+            "\x1b[1m## Usage\x1b[0m\n\
+             \x1b[2m```rust\x1b[0m\n\
+             \x1b[36mlet x = 1;\x1b[0m\n\
+             \x1b[2m```\x1b[0m\n\
+             plain #not-a-header"
+        );
+        // 不着色时只折行
+        assert_eq!(render(text, None, false), text);
+    }
+
+    #[test]
+    fn test_no_color_disables_styles() {
+        assert!(color_enabled(None, true));
+        assert!(color_enabled(Some(OsStr::new("")), true));
+        assert!(!color_enabled(Some(OsStr::new("1")), true));
+        assert!(!color_enabled(None, false));
+    }
+
+    #[test]
+    fn test_wrap_keeps_indent_and_splits_wide_text() {
+        assert_eq!(wrap("  - item one two", 10), "  - item\n  one two");
+        // 中文每个字占两列
+        assert_eq!(
+            wrap("这是一个没有空格的长句子", 10),
+            "这是一个没\n有空格的长\n句子"
+        );
+    }
+}
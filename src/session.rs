@@ -0,0 +1,268 @@
+//! 会话持久化
+//!
+//! 把对话历史保存为 `.mentat/sessions/<name>.json`，下次启动后可以恢复继续。
+//! `/new` 开始新对话前把当前对话自动归档为 `auto-<时间戳>`。
+
+use crate::api::{Message, MessageContent};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 会话文件的默认目录（相对于工作区）
+pub const SESSIONS_DIR: &str = ".mentat/sessions";
+
+/// 自动归档的会话名称前缀
+pub const ARCHIVE_PREFIX: &str = "auto-";
+
+/// 会话错误
+#[derive(Debug)]
+pub enum SessionError {
+    /// 会话名称包含非法字符
+    InvalidName(String),
+    /// 会话不存在
+    NotFound(String),
+    /// 读写会话文件失败
+    Io(String),
+    /// 会话文件格式错误
+    Parse(String),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::InvalidName(name) => {
+                write!(f, "会话名称无效: {}（只能包含字母、数字、- 和 _）", name)
+            }
+            SessionError::NotFound(name) => write!(f, "会话不存在: {}", name),
+            SessionError::Io(msg) => write!(f, "读写会话文件失败: {}", msg),
+            SessionError::Parse(msg) => write!(f, "会话文件格式错误: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// 会话文件内容
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub messages: Vec<Message>,
+    /// 保存时启用的角色预设
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub persona: Option<String>,
+}
+
+/// 校验会话名称，防止写到会话目录之外
+fn session_path(dir: &Path, name: &str) -> Result<PathBuf, SessionError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(SessionError::InvalidName(name.to_string()));
+    }
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+/// 保存会话
+///
+/// # Returns
+/// 会话文件路径
+pub fn save(
+    dir: &Path,
+    name: &str,
+    messages: &[Message],
+    persona: Option<&str>,
+) -> Result<PathBuf, SessionError> {
+    let path = session_path(dir, name)?;
+    let file = Session {
+        messages: messages.to_vec(),
+        persona: persona.map(str::to_string),
+    };
+    let json =
+        serde_json::to_string_pretty(&file).map_err(|e| SessionError::Parse(e.to_string()))?;
+
+    fs::create_dir_all(dir).map_err(|e| SessionError::Io(e.to_string()))?;
+    fs::write(&path, json).map_err(|e| SessionError::Io(e.to_string()))?;
+    Ok(path)
+}
+
+/// 加载会话
+pub fn load(dir: &Path, name: &str) -> Result<Session, SessionError> {
+    let path = session_path(dir, name)?;
+    if !path.exists() {
+        return Err(SessionError::NotFound(name.to_string()));
+    }
+
+    let json = fs::read_to_string(&path).map_err(|e| SessionError::Io(e.to_string()))?;
+    serde_json::from_str(&json).map_err(|e| SessionError::Parse(e.to_string()))
+}
+
+/// 以 `auto-<毫秒时间戳>` 为名归档会话，同一毫秒内重复归档时顺延时间戳
+///
+/// # Returns
+/// 会话名称和文件路径
+pub fn archive(
+    dir: &Path,
+    messages: &[Message],
+    persona: Option<&str>,
+) -> Result<(String, PathBuf), SessionError> {
+    let mut timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let mut name = format!("{}{}", ARCHIVE_PREFIX, timestamp);
+    while dir.join(format!("{}.json", name)).exists() {
+        timestamp += 1;
+        name = format!("{}{}", ARCHIVE_PREFIX, timestamp);
+    }
+    let path = save(dir, &name, messages, persona)?;
+    Ok((name, path))
+}
+
+/// 已保存的会话名称，按修改时间从新到旧排列；目录不存在时为空
+pub fn list(dir: &Path) -> Vec<String> {
+    let mut sessions: Vec<(SystemTime, String)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            let name = path.file_stem()?.to_str()?.to_string();
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, name))
+        })
+        .collect();
+    sessions.sort_by(|a, b| b.cmp(a));
+    sessions.into_iter().map(|(_, name)| name).collect()
+}
+
+/// 会话中调用过、但不在 `available` 中的工具名称（去重，按出现顺序）
+pub fn missing_tools(messages: &[Message], available: &[&str]) -> Vec<String> {
+    let mut missing: Vec<String> = Vec::new();
+
+    for message in messages {
+        let MessageContent::Blocks(blocks) = &message.content else {
+            continue;
+        };
+        for block in blocks {
+            if block.get("type").and_then(|t| t.as_str()) != Some("tool_use") {
+                continue;
+            }
+            if let Some(name) = block.get("name").and_then(|n| n.as_str()) {
+                if !available.contains(&name) && !missing.iter().any(|m| m == name) {
+                    missing.push(name.to_string());
+                }
+            }
+        }
+    }
+
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fixture_dir(name: &str) -> PathBuf {
+        let dir = std::env::current_dir().unwrap().join("target").join(name);
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn conversation() -> Vec<Message> {
+        vec![
+            Message {
+                role: "user".to_string(),
+                content: MessageContent::Text("read Cargo.toml".to_string()),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: MessageContent::Blocks(vec![json!({
+                    "type": "tool_use", "id": "t1", "name": "read_file",
+                    "input": {"file_path": "Cargo.toml"}
+                })]),
+            },
+            Message {
+                role: "user".to_string(),
+                content: MessageContent::Blocks(vec![json!({
+                    "type": "tool_result", "tool_use_id": "t1", "content": "[package]"
+                })]),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = fixture_dir("test_session_round_trip");
+        let messages = conversation();
+
+        let path = save(&dir, "debug-1", &messages, Some("reviewer")).unwrap();
+        assert!(path.ends_with("debug-1.json"));
+
+        let session = load(&dir, "debug-1").unwrap();
+        assert_eq!(session.persona.as_deref(), Some("reviewer"));
+        let loaded = session.messages;
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&messages).unwrap()
+        );
+        assert!(matches!(loaded[0].content, MessageContent::Text(_)));
+        assert!(matches!(loaded[1].content, MessageContent::Blocks(_)));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_missing_session() {
+        let dir = fixture_dir("test_session_missing");
+        assert!(matches!(load(&dir, "nope"), Err(SessionError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_invalid_names_rejected() {
+        let dir = fixture_dir("test_session_names");
+        for name in ["", "../escape", "a/b", "a.b"] {
+            assert!(matches!(
+                save(&dir, name, &[], None),
+                Err(SessionError::InvalidName(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_archive_and_list() {
+        let dir = fixture_dir("test_session_archive");
+        assert!(list(&dir).is_empty());
+
+        let messages = conversation();
+        let (first, path) = archive(&dir, &messages, None).unwrap();
+        assert!(first.starts_with(ARCHIVE_PREFIX));
+        assert!(path.ends_with(format!("{}.json", first)));
+        // 连续归档不会覆盖之前的文件
+        let (second, _) = archive(&dir, &messages, None).unwrap();
+        assert_ne!(first, second);
+        let session = load(&dir, &first).unwrap();
+        assert_eq!(session.messages.len(), 3);
+        assert!(session.persona.is_none());
+
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        let mut names = list(&dir);
+        names.sort();
+        assert_eq!(names, vec![first, second]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_missing_tools() {
+        let messages = conversation();
+        assert!(missing_tools(&messages, &["read_file", "write_file"]).is_empty());
+        assert_eq!(missing_tools(&messages, &["write_file"]), vec!["read_file"]);
+    }
+}
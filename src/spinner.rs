@@ -0,0 +1,177 @@
+//! 等待 API 响应时的进度提示
+//!
+//! 请求发出后由后台线程定时刷新一帧动画和已等待的秒数，响应到达时清除该行。
+//! 流式响应在收到响应头、开始输出正文之前就已清除，不会与回复交错。
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// 动画帧
+pub const FRAMES: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// 不支持 Unicode 的终端使用的动画帧
+pub const ASCII_FRAMES: &[char] = &['|', '/', '-', '\\'];
+
+/// 刷新间隔
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 响应在此时间内到达时不显示，避免一闪而过
+const SHOW_AFTER: Duration = Duration::from_millis(300);
+
+/// 动画状态，与终端无关
+#[derive(Debug)]
+pub struct SpinnerState {
+    label: String,
+    frames: &'static [char],
+    /// 开始等待的时间，未运行时为 `None`
+    started: Option<Instant>,
+    frame: usize,
+    /// 是否已经绘制过，停止时据此决定是否需要清除该行
+    drawn: bool,
+}
+
+impl SpinnerState {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            frames: FRAMES,
+            started: None,
+            frame: 0,
+            drawn: false,
+        }
+    }
+
+    /// 使用指定的动画帧
+    pub fn with_frames(mut self, frames: &'static [char]) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    /// 开始计时
+    pub fn start(&mut self, now: Instant) {
+        self.started = Some(now);
+        self.frame = 0;
+        self.drawn = false;
+    }
+
+    /// 下一帧要显示的内容；未运行或尚未到显示时间时返回 `None`
+    pub fn tick(&mut self, now: Instant) -> Option<String> {
+        let elapsed = now.saturating_duration_since(self.started?);
+        if elapsed < SHOW_AFTER {
+            return None;
+        }
+        let frame = self.frames[self.frame % self.frames.len()];
+        self.frame += 1;
+        self.drawn = true;
+        Some(format!("{} {}… {}s", frame, self.label, elapsed.as_secs()))
+    }
+
+    /// 停止计时；返回是否需要清除已绘制的内容
+    pub fn stop(&mut self) -> bool {
+        self.started = None;
+        std::mem::take(&mut self.drawn)
+    }
+}
+
+/// 在后台线程中绘制的进度提示，drop 时停止并清除
+pub struct Spinner {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    /// 开始在标准输出上显示进度提示，调用方应确保标准输出是终端
+    pub fn start(label: &str, frames: &'static [char]) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let mut state = SpinnerState::new(label).with_frames(frames);
+        state.start(Instant::now());
+
+        let handle = thread::spawn(move || {
+            let mut stdout = io::stdout();
+            while !stopped.load(Ordering::SeqCst) {
+                if let Some(line) = state.tick(Instant::now()) {
+                    let _ = write!(stdout, "\r\x1b[2K{}", line);
+                    let _ = stdout.flush();
+                }
+                thread::park_timeout(TICK_INTERVAL);
+            }
+            if state.stop() {
+                let _ = write!(stdout, "\r\x1b[2K");
+                let _ = stdout.flush();
+            }
+        });
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spinner_state_machine() {
+        let mut state = SpinnerState::new("思考中");
+        let now = Instant::now();
+
+        // 未开始时不显示
+        assert_eq!(state.tick(now), None);
+        assert!(!state.stop());
+
+        state.start(now);
+        // 很快返回的请求不显示
+        assert_eq!(state.tick(now + Duration::from_millis(100)), None);
+        assert!(!state.stop());
+
+        state.start(now);
+        assert_eq!(
+            state.tick(now + Duration::from_millis(1200)).unwrap(),
+            "⠋ 思考中… 1s"
+        );
+        assert_eq!(
+            state.tick(now + Duration::from_millis(1300)).unwrap(),
+            "⠙ 思考中… 1s"
+        );
+        assert_eq!(
+            state.tick(now + Duration::from_secs(3)).unwrap(),
+            "⠹ 思考中… 3s"
+        );
+
+        // 停止后需要清除，之后不再显示
+        assert!(state.stop());
+        assert_eq!(state.tick(now + Duration::from_secs(4)), None);
+        assert!(!state.stop());
+    }
+
+    #[test]
+    fn test_frames_wrap_around() {
+        let mut state = SpinnerState::new("x");
+        let now = Instant::now();
+        state.start(now);
+        let later = now + Duration::from_secs(1);
+        let frames: Vec<String> = (0..=FRAMES.len())
+            .map(|_| state.tick(later).unwrap())
+            .collect();
+        assert_eq!(frames[0], frames[FRAMES.len()]);
+
+        let mut state = SpinnerState::new("x").with_frames(ASCII_FRAMES);
+        state.start(now);
+        assert_eq!(state.tick(later).unwrap(), "| x… 1s");
+    }
+}
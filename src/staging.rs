@@ -0,0 +1,324 @@
+//! 暂存区模块
+//!
+//! 启用暂存模式后，write_file / edit_file 的调用不会直接写盘，而是先记录在暂存区中。
+//! 用户通过 `/apply` 逐个审阅 diff，决定保留、跳过或编辑后再应用。
+
+use crate::tools::{apply_edit, PathValidator};
+use serde_json::Value;
+use similar::TextDiff;
+use std::fs;
+
+/// diff 的上下文行数
+pub const DIFF_CONTEXT_LINES: usize = 3;
+
+/// 一次被暂存的文件修改
+#[derive(Debug, Clone, PartialEq)]
+pub struct StagedChange {
+    /// 目标文件路径（工作目录内的相对路径）
+    pub file_path: String,
+    /// 修改后的完整内容
+    pub content: String,
+}
+
+impl StagedChange {
+    /// 转换为 write_file 工具的输入，用于真正应用修改
+    pub fn to_tool_input(&self) -> Value {
+        serde_json::json!({
+            "file_path": self.file_path,
+            "content": self.content
+        })
+    }
+
+    /// 生成当前文件内容与暂存内容之间的统一 diff
+    ///
+    /// 只读取通过写入校验的路径；校验失败或文件不存在时视为空文件。
+    pub fn unified_diff(&self, validator: &PathValidator) -> String {
+        let original = validator
+            .validate_for_write(&self.file_path)
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        render_unified_diff(&original, &self.content, &self.file_path)
+    }
+}
+
+/// 用户对单个暂存修改的审阅选择
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewChoice {
+    /// 保留并应用
+    Keep,
+    /// 跳过，不应用
+    Skip,
+    /// 在编辑器中修改后再应用
+    Edit,
+}
+
+/// 解析审阅提示中的用户输入
+pub fn parse_review_choice(input: &str) -> Option<ReviewChoice> {
+    match input.trim().to_lowercase().as_str() {
+        "k" | "keep" | "y" | "yes" => Some(ReviewChoice::Keep),
+        "s" | "skip" | "n" | "no" => Some(ReviewChoice::Skip),
+        "e" | "edit" => Some(ReviewChoice::Edit),
+        _ => None,
+    }
+}
+
+/// 暂存区
+#[derive(Debug, Default)]
+pub struct StagingArea {
+    changes: Vec<StagedChange>,
+}
+
+impl StagingArea {
+    /// 创建空暂存区
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 尝试暂存一次工具调用
+    ///
+    /// 如果该工具不需要暂存则返回 `None`，调用者应正常执行工具；
+    /// 否则返回交给模型的 tool_result 内容。路径在暂存时就按写入规则校验，
+    /// 不允许写入的路径直接拒绝，不会等到 `/apply` 时才失败。
+    pub fn stage_tool_call(
+        &mut self,
+        name: &str,
+        input: &Value,
+        validator: &PathValidator,
+    ) -> Option<String> {
+        let result = match name {
+            "write_file" => self.stage_write(input, validator),
+            "edit_file" => self.stage_edit(input, validator),
+            _ => return None,
+        };
+
+        let output = match result {
+            Ok(file_path) => serde_json::json!({
+                "success": true,
+                "message": format!(
+                    "Change to {} staged for review; it will be written after the user approves it via /apply",
+                    file_path
+                ),
+                "error": null
+            }),
+            Err(e) => serde_json::json!({
+                "success": false,
+                "message": null,
+                "error": e
+            }),
+        };
+
+        Some(output.to_string())
+    }
+
+    /// 暂存 write_file 调用
+    fn stage_write(&mut self, input: &Value, validator: &PathValidator) -> Result<String, String> {
+        let file_path = input.get("file_path").and_then(|v| v.as_str());
+        let content = input.get("content").and_then(|v| v.as_str());
+
+        match (file_path, content) {
+            (Some(file_path), Some(content)) => {
+                validator
+                    .validate_for_write(file_path)
+                    .map_err(|e| e.to_string())?;
+                self.stage(file_path, content);
+                Ok(file_path.to_string())
+            }
+            _ => Err("Invalid input: file_path and content are required".to_string()),
+        }
+    }
+
+    /// 暂存 edit_file 调用：基于已暂存内容（或磁盘内容）计算替换结果
+    fn stage_edit(&mut self, input: &Value, validator: &PathValidator) -> Result<String, String> {
+        let field = |key: &str| input.get(key).and_then(|v| v.as_str());
+        let (file_path, old_string, new_string) =
+            match (field("file_path"), field("old_string"), field("new_string")) {
+                (Some(f), Some(o), Some(n)) => (f, o, n),
+                _ => {
+                    return Err(
+                        "Invalid input: file_path, old_string and new_string are required"
+                            .to_string(),
+                    )
+                }
+            };
+        let expected_count = input
+            .get("expected_count")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32);
+
+        let validated = validator
+            .validate_for_write(file_path)
+            .map_err(|e| e.to_string())?;
+        let current = match self.changes.iter().find(|c| c.file_path == file_path) {
+            Some(staged) => staged.content.clone(),
+            None => {
+                fs::read_to_string(&validated).map_err(|e| format!("Failed to read file: {}", e))?
+            }
+        };
+
+        let (updated, _) = apply_edit(&current, old_string, new_string, expected_count)?;
+        self.stage(file_path, &updated);
+        Ok(file_path.to_string())
+    }
+
+    /// 暂存一个文件修改；同一路径的后续修改会覆盖之前的暂存内容
+    pub fn stage(&mut self, file_path: &str, content: &str) {
+        let change = StagedChange {
+            file_path: file_path.to_string(),
+            content: content.to_string(),
+        };
+
+        match self.changes.iter_mut().find(|c| c.file_path == file_path) {
+            Some(existing) => *existing = change,
+            None => self.changes.push(change),
+        }
+    }
+
+    /// 取出所有暂存修改并清空暂存区
+    pub fn take(&mut self) -> Vec<StagedChange> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// 暂存的修改数量
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// 暂存区是否为空
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// 渲染统一 diff（unified diff）
+pub fn render_unified_diff(original: &str, modified: &str, file_path: &str) -> String {
+    TextDiff::from_lines(original, modified)
+        .unified_diff()
+        .context_radius(DIFF_CONTEXT_LINES)
+        .header(&format!("a/{}", file_path), &format!("b/{}", file_path))
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::IgnoreRules;
+
+    fn validator() -> PathValidator {
+        PathValidator::with_root(std::env::current_dir().unwrap())
+    }
+
+    #[test]
+    fn test_stage_tool_call_ignores_other_tools() {
+        let mut staging = StagingArea::new();
+        let input = serde_json::json!({"file_path": "Cargo.toml"});
+        assert!(staging
+            .stage_tool_call("read_file", &input, &validator())
+            .is_none());
+        assert!(staging.is_empty());
+    }
+
+    #[test]
+    fn test_stage_tool_call_records_write() {
+        let mut staging = StagingArea::new();
+        let input = serde_json::json!({"file_path": "a.txt", "content": "hello"});
+        let output = staging
+            .stage_tool_call("write_file", &input, &validator())
+            .unwrap();
+        assert!(output.contains("\"success\":true"));
+        assert!(output.contains("/apply"));
+        assert_eq!(staging.len(), 1);
+    }
+
+    #[test]
+    fn test_stage_tool_call_invalid_input() {
+        let mut staging = StagingArea::new();
+        let input = serde_json::json!({"file_path": "a.txt"});
+        let output = staging
+            .stage_tool_call("write_file", &input, &validator())
+            .unwrap();
+        assert!(output.contains("\"success\":false"));
+        assert!(staging.is_empty());
+    }
+
+    #[test]
+    fn test_stage_rejects_paths_the_validator_refuses() {
+        let mut staging = StagingArea::new();
+        let validator = validator().with_ignore_rules(IgnoreRules::parse(".env\n"));
+
+        let write = serde_json::json!({"file_path": "/etc/passwd", "content": "x"});
+        let output = staging
+            .stage_tool_call("write_file", &write, &validator)
+            .unwrap();
+        assert!(output.contains("\"success\":false"));
+
+        let write = serde_json::json!({"file_path": ".env", "content": "x"});
+        let output = staging
+            .stage_tool_call("write_file", &write, &validator)
+            .unwrap();
+        assert!(output.contains("\"success\":false"));
+
+        // 不会读取工作区外的文件，也不会泄露 old_string 是否匹配
+        let edit = serde_json::json!({
+            "file_path": "../../etc/hostname",
+            "old_string": "a",
+            "new_string": "b"
+        });
+        let output = staging
+            .stage_tool_call("edit_file", &edit, &validator)
+            .unwrap();
+        assert!(output.contains("\"success\":false"));
+        assert!(!output.contains("Failed to read") && !output.contains("not found"));
+        assert!(staging.is_empty());
+    }
+
+    #[test]
+    fn test_stage_edit_applies_on_staged_content() {
+        let mut staging = StagingArea::new();
+        staging.stage("target/never_written.txt", "let x = 1;\n");
+
+        let input = serde_json::json!({
+            "file_path": "target/never_written.txt",
+            "old_string": "x = 1",
+            "new_string": "x = 2"
+        });
+        let output = staging
+            .stage_tool_call("edit_file", &input, &validator())
+            .unwrap();
+        assert!(output.contains("\"success\":true"));
+
+        let changes = staging.take();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].content, "let x = 2;\n");
+    }
+
+    #[test]
+    fn test_stage_same_path_replaces_previous() {
+        let mut staging = StagingArea::new();
+        staging.stage("a.txt", "first");
+        staging.stage("b.txt", "other");
+        staging.stage("a.txt", "second");
+
+        let changes = staging.take();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].content, "second");
+        assert!(staging.is_empty());
+    }
+
+    #[test]
+    fn test_render_unified_diff() {
+        let diff = render_unified_diff("a\nb\nc\n", "a\nB\nc\n", "x.txt");
+        assert!(diff.contains("--- a/x.txt"));
+        assert!(diff.contains("+++ b/x.txt"));
+        assert!(diff.contains("-b"));
+        assert!(diff.contains("+B"));
+    }
+
+    #[test]
+    fn test_parse_review_choice() {
+        assert_eq!(parse_review_choice("k"), Some(ReviewChoice::Keep));
+        assert_eq!(parse_review_choice(" Skip "), Some(ReviewChoice::Skip));
+        assert_eq!(parse_review_choice("e"), Some(ReviewChoice::Edit));
+        assert_eq!(parse_review_choice("x"), None);
+    }
+}
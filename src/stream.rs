@@ -0,0 +1,419 @@
+//! 流式响应处理
+//!
+//! 解析 Anthropic Messages API 的 `text/event-stream` 响应：
+//! - 文本增量 (`text_delta`) 通过回调实时交给调用者显示
+//! - 工具调用的输入 (`input_json_delta`) 在 `content_block_stop` 时拼接并解析
+//! - 最终组装成与非流式响应相同的 [`AnthropicResponse`]
+
+use crate::api::{AnthropicResponse, Usage};
+use serde_json::Value;
+use std::fmt;
+use std::io::BufRead;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// SSE 数据行前缀
+const SSE_DATA_PREFIX: &str = "data:";
+
+/// 流式处理错误
+#[derive(Debug)]
+pub enum StreamError {
+    /// 用户中断（Ctrl+C）
+    Interrupted,
+    /// 服务端通过 `error` 事件返回的错误
+    Api(String),
+    /// 事件格式无法解析
+    Parse(String),
+    /// 读取响应流失败
+    Io(std::io::Error),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Interrupted => write!(f, "Stream interrupted by user"),
+            StreamError::Api(msg) => write!(f, "Stream error from API: {}", msg),
+            StreamError::Parse(msg) => write!(f, "Invalid stream event: {}", msg),
+            StreamError::Io(e) => write!(f, "Failed to read stream: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+/// 需要实时显示的流式文本事件
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextEvent {
+    /// 一个文本块开始
+    Start,
+    /// 文本增量
+    Delta(String),
+    /// 一个文本块结束
+    Stop,
+}
+
+/// 流式事件累加器：把增量事件组装成完整的 content blocks
+#[derive(Debug, Default)]
+pub struct StreamAccumulator {
+    blocks: Vec<Value>,
+    /// 每个 block 累积的工具输入 JSON 片段
+    partial_json: Vec<String>,
+    stop_reason: Option<String>,
+    usage: Option<Usage>,
+}
+
+impl StreamAccumulator {
+    /// 创建空累加器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 处理一个事件，返回需要显示的内容（如果有）
+    pub fn handle_event(&mut self, event: &Value) -> Result<Option<TextEvent>, StreamError> {
+        let event_type = event.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+        match event_type {
+            "content_block_start" => {
+                let index = self.block_index(event)?;
+                let block = event
+                    .get("content_block")
+                    .cloned()
+                    .ok_or_else(|| StreamError::Parse("missing content_block".to_string()))?;
+                let is_text = block.get("type").and_then(|t| t.as_str()) == Some("text");
+
+                // 内容块按顺序到达，跳过下标的事件视为无效，避免按异常的下标分配内存
+                if index > self.blocks.len() {
+                    return Err(StreamError::Parse(format!(
+                        "content block {} started before block {}",
+                        index,
+                        self.blocks.len()
+                    )));
+                }
+                if index == self.blocks.len() {
+                    self.blocks.push(Value::Null);
+                    self.partial_json.push(String::new());
+                }
+                self.blocks[index] = block;
+
+                Ok(is_text.then_some(TextEvent::Start))
+            }
+            "content_block_delta" => {
+                let index = self.block_index(event)?;
+                let delta = event.get("delta").unwrap_or(&Value::Null);
+                let block = self.blocks.get_mut(index).ok_or_else(|| {
+                    StreamError::Parse(format!("delta for unknown block {}", index))
+                })?;
+
+                match delta.get("type").and_then(|t| t.as_str()) {
+                    Some("text_delta") => {
+                        let text = delta.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                        append_str_field(block, "text", text);
+                        Ok(Some(TextEvent::Delta(text.to_string())))
+                    }
+                    Some("input_json_delta") => {
+                        let partial = delta
+                            .get("partial_json")
+                            .and_then(|t| t.as_str())
+                            .unwrap_or("");
+                        self.partial_json[index].push_str(partial);
+                        Ok(None)
+                    }
+                    Some("thinking_delta") => {
+                        let thinking = delta.get("thinking").and_then(|t| t.as_str()).unwrap_or("");
+                        append_str_field(block, "thinking", thinking);
+                        Ok(None)
+                    }
+                    Some("signature_delta") => {
+                        let signature = delta
+                            .get("signature")
+                            .and_then(|t| t.as_str())
+                            .unwrap_or("");
+                        append_str_field(block, "signature", signature);
+                        Ok(None)
+                    }
+                    _ => Ok(None),
+                }
+            }
+            "content_block_stop" => {
+                let index = self.block_index(event)?;
+                let block = self.blocks.get_mut(index).ok_or_else(|| {
+                    StreamError::Parse(format!("stop for unknown block {}", index))
+                })?;
+                let is_text = block.get("type").and_then(|t| t.as_str()) == Some("text");
+
+                // 工具输入在块结束时才是完整的 JSON
+                let partial = std::mem::take(&mut self.partial_json[index]);
+                if !partial.is_empty() {
+                    let input: Value = serde_json::from_str(&partial).map_err(|e| {
+                        StreamError::Parse(format!("tool input is not valid JSON: {}", e))
+                    })?;
+                    block["input"] = input;
+                }
+
+                Ok(is_text.then_some(TextEvent::Stop))
+            }
+            "message_start" => {
+                // 输入 token 数在 message_start 中给出
+                if let Some(usage) = event.get("message").and_then(|m| m.get("usage")) {
+                    self.merge_usage(usage);
+                }
+                Ok(None)
+            }
+            "message_delta" => {
+                // 输出 token 数在 message_delta 中给出（累计值）
+                if let Some(usage) = event.get("usage") {
+                    self.merge_usage(usage);
+                }
+                if let Some(reason) = event
+                    .get("delta")
+                    .and_then(|d| d.get("stop_reason"))
+                    .and_then(|r| r.as_str())
+                {
+                    self.stop_reason = Some(reason.to_string());
+                }
+                Ok(None)
+            }
+            "error" => {
+                let message = event
+                    .get("error")
+                    .and_then(|e| e.get("message"))
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("unknown error");
+                Err(StreamError::Api(message.to_string()))
+            }
+            // message_stop / ping 等无需处理
+            _ => Ok(None),
+        }
+    }
+
+    /// 完成累积，生成完整响应
+    pub fn finish(self) -> AnthropicResponse {
+        AnthropicResponse {
+            content: self.blocks.into_iter().filter(|b| !b.is_null()).collect(),
+            stop_reason: self.stop_reason,
+            usage: self.usage,
+        }
+    }
+
+    /// 合并事件中出现的 usage 字段（后出现的值覆盖先前的值）
+    fn merge_usage(&mut self, usage: &Value) {
+        let current = self.usage.get_or_insert_with(Usage::default);
+        if let Some(input) = usage.get("input_tokens").and_then(|v| v.as_u64()) {
+            current.input_tokens = input;
+        }
+        if let Some(output) = usage.get("output_tokens").and_then(|v| v.as_u64()) {
+            current.output_tokens = output;
+        }
+    }
+
+    fn block_index(&self, event: &Value) -> Result<usize, StreamError> {
+        event
+            .get("index")
+            .and_then(|i| i.as_u64())
+            .map(|i| i as usize)
+            .ok_or_else(|| StreamError::Parse("missing index".to_string()))
+    }
+}
+
+/// 在 block 的字符串字段后追加内容
+fn append_str_field(block: &mut Value, field: &str, text: &str) {
+    let current = block.get(field).and_then(|t| t.as_str()).unwrap_or("");
+    block[field] = Value::String(format!("{}{}", current, text));
+}
+
+/// 从 SSE 流中读取事件直到结束
+///
+/// 每读取一行都会检查 `interrupted`，被置位时立即放弃读取并返回
+/// [`StreamError::Interrupted`]。
+///
+/// # Arguments
+/// * `reader` - 响应体
+/// * `interrupted` - 中断标记（由 Ctrl+C 处理器设置）
+/// * `on_display` - 需要实时显示内容时的回调
+pub fn read_stream<R: BufRead>(
+    reader: R,
+    interrupted: &AtomicBool,
+    mut on_display: impl FnMut(TextEvent),
+) -> Result<AnthropicResponse, StreamError> {
+    let mut accumulator = StreamAccumulator::new();
+
+    for line in reader.lines() {
+        if interrupted.load(Ordering::SeqCst) {
+            return Err(StreamError::Interrupted);
+        }
+
+        let line = line.map_err(StreamError::Io)?;
+        let data = match line.strip_prefix(SSE_DATA_PREFIX) {
+            Some(data) => data.trim(),
+            // 忽略 event: 行、注释和空行（事件类型已包含在 data 的 JSON 中）
+            None => continue,
+        };
+        if data.is_empty() {
+            continue;
+        }
+
+        let event: Value =
+            serde_json::from_str(data).map_err(|e| StreamError::Parse(e.to_string()))?;
+        let is_stop = event.get("type").and_then(|t| t.as_str()) == Some("message_stop");
+
+        if let Some(display) = accumulator.handle_event(&event)? {
+            on_display(display);
+        }
+        if is_stop {
+            break;
+        }
+    }
+
+    Ok(accumulator.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const SAMPLE_STREAM: &str = r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_1","content":[],"stop_reason":null,"usage":{"input_tokens":25,"output_tokens":1}}}
+
+event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}
+
+event: ping
+data: {"type":"ping"}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Let me "}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"check."}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":0}
+
+event: content_block_start
+data: {"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"read_file","input":{}}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"file_pa"}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"th\": \"Cargo.toml\"}"}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":1}
+
+event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":15}}
+
+event: message_stop
+data: {"type":"message_stop"}
+"#;
+
+    #[test]
+    fn test_read_stream_accumulates_text_and_tool_use() {
+        let interrupted = AtomicBool::new(false);
+        let mut displayed = Vec::new();
+        let response = read_stream(Cursor::new(SAMPLE_STREAM), &interrupted, |d| {
+            displayed.push(d)
+        })
+        .unwrap();
+
+        assert_eq!(response.stop_reason.as_deref(), Some("tool_use"));
+        assert_eq!(
+            response.usage,
+            Some(Usage {
+                input_tokens: 25,
+                output_tokens: 15
+            })
+        );
+        assert_eq!(response.content.len(), 2);
+        assert_eq!(response.content[0]["text"], "Let me check.");
+        assert_eq!(response.content[1]["name"], "read_file");
+        assert_eq!(response.content[1]["input"]["file_path"], "Cargo.toml");
+
+        assert_eq!(
+            displayed,
+            vec![
+                TextEvent::Start,
+                TextEvent::Delta("Let me ".to_string()),
+                TextEvent::Delta("check.".to_string()),
+                TextEvent::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_stream_interrupted() {
+        let interrupted = AtomicBool::new(true);
+        let result = read_stream(Cursor::new(SAMPLE_STREAM), &interrupted, |_| {});
+        assert!(matches!(result, Err(StreamError::Interrupted)));
+    }
+
+    #[test]
+    fn test_read_stream_error_event() {
+        let stream = "event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n";
+        let interrupted = AtomicBool::new(false);
+        let result = read_stream(Cursor::new(stream), &interrupted, |_| {});
+        match result {
+            Err(StreamError::Api(msg)) => assert_eq!(msg, "Overloaded"),
+            other => panic!("unexpected result: {:?}", other.map(|r| r.content)),
+        }
+    }
+
+    #[test]
+    fn test_unknown_block_kept_unchanged() {
+        let mut accumulator = StreamAccumulator::new();
+        let events = [
+            serde_json::json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": {"type": "redacted_thinking", "data": "opaque"}
+            }),
+            serde_json::json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "future_delta", "value": 1}
+            }),
+            serde_json::json!({"type": "content_block_stop", "index": 0}),
+        ];
+        for event in &events {
+            assert_eq!(accumulator.handle_event(event).unwrap(), None);
+        }
+
+        let response = accumulator.finish();
+        assert_eq!(
+            response.content,
+            vec![serde_json::json!({"type": "redacted_thinking", "data": "opaque"})]
+        );
+    }
+
+    #[test]
+    fn test_out_of_order_block_index_rejected() {
+        let mut accumulator = StreamAccumulator::new();
+        let start = serde_json::json!({
+            "type": "content_block_start",
+            "index": 4_000_000_000u64,
+            "content_block": {"type": "text", "text": ""}
+        });
+        assert!(matches!(
+            accumulator.handle_event(&start),
+            Err(StreamError::Parse(_))
+        ));
+        assert!(accumulator.finish().content.is_empty());
+    }
+
+    #[test]
+    fn test_tool_use_without_input_deltas_keeps_empty_input() {
+        let mut accumulator = StreamAccumulator::new();
+        let start = serde_json::json!({
+            "type": "content_block_start",
+            "index": 0,
+            "content_block": {"type": "tool_use", "id": "t", "name": "noop", "input": {}}
+        });
+        let stop = serde_json::json!({"type": "content_block_stop", "index": 0});
+        accumulator.handle_event(&start).unwrap();
+        accumulator.handle_event(&stop).unwrap();
+
+        let response = accumulator.finish();
+        assert_eq!(response.content[0]["input"], serde_json::json!({}));
+    }
+}
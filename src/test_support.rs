@@ -0,0 +1,178 @@
+//! 测试辅助：本地 HTTP 模拟服务器
+//!
+//! 按顺序返回预设的响应，并记录收到的请求，
+//! 用于在不访问真实 API 的情况下驱动 `ChatClient`。
+
+// 各测试只用到其中一部分辅助方法
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// 预设的 HTTP 响应
+#[derive(Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// 返回响应前的等待时间，用于模拟慢请求
+    pub delay: Option<Duration>,
+    /// 不返回响应直接关闭连接，用于模拟代理断开空闲连接
+    pub drop_connection: bool,
+}
+
+impl MockResponse {
+    /// 200 JSON 响应
+    pub fn json(body: serde_json::Value) -> Self {
+        Self::with_status(200, body.to_string())
+    }
+
+    /// 指定状态码的响应
+    pub fn with_status(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: body.into(),
+            delay: None,
+            drop_connection: false,
+        }
+    }
+
+    /// 读取请求后不返回任何响应，直接关闭连接
+    pub fn dropped() -> Self {
+        Self {
+            drop_connection: true,
+            ..Self::with_status(200, "")
+        }
+    }
+
+    /// 追加响应头
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// 等待 `delay` 后再返回响应
+    pub fn delayed(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+/// 收到的请求
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    /// 按名称（不区分大小写）获取请求头
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 把请求体解析为 JSON
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap_or(serde_json::Value::Null)
+    }
+}
+
+/// 模拟服务器
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockServer {
+    /// 启动服务器，依次返回 `responses`；用完后对后续请求返回最后一个响应
+    pub fn start(responses: Vec<MockResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&requests);
+
+        thread::spawn(move || {
+            let mut index = 0;
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                let Some(request) = read_request(&stream) else {
+                    continue;
+                };
+                recorded.lock().unwrap().push(request);
+
+                let response = &responses[index.min(responses.len() - 1)];
+                index += 1;
+                write_response(stream, response);
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// 已收到的请求
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Option<RecordedRequest> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let path = request_line.split_whitespace().nth(1)?.to_string();
+
+    let mut headers = Vec::new();
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim().to_string();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse().unwrap_or(0);
+            }
+            headers.push((name.to_string(), value));
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(RecordedRequest {
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+fn write_response(mut stream: TcpStream, response: &MockResponse) {
+    if let Some(delay) = response.delay {
+        thread::sleep(delay);
+    }
+    if response.drop_connection {
+        return;
+    }
+    let mut raw = format!("HTTP/1.1 {} Mock\r\n", response.status);
+    for (name, value) in &response.headers {
+        raw.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    raw.push_str(&format!(
+        "content-length: {}\r\nconnection: close\r\n\r\n{}",
+        response.body.len(),
+        response.body
+    ));
+    let _ = stream.write_all(raw.as_bytes());
+    let _ = stream.flush();
+}
@@ -0,0 +1,81 @@
+//! 单轮对话内的工具调用去重
+//!
+//! 模型陷入循环时可能在同一轮中反复发出完全相同的工具调用（名称和输入都相同）。
+//! 缓存只在一次 `send_message` 内有效，相同的只读调用直接返回之前的结果；
+//! 修改类工具（如 append_file、run_command）每次都真正执行、从不缓存，
+//! 执行后还会清空缓存，以免返回过期的读取结果。
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// 一轮对话内已执行的工具调用结果
+#[derive(Debug, Default)]
+pub struct ToolCallCache {
+    /// `(工具名, 输入的 JSON 文本)` -> 工具输出
+    entries: HashMap<(String, String), String>,
+}
+
+impl ToolCallCache {
+    /// 查找相同调用之前的结果
+    pub fn get(&self, name: &str, input: &Value) -> Option<&str> {
+        self.entries.get(&key(name, input)).map(String::as_str)
+    }
+
+    /// 记录一次调用的结果
+    ///
+    /// 失败的调用不缓存（可能是被用户拒绝或临时错误，重试应当真正执行）；
+    /// 修改类工具不缓存自身结果，只清空缓存，因为之前读到的内容可能已经变化。
+    pub fn record(&mut self, name: &str, input: &Value, output: &str, mutating: bool) {
+        if mutating {
+            self.entries.clear();
+            return;
+        }
+        let success = serde_json::from_str::<Value>(output)
+            .ok()
+            .and_then(|v| v.get("success").and_then(Value::as_bool))
+            .unwrap_or(false);
+        if success {
+            self.entries.insert(key(name, input), output.to_string());
+        }
+    }
+}
+
+/// 缓存键：serde_json 的对象按键排序，相同的输入总是序列化为相同的文本
+fn key(name: &str, input: &Value) -> (String, String) {
+    (name.to_string(), input.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const OK: &str = r#"{"success":true,"content":"a"}"#;
+
+    #[test]
+    fn test_identical_call_hits_regardless_of_key_order() {
+        let mut cache = ToolCallCache::default();
+        let input: Value = serde_json::from_str(r#"{"file_path":"a","start_line":1}"#).unwrap();
+        cache.record("read_file", &input, OK, false);
+
+        let reordered: Value = serde_json::from_str(r#"{"start_line":1,"file_path":"a"}"#).unwrap();
+        assert_eq!(cache.get("read_file", &reordered), Some(OK));
+        assert_eq!(cache.get("search", &reordered), None);
+        assert_eq!(cache.get("read_file", &json!({"file_path": "b"})), None);
+    }
+
+    #[test]
+    fn test_mutating_call_invalidates_and_failures_are_not_cached() {
+        let mut cache = ToolCallCache::default();
+        let read = json!({"file_path": "a"});
+        cache.record("read_file", &read, OK, false);
+
+        let write = json!({"file_path": "a", "content": "x"});
+        cache.record("write_file", &write, r#"{"success":true}"#, true);
+        assert_eq!(cache.get("read_file", &read), None);
+        assert_eq!(cache.get("write_file", &write), None);
+
+        cache.record("read_file", &read, r#"{"success":false}"#, false);
+        assert_eq!(cache.get("read_file", &read), None);
+    }
+}
@@ -0,0 +1,130 @@
+//! 工具调用审计日志
+//!
+//! 启用 `tool_log` 后，每次工具执行都会向 `.mentat/tool_log.jsonl` 追加一行 JSON，
+//! 记录时间、工具名、输入、是否成功以及（截断后的）输出，便于事后审计。
+
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 日志文件路径（相对于工作区）
+pub const TOOL_LOG_PATH: &str = ".mentat/tool_log.jsonl";
+
+/// 日志中的一行
+#[derive(Debug, Serialize)]
+struct ToolLogEntry<'a> {
+    /// Unix 毫秒时间戳
+    timestamp: u64,
+    tool: &'a str,
+    input: &'a Value,
+    success: bool,
+    truncated_output: String,
+}
+
+/// 追加写入的工具调用日志
+#[derive(Debug)]
+pub struct ToolLog {
+    path: PathBuf,
+    /// 输出保留的最大字节数
+    max_output_bytes: usize,
+}
+
+impl ToolLog {
+    pub fn new(path: PathBuf, max_output_bytes: usize) -> Self {
+        Self {
+            path,
+            max_output_bytes,
+        }
+    }
+
+    /// 记录一次工具调用
+    ///
+    /// `output` 是工具返回的 JSON 字符串，其中 `success` 为 true 才视为成功。
+    pub fn record(&self, tool: &str, input: &Value, output: &str) -> std::io::Result<()> {
+        let success = serde_json::from_str::<Value>(output)
+            .ok()
+            .and_then(|v| v.get("success").and_then(Value::as_bool))
+            .unwrap_or(false);
+        let entry = ToolLogEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            tool,
+            input,
+            success,
+            truncated_output: truncate(output, self.max_output_bytes),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)
+    }
+}
+
+/// 按字节截断（不拆开 UTF-8 字符），并注明原始长度
+fn truncate(s: &str, max_bytes: usize) -> String {
+    if s.len() <= max_bytes {
+        return s.to_string();
+    }
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...[truncated, {} bytes total]", &s[..end], s.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_appends_lines_and_truncates() {
+        let path = std::env::current_dir()
+            .unwrap()
+            .join("target/test_tool_log/tool_log.jsonl");
+        let _ = fs::remove_file(&path);
+        let log = ToolLog::new(path.clone(), 16);
+
+        log.record(
+            "read_file",
+            &serde_json::json!({"file_path": "a.txt"}),
+            r#"{"success":true,"content":"a long file content"}"#,
+        )
+        .unwrap();
+        log.record("search", &serde_json::json!({}), "not json")
+            .unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["tool"], "read_file");
+        assert_eq!(lines[0]["success"], true);
+        assert_eq!(lines[0]["input"]["file_path"], "a.txt");
+        assert!(lines[0]["truncated_output"]
+            .as_str()
+            .unwrap()
+            .ends_with("[truncated, 48 bytes total]"));
+        assert_eq!(lines[1]["success"], false);
+        assert_eq!(lines[1]["truncated_output"], "not json");
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_truncate_respects_char_boundary() {
+        assert_eq!(truncate("短文本", 100), "短文本");
+        assert!(truncate("中文内容", 4).starts_with("中..."));
+    }
+}
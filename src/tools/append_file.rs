@@ -0,0 +1,197 @@
+//! append_file 工具 - 在文件末尾追加内容
+
+use super::{to_json, Tool, ToolContext};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+/// append_file 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct AppendFileInput {
+    pub file_path: String,
+    pub content: String,
+}
+
+/// append_file 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct AppendFileOutput {
+    pub success: bool,
+    /// 追加后文件的总字节数
+    pub size: Option<u64>,
+    pub message: Option<String>,
+    pub error: Option<String>,
+}
+
+impl AppendFileOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            size: None,
+            message: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// AppendFile 工具实现
+pub struct AppendFileTool;
+
+impl Tool for AppendFileTool {
+    fn name(&self) -> &'static str {
+        "append_file"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "append_file",
+            "description": "Append content to the end of a file, creating the file and any missing parent directories if needed. Use this for logs, changelogs and adding entries to existing files without rewriting them. Returns the new total size.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The path to the file to append to (relative to the workspace)"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "The content to append. Include a trailing newline if the file is line-based."
+                    }
+                },
+                "required": ["file_path", "content"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: AppendFileInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&AppendFileOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = execute_append_file(ctx, &tool_input);
+        to_json(&result)
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn supports_dry_run(&self) -> bool {
+        true
+    }
+}
+
+/// 执行追加写入
+fn execute_append_file(ctx: &ToolContext, input: &AppendFileInput) -> AppendFileOutput {
+    let validator = ctx.validator();
+
+    // 安全检查：验证路径
+    let validated_path = match validator.validate_for_write(&input.file_path) {
+        Ok(p) => p,
+        Err(e) => return AppendFileOutput::failure(e.to_string()),
+    };
+    if validated_path.is_dir() {
+        return AppendFileOutput::failure(format!("Is a directory: {}", input.file_path));
+    }
+
+    // dry-run：只报告将要追加的内容
+    if ctx.is_dry_run() {
+        let existing = fs::metadata(&validated_path).map_or(0, |m| m.len());
+        return AppendFileOutput {
+            success: true,
+            size: Some(existing + input.content.len() as u64),
+            message: Some(format!(
+                "[DRY RUN] Would append {} bytes to {}; nothing was written",
+                input.content.len(),
+                input.file_path
+            )),
+            error: None,
+        };
+    }
+
+    // 确保父目录存在
+    if let Some(parent) = validated_path.parent() {
+        if !parent.as_os_str().is_empty() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                return AppendFileOutput::failure(format!("Failed to create directory: {}", e));
+            }
+        }
+    }
+
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&validated_path)
+        .and_then(|mut file| {
+            file.write_all(input.content.as_bytes())?;
+            file.metadata()
+        });
+
+    match result {
+        Ok(metadata) => AppendFileOutput {
+            success: true,
+            size: Some(metadata.len()),
+            message: Some(format!(
+                "Appended {} bytes to {} ({} bytes total)",
+                input.content.len(),
+                input.file_path,
+                metadata.len()
+            )),
+            error: None,
+        },
+        Err(e) => AppendFileOutput::failure(format!("Failed to append to file: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+
+    #[test]
+    fn test_append_twice_accumulates() {
+        let root = "target/test_append_file";
+        let _ = fs::remove_dir_all(root);
+        let path = "target/test_append_file/logs/changes.log";
+
+        let input = serde_json::json!({"file_path": path, "content": "first\n"});
+        let result: Value =
+            serde_json::from_str(&AppendFileTool.execute(&test_context(), &input)).unwrap();
+        assert_eq!(result["success"], true);
+        assert_eq!(result["size"], 6);
+
+        let input = serde_json::json!({"file_path": path, "content": "second\n"});
+        let result: Value =
+            serde_json::from_str(&AppendFileTool.execute(&test_context(), &input)).unwrap();
+        assert_eq!(result["size"], 13);
+        assert_eq!(fs::read_to_string(path).unwrap(), "first\nsecond\n");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_dry_run_does_not_write() {
+        let path = "target/test_append_dry_run.txt";
+        fs::create_dir_all("target").unwrap();
+        fs::write(path, "abc").unwrap();
+
+        let mut ctx = test_context();
+        ctx.dry_run = true;
+        let input = serde_json::json!({"file_path": path, "content": "def"});
+        let result: Value = serde_json::from_str(&AppendFileTool.execute(&ctx, &input)).unwrap();
+        assert_eq!(result["success"], true);
+        assert_eq!(result["size"], 6);
+        assert!(result["message"].as_str().unwrap().starts_with("[DRY RUN]"));
+        assert_eq!(fs::read_to_string(path).unwrap(), "abc");
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_path_traversal_blocked() {
+        let input = serde_json::json!({"file_path": "../outside.log", "content": "x"});
+        let result = AppendFileTool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":false"));
+    }
+}
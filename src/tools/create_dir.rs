@@ -0,0 +1,146 @@
+//! create_dir 工具 - 创建目录（含所有父目录）
+
+use super::{to_json, Tool, ToolContext};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+
+/// create_dir 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct CreateDirInput {
+    pub path: String,
+}
+
+/// create_dir 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct CreateDirOutput {
+    pub success: bool,
+    pub message: Option<String>,
+    pub error: Option<String>,
+}
+
+impl CreateDirOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            message: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// CreateDir 工具实现
+pub struct CreateDirTool;
+
+impl Tool for CreateDirTool {
+    fn name(&self) -> &'static str {
+        "create_dir"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "create_dir",
+            "description": "Create a directory, including any missing parent directories. Succeeds if the directory already exists. Use this to scaffold empty project layouts.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The directory to create (relative to the workspace)"
+                    }
+                },
+                "required": ["path"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: CreateDirInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&CreateDirOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = execute_create_dir(ctx, &tool_input);
+        to_json(&result)
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+}
+
+/// 执行目录创建
+fn execute_create_dir(ctx: &ToolContext, input: &CreateDirInput) -> CreateDirOutput {
+    let validator = ctx.validator();
+
+    // 安全检查：验证路径
+    let validated_path = match validator.validate_for_write(&input.path) {
+        Ok(p) => p,
+        Err(e) => return CreateDirOutput::failure(e.to_string()),
+    };
+
+    if validated_path.is_dir() {
+        return CreateDirOutput {
+            success: true,
+            message: Some(format!("Directory already exists: {}", input.path)),
+            error: None,
+        };
+    }
+    if validated_path.exists() {
+        return CreateDirOutput::failure(format!("A file already exists at {}", input.path));
+    }
+
+    match fs::create_dir_all(&validated_path) {
+        Ok(()) => CreateDirOutput {
+            success: true,
+            message: Some(format!("Created directory {}", input.path)),
+            error: None,
+        },
+        Err(e) => CreateDirOutput::failure(format!("Failed to create directory: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+
+    #[test]
+    fn test_create_nested_dir_is_idempotent() {
+        let root = "target/test_create_dir";
+        let _ = fs::remove_dir_all(root);
+        let input = serde_json::json!({"path": "target/test_create_dir/nested/dir"});
+
+        let result = CreateDirTool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":true"));
+        assert!(result.contains("Created directory"));
+        assert!(std::path::Path::new("target/test_create_dir/nested/dir").is_dir());
+
+        // 再次创建同一目录仍然成功
+        let result = CreateDirTool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":true"));
+        assert!(result.contains("already exists"));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_existing_file_is_error() {
+        let path = "target/test_create_dir_file.txt";
+        fs::create_dir_all("target").unwrap();
+        fs::write(path, "").unwrap();
+
+        let result = CreateDirTool.execute(&test_context(), &serde_json::json!({"path": path}));
+        assert!(result.contains("\"success\":false"));
+        assert!(result.contains("file already exists"));
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_path_traversal_blocked() {
+        let result =
+            CreateDirTool.execute(&test_context(), &serde_json::json!({"path": "../outside"}));
+        assert!(result.contains("traversal") || result.contains("not allowed"));
+    }
+}
@@ -0,0 +1,304 @@
+//! delete_file 工具 - 把文件移入回收站而不是直接删除
+//!
+//! 被删除的文件移动到 `.mentat/trash/<时间戳>/<相对路径>`，
+//! 误删后可以通过 `/restore <时间戳>` 或手动移回原处恢复。
+
+use super::{to_json, Tool, ToolContext};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 回收站目录（相对于工作区）
+pub const TRASH_DIR: &str = ".mentat/trash";
+
+/// delete_file 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct DeleteFileInput {
+    pub file_path: String,
+}
+
+/// delete_file 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct DeleteFileOutput {
+    pub success: bool,
+    /// 文件在回收站中的位置
+    pub trash_path: Option<String>,
+    pub message: Option<String>,
+    pub error: Option<String>,
+}
+
+impl DeleteFileOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            trash_path: None,
+            message: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// DeleteFile 工具实现
+pub struct DeleteFileTool;
+
+impl Tool for DeleteFileTool {
+    fn name(&self) -> &'static str {
+        "delete_file"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "delete_file",
+            "description": "Delete a file by moving it to the workspace trash (.mentat/trash), where the user can restore it. Only regular files can be deleted.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The path to the file to delete (relative to the workspace)"
+                    }
+                },
+                "required": ["file_path"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: DeleteFileInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&DeleteFileOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = execute_delete_file(ctx, &tool_input);
+        to_json(&result)
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+}
+
+/// 移动文件；跨文件系统无法重命名时退化为复制后删除
+pub(super) fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+/// 执行文件删除
+fn execute_delete_file(ctx: &ToolContext, input: &DeleteFileInput) -> DeleteFileOutput {
+    let validator = ctx.validator();
+
+    // 安全检查：文件必须存在且在工作区内，并且允许写入
+    let validated_path = match validator
+        .validate_for_read(&input.file_path)
+        .and_then(|_| validator.validate_for_write(&input.file_path))
+    {
+        Ok(p) => p,
+        Err(e) => return DeleteFileOutput::failure(e.to_string()),
+    };
+    if !validated_path.is_file() {
+        return DeleteFileOutput::failure(format!("Not a regular file: {}", input.file_path));
+    }
+
+    // 按规范化后的路径判断，`.mentat//trash` 之类的写法不能绕过回收站检查
+    let root = ctx.workspace_root();
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let Ok(relative) = validated_path.strip_prefix(&root) else {
+        return DeleteFileOutput::failure(format!(
+            "Path is outside the workspace: {}",
+            input.file_path
+        ));
+    };
+    if relative.starts_with(TRASH_DIR) {
+        return DeleteFileOutput::failure("Files in the trash cannot be deleted".to_string());
+    }
+    let relative = relative.to_string_lossy().replace('\\', "/");
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let trash_path = format!("{}/{}/{}", TRASH_DIR, timestamp, relative);
+
+    match move_file(&validated_path, &ctx.workspace_root().join(&trash_path)) {
+        Ok(()) => DeleteFileOutput {
+            success: true,
+            message: Some(format!(
+                "Moved {} to trash; restore with /restore {}",
+                input.file_path, timestamp
+            )),
+            trash_path: Some(trash_path),
+            error: None,
+        },
+        Err(e) => DeleteFileOutput::failure(format!("Failed to move file to trash: {}", e)),
+    }
+}
+
+/// 回收站中的删除批次（时间戳），从新到旧
+pub fn trash_batches(workspace_root: &Path) -> Vec<String> {
+    let mut batches: Vec<String> = fs::read_dir(workspace_root.join(TRASH_DIR))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    batches.sort_by(|a, b| b.cmp(a));
+    batches
+}
+
+/// 把一个批次中的文件移回原处
+///
+/// # Returns
+/// 恢复的文件路径
+///
+/// # Errors
+/// 批次不存在，或原位置已有同名文件（不会覆盖）
+pub fn restore_batch(workspace_root: &Path, batch: &str) -> Result<Vec<String>, String> {
+    if batch.is_empty() || !batch.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid trash entry: {}", batch));
+    }
+    let batch_dir = workspace_root.join(TRASH_DIR).join(batch);
+    if !batch_dir.is_dir() {
+        return Err(format!("Trash entry not found: {}", batch));
+    }
+
+    let mut files = Vec::new();
+    collect_files(&batch_dir, &batch_dir, &mut files);
+    files.sort();
+
+    if let Some(existing) = files.iter().find(|f| workspace_root.join(f).exists()) {
+        return Err(format!("{} already exists; move it away first", existing));
+    }
+
+    for file in &files {
+        move_file(&batch_dir.join(file), &workspace_root.join(file))
+            .map_err(|e| format!("Failed to restore {}: {}", file, e))?;
+    }
+    let _ = fs::remove_dir_all(&batch_dir);
+    Ok(files)
+}
+
+/// 收集目录下所有文件相对于 `base` 的路径
+fn collect_files(base: &Path, dir: &Path, files: &mut Vec<String>) {
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(base, &path, files);
+        } else if let Ok(relative) = path.strip_prefix(base) {
+            files.push(relative.to_string_lossy().replace('\\', "/"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+    use std::path::PathBuf;
+
+    fn delete(path: &str) -> Value {
+        delete_in(&test_context(), path)
+    }
+
+    fn delete_in(ctx: &ToolContext, path: &str) -> Value {
+        let input = serde_json::json!({"file_path": path});
+        serde_json::from_str(&DeleteFileTool.execute(ctx, &input)).unwrap()
+    }
+
+    /// 以临时目录为工作区，避免在仓库中留下回收站
+    fn temp_workspace(name: &str) -> (PathBuf, ToolContext) {
+        let root = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        (root.clone(), ToolContext::new(root))
+    }
+
+    #[test]
+    fn test_delete_moves_file_to_trash_and_restores() {
+        let (root, ctx) = temp_workspace("mentat_test_delete_file");
+        let path = "docs/notes.txt";
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join(path), "keep me").unwrap();
+
+        let result = delete_in(&ctx, path);
+        assert_eq!(result["success"], true);
+        assert!(!root.join(path).exists());
+
+        let trash_path = result["trash_path"].as_str().unwrap();
+        assert!(trash_path.starts_with(TRASH_DIR));
+        assert!(trash_path.ends_with(path));
+        assert_eq!(
+            fs::read_to_string(root.join(trash_path)).unwrap(),
+            "keep me"
+        );
+
+        // 从回收站恢复
+        let batch = trash_path
+            .trim_start_matches(TRASH_DIR)
+            .split('/')
+            .nth(1)
+            .unwrap();
+        assert_eq!(restore_batch(&root, batch).unwrap(), vec![path.to_string()]);
+        assert_eq!(fs::read_to_string(root.join(path)).unwrap(), "keep me");
+        assert!(!root.join(trash_path).exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_delete_rejects_trash_paths_in_any_spelling() {
+        let (root, ctx) = temp_workspace("mentat_test_delete_trash");
+        fs::create_dir_all(root.join(TRASH_DIR).join("1")).unwrap();
+        fs::write(root.join(TRASH_DIR).join("1/x"), "old").unwrap();
+
+        for path in [
+            ".mentat/trash/1/x",
+            "./.mentat/trash/1/x",
+            ".mentat//trash/1/x",
+            "./.mentat/./trash/1/x",
+        ] {
+            let result = delete_in(&ctx, path);
+            assert_eq!(result["success"], false, "{}", path);
+            assert_eq!(result["error"], "Files in the trash cannot be deleted");
+        }
+        assert!(root.join(TRASH_DIR).join("1/x").exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_delete_missing_file_fails() {
+        let result = delete("target/test_delete_missing.txt");
+        assert_eq!(result["success"], false);
+    }
+
+    #[test]
+    fn test_delete_directory_rejected() {
+        fs::create_dir_all("target/test_delete_dir").unwrap();
+        let result = delete("target/test_delete_dir");
+        assert_eq!(result["success"], false);
+        assert!(Path::new("target/test_delete_dir").is_dir());
+    }
+
+    #[test]
+    fn test_restore_rejects_invalid_batch() {
+        let root = std::env::current_dir().unwrap();
+        assert!(restore_batch(&root, "../etc").is_err());
+        assert!(restore_batch(&root, "1").is_err());
+    }
+
+    #[test]
+    fn test_path_traversal_blocked() {
+        let result = delete("../etc/passwd");
+        assert_eq!(result["success"], false);
+    }
+}
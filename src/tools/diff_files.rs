@@ -0,0 +1,244 @@
+//! diff_files 工具 - 比较两个文件，或比较文件与给定内容
+
+use super::read_file::decode_text;
+use super::{to_json, Tool, ToolContext};
+use crate::staging::DIFF_CONTEXT_LINES;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::{ChangeTag, TextDiff};
+use std::fs;
+
+/// 返回的 diff 的最大字节数，超过时截断
+const MAX_DIFF_BYTES: usize = 32 * 1024;
+
+/// 两侧内容合计超过该字节数时不计算 diff
+const MAX_DIFF_INPUT_BYTES: usize = 2 * 1024 * 1024;
+
+/// diff_files 工具的输入参数
+///
+/// 比较两个文件时使用 `left`/`right`；比较文件与内容时使用 `left_path`/`right_content`。
+#[derive(Debug, Deserialize)]
+pub struct DiffFilesInput {
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub left_path: Option<String>,
+    pub right_content: Option<String>,
+}
+
+/// diff_files 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct DiffFilesOutput {
+    pub success: bool,
+    /// unified diff，两侧相同时为空字符串
+    pub diff: Option<String>,
+    pub added: usize,
+    pub removed: usize,
+    /// diff 超过大小上限被截断
+    pub truncated: bool,
+    pub error: Option<String>,
+}
+
+impl DiffFilesOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            diff: None,
+            added: 0,
+            removed: 0,
+            truncated: false,
+            error: Some(error),
+        }
+    }
+}
+
+/// DiffFiles 工具实现
+pub struct DiffFilesTool;
+
+impl Tool for DiffFilesTool {
+    fn name(&self) -> &'static str {
+        "diff_files"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "diff_files",
+            "description": "Show a unified diff between two files, or between a file and proposed content, without reading both. Pass left and right to compare two files, or left_path and right_content to compare a file against inline content. Returns the diff with added/removed line counts; large diffs are truncated.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "left": {
+                        "type": "string",
+                        "description": "The original file to compare (use with right)"
+                    },
+                    "right": {
+                        "type": "string",
+                        "description": "The changed file to compare (use with left)"
+                    },
+                    "left_path": {
+                        "type": "string",
+                        "description": "The original file to compare (use with right_content)"
+                    },
+                    "right_content": {
+                        "type": "string",
+                        "description": "Proposed content to compare against left_path"
+                    }
+                }
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: DiffFilesInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&DiffFilesOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = execute_diff_files(ctx, &tool_input);
+        to_json(&result)
+    }
+}
+
+/// 执行比较
+fn execute_diff_files(ctx: &ToolContext, input: &DiffFilesInput) -> DiffFilesOutput {
+    let sides = match input {
+        DiffFilesInput {
+            left: Some(left),
+            right: Some(right),
+            left_path: None,
+            right_content: None,
+        } => read_text(ctx, left).and_then(|original| {
+            read_text(ctx, right)
+                .map(|modified| (original, modified, left.as_str(), right.as_str()))
+        }),
+        DiffFilesInput {
+            left: None,
+            right: None,
+            left_path: Some(left),
+            right_content: Some(content),
+        } => read_text(ctx, left)
+            .map(|original| (original, content.clone(), left.as_str(), "proposed")),
+        _ => Err("Provide either left and right, or left_path and right_content".to_string()),
+    };
+    let (original, modified, left_name, right_name) = match sides {
+        Ok(sides) => sides,
+        Err(e) => return DiffFilesOutput::failure(e),
+    };
+
+    if original.len() + modified.len() > MAX_DIFF_INPUT_BYTES {
+        return DiffFilesOutput::failure(format!(
+            "Inputs too large to diff ({} bytes, limit {})",
+            original.len() + modified.len(),
+            MAX_DIFF_INPUT_BYTES
+        ));
+    }
+
+    let diff = TextDiff::from_lines(&original, &modified);
+    let (mut added, mut removed) = (0, 0);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => added += 1,
+            ChangeTag::Delete => removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    let unified = diff
+        .unified_diff()
+        .context_radius(DIFF_CONTEXT_LINES)
+        .header(&format!("a/{}", left_name), &format!("b/{}", right_name))
+        .to_string();
+    let (unified, truncated) = truncate_diff(unified);
+
+    DiffFilesOutput {
+        success: true,
+        diff: Some(unified),
+        added,
+        removed,
+        truncated,
+        error: None,
+    }
+}
+
+/// 校验路径并以文本读取文件
+fn read_text(ctx: &ToolContext, path: &str) -> Result<String, String> {
+    let validated = ctx
+        .validator()
+        .validate_for_read(path)
+        .map_err(|e| e.to_string())?;
+    let bytes = fs::read(&validated).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    decode_text(bytes).map_err(|len| format!("{} appears to be binary ({} bytes)", path, len))
+}
+
+/// 超过 [`MAX_DIFF_BYTES`] 时在行边界截断
+fn truncate_diff(diff: String) -> (String, bool) {
+    if diff.len() <= MAX_DIFF_BYTES {
+        return (diff, false);
+    }
+    let end = diff[..MAX_DIFF_BYTES].rfind('\n').map_or(0, |i| i + 1);
+    (format!("{}...[diff truncated]\n", &diff[..end]), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+
+    const FIXTURE_DIR: &str = "target/test_diff_files";
+
+    fn write_fixture(name: &str, content: &str) -> String {
+        fs::create_dir_all(FIXTURE_DIR).unwrap();
+        let path = format!("{}/{}", FIXTURE_DIR, name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn run(input: Value) -> Value {
+        serde_json::from_str(&DiffFilesTool.execute(&test_context(), &input)).unwrap()
+    }
+
+    #[test]
+    fn test_diff_two_files() {
+        let left = write_fixture("left.txt", "one\ntwo\nthree\n");
+        let right = write_fixture("right.txt", "one\nTWO\nthree\nfour\n");
+
+        let result = run(serde_json::json!({"left": left, "right": right}));
+        assert_eq!(result["success"], true);
+        assert_eq!(result["added"], 2);
+        assert_eq!(result["removed"], 1);
+        let diff = result["diff"].as_str().unwrap();
+        assert!(diff.contains(&format!("--- a/{}", left)));
+        assert!(diff.contains("\n-two\n"));
+        assert!(diff.contains("\n+TWO\n"));
+        assert!(diff.contains("\n+four\n"));
+        assert!(diff.contains("\n three\n"));
+    }
+
+    #[test]
+    fn test_diff_file_against_content() {
+        let left = write_fixture("inline.txt", "a\nb\n");
+
+        let result = run(serde_json::json!({"left_path": left, "right_content": "a\nb\n"}));
+        assert_eq!(result["success"], true);
+        assert_eq!(result["diff"], "");
+
+        let result = run(serde_json::json!({"left_path": left, "right_content": "a\n"}));
+        assert_eq!(result["removed"], 1);
+        assert!(result["diff"].as_str().unwrap().contains("+++ b/proposed"));
+    }
+
+    #[test]
+    fn test_invalid_inputs_rejected() {
+        let result = run(serde_json::json!({"left": "Cargo.toml"}));
+        assert_eq!(result["success"], false);
+
+        let result = run(serde_json::json!({"left": "../outside", "right": "Cargo.toml"}));
+        assert_eq!(result["success"], false);
+    }
+
+    #[test]
+    fn test_large_diff_truncated() {
+        let (diff, truncated) = truncate_diff("+line\n".repeat(MAX_DIFF_BYTES));
+        assert!(truncated);
+        assert!(diff.len() <= MAX_DIFF_BYTES + 32);
+        assert!(diff.ends_with("+line\n...[diff truncated]\n"));
+    }
+}
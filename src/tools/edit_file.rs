@@ -0,0 +1,326 @@
+//! edit_file 工具 - 对文件做精确的字符串替换
+
+use super::{to_json, Tool, ToolContext};
+use crate::staging::render_unified_diff;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+
+/// 默认期望的匹配次数
+const DEFAULT_EXPECTED_COUNT: u32 = 1;
+
+/// edit_file 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct EditFileInput {
+    pub file_path: String,
+    pub old_string: String,
+    pub new_string: String,
+    pub expected_count: Option<u32>,
+}
+
+/// edit_file 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct EditFileOutput {
+    pub success: bool,
+    pub replacements: Option<u32>,
+    pub message: Option<String>,
+    /// dry-run 模式下的 unified diff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    pub error: Option<String>,
+}
+
+impl EditFileOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            replacements: None,
+            message: None,
+            diff: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// EditFile 工具实现
+pub struct EditFileTool;
+
+impl Tool for EditFileTool {
+    fn name(&self) -> &'static str {
+        "edit_file"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "edit_file",
+            "description": "Replace an exact string in an existing file. The old_string must occur exactly expected_count times (default 1); include enough surrounding context to make it unique. Prefer this over write_file for targeted changes.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "file_path": {
+                        "type": "string",
+                        "description": "The path to the file to edit (relative to the workspace)"
+                    },
+                    "old_string": {
+                        "type": "string",
+                        "description": "The exact text to replace"
+                    },
+                    "new_string": {
+                        "type": "string",
+                        "description": "The text to replace it with"
+                    },
+                    "expected_count": {
+                        "type": "integer",
+                        "description": "How many times old_string is expected to occur (default 1)"
+                    }
+                },
+                "required": ["file_path", "old_string", "new_string"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: EditFileInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&EditFileOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = execute_edit_file(ctx, &tool_input);
+        to_json(&result)
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn supports_dry_run(&self) -> bool {
+        true
+    }
+}
+
+/// 在内容中执行替换，校验匹配次数
+///
+/// # Returns
+/// 替换后的内容和实际替换次数
+///
+/// # Errors
+/// `old_string` 为空，或出现次数与 `expected_count` 不一致
+pub fn apply_edit(
+    content: &str,
+    old_string: &str,
+    new_string: &str,
+    expected_count: Option<u32>,
+) -> Result<(String, u32), String> {
+    if old_string.is_empty() {
+        return Err("old_string must not be empty".to_string());
+    }
+
+    let expected = expected_count.unwrap_or(DEFAULT_EXPECTED_COUNT);
+    let found = content.matches(old_string).count() as u32;
+
+    if found == 0 {
+        return Err("old_string not found in file".to_string());
+    }
+
+    if found != expected {
+        return Err(format!(
+            "old_string found {} times but expected {}; add more surrounding context to make it unique or set expected_count",
+            found, expected
+        ));
+    }
+
+    Ok((content.replace(old_string, new_string), found))
+}
+
+/// 执行文件编辑
+fn execute_edit_file(ctx: &ToolContext, input: &EditFileInput) -> EditFileOutput {
+    let validator = ctx.validator();
+
+    // 安全检查：文件必须存在且可读，同时允许写入
+    let validated_path = match validator
+        .validate_for_read(&input.file_path)
+        .and_then(|_| validator.validate_for_write(&input.file_path))
+    {
+        Ok(p) => p,
+        Err(e) => return EditFileOutput::failure(e.to_string()),
+    };
+
+    let content = match fs::read_to_string(&validated_path) {
+        Ok(c) => c,
+        Err(e) => return EditFileOutput::failure(format!("Failed to read file: {}", e)),
+    };
+
+    let (updated, replacements) = match apply_edit(
+        &content,
+        &input.old_string,
+        &input.new_string,
+        input.expected_count,
+    ) {
+        Ok(result) => result,
+        Err(e) => return EditFileOutput::failure(e),
+    };
+
+    // dry-run：只报告将要进行的替换
+    if ctx.is_dry_run() {
+        return EditFileOutput {
+            success: true,
+            replacements: Some(replacements),
+            message: Some(format!(
+                "[DRY RUN] Would replace {} occurrence(s) in {} ({} -> {} bytes); nothing was written",
+                replacements,
+                input.file_path,
+                content.len(),
+                updated.len()
+            )),
+            diff: Some(render_unified_diff(&content, &updated, &input.file_path)),
+            error: None,
+        };
+    }
+
+    match fs::write(&validated_path, updated) {
+        Ok(()) => EditFileOutput {
+            success: true,
+            replacements: Some(replacements),
+            message: Some(format!(
+                "Replaced {} occurrence(s) in {}",
+                replacements, input.file_path
+            )),
+            diff: None,
+            error: None,
+        },
+        Err(e) => EditFileOutput::failure(format!("Failed to write file: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+
+    fn write_fixture(path: &str, content: &str) {
+        fs::create_dir_all("target").unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_edit_single_match() {
+        let path = "target/test_edit_single.txt";
+        write_fixture(path, "fn main() {\n    old();\n}\n");
+
+        let tool = EditFileTool;
+        let input = serde_json::json!({
+            "file_path": path,
+            "old_string": "old();",
+            "new_string": "new();"
+        });
+        let result = tool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":true"));
+        assert!(result.contains("\"replacements\":1"));
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "fn main() {\n    new();\n}\n"
+        );
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_dry_run_leaves_file_unchanged() {
+        let path = "target/test_edit_dry_run.txt";
+        write_fixture(path, "fn main() {\n    old();\n}\n");
+
+        let mut ctx = test_context();
+        ctx.dry_run = true;
+        let input = serde_json::json!({
+            "file_path": path,
+            "old_string": "old();",
+            "new_string": "new();"
+        });
+        let result: Value = serde_json::from_str(&EditFileTool.execute(&ctx, &input)).unwrap();
+
+        assert_eq!(result["success"], true);
+        assert_eq!(result["replacements"], 1);
+        assert!(result["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("[DRY RUN] Would replace 1 occurrence(s)"));
+        let diff = result["diff"].as_str().unwrap();
+        assert!(diff.contains("-    old();"));
+        assert!(diff.contains("+    new();"));
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "fn main() {\n    old();\n}\n"
+        );
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_edit_ambiguous_match_rejected() {
+        let path = "target/test_edit_ambiguous.txt";
+        write_fixture(path, "x = 1;\nx = 1;\n");
+
+        let tool = EditFileTool;
+        let input = serde_json::json!({
+            "file_path": path,
+            "old_string": "x = 1;",
+            "new_string": "x = 2;"
+        });
+        let result = tool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":false"));
+        assert!(result.contains("found 2 times but expected 1"));
+        // 文件保持不变
+        assert_eq!(fs::read_to_string(path).unwrap(), "x = 1;\nx = 1;\n");
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_edit_zero_match_rejected() {
+        let path = "target/test_edit_zero.txt";
+        write_fixture(path, "hello\n");
+
+        let tool = EditFileTool;
+        let input = serde_json::json!({
+            "file_path": path,
+            "old_string": "missing",
+            "new_string": "found"
+        });
+        let result = tool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":false"));
+        assert!(result.contains("not found"));
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_edit_expected_count() {
+        let (updated, count) = apply_edit("a a a", "a", "b", Some(3)).unwrap();
+        assert_eq!(updated, "b b b");
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_edit_nonexistent_file() {
+        let tool = EditFileTool;
+        let input = serde_json::json!({
+            "file_path": "target/does_not_exist_edit.txt",
+            "old_string": "a",
+            "new_string": "b"
+        });
+        let result = tool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":false"));
+    }
+
+    #[test]
+    fn test_path_traversal_blocked() {
+        let tool = EditFileTool;
+        let input = serde_json::json!({
+            "file_path": "../etc/passwd",
+            "old_string": "root",
+            "new_string": "evil"
+        });
+        let result = tool.execute(&test_context(), &input);
+        assert!(result.contains("traversal") || result.contains("not allowed"));
+    }
+}
@@ -0,0 +1,579 @@
+//! fetch_url 工具 - 读取网页或文档的文本内容
+//!
+//! 只允许 http/https，拒绝 localhost、私有和链路本地地址（包括重定向的目标），
+//! 避免模型借助该工具访问内网服务（SSRF）。HTML 会转换为纯文本后再返回。
+//!
+//! 请求前的检查只用于给出明确的错误信息；真正建立连接时由 [`PublicResolver`]
+//! 再解析一次并过滤掉受限地址，连接使用的就是检查过的地址，DNS rebinding
+//! （检查时解析到公网地址、连接时解析到 127.0.0.1）无法绕过。
+
+use super::{to_json, Tool, ToolContext};
+use regex::Regex;
+use reqwest::blocking::Client;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::{Attempt, Policy};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, LazyLock, OnceLock};
+use std::time::Duration;
+
+/// 默认返回的最大字节数
+const DEFAULT_MAX_BYTES: usize = 64 * 1024;
+
+/// `max_bytes` 的上限
+const MAX_MAX_BYTES: usize = 1024 * 1024;
+
+/// 最多下载的原始字节数（HTML 转换为文本前）
+const MAX_DOWNLOAD_BYTES: u64 = 5 * 1024 * 1024;
+
+/// 请求超时
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 最多跟随的重定向次数
+const MAX_REDIRECTS: usize = 5;
+
+/// 不显示的元素和注释
+static HTML_HIDDEN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<script\b.*?</script\s*>|<style\b.*?</style\s*>|<noscript\b.*?</noscript\s*>|<!--.*?-->")
+        .unwrap()
+});
+
+/// 换行的块级元素
+static HTML_BREAK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)<(?:br|hr|/?p|/?div|/?h[1-6]|/?tr|/?pre|/?ul|/?ol|/?table|/?section|/?article|/?blockquote)\b[^>]*>")
+        .unwrap()
+});
+
+/// 列表项
+static HTML_LIST_ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<li\b[^>]*>").unwrap());
+
+/// 其余标签
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// 字符实体
+static HTML_ENTITY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap());
+
+/// fetch_url 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct FetchUrlInput {
+    pub url: String,
+    pub max_bytes: Option<usize>,
+}
+
+/// fetch_url 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct FetchUrlOutput {
+    pub success: bool,
+    /// 跟随重定向后的最终地址
+    pub url: Option<String>,
+    pub status: Option<u16>,
+    pub content_type: Option<String>,
+    pub content: Option<String>,
+    /// 内容超过 `max_bytes` 被截断
+    pub truncated: bool,
+    pub error: Option<String>,
+}
+
+impl FetchUrlOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            url: None,
+            status: None,
+            content_type: None,
+            content: None,
+            truncated: false,
+            error: Some(error),
+        }
+    }
+}
+
+/// FetchUrl 工具实现
+pub struct FetchUrlTool {
+    /// 首次使用时创建，之后的请求复用连接
+    client: OnceLock<Client>,
+    /// 允许访问本机和私有地址（仅测试使用）
+    allow_private: bool,
+}
+
+impl FetchUrlTool {
+    pub fn new() -> Self {
+        Self {
+            client: OnceLock::new(),
+            allow_private: false,
+        }
+    }
+
+    /// 允许访问本机地址，用于对本地模拟服务器测试
+    #[cfg(test)]
+    fn allowing_private() -> Self {
+        Self {
+            allow_private: true,
+            ..Self::new()
+        }
+    }
+
+    fn client(&self) -> Result<&Client, String> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
+        }
+        let allow_private = self.allow_private;
+        // 每一跳重定向都重新检查目标地址
+        let policy = Policy::custom(move |attempt: Attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(format!("Too many redirects (limit {})", MAX_REDIRECTS));
+            }
+            match check_url(attempt.url(), allow_private) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        });
+        let mut builder = Client::builder().timeout(FETCH_TIMEOUT).redirect(policy);
+        if !allow_private {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = builder
+            .user_agent(concat!("mentat-code/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        Ok(self.client.get_or_init(|| client))
+    }
+}
+
+impl Tool for FetchUrlTool {
+    fn name(&self) -> &'static str {
+        "fetch_url"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "fetch_url",
+            "description": "Fetch a public http(s) URL and return its text content, e.g. API documentation or a page linked from an error message. HTML is converted to plain text. Local and private network addresses are blocked. Content beyond max_bytes is truncated.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "The http or https URL to fetch"
+                    },
+                    "max_bytes": {
+                        "type": "integer",
+                        "description": format!(
+                            "Maximum bytes of content to return (default {}, max {})",
+                            DEFAULT_MAX_BYTES, MAX_MAX_BYTES
+                        )
+                    }
+                },
+                "required": ["url"]
+            }
+        })
+    }
+
+    fn execute(&self, _ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: FetchUrlInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&FetchUrlOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = self.fetch(&tool_input);
+        to_json(&result)
+    }
+}
+
+impl FetchUrlTool {
+    /// 执行请求
+    fn fetch(&self, input: &FetchUrlInput) -> FetchUrlOutput {
+        let max_bytes = input.max_bytes.unwrap_or(DEFAULT_MAX_BYTES);
+        if max_bytes == 0 || max_bytes > MAX_MAX_BYTES {
+            return FetchUrlOutput::failure(format!(
+                "max_bytes must be between 1 and {}",
+                MAX_MAX_BYTES
+            ));
+        }
+        let url = match Url::parse(&input.url) {
+            Ok(url) => url,
+            Err(e) => return FetchUrlOutput::failure(format!("Invalid URL: {}", e)),
+        };
+        if let Err(e) = check_url(&url, self.allow_private) {
+            return FetchUrlOutput::failure(e);
+        }
+
+        let client = match self.client() {
+            Ok(client) => client,
+            Err(e) => return FetchUrlOutput::failure(e),
+        };
+        let response = match client.get(url).send() {
+            Ok(response) => response,
+            Err(e) => return FetchUrlOutput::failure(format!("Request failed: {}", e)),
+        };
+
+        let status = response.status();
+        let final_url = response.url().to_string();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        if !status.is_success() {
+            return FetchUrlOutput {
+                url: Some(final_url),
+                status: Some(status.as_u16()),
+                ..FetchUrlOutput::failure(format!("HTTP {}", status))
+            };
+        }
+        let mime = content_type
+            .as_deref()
+            .and_then(|v| v.split(';').next())
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        if !is_text_type(&mime) {
+            return FetchUrlOutput::failure(format!("Unsupported content type: {}", mime));
+        }
+
+        let mut body = Vec::new();
+        if let Err(e) = response.take(MAX_DOWNLOAD_BYTES).read_to_end(&mut body) {
+            return FetchUrlOutput::failure(format!("Failed to read response: {}", e));
+        }
+        let text = String::from_utf8_lossy(&body);
+        let text = if mime == "text/html" || mime == "application/xhtml+xml" {
+            html_to_text(&text)
+        } else {
+            text.into_owned()
+        };
+        let (content, truncated) = truncate(text, max_bytes);
+
+        FetchUrlOutput {
+            success: true,
+            url: Some(final_url),
+            status: Some(status.as_u16()),
+            content_type,
+            content: Some(content),
+            truncated,
+            error: None,
+        }
+    }
+}
+
+/// 是否为可以按文本返回的内容类型；未声明类型时按文本处理
+fn is_text_type(mime: &str) -> bool {
+    mime.is_empty()
+        || mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime,
+            "application/json" | "application/xml" | "application/javascript"
+        )
+}
+
+/// 检查 URL 是否允许访问：只允许 http/https，主机不能解析到本机或私有地址
+fn check_url(url: &Url, allow_private: bool) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "Blocked URL scheme: {} (only http and https are allowed)",
+            url.scheme()
+        ));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| "URL has no host".to_string())?;
+    if allow_private {
+        return Ok(());
+    }
+
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.eq_ignore_ascii_case("localhost") || host.to_ascii_lowercase().ends_with(".localhost") {
+        return Err(format!("Blocked host: {}", host));
+    }
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?;
+    for addr in addrs {
+        if is_blocked_ip(addr.ip()) {
+            return Err(format!(
+                "Blocked host: {} resolves to a local or private address ({})",
+                host,
+                addr.ip()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 建立连接时使用的 DNS 解析器：丢弃本机和私有地址，只连接公网地址
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Addrs = Box::new(resolve_public(&host)?.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// 解析主机名并过滤掉受限地址；全部受限时返回错误
+fn resolve_public(host: &str) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = (host, 0)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+        .collect();
+    let public: Vec<SocketAddr> = addrs
+        .iter()
+        .copied()
+        .filter(|addr| !is_blocked_ip(addr.ip()))
+        .collect();
+    if public.is_empty() && !addrs.is_empty() {
+        return Err(format!(
+            "Blocked host: {} resolves to a local or private address ({})",
+            host,
+            addrs[0].ip()
+        ));
+    }
+    Ok(public)
+}
+
+/// 本机、私有、链路本地等不应从工具访问的地址
+fn is_blocked_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_blocked_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_blocked_ipv4(v4),
+            None => is_blocked_ipv6(ip),
+        },
+    }
+}
+
+fn is_blocked_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || a == 0
+        // 100.64.0.0/10 运营商级 NAT
+        || (a == 100 && (64..128).contains(&b))
+}
+
+fn is_blocked_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // fc00::/7 唯一本地地址
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10 链路本地地址
+        || (first & 0xffc0) == 0xfe80
+}
+
+/// 把 HTML 转换为纯文本：去掉脚本、样式和标签，块级元素换行，解码常用实体
+fn html_to_text(html: &str) -> String {
+    let text = HTML_HIDDEN.replace_all(html, "");
+    let text = HTML_BREAK.replace_all(&text, "\n");
+    let text = HTML_LIST_ITEM.replace_all(&text, "\n- ");
+    let text = HTML_TAG.replace_all(&text, "");
+    let text = HTML_ENTITY.replace_all(&text, |caps: &regex::Captures| {
+        decode_entity(&caps[1]).unwrap_or_else(|| caps[0].to_string())
+    });
+
+    // 折叠行内空白，连续空行只保留一个
+    let mut out = String::new();
+    let mut blank = true;
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            if !blank {
+                out.push('\n');
+            }
+            blank = true;
+        } else {
+            out.push_str(&line);
+            out.push('\n');
+            blank = false;
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// 解码单个字符实体（不含 `&` 和 `;`）
+fn decode_entity(entity: &str) -> Option<String> {
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        _ => {
+            let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => entity.strip_prefix('#')?.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some(c.to_string())
+}
+
+/// 截断到 `max_bytes` 以内的字符边界
+fn truncate(mut text: String, max_bytes: usize) -> (String, bool) {
+    if text.len() <= max_bytes {
+        return (text, false);
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    (text, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockResponse, MockServer};
+    use crate::tools::test_context;
+
+    fn html_response(body: &str) -> MockResponse {
+        MockResponse {
+            headers: vec![(
+                "content-type".to_string(),
+                "text/html; charset=utf-8".to_string(),
+            )],
+            ..MockResponse::with_status(200, body)
+        }
+    }
+
+    fn run(tool: &FetchUrlTool, input: Value) -> Value {
+        serde_json::from_str(&tool.execute(&test_context(), &input)).unwrap()
+    }
+
+    #[test]
+    fn test_fetch_html_as_text_and_truncate() {
+        let page = format!(
+            "<html><head><style>p {{ color: red; }}</style><script>alert(1)</script></head>\
+             <body><h1>Docs &amp; Guides</h1><p>First&nbsp;paragraph</p><ul><li>one</li><li>two</li></ul>\
+             <p>{}</p></body></html>",
+            "x".repeat(500)
+        );
+        let server = MockServer::start(vec![html_response(&page)]);
+        let tool = FetchUrlTool::allowing_private();
+
+        let result = run(
+            &tool,
+            serde_json::json!({"url": format!("{}/docs", server.url)}),
+        );
+        assert_eq!(result["success"], true);
+        assert_eq!(result["status"], 200);
+        assert_eq!(result["truncated"], false);
+        let content = result["content"].as_str().unwrap();
+        assert!(content.starts_with("Docs & Guides\n\nFirst paragraph\n\n- one\n- two"));
+        assert!(!content.contains("alert"));
+        assert!(!content.contains("color"));
+
+        let result = run(
+            &tool,
+            serde_json::json!({"url": format!("{}/docs", server.url), "max_bytes": 20}),
+        );
+        assert_eq!(result["truncated"], true);
+        assert_eq!(result["content"], "Docs & Guides\n\nFirst");
+        assert_eq!(server.requests()[1].path, "/docs");
+    }
+
+    #[test]
+    fn test_http_error_and_binary_rejected() {
+        let server = MockServer::start(vec![
+            MockResponse::with_status(404, "{}"),
+            MockResponse {
+                headers: vec![("content-type".to_string(), "image/png".to_string())],
+                ..MockResponse::with_status(200, "png")
+            },
+        ]);
+        let tool = FetchUrlTool::allowing_private();
+
+        let result = run(&tool, serde_json::json!({"url": server.url}));
+        assert_eq!(result["success"], false);
+        assert_eq!(result["status"], 404);
+
+        let result = run(&tool, serde_json::json!({"url": server.url}));
+        assert_eq!(result["success"], false);
+        assert!(result["error"].as_str().unwrap().contains("image/png"));
+    }
+
+    #[test]
+    fn test_local_and_private_urls_blocked() {
+        let server = MockServer::start(vec![html_response("secret")]);
+        let tool = FetchUrlTool::new();
+
+        for url in [
+            server.url.clone(),
+            "http://localhost:8080/".to_string(),
+            "http://api.localhost/".to_string(),
+            "http://10.0.0.1/".to_string(),
+            "http://192.168.1.1/admin".to_string(),
+            "http://169.254.169.254/latest/meta-data/".to_string(),
+            "http://[::1]/".to_string(),
+            "http://[::ffff:127.0.0.1]/".to_string(),
+            "file:///etc/passwd".to_string(),
+            "ftp://example.com/".to_string(),
+        ] {
+            let result = run(&tool, serde_json::json!({"url": url}));
+            assert_eq!(result["success"], false, "{}", url);
+            assert!(
+                result["error"].as_str().unwrap().starts_with("Blocked"),
+                "{}: {}",
+                url,
+                result["error"]
+            );
+        }
+        // 被拦截的请求不会发出
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn test_blocked_ip_ranges() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "100.64.0.1",
+            "0.0.0.0",
+            "fd00::1",
+            "fe80::1",
+        ] {
+            assert!(is_blocked_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["93.184.216.34", "8.8.8.8", "2606:4700::1111"] {
+            assert!(!is_blocked_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_connect_time_resolution_drops_private_addresses() {
+        // 连接时的解析结果同样经过过滤，rebinding 到本机的域名无法连接
+        let e = resolve_public("localhost").unwrap_err();
+        assert!(e.starts_with("Blocked host: localhost"), "{}", e);
+        assert!(resolve_public("127.0.0.1").is_err());
+        assert!(resolve_public("169.254.169.254").is_err());
+
+        let addrs = resolve_public("93.184.216.34").unwrap();
+        assert_eq!(addrs[0].ip().to_string(), "93.184.216.34");
+    }
+
+    #[test]
+    fn test_invalid_max_bytes() {
+        let tool = FetchUrlTool::new();
+        let result = run(
+            &tool,
+            serde_json::json!({"url": "https://example.com", "max_bytes": 0}),
+        );
+        assert_eq!(result["success"], false);
+    }
+}
@@ -0,0 +1,142 @@
+//! 简单的 glob 匹配与展开
+//!
+//! 支持 `*`（不跨目录的任意字符）、`?`（单个字符）和 `**`（任意层目录），
+//! 路径统一使用 `/` 分隔并相对于工作目录。
+
+use std::fs;
+use std::path::Path;
+
+/// 展开时跳过的目录（除非模式的字面前缀显式指向其中）
+const IGNORED_DIRS: &[&str] = &[".git", "target", "node_modules"];
+
+/// 路径是否包含 glob 通配符
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// 判断相对路径是否匹配 glob 模式
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    match_from(&pattern, &path)
+}
+
+fn match_from(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            // `**/` 匹配零层或多层目录
+            match_from(rest, path)
+                || path
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == '/')
+                    .any(|(i, _)| match_from(rest, &path[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|i| match_from(rest, &path[i..])),
+        ['*', rest @ ..] => {
+            let segment_end = path.iter().position(|c| *c == '/').unwrap_or(path.len());
+            (0..=segment_end).any(|i| match_from(rest, &path[i..]))
+        }
+        ['?', rest @ ..] => matches!(path, [c, tail @ ..] if *c != '/' && match_from(rest, tail)),
+        [p, rest @ ..] => matches!(path, [c, tail @ ..] if c == p && match_from(rest, tail)),
+    }
+}
+
+/// 遍历时决定是否跳过某个路径：参数为相对路径和是否为目录
+pub type SkipFn<'a> = &'a dyn Fn(&str, bool) -> bool;
+
+/// 在 `root` 下展开 glob 模式，返回匹配的文件（相对路径，已排序）
+///
+/// 只从模式中不含通配符的目录前缀开始遍历，不跟随符号链接。
+pub fn expand(root: &Path, pattern: &str, skip: SkipFn) -> Vec<String> {
+    let base: Vec<&str> = pattern
+        .split('/')
+        .take_while(|segment| !is_glob(segment))
+        .collect();
+    // 最后一段若是字面量，它是文件名而非目录
+    let base = if base.len() == pattern.split('/').count() {
+        &base[..base.len().saturating_sub(1)]
+    } else {
+        &base[..]
+    };
+    let base = base.join("/");
+
+    let mut matches = Vec::new();
+    walk(root, &base, skip, &mut |relative| {
+        if glob_match(pattern, relative) {
+            matches.push(relative.to_string());
+        }
+    });
+    matches.sort();
+    matches
+}
+
+/// 递归遍历 `root/relative_dir` 下的文件，以相对路径回调
+///
+/// 跳过 [`IGNORED_DIRS`] 中的目录和 `skip` 返回 true 的路径，不跟随符号链接。
+pub fn walk(root: &Path, relative_dir: &str, skip: SkipFn, visit: &mut dyn FnMut(&str)) {
+    let Ok(entries) = fs::read_dir(root.join(relative_dir)) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = if relative_dir.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", relative_dir, name)
+        };
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            if !IGNORED_DIRS.contains(&name.as_str()) && !skip(&relative, true) {
+                walk(root, &relative, skip, visit);
+            }
+        } else if file_type.is_file() && !skip(&relative, false) {
+            visit(&relative);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match_single_segment() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", "src/main.rs"));
+        assert!(glob_match("src/?ain.rs", "src/main.rs"));
+        assert!(!glob_match("src/*.rs", "src/tools/mod.rs"));
+    }
+
+    #[test]
+    fn test_glob_match_recursive() {
+        assert!(glob_match("**/*.rs", "main.rs"));
+        assert!(glob_match("**/*.rs", "src/tools/mod.rs"));
+        assert!(glob_match("src/**", "src/tools/mod.rs"));
+        assert!(!glob_match("**/*.rs", "README.md"));
+    }
+
+    #[test]
+    fn test_expand_from_literal_prefix() {
+        let root = std::env::current_dir().unwrap();
+        let dir = root.join("target/test_glob_expand/nested");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "").unwrap();
+        fs::write(dir.join("b.md"), "").unwrap();
+
+        // 即使 target 默认被跳过，字面前缀指向它时仍然会遍历
+        let found = expand(&root, "target/test_glob_expand/**/*.txt", &|_, _| false);
+        assert_eq!(found, vec!["target/test_glob_expand/nested/a.txt"]);
+
+        // 被跳过的目录不再进入
+        let skip_nested = |path: &str, is_dir: bool| is_dir && path.ends_with("/nested");
+        assert!(expand(&root, "target/test_glob_expand/**", &skip_nested).is_empty());
+
+        let _ = fs::remove_dir_all(root.join("target/test_glob_expand"));
+    }
+}
@@ -0,0 +1,196 @@
+//! 忽略规则 - 让工具不读取、不修改指定的路径
+//!
+//! 规则来自工作区根目录的 `.mentatignore` 和 `.mentat/ignore`（以及可选的 `.gitignore`），
+//! 使用 gitignore 的常见语法：`#` 注释、`!` 取反、结尾 `/` 只匹配目录、
+//! 含 `/` 的模式相对于根目录，否则匹配任意层级的同名文件或目录。
+//! 一个路径的任一上级目录被忽略时，该路径也被忽略。
+//!
+//! 规则在创建工具上下文时加载一次，由 [`PathValidator`](super::path_validator::PathValidator)
+//! 统一用于读写检查和目录遍历，各工具不再各自实现排除逻辑。
+//!
+//! 这里只是为了节省 token、避免读到无关或敏感的文件，路径安全仍由
+//! [`PathValidator`](super::path_validator::PathValidator) 的穿越检查保证。
+
+use super::glob::glob_match;
+use std::fs;
+use std::path::{Component, Path};
+
+/// 工作区内的忽略规则文件
+pub const IGNORE_FILE: &str = ".mentat/ignore";
+
+/// 工作区根目录下的忽略规则文件，与 `.gitignore` 并列
+pub const ROOT_IGNORE_FILE: &str = ".mentatignore";
+
+/// 一条忽略规则
+#[derive(Debug, Clone)]
+struct Rule {
+    /// 相对工作区根目录的 glob 模式
+    pattern: String,
+    /// `!` 开头的规则，重新包含之前被忽略的路径
+    negated: bool,
+    /// 以 `/` 结尾的规则只匹配目录
+    dir_only: bool,
+}
+
+/// 忽略规则集合，后出现的规则优先
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    /// 读取工作区根目录下的规则文件；`use_gitignore` 为 true 时先加载 `.gitignore`
+    ///
+    /// 依次加载 `.gitignore`、`.mentatignore`、`.mentat/ignore`，后加载的规则优先。
+    /// 文件不存在时没有规则。只读取根目录下的 `.gitignore`。
+    pub fn load(root: &Path, use_gitignore: bool) -> Self {
+        let files = [".gitignore", ROOT_IGNORE_FILE, IGNORE_FILE];
+        let text: Vec<String> = files
+            .into_iter()
+            .filter(|file| use_gitignore || *file != ".gitignore")
+            .map(|file| fs::read_to_string(root.join(file)).unwrap_or_default())
+            .collect();
+        Self::parse(&text.join("\n"))
+    }
+
+    /// 解析 gitignore 格式的规则
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                // 不含 `/` 的模式匹配任意层级
+                let pattern = match line.strip_prefix('/') {
+                    Some(anchored) => anchored.to_string(),
+                    None if line.contains('/') => line.to_string(),
+                    None => format!("**/{}", line),
+                };
+                Rule {
+                    pattern,
+                    negated,
+                    dir_only,
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// 判断相对工作区根目录的路径是否被忽略
+    ///
+    /// `is_dir` 表示路径本身是否为目录（决定只匹配目录的规则能否作用于它）。
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let components: Vec<&str> = relative
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => name.to_str(),
+                _ => None,
+            })
+            .collect();
+
+        // 从最上层开始逐级检查，上级目录被忽略时其下的所有路径都被忽略
+        (1..=components.len()).any(|depth| {
+            let prefix = components[..depth].join("/");
+            let prefix_is_dir = depth < components.len() || is_dir;
+            self.matches(&prefix, prefix_is_dir)
+        })
+    }
+
+    /// 单个路径（不考虑上级目录）是否被忽略：最后一条匹配的规则决定结果
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && glob_match(&rule.pattern, path))
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored(rules: &IgnoreRules, path: &str) -> bool {
+        rules.is_ignored(Path::new(path), false)
+    }
+
+    #[test]
+    fn test_gitignore_style_rules() {
+        let rules = IgnoreRules::parse(
+            "# build output\ntarget/\nnode_modules/\n.env\n*.log\n/docs/private\n!keep.log\n",
+        );
+        assert!(ignored(&rules, "target/foo"));
+        assert!(ignored(&rules, "target/debug/build/x.rs"));
+        assert!(ignored(&rules, "web/node_modules/pkg/index.js"));
+        assert!(ignored(&rules, ".env"));
+        assert!(ignored(&rules, "config/.env"));
+        assert!(ignored(&rules, "logs/app.log"));
+        assert!(ignored(&rules, "docs/private/plan.md"));
+        assert!(!ignored(&rules, "src/docs/private/plan.md"));
+        assert!(!ignored(&rules, "keep.log"));
+        assert!(!ignored(&rules, "src/main.rs"));
+        assert!(!ignored(&rules, "./src/main.rs"));
+        // 只匹配目录的规则不匹配同名文件
+        assert!(!ignored(&rules, "target"));
+        assert!(rules.is_ignored(Path::new("target"), true));
+    }
+
+    #[test]
+    fn test_negation_and_directory_rules() {
+        let rules = IgnoreRules::parse("*.log\ntarget/\n!important.log\n");
+        assert!(ignored(&rules, "debug.log"));
+        assert!(ignored(&rules, "logs/nested/debug.log"));
+        assert!(!ignored(&rules, "important.log"));
+        assert!(!ignored(&rules, "logs/important.log"));
+        assert!(rules.is_ignored(Path::new("target"), true));
+        assert!(rules.is_ignored(Path::new("crates/core/target"), true));
+        // 与 git 相同：上级目录被忽略时，取反规则不能重新包含其中的文件
+        assert!(ignored(&rules, "target/important.log"));
+
+        // 取反规则在前时会被之后的规则覆盖
+        let rules = IgnoreRules::parse("!important.log\n*.log\n");
+        assert!(ignored(&rules, "important.log"));
+
+        // 以 `/` 开头的规则只匹配根目录下的路径
+        let rules = IgnoreRules::parse("/build/\n");
+        assert!(rules.is_ignored(Path::new("build"), true));
+        assert!(!rules.is_ignored(Path::new("web/build"), true));
+    }
+
+    #[test]
+    fn test_load_combines_rule_files() {
+        let root = std::env::temp_dir().join("mentat_test_ignore_load");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(".mentat")).unwrap();
+        fs::write(root.join(".gitignore"), "dist/\n").unwrap();
+        fs::write(root.join(ROOT_IGNORE_FILE), "*.log\n").unwrap();
+        fs::write(root.join(IGNORE_FILE), "!important.log\n").unwrap();
+
+        let rules = IgnoreRules::load(&root, false);
+        assert!(ignored(&rules, "debug.log"));
+        assert!(!ignored(&rules, "important.log"));
+        assert!(!rules.is_ignored(Path::new("dist"), true));
+
+        let rules = IgnoreRules::load(&root, true);
+        assert!(rules.is_ignored(Path::new("dist"), true));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_empty_rules_ignore_nothing() {
+        let rules = IgnoreRules::parse("\n# nothing\n");
+        assert!(rules.rules.is_empty());
+        assert!(!ignored(&rules, ".env"));
+    }
+}
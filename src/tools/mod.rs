@@ -2,14 +2,118 @@
 //!
 //! 提供统一的 Tool trait 和 ToolRegistry 用于管理所有可用工具。
 
+mod append_file;
+mod create_dir;
+mod delete_file;
+mod diff_files;
+mod edit_file;
+mod fetch_url;
+mod glob;
+mod ignore;
+mod move_file;
+mod outline;
 mod path_validator;
 mod read_file;
+mod read_many_files;
+mod redact;
+mod replace_in_files;
+mod run_command;
+mod schema;
+mod search;
+mod stat;
+mod text_format;
 mod write_file;
 
-// PathValidator 和 PathValidationError 在内部使用，不需要公开导出
+// PathValidationError 在内部使用，不需要公开导出
 
+pub use delete_file::{restore_batch, trash_batches};
+pub use edit_file::apply_edit;
+pub use ignore::IgnoreRules;
+pub use path_validator::{is_root_name, PathValidator, SymlinkPolicy};
+
+use crate::config::Settings;
+use crate::tool_log::ToolLog;
+use serde::Serialize;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// 工具执行上下文，由注册表持有并在每次执行时传入
+///
+/// 工作区根目录在创建时确定，之后进程的当前目录变化不会移动沙箱。
+#[derive(Clone)]
+pub struct ToolContext {
+    validator: PathValidator,
+    /// dry-run 模式：修改类工具只报告将要做的修改，不写入磁盘
+    dry_run: bool,
+    /// read_file 返回内容前先对密钥等敏感信息脱敏
+    redact_secrets: bool,
+}
+
+impl ToolContext {
+    /// 以指定的工作区根目录创建上下文
+    pub fn new(workspace_root: PathBuf) -> Self {
+        Self {
+            validator: PathValidator::with_root(workspace_root),
+            dry_run: false,
+            redact_secrets: false,
+        }
+    }
+
+    /// 设置忽略规则，匹配的路径不能被工具读取或修改
+    pub fn with_ignore_rules(mut self, ignore: IgnoreRules) -> Self {
+        self.validator = self.validator.with_ignore_rules(ignore);
+        self
+    }
+
+    /// 设置 read_file 是否对敏感信息脱敏
+    pub fn with_redact_secrets(mut self, redact: bool) -> Self {
+        self.redact_secrets = redact;
+        self
+    }
+
+    /// 限制写入操作只能发生在指定目录内，见 [`PathValidator::with_writable_roots`]
+    pub fn with_writable_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.validator = self.validator.with_writable_roots(roots);
+        self
+    }
+
+    /// 添加只读的命名根目录，见 [`PathValidator::with_extra_roots`]
+    pub fn with_extra_roots(mut self, roots: HashMap<String, PathBuf>) -> Self {
+        self.validator = self.validator.with_extra_roots(roots);
+        self
+    }
+
+    /// 设置路径中包含符号链接时的处理策略
+    pub fn with_symlink_policy(mut self, policy: SymlinkPolicy) -> Self {
+        self.validator = self.validator.with_symlink_policy(policy);
+        self
+    }
+
+    /// 是否处于 dry-run 模式
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// 是否对读取的内容脱敏
+    pub fn redacts_secrets(&self) -> bool {
+        self.redact_secrets
+    }
+
+    /// 所有工具共享的路径验证器
+    pub fn validator(&self) -> &PathValidator {
+        &self.validator
+    }
+
+    /// 工作区根目录
+    pub fn workspace_root(&self) -> &Path {
+        self.validator.root()
+    }
+}
 
 /// 工具 trait - 所有工具必须实现此接口
 pub trait Tool: Send + Sync {
@@ -20,33 +124,83 @@ pub trait Tool: Send + Sync {
     fn definition(&self) -> Value;
 
     /// 执行工具
-    fn execute(&self, input: &Value) -> String;
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String;
+
+    /// 是否会修改文件；修改类工具执行前需要用户确认
+    fn is_mutating(&self) -> bool {
+        false
+    }
+
+    /// 是否自行支持 dry-run（只报告将要进行的修改）
+    ///
+    /// 不支持的修改类工具在 dry-run 模式下不会被执行。
+    fn supports_dry_run(&self) -> bool {
+        false
+    }
 }
 
 /// 工具注册表 - 管理所有可用工具
 pub struct ToolRegistry {
-    tools: HashMap<String, Box<dyn Tool>>,
+    tools: HashMap<String, Arc<dyn Tool>>,
+    context: ToolContext,
+    /// 按工具名配置的超时，未配置的工具不限时
+    timeouts: HashMap<String, Duration>,
+    /// 工具调用审计日志（未启用时为 `None`）
+    log: Option<ToolLog>,
 }
 
 impl ToolRegistry {
     /// 创建新的工具注册表
-    pub fn new() -> Self {
+    pub fn new(context: ToolContext) -> Self {
         Self {
             tools: HashMap::new(),
+            context,
+            timeouts: HashMap::new(),
+            log: None,
         }
     }
 
     /// 创建并注册所有内置工具
-    pub fn with_builtins() -> Self {
-        let mut registry = Self::new();
-        registry.register(Box::new(read_file::ReadFileTool));
-        registry.register(Box::new(write_file::WriteFileTool));
+    #[allow(dead_code)]
+    pub fn with_builtins(context: ToolContext) -> Self {
+        let mut registry = Self::new(context);
+        for tool in builtin_tools(&Settings::default()) {
+            registry.register(tool);
+        }
+        registry
+    }
+
+    /// 按配置的允许/禁用列表注册内置工具
+    ///
+    /// 未配置 `enabled_tools` 时默认全部允许；同时出现在两个列表中的工具会被禁用。
+    pub fn with_builtins_filtered(settings: &Settings, context: ToolContext) -> Self {
+        let is_listed =
+            |list: &Option<Vec<String>>, name: &str| list.iter().flatten().any(|n| n == name);
+
+        let mut registry = Self::new(context);
+        for tool in builtin_tools(settings) {
+            let allowed =
+                settings.enabled_tools.is_none() || is_listed(&settings.enabled_tools, tool.name());
+            if allowed && !is_listed(&settings.disabled_tools, tool.name()) {
+                registry.register(tool);
+            }
+        }
         registry
     }
 
     /// 注册一个工具
     pub fn register(&mut self, tool: Box<dyn Tool>) {
-        self.tools.insert(tool.name().to_string(), tool);
+        self.tools.insert(tool.name().to_string(), Arc::from(tool));
+    }
+
+    /// 设置工具超时（按工具名）
+    pub fn set_timeouts(&mut self, timeouts: HashMap<String, Duration>) {
+        self.timeouts = timeouts;
+    }
+
+    /// 启用工具调用日志，之后每次执行都会追加一条记录
+    pub fn set_log(&mut self, log: ToolLog) {
+        self.log = Some(log);
     }
 
     /// 获取所有工具的定义（用于 API 请求）
@@ -56,10 +210,90 @@ impl ToolRegistry {
 
     /// 执行指定工具
     pub fn execute(&self, name: &str, input: &Value) -> String {
-        match self.tools.get(name) {
-            Some(tool) => tool.execute(input),
+        let output = match self.tools.get(name) {
+            Some(tool)
+                if self.context.dry_run && tool.is_mutating() && !tool.supports_dry_run() =>
+            {
+                serde_json::json!({
+                    "success": false,
+                    "error": format!("[DRY RUN] {} was not executed because dry-run mode is on", name),
+                })
+                .to_string()
+            }
+            Some(tool) => match schema::validate_input(&tool.definition()["input_schema"], input)
+            {
+                Ok(()) => self.run_with_timeout(name, tool, input),
+                Err(e) => serde_json::json!({
+                    "success": false,
+                    "error": format!("Invalid input for {}: {}", name, e),
+                })
+                .to_string(),
+            },
             None => format!(r#"{{"error": "Unknown tool: {}"}}"#, name),
+        };
+        if let Some(log) = &self.log {
+            if let Err(e) = log.record(name, input, &output) {
+                log::warn!("写入工具调用日志失败: {}", e);
+            }
         }
+        output
+    }
+
+    /// 执行工具；配置了超时的工具在工作线程中运行，超时后返回错误结果
+    ///
+    /// 超时后不再等待工作线程，它会在后台继续运行直到工具自行返回，
+    /// 结果被丢弃。run_command 的子进程由其自身的超时负责终止。
+    fn run_with_timeout(&self, name: &str, tool: &Arc<dyn Tool>, input: &Value) -> String {
+        let Some(&timeout) = self.timeouts.get(name) else {
+            return tool.execute(&self.context, input);
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let worker_tool = Arc::clone(tool);
+        let context = self.context.clone();
+        let worker_input = input.clone();
+        thread::spawn(move || {
+            let _ = sender.send(worker_tool.execute(&context, &worker_input));
+        });
+
+        let error = match receiver.recv_timeout(timeout) {
+            Ok(output) => return output,
+            Err(RecvTimeoutError::Timeout) => {
+                log::warn!("工具 {} 超时（{} 秒）", name, timeout.as_secs_f64());
+                format!(
+                    "ToolTimeout: {} did not finish within {}s",
+                    name,
+                    timeout.as_secs_f64()
+                )
+            }
+            Err(RecvTimeoutError::Disconnected) => format!("{} panicked", name),
+        };
+        serde_json::json!({"success": false, "error": error}).to_string()
+    }
+
+    /// 指定工具是否会修改文件（未知工具视为不修改）
+    pub fn is_mutating(&self, name: &str) -> bool {
+        self.tools.get(name).is_some_and(|t| t.is_mutating())
+    }
+
+    /// 开启或关闭 dry-run 模式
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.context.dry_run = dry_run;
+    }
+
+    /// 是否处于 dry-run 模式
+    pub fn is_dry_run(&self) -> bool {
+        self.context.dry_run
+    }
+
+    /// 工具沙箱的工作区根目录
+    pub fn workspace_root(&self) -> &Path {
+        self.context.workspace_root()
+    }
+
+    /// 工具使用的路径验证器
+    pub fn validator(&self) -> &PathValidator {
+        self.context.validator()
     }
 
     /// 获取已注册的工具数量
@@ -68,6 +302,7 @@ impl ToolRegistry {
     }
 
     /// 检查是否为空
+    #[allow(dead_code)]
     pub fn is_empty(&self) -> bool {
         self.tools.is_empty()
     }
@@ -76,30 +311,412 @@ impl ToolRegistry {
     pub fn tool_names(&self) -> Vec<&str> {
         self.tools.keys().map(|s| s.as_str()).collect()
     }
+
+    /// 获取指定工具的定义
+    pub fn definition(&self, name: &str) -> Option<Value> {
+        self.tools.get(name).map(|t| t.definition())
+    }
+
+    /// 所有工具的名称和简短描述（描述的第一句），按名称排序
+    pub fn summaries(&self) -> Vec<(String, String)> {
+        let mut summaries: Vec<(String, String)> = self
+            .definitions()
+            .iter()
+            .map(|def| {
+                let name = def.get("name").and_then(|n| n.as_str()).unwrap_or("");
+                let description = def
+                    .get("description")
+                    .and_then(|d| d.as_str())
+                    .unwrap_or("");
+                (name.to_string(), first_sentence(description).to_string())
+            })
+            .collect();
+        summaries.sort();
+        summaries
+    }
+}
+
+/// 把工具输出序列化为 JSON 字符串
+///
+/// 序列化失败时返回一个错误结果而不是 panic，单个工具的异常输出不会中断整个会话。
+pub fn to_json<T: Serialize>(output: &T) -> String {
+    serde_json::to_string(output).unwrap_or_else(|e| {
+        serde_json::json!({
+            "success": false,
+            "error": format!("Failed to serialize tool output: {}", e),
+        })
+        .to_string()
+    })
+}
+
+/// 所有内置工具
+fn builtin_tools(settings: &Settings) -> Vec<Box<dyn Tool>> {
+    vec![
+        Box::new(read_file::ReadFileTool),
+        Box::new(read_many_files::ReadManyFilesTool),
+        Box::new(write_file::WriteFileTool),
+        Box::new(append_file::AppendFileTool),
+        Box::new(edit_file::EditFileTool),
+        Box::new(replace_in_files::ReplaceInFilesTool),
+        Box::new(text_format::TextFormatTool),
+        Box::new(search::SearchTool),
+        Box::new(stat::StatTool),
+        Box::new(diff_files::DiffFilesTool),
+        Box::new(fetch_url::FetchUrlTool::new()),
+        Box::new(create_dir::CreateDirTool),
+        Box::new(delete_file::DeleteFileTool),
+        Box::new(move_file::MoveFileTool),
+        Box::new(run_command::RunCommandTool::new(
+            settings.get_allowed_commands(),
+            settings.get_command_timeout(),
+        )),
+    ]
+}
+
+/// 所有内置工具的名称（用于校验配置）
+pub fn builtin_tool_names() -> Vec<&'static str> {
+    builtin_tools(&Settings::default())
+        .iter()
+        .map(|t| t.name())
+        .collect()
 }
 
-impl Default for ToolRegistry {
-    fn default() -> Self {
-        Self::with_builtins()
+/// 截取描述的第一句
+fn first_sentence(description: &str) -> &str {
+    match description.find(". ") {
+        Some(end) => &description[..=end],
+        None => description,
     }
 }
 
+/// 以当前目录为工作区的上下文（测试用）
+#[cfg(test)]
+pub fn test_context() -> ToolContext {
+    ToolContext::new(std::env::current_dir().unwrap())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_to_json_falls_back_on_serialize_error() {
+        // 键不是字符串的 map 无法序列化为 JSON
+        let output = HashMap::from([((1, 2), "value")]);
+        let json: Value = serde_json::from_str(&to_json(&output)).unwrap();
+        assert_eq!(json["success"], false);
+        assert!(json["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Failed to serialize tool output"));
+
+        assert_eq!(
+            to_json(&serde_json::json!({"success": true})),
+            r#"{"success":true}"#
+        );
+    }
+
     #[test]
     fn test_registry_builtins() {
-        let registry = ToolRegistry::with_builtins();
-        assert_eq!(registry.len(), 2);
+        let registry = ToolRegistry::with_builtins(test_context());
+        assert_eq!(registry.len(), 15);
         assert!(registry.tool_names().contains(&"read_file"));
         assert!(registry.tool_names().contains(&"write_file"));
+        assert!(registry.tool_names().contains(&"append_file"));
+        assert!(registry.tool_names().contains(&"edit_file"));
+        assert!(registry.tool_names().contains(&"text_format"));
+        assert!(registry.tool_names().contains(&"search"));
+        assert!(registry.tool_names().contains(&"create_dir"));
+        assert!(registry.tool_names().contains(&"delete_file"));
+        assert!(registry.tool_names().contains(&"move_file"));
+        assert!(registry.tool_names().contains(&"stat"));
+        assert!(registry.tool_names().contains(&"replace_in_files"));
+        assert!(registry.tool_names().contains(&"run_command"));
+    }
+
+    /// 测试用的假工具：回显输入中的 `text` 字段
+    struct EchoTool;
+
+    impl Tool for EchoTool {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn definition(&self) -> Value {
+            serde_json::json!({
+                "name": "echo",
+                "description": "Echo back the given text.",
+                "input_schema": {
+                    "type": "object",
+                    "properties": {
+                        "text": { "type": "string" }
+                    },
+                    "required": ["text"]
+                }
+            })
+        }
+
+        fn execute(&self, _ctx: &ToolContext, input: &Value) -> String {
+            let text = input.get("text").and_then(|t| t.as_str()).unwrap_or("");
+            serde_json::json!({ "success": true, "echo": text }).to_string()
+        }
+    }
+
+    #[test]
+    fn test_registry_fake_tool_end_to_end() {
+        let mut registry = ToolRegistry::new(test_context());
+        assert!(registry.is_empty());
+        registry.register(Box::new(EchoTool));
+
+        // 定义会出现在 API 请求的 tools 列表中
+        let definitions = registry.definitions();
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0]["name"], "echo");
+
+        // 按名称分发 tool_use 并拿到结果
+        let output = registry.execute("echo", &serde_json::json!({"text": "hi"}));
+        let parsed: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(parsed["success"], true);
+        assert_eq!(parsed["echo"], "hi");
+    }
+
+    #[test]
+    fn test_summaries_sorted_with_first_sentence() {
+        let registry = ToolRegistry::with_builtins(test_context());
+        let summaries = registry.summaries();
+
+        let names: Vec<&str> = summaries.iter().map(|(n, _)| n.as_str()).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+
+        let (_, read_description) = summaries.iter().find(|(n, _)| n == "read_file").unwrap();
+        assert_eq!(
+            read_description,
+            "Read the contents of a file at the specified path."
+        );
+        assert!(registry.definition("read_file").is_some());
+        assert!(registry.definition("missing").is_none());
+    }
+
+    #[test]
+    fn test_filtered_registry_denylist() {
+        let settings = Settings {
+            disabled_tools: Some(vec!["write_file".to_string()]),
+            ..Default::default()
+        };
+        let registry = ToolRegistry::with_builtins_filtered(&settings, test_context());
+        assert_eq!(registry.len(), builtin_tool_names().len() - 1);
+        assert!(!registry.tool_names().contains(&"write_file"));
+    }
+
+    #[test]
+    fn test_filtered_registry_denylist_wins_over_allowlist() {
+        let settings = Settings {
+            enabled_tools: Some(vec!["read_file".to_string(), "write_file".to_string()]),
+            disabled_tools: Some(vec!["write_file".to_string()]),
+            ..Default::default()
+        };
+        let registry = ToolRegistry::with_builtins_filtered(&settings, test_context());
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.tool_names(), vec!["read_file"]);
+    }
+
+    #[test]
+    fn test_is_mutating_flags() {
+        let registry = ToolRegistry::with_builtins(test_context());
+        assert!(!registry.is_mutating("read_file"));
+        assert!(!registry.is_mutating("search"));
+        assert!(registry.is_mutating("write_file"));
+        assert!(registry.is_mutating("edit_file"));
+        assert!(registry.is_mutating("text_format"));
+        assert!(registry.is_mutating("create_dir"));
+        assert!(registry.is_mutating("delete_file"));
+        assert!(!registry.is_mutating("unknown"));
+        // 默认实现不修改文件
+        assert!(!EchoTool.is_mutating());
+    }
+
+    #[test]
+    fn test_writable_roots_limit_mutating_tools() {
+        let root = std::env::temp_dir().join("mentat_test_writable_roots");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("README.md"), "readme\n").unwrap();
+
+        let context =
+            ToolContext::new(root.clone()).with_writable_roots(vec![PathBuf::from("src")]);
+        let registry = ToolRegistry::with_builtins(context);
+
+        let output = registry.execute(
+            "write_file",
+            &serde_json::json!({"file_path": "src/x.rs", "content": "fn x() {}\n"}),
+        );
+        assert!(output.contains("\"success\":true"), "{}", output);
+        assert!(root.join("src/x.rs").exists());
+
+        let output = registry.execute(
+            "write_file",
+            &serde_json::json!({"file_path": "README.md", "content": "changed"}),
+        );
+        assert!(output.contains("outside the writable roots"), "{}", output);
+        let output = registry.execute(
+            "delete_file",
+            &serde_json::json!({"file_path": "README.md"}),
+        );
+        assert!(output.contains("\"success\":false"));
+        assert_eq!(
+            std::fs::read_to_string(root.join("README.md")).unwrap(),
+            "readme\n"
+        );
+
+        // 读取不受限制
+        let output = registry.execute("read_file", &serde_json::json!({"file_path": "README.md"}));
+        assert!(output.contains("readme"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_tools_resolve_paths_against_context_root() {
+        let root = std::env::temp_dir().join("mentat_test_context_root");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "fn sandboxed() {}\n").unwrap();
+
+        let registry = ToolRegistry::with_builtins(ToolContext::new(root.clone()));
+        assert_eq!(registry.workspace_root(), root.as_path());
+
+        let output = registry.execute("read_file", &serde_json::json!({"file_path": "src/lib.rs"}));
+        assert!(output.contains("fn sandboxed"));
+
+        // 相对路径以上下文根目录为准，而不是进程当前目录
+        let output = registry.execute("read_file", &serde_json::json!({"file_path": "Cargo.toml"}));
+        assert!(output.contains("\"success\":false"));
+
+        let output = registry.execute("search", &serde_json::json!({"pattern": "sandboxed"}));
+        assert!(output.contains("src/lib.rs"));
+
+        registry.execute(
+            "write_file",
+            &serde_json::json!({"file_path": "notes.txt", "content": "x"}),
+        );
+        assert!(root.join("notes.txt").is_file());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_tool_log_records_each_execution() {
+        let root = std::env::temp_dir().join("mentat_test_tool_log");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.txt"), "alpha\n").unwrap();
+
+        let mut registry = ToolRegistry::with_builtins(ToolContext::new(root.clone()));
+        let log_path = root.join(crate::tool_log::TOOL_LOG_PATH);
+        registry.set_log(ToolLog::new(log_path.clone(), 1024));
+
+        registry.execute("read_file", &serde_json::json!({"file_path": "a.txt"}));
+        registry.execute("search", &serde_json::json!({"pattern": "alpha"}));
+
+        let lines: Vec<Value> = std::fs::read_to_string(&log_path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["tool"], "read_file");
+        assert_eq!(lines[0]["success"], true);
+        assert_eq!(lines[1]["tool"], "search");
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_dry_run_skips_tools_without_support() {
+        let root = std::env::temp_dir().join("mentat_test_dry_run_registry");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        let mut registry = ToolRegistry::with_builtins(ToolContext::new(root.clone()));
+        registry.set_dry_run(true);
+        assert!(registry.is_dry_run());
+
+        let output = registry.execute("create_dir", &serde_json::json!({"path": "new_dir"}));
+        assert!(output.contains("[DRY RUN]"));
+        assert!(!root.join("new_dir").exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    /// 执行很慢的工具
+    struct SlowTool;
+
+    impl Tool for SlowTool {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn definition(&self) -> Value {
+            serde_json::json!({"name": "slow"})
+        }
+
+        fn execute(&self, _ctx: &ToolContext, _input: &Value) -> String {
+            thread::sleep(Duration::from_secs(2));
+            r#"{"success":true}"#.to_string()
+        }
+    }
+
+    #[test]
+    fn test_slow_tool_times_out() {
+        let mut registry = ToolRegistry::new(test_context());
+        registry.register(Box::new(SlowTool));
+        registry.register(Box::new(stat::StatTool));
+        registry.set_timeouts(HashMap::from([
+            ("slow".to_string(), Duration::from_millis(100)),
+            ("stat".to_string(), Duration::from_secs(5)),
+        ]));
+
+        let started = std::time::Instant::now();
+        let output: Value = serde_json::from_str(&registry.execute("slow", &Value::Null)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(output["success"], false);
+        assert!(output["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("ToolTimeout: slow"));
+
+        // 在限时内完成的工具照常返回结果
+        let output = registry.execute("stat", &serde_json::json!({"path": "Cargo.toml"}));
+        assert!(output.contains(r#""exists":true"#));
     }
 
     #[test]
     fn test_execute_unknown_tool() {
-        let registry = ToolRegistry::new();
+        let registry = ToolRegistry::new(test_context());
         let result = registry.execute("unknown", &Value::Null);
         assert!(result.contains("Unknown tool"));
     }
+
+    #[test]
+    fn test_invalid_input_names_field() {
+        let registry = ToolRegistry::with_builtins(test_context());
+        let output = registry.execute("read_file", &serde_json::json!({"path": "Cargo.toml"}));
+        let parsed: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(parsed["success"], false);
+        let error = parsed["error"].as_str().unwrap();
+        assert!(
+            error.starts_with("Invalid input for read_file: missing required field 'file_path'"),
+            "{}",
+            error
+        );
+        assert!(error.contains("file_path: string (required)"), "{}", error);
+
+        let output = registry.execute(
+            "read_file",
+            &serde_json::json!({"file_path": "Cargo.toml", "start_line": "1"}),
+        );
+        assert!(output.contains("field 'start_line' must be integer, got string"));
+    }
 }
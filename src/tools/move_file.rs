@@ -0,0 +1,186 @@
+//! move_file 工具 - 移动或重命名文件
+
+use super::delete_file::move_file;
+use super::{to_json, Tool, ToolContext};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// move_file 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct MoveFileInput {
+    pub from: String,
+    pub to: String,
+    /// 目标已存在时是否覆盖
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// move_file 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct MoveFileOutput {
+    pub success: bool,
+    pub message: Option<String>,
+    pub error: Option<String>,
+}
+
+impl MoveFileOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            message: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// MoveFile 工具实现
+pub struct MoveFileTool;
+
+impl Tool for MoveFileTool {
+    fn name(&self) -> &'static str {
+        "move_file"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "move_file",
+            "description": "Move or rename a file in one step. Missing parent directories of the destination are created. Fails if the destination already exists unless overwrite is true.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "from": {
+                        "type": "string",
+                        "description": "The file to move (relative to the workspace)"
+                    },
+                    "to": {
+                        "type": "string",
+                        "description": "The new path of the file (relative to the workspace)"
+                    },
+                    "overwrite": {
+                        "type": "boolean",
+                        "description": "Replace the destination if it already exists (default: false)"
+                    }
+                },
+                "required": ["from", "to"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: MoveFileInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&MoveFileOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = execute_move_file(ctx, &tool_input);
+        to_json(&result)
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+}
+
+/// 执行文件移动
+fn execute_move_file(ctx: &ToolContext, input: &MoveFileInput) -> MoveFileOutput {
+    let validator = ctx.validator();
+
+    // 安全检查：源文件必须存在，两个路径都必须在工作区内并且允许写入
+    let from = match validator
+        .validate_for_read(&input.from)
+        .and_then(|_| validator.validate_for_write(&input.from))
+    {
+        Ok(p) => p,
+        Err(e) => return MoveFileOutput::failure(e.to_string()),
+    };
+    let to = match validator.validate_for_write(&input.to) {
+        Ok(p) => p,
+        Err(e) => return MoveFileOutput::failure(e.to_string()),
+    };
+
+    if !from.is_file() {
+        return MoveFileOutput::failure(format!("Not a regular file: {}", input.from));
+    }
+    if to.is_dir() {
+        return MoveFileOutput::failure(format!("A directory already exists at {}", input.to));
+    }
+    if to.exists() && !input.overwrite {
+        return MoveFileOutput::failure(format!(
+            "Destination already exists: {} (set overwrite to true to replace it)",
+            input.to
+        ));
+    }
+
+    match move_file(&from, &to) {
+        Ok(()) => MoveFileOutput {
+            success: true,
+            message: Some(format!("Moved {} to {}", input.from, input.to)),
+            error: None,
+        },
+        Err(e) => MoveFileOutput::failure(format!("Failed to move file: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_move_creates_parent_dirs() {
+        let root = "target/test_move_file";
+        let _ = fs::remove_dir_all(root);
+        fs::create_dir_all(root).unwrap();
+        fs::write("target/test_move_file/a.txt", "hello").unwrap();
+
+        let input = serde_json::json!({
+            "from": "target/test_move_file/a.txt",
+            "to": "target/test_move_file/nested/b.txt"
+        });
+        let result = MoveFileTool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":true"));
+        assert!(!Path::new("target/test_move_file/a.txt").exists());
+        assert_eq!(
+            fs::read_to_string("target/test_move_file/nested/b.txt").unwrap(),
+            "hello"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_existing_destination_requires_overwrite() {
+        let root = "target/test_move_file_overwrite";
+        let _ = fs::remove_dir_all(root);
+        fs::create_dir_all(root).unwrap();
+        let from = "target/test_move_file_overwrite/from.txt";
+        let to = "target/test_move_file_overwrite/to.txt";
+        fs::write(from, "new").unwrap();
+        fs::write(to, "old").unwrap();
+
+        let input = serde_json::json!({"from": from, "to": to});
+        let result = MoveFileTool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":false"));
+        assert!(result.contains("already exists"));
+        assert_eq!(fs::read_to_string(to).unwrap(), "old");
+        assert!(Path::new(from).exists());
+
+        let input = serde_json::json!({"from": from, "to": to, "overwrite": true});
+        let result = MoveFileTool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":true"));
+        assert_eq!(fs::read_to_string(to).unwrap(), "new");
+        assert!(!Path::new(from).exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_path_traversal_blocked() {
+        let input = serde_json::json!({"from": "Cargo.toml", "to": "../outside.toml"});
+        let result = MoveFileTool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":false"));
+        assert!(Path::new("Cargo.toml").exists());
+    }
+}
@@ -0,0 +1,110 @@
+//! 源文件大纲
+//!
+//! read_file 的 `outline` 模式只返回顶层声明（及 impl/trait 中的方法）的签名和行号，
+//! 大文件只需要了解结构时可以省下大量 token。目前只识别 Rust。
+
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// 顶层条目：可见性和修饰符之后的 fn、struct、enum、union、impl、trait、mod
+static RUST_ITEM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^(?:pub(?:\([^)]*\))?\s+)?(?:(?:default|const|async|unsafe|extern(?:\s+"[^"]*")?)\s+)*(fn|struct|enum|union|impl|trait|mod)\b"#,
+    )
+    .unwrap()
+});
+
+/// 生成文件大纲；不支持的语言返回 `None`
+pub fn outline(path: &Path, content: &str) -> Option<String> {
+    match path.extension()?.to_str()? {
+        "rs" => Some(rust_outline(content)),
+        _ => None,
+    }
+}
+
+/// Rust 大纲：每行 `行号| 签名`，impl/trait 中的方法缩进一级
+fn rust_outline(content: &str) -> String {
+    let total_lines = content.lines().count();
+    let width = total_lines.max(1).to_string().len();
+    let mut entries = Vec::new();
+    // 当前是否处于顶层 impl/trait 块中
+    let mut in_impl = false;
+
+    for (index, line) in content.lines().enumerate() {
+        let signature = if !line.starts_with(char::is_whitespace) {
+            let Some(item) = RUST_ITEM.captures(line) else {
+                if line.starts_with('}') {
+                    in_impl = false;
+                }
+                continue;
+            };
+            in_impl = matches!(&item[1], "impl" | "trait");
+            line
+        } else if in_impl {
+            match line.strip_prefix("    ") {
+                Some(method) if !method.starts_with(char::is_whitespace) => {
+                    match RUST_ITEM.captures(method) {
+                        Some(item) if &item[1] == "fn" => line,
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            }
+        } else {
+            continue;
+        };
+
+        let signature = signature.trim_end().trim_end_matches('{').trim_end();
+        entries.push(format!(
+            "{:>width$}| {}\n",
+            index + 1,
+            signature,
+            width = width
+        ));
+    }
+    entries.concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_outline() {
+        let source = r#"//! docs
+use std::fmt;
+
+/// A point
+pub struct Point {
+    x: i32,
+}
+
+impl Point {
+    pub fn new() -> Self {
+        let f = |x: i32| x;
+        Self { x: f(0) }
+    }
+
+    fn private(&self) {}
+}
+
+pub(crate) async fn run() {}
+
+#[cfg(test)]
+mod tests {
+    fn helper() {}
+}
+"#;
+        assert_eq!(
+            outline(Path::new("a.rs"), source).unwrap(),
+            " 5| pub struct Point\n 9| impl Point\n10|     pub fn new() -> Self\n15|     fn private(&self) {}\n18| pub(crate) async fn run() {}\n21| mod tests\n"
+        );
+    }
+
+    #[test]
+    fn test_unknown_language_has_no_outline() {
+        assert!(outline(Path::new("notes.txt"), "fn main() {}").is_none());
+        assert!(outline(Path::new("Makefile"), "all:").is_none());
+    }
+}
@@ -1,7 +1,13 @@
 //! 路径验证模块 - 防止路径穿越攻击
 //!
 //! 提供安全的路径验证功能，确保所有文件操作都在工作目录内进行。
+//!
+//! 配置了额外的命名根目录时，`名称:相对路径` 形式的路径在对应根目录内解析，
+//! 同样不能越出该根目录，且只能读取。
 
+use super::glob;
+use super::ignore::IgnoreRules;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 /// 路径验证错误类型
@@ -17,6 +23,35 @@ pub enum PathValidationError {
     WorkspaceDirError(String),
     /// 路径规范化失败
     CanonicalizationFailed(String),
+    /// 写入路径不在允许写入的目录内
+    NotWritable(String),
+    /// 路径经过符号链接（`reject` 策略，或链接目标不存在无法验证）
+    SymlinkNotAllowed(String),
+    /// 路径匹配忽略规则
+    PathIgnored(String),
+    /// `名称:路径` 中的根目录名称未配置
+    UnknownRoot(String),
+}
+
+/// 请求路径中包含符号链接时的处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// 解析符号链接，目标仍在工作空间内时允许访问
+    #[default]
+    Resolve,
+    /// 拒绝任何经过符号链接的路径
+    Reject,
+}
+
+impl SymlinkPolicy {
+    /// 解析配置中的名称
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "resolve" => Some(SymlinkPolicy::Resolve),
+            "reject" => Some(SymlinkPolicy::Reject),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for PathValidationError {
@@ -32,11 +67,23 @@ impl std::fmt::Display for PathValidationError {
                 write!(f, "Path not found: {}", path)
             }
             PathValidationError::WorkspaceDirError(msg) => {
-                write!(f, "Failed to get workspace directory: {}", msg)
+                write!(f, "Workspace directory unavailable: {}", msg)
             }
             PathValidationError::CanonicalizationFailed(msg) => {
                 write!(f, "Invalid path: {}", msg)
             }
+            PathValidationError::NotWritable(path) => {
+                write!(f, "Path is outside the writable roots: {}", path)
+            }
+            PathValidationError::SymlinkNotAllowed(path) => {
+                write!(f, "Symlinks are not allowed in path: {}", path)
+            }
+            PathValidationError::PathIgnored(path) => {
+                write!(f, "Path is ignored by the workspace ignore rules: {}", path)
+            }
+            PathValidationError::UnknownRoot(name) => {
+                write!(f, "Unknown root: {} (not configured in extra_roots)", name)
+            }
         }
     }
 }
@@ -46,26 +93,105 @@ impl std::error::Error for PathValidationError {}
 /// 路径验证器
 ///
 /// 用于验证文件路径是否安全，防止路径穿越攻击。
+/// 工作空间根目录在创建时规范化一次，由所有工具调用共享。
+#[derive(Clone)]
 pub struct PathValidator {
     workspace_root: PathBuf,
+    /// 规范化后的工作空间根目录；创建时根目录不存在则为 `None`
+    canonical_root: Option<PathBuf>,
+    /// 允许写入的目录（相对工作空间根目录）；为空时整个工作空间都可写入
+    writable_roots: Vec<PathBuf>,
+    symlink_policy: SymlinkPolicy,
+    /// 忽略规则（`.mentat/ignore`），匹配的路径不能读取或写入
+    ignore: IgnoreRules,
+    /// 额外的命名根目录（只读），各自使用独立的验证器
+    extra_roots: HashMap<String, PathValidator>,
 }
 
 impl PathValidator {
     /// 创建新的路径验证器
     ///
     /// 使用当前工作目录作为工作空间根目录。
+    #[allow(dead_code)]
     pub fn new() -> Result<Self, PathValidationError> {
         let workspace_root = std::env::current_dir()
             .map_err(|e| PathValidationError::WorkspaceDirError(e.to_string()))?;
-        Ok(Self { workspace_root })
+        Ok(Self::with_root(workspace_root))
     }
 
     /// 使用指定的工作空间根目录创建验证器
-    #[allow(dead_code)]
     pub fn with_root(root: PathBuf) -> Self {
         Self {
+            canonical_root: root.canonicalize().ok(),
             workspace_root: root,
+            writable_roots: Vec::new(),
+            symlink_policy: SymlinkPolicy::default(),
+            ignore: IgnoreRules::default(),
+            extra_roots: HashMap::new(),
+        }
+    }
+
+    /// 设置忽略规则
+    pub fn with_ignore_rules(mut self, ignore: IgnoreRules) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    /// 设置路径中包含符号链接时的处理策略
+    pub fn with_symlink_policy(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlink_policy = policy;
+        for root in self.extra_roots.values_mut() {
+            root.symlink_policy = policy;
         }
+        self
+    }
+
+    /// 添加额外的命名根目录，通过 `名称:相对路径` 读取其中的文件
+    ///
+    /// 根目录应为用户配置的已存在目录；其中的路径同样不能越出根目录，且不能写入。
+    pub fn with_extra_roots(mut self, roots: HashMap<String, PathBuf>) -> Self {
+        self.extra_roots = roots
+            .into_iter()
+            .map(|(name, root)| {
+                let validator =
+                    PathValidator::with_root(root).with_symlink_policy(self.symlink_policy);
+                (name, validator)
+            })
+            .collect();
+        self
+    }
+
+    /// 解析 `名称:相对路径`；未配置额外根目录，或冒号前不是合法名称时按普通路径处理
+    fn named_root<'a>(
+        &self,
+        path: &'a str,
+    ) -> Result<Option<(&PathValidator, &'a str)>, PathValidationError> {
+        if self.extra_roots.is_empty() {
+            return Ok(None);
+        }
+        let Some((name, rest)) = path.split_once(':') else {
+            return Ok(None);
+        };
+        if !is_root_name(name) {
+            return Ok(None);
+        }
+        match self.extra_roots.get(name) {
+            Some(root) => Ok(Some((root, rest))),
+            None => Err(PathValidationError::UnknownRoot(name.to_string())),
+        }
+    }
+
+    /// 限制写入操作只能发生在指定目录（相对工作空间根目录）内
+    ///
+    /// 读取操作不受影响，仍可访问整个工作空间。
+    pub fn with_writable_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.writable_roots = roots;
+        self
+    }
+
+    /// 工作空间根目录
+    pub fn root(&self) -> &Path {
+        &self.workspace_root
     }
 
     /// 验证路径是否安全（用于读取操作）
@@ -75,7 +201,11 @@ impl PathValidator {
     /// 2. 路径不包含路径穿越序列
     /// 3. 规范化后的路径在工作目录内
     /// 4. 路径存在
+    /// 5. 路径不匹配忽略规则
     pub fn validate_for_read(&self, path: &str) -> Result<PathBuf, PathValidationError> {
+        if let Some((root, relative)) = self.named_root(path)? {
+            return root.validate_for_read(relative);
+        }
         let validated = self.validate_path(path)?;
 
         // 对于读取操作，路径必须存在
@@ -83,6 +213,7 @@ impl PathValidator {
             return Err(PathValidationError::PathNotFound(path.to_string()));
         }
 
+        self.check_ignored(path, &validated)?;
         Ok(validated)
     }
 
@@ -92,10 +223,75 @@ impl PathValidator {
     /// 1. 路径不是绝对路径
     /// 2. 路径不包含路径穿越序列
     /// 3. 规范化后的路径在工作目录内
+    /// 4. 配置了允许写入的目录时，规范化后的路径在其中之一内
+    /// 5. 路径不匹配忽略规则
     ///
     /// 注意：写入操作不要求路径存在
     pub fn validate_for_write(&self, path: &str) -> Result<PathBuf, PathValidationError> {
-        self.validate_path(path)
+        // 额外的根目录只读
+        if self.named_root(path)?.is_some() {
+            return Err(PathValidationError::NotWritable(path.to_string()));
+        }
+        let validated = self.validate_path(path)?;
+
+        if !self.is_writable(&validated) {
+            return Err(PathValidationError::NotWritable(path.to_string()));
+        }
+
+        self.check_ignored(path, &validated)?;
+        Ok(validated)
+    }
+
+    /// 遍历工作区内 `relative_dir` 下的文件，跳过被忽略的目录和文件
+    ///
+    /// 被忽略的目录整个不进入，所有遍历目录的工具因此使用同一套排除规则。
+    pub fn walk(&self, relative_dir: &str, visit: &mut dyn FnMut(&str)) {
+        let skip = |path: &str, is_dir: bool| self.ignore.is_ignored(Path::new(path), is_dir);
+        glob::walk(&self.workspace_root, relative_dir, &skip, visit);
+    }
+
+    /// 在工作区内展开 glob 模式，结果不含被忽略的文件
+    pub fn expand(&self, pattern: &str) -> Vec<String> {
+        let skip = |path: &str, is_dir: bool| self.ignore.is_ignored(Path::new(path), is_dir);
+        glob::expand(&self.workspace_root, pattern, &skip)
+    }
+
+    /// 忽略规则检查，与穿越等安全检查相互独立
+    fn check_ignored(&self, path: &str, validated: &Path) -> Result<(), PathValidationError> {
+        if self.ignore.is_ignored(Path::new(path), validated.is_dir()) {
+            return Err(PathValidationError::PathIgnored(path.to_string()));
+        }
+        Ok(())
+    }
+
+    /// 验证路径是否安全（用于只查询元数据的操作）
+    ///
+    /// 与读取相同的检查，但不要求路径存在，也不受允许写入的目录限制。
+    pub fn validate_for_lookup(&self, path: &str) -> Result<PathBuf, PathValidationError> {
+        match self.named_root(path)? {
+            Some((root, relative)) => root.validate_for_lookup(relative),
+            None => self.validate_path(path),
+        }
+    }
+
+    /// 检查路径是否在允许写入的目录内（按规范化后的路径比较，符号链接不能绕过限制）
+    fn is_writable(&self, path: &Path) -> bool {
+        if self.writable_roots.is_empty() {
+            return true;
+        }
+        let resolved = self.resolve(path);
+        self.writable_roots
+            .iter()
+            .any(|root| resolved.starts_with(self.resolve(&self.workspace_root.join(root))))
+    }
+
+    /// 规范化最近的存在的祖先目录，再拼接其余不存在的部分
+    fn resolve(&self, path: &Path) -> PathBuf {
+        let existing = self.find_nearest_existing_ancestor(path);
+        let rest = path.strip_prefix(&existing).unwrap_or(Path::new(""));
+        existing
+            .canonicalize()
+            .map_or_else(|_| path.to_path_buf(), |canonical| canonical.join(rest))
     }
 
     /// 内部路径验证逻辑
@@ -103,8 +299,9 @@ impl PathValidator {
     /// 验证步骤：
     /// 1. 拒绝绝对路径
     /// 2. 检查路径组件中是否包含 ".."
-    /// 3. 构建完整路径并规范化
-    /// 4. 确保路径在工作目录内
+    /// 3. 按策略检查路径中的符号链接
+    /// 4. 构建完整路径并规范化
+    /// 5. 确保路径在工作目录内
     fn validate_path(&self, path: &str) -> Result<PathBuf, PathValidationError> {
         let requested = Path::new(path);
 
@@ -118,14 +315,25 @@ impl PathValidator {
             return Err(PathValidationError::PathTraversalDetected);
         }
 
-        // 步骤 3: 构建完整路径
+        // 工作区在会话中途被删除时，后续所有路径都无法解析
+        if !self.workspace_exists() {
+            return Err(PathValidationError::WorkspaceDirError(format!(
+                "{} no longer exists; cd into an existing directory and restart",
+                self.workspace_root.display()
+            )));
+        }
+        let canonical_workspace = self.get_canonical_workspace()?;
+
+        // 步骤 3: 检查路径中的符号链接
+        self.check_symlinks(path, requested, &canonical_workspace)?;
+
+        // 步骤 4: 构建完整路径
         let full_path = self.workspace_root.join(requested);
 
-        // 步骤 4: 规范化路径并验证在工作目录内
+        // 步骤 5: 规范化路径并验证在工作目录内
         let canonical_path = self.canonicalize_path(&full_path, requested)?;
-        let canonical_workspace = self.get_canonical_workspace()?;
 
-        // 步骤 5: 验证路径在工作目录内
+        // 步骤 6: 验证路径在工作目录内
         if !self.is_within_workspace(&canonical_path, &canonical_workspace) {
             return Err(PathValidationError::PathTraversalDetected);
         }
@@ -133,6 +341,40 @@ impl PathValidator {
         Ok(full_path)
     }
 
+    /// 逐级检查请求路径的每个组件是否为符号链接
+    ///
+    /// 即使最终路径尚不存在（写入新文件），中间的符号链接也会被检查：
+    /// `reject` 策略直接拒绝；`resolve` 策略要求链接目标仍在工作空间内。
+    /// 目标不存在的悬空链接无法验证，两种策略下都拒绝。
+    fn check_symlinks(
+        &self,
+        path: &str,
+        requested: &Path,
+        canonical_workspace: &Path,
+    ) -> Result<(), PathValidationError> {
+        let mut current = self.workspace_root.clone();
+        for component in requested.components() {
+            current.push(component);
+            let metadata = match current.symlink_metadata() {
+                Ok(metadata) => metadata,
+                // 之后的组件都不存在，也就不会是符号链接
+                Err(_) => break,
+            };
+            if !metadata.file_type().is_symlink() {
+                continue;
+            }
+            if self.symlink_policy == SymlinkPolicy::Reject {
+                return Err(PathValidationError::SymlinkNotAllowed(path.to_string()));
+            }
+            match current.canonicalize() {
+                Ok(target) if target.starts_with(canonical_workspace) => {}
+                Ok(_) => return Err(PathValidationError::PathTraversalDetected),
+                Err(_) => return Err(PathValidationError::SymlinkNotAllowed(path.to_string())),
+            }
+        }
+        Ok(())
+    }
+
     /// 检查路径是否包含父目录组件 (..)
     fn contains_parent_dir(&self, path: &Path) -> bool {
         path.components()
@@ -182,11 +424,20 @@ impl PathValidator {
         }
     }
 
-    /// 获取规范化的工作目录
+    /// 工作区根目录是否仍然存在
+    pub fn workspace_exists(&self) -> bool {
+        self.workspace_root.is_dir()
+    }
+
+    /// 获取规范化的工作目录（优先使用创建时的缓存）
     fn get_canonical_workspace(&self) -> Result<PathBuf, PathValidationError> {
-        self.workspace_root
-            .canonicalize()
-            .map_err(|e| PathValidationError::WorkspaceDirError(e.to_string()))
+        match &self.canonical_root {
+            Some(root) => Ok(root.clone()),
+            None => self
+                .workspace_root
+                .canonicalize()
+                .map_err(|e| PathValidationError::WorkspaceDirError(e.to_string())),
+        }
     }
 
     /// 检查路径是否在工作目录内
@@ -217,8 +468,16 @@ impl PathValidator {
     }
 }
 
+/// 额外根目录的名称只能包含字母、数字、`_` 和 `-`
+pub fn is_root_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
 // 注意：不实现 Default trait，因为 PathValidator::new() 可能失败
-// 使用者应该显式调用 PathValidator::new() 并处理错误
+// 工具通过 ToolContext 共享同一个 PathValidator::with_root() 创建的验证器
 
 #[cfg(test)]
 mod tests {
@@ -301,6 +560,223 @@ mod tests {
         assert!(matches!(result, Err(PathValidationError::PathNotFound(_))));
     }
 
+    #[test]
+    fn test_validator_with_custom_root() {
+        let root = std::env::temp_dir().join("mentat_test_validator_root");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs/guide.md"), "").unwrap();
+
+        let validator = PathValidator::with_root(root.clone());
+        assert_eq!(validator.root(), root.as_path());
+        assert_eq!(
+            validator.validate_for_read("docs/guide.md").unwrap(),
+            root.join("docs/guide.md")
+        );
+        assert_eq!(
+            validator.validate_for_write("docs/new.md").unwrap(),
+            root.join("docs/new.md")
+        );
+        // 当前目录下存在的文件在该根目录下不存在
+        assert!(matches!(
+            validator.validate_for_read("Cargo.toml"),
+            Err(PathValidationError::PathNotFound(_))
+        ));
+        assert!(validator.validate_for_write("../escape.txt").is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_extra_roots_resolve_named_paths() {
+        let base = std::env::temp_dir().join("mentat_test_extra_roots");
+        let _ = std::fs::remove_dir_all(&base);
+        let workspace = base.join("frontend");
+        let shared = base.join("shared");
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::write(shared.join("lib.rs"), "pub fn shared() {}").unwrap();
+        std::fs::write(workspace.join("app.rs"), "").unwrap();
+
+        let validator = PathValidator::with_root(workspace.clone())
+            .with_extra_roots(HashMap::from([("shared".to_string(), shared.clone())]));
+
+        assert_eq!(
+            validator.validate_for_read("shared:lib.rs").unwrap(),
+            shared.join("lib.rs")
+        );
+        assert!(validator.validate_for_read("app.rs").is_ok());
+        assert!(validator.validate_for_lookup("shared:missing.rs").is_ok());
+
+        // 未配置的名称被拒绝
+        assert!(matches!(
+            validator.validate_for_read("secrets:key.pem"),
+            Err(PathValidationError::UnknownRoot(name)) if name == "secrets"
+        ));
+        // 额外根目录内同样不能穿越或使用绝对路径，也不能写入
+        assert!(matches!(
+            validator.validate_for_read("shared:../frontend/app.rs"),
+            Err(PathValidationError::PathTraversalDetected)
+        ));
+        assert!(validator.validate_for_read("shared:/etc/passwd").is_err());
+        assert!(matches!(
+            validator.validate_for_write("shared:lib.rs"),
+            Err(PathValidationError::NotWritable(_))
+        ));
+
+        // 未配置额外根目录时，带冒号的路径按普通路径处理
+        let plain = PathValidator::with_root(workspace.clone());
+        assert!(matches!(
+            plain.validate_for_read("shared:lib.rs"),
+            Err(PathValidationError::PathNotFound(_))
+        ));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_deleted_workspace_reports_clear_error() {
+        let root = std::env::temp_dir().join("mentat_test_validator_deleted");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("notes.md"), "").unwrap();
+
+        let validator = PathValidator::with_root(root.clone());
+        assert!(validator.validate_for_read("notes.md").is_ok());
+
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(!validator.workspace_exists());
+        for result in [
+            validator.validate_for_read("notes.md"),
+            validator.validate_for_write("new.md"),
+            validator.validate_for_lookup("."),
+        ] {
+            let err = result.unwrap_err();
+            assert!(matches!(err, PathValidationError::WorkspaceDirError(_)));
+            assert!(err.to_string().contains("no longer exists"), "{}", err);
+            assert!(err.to_string().contains("restart"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_shared_validator_handles_many_validations() {
+        let validator = create_test_validator();
+        let started = std::time::Instant::now();
+        for _ in 0..1000 {
+            assert!(validator.validate_for_read("src/main.rs").is_ok());
+        }
+        let elapsed = started.elapsed();
+        // 宽松上限，只用于发现每次调用都重建验证器之类的退化
+        assert!(elapsed < std::time::Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_writable_roots_restrict_writes_only() {
+        let validator = create_test_validator().with_writable_roots(vec![PathBuf::from("src")]);
+
+        assert!(validator.validate_for_write("src/x.rs").is_ok());
+        assert!(validator.validate_for_write("src/nested/new/y.rs").is_ok());
+        assert!(matches!(
+            validator.validate_for_write("README.md"),
+            Err(PathValidationError::NotWritable(_))
+        ));
+        // 前缀相同的兄弟目录不算在内
+        assert!(validator.validate_for_write("srcx/a.rs").is_err());
+        // 读取仍然覆盖整个工作空间
+        assert!(validator.validate_for_read("README.md").is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policies() {
+        use std::os::unix::fs::symlink;
+
+        let root = std::env::temp_dir().join("mentat_test_symlink_policy");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+        symlink("/etc", root.join("etc_link")).unwrap();
+        symlink(root.join("src"), root.join("src_link")).unwrap();
+        symlink(root.join("missing"), root.join("dangling")).unwrap();
+
+        let validator = PathValidator::with_root(root.clone());
+        // 指向工作空间外的链接：无论目标是否存在都被阻止
+        assert!(matches!(
+            validator.validate_for_read("etc_link/passwd"),
+            Err(PathValidationError::PathTraversalDetected)
+        ));
+        assert!(validator.validate_for_write("etc_link/new.conf").is_err());
+        assert!(validator
+            .validate_for_write("etc_link/nested/new.conf")
+            .is_err());
+        // 悬空链接无法验证目标
+        assert!(matches!(
+            validator.validate_for_write("dangling"),
+            Err(PathValidationError::SymlinkNotAllowed(_))
+        ));
+        // resolve 策略下工作空间内的链接可用
+        assert!(validator.validate_for_read("src_link/lib.rs").is_ok());
+
+        let validator =
+            PathValidator::with_root(root.clone()).with_symlink_policy(SymlinkPolicy::Reject);
+        assert!(matches!(
+            validator.validate_for_read("etc_link/passwd"),
+            Err(PathValidationError::SymlinkNotAllowed(_))
+        ));
+        assert!(matches!(
+            validator.validate_for_read("src_link/lib.rs"),
+            Err(PathValidationError::SymlinkNotAllowed(_))
+        ));
+        assert!(validator.validate_for_read("src/lib.rs").is_ok());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_ignored_paths_blocked() {
+        let root = std::env::temp_dir().join("mentat_test_ignore_rules");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join(".mentat")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join(".mentat/ignore"), "target/\n.env\n").unwrap();
+        std::fs::write(root.join("target/foo"), "").unwrap();
+        std::fs::write(root.join(".env"), "API_KEY=x").unwrap();
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+
+        let validator = PathValidator::with_root(root.clone())
+            .with_ignore_rules(IgnoreRules::load(&root, false));
+        assert!(matches!(
+            validator.validate_for_read("target/foo"),
+            Err(PathValidationError::PathIgnored(_))
+        ));
+        assert!(matches!(
+            validator.validate_for_read(".env"),
+            Err(PathValidationError::PathIgnored(_))
+        ));
+        assert!(matches!(
+            validator.validate_for_write("target/new.txt"),
+            Err(PathValidationError::PathIgnored(_))
+        ));
+        assert!(validator.validate_for_read("src/main.rs").is_ok());
+
+        // 遍历和展开时跳过被忽略的路径
+        let mut walked = Vec::new();
+        validator.walk("", &mut |file| walked.push(file.to_string()));
+        walked.retain(|file| !file.starts_with(".mentat"));
+        assert_eq!(walked, vec!["src/main.rs"]);
+        assert_eq!(
+            validator.expand("**/*"),
+            vec![".mentat/ignore", "src/main.rs"]
+        );
+        // 穿越检查先于忽略规则
+        assert!(matches!(
+            validator.validate_for_read("../.env"),
+            Err(PathValidationError::PathTraversalDetected)
+        ));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
     #[test]
     fn test_valid_nested_path() {
         let validator = create_test_validator();
@@ -0,0 +1,234 @@
+//! read_many_files 工具 - 一次读取多个文件
+//!
+//! 了解一个模块通常需要连续读取多个文件，每次都是一个 API 往返。
+//! 每个文件按 read_file 的规则读取，单个文件失败不影响其他文件；
+//! 所有文件内容合计不超过 [`MAX_TOTAL_BYTES`]。
+
+use super::read_file::{execute_read_file, ReadFileInput};
+use super::{to_json, Tool, ToolContext};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// 一次最多读取的文件数
+const MAX_PATHS: usize = 50;
+
+/// 所有文件内容合计的最大字节数，超出部分截断
+const MAX_TOTAL_BYTES: usize = 256 * 1024;
+
+/// read_many_files 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct ReadManyFilesInput {
+    pub paths: Vec<String>,
+}
+
+/// 单个文件的读取结果
+#[derive(Debug, Serialize)]
+pub struct FileResult {
+    pub path: String,
+    pub success: bool,
+    pub content: Option<String>,
+    pub total_lines: Option<usize>,
+    /// 内容因总大小上限被截断
+    pub truncated: bool,
+    pub error: Option<String>,
+}
+
+/// read_many_files 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct ReadManyFilesOutput {
+    pub success: bool,
+    pub files: Vec<FileResult>,
+    pub error: Option<String>,
+}
+
+impl ReadManyFilesOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            files: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
+
+/// ReadManyFiles 工具实现
+pub struct ReadManyFilesTool;
+
+impl Tool for ReadManyFilesTool {
+    fn name(&self) -> &'static str {
+        "read_many_files"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "read_many_files",
+            "description": "Read several files in one call instead of calling read_file repeatedly, e.g. to understand a module. Returns one result per path with its content and total_lines, or an error for that path only. The combined content is capped; files past the cap are truncated and marked truncated, so read them again with read_file ranges if needed.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "paths": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "The files to read (relative or absolute)"
+                    }
+                },
+                "required": ["paths"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: ReadManyFilesInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => {
+                return to_json(&ReadManyFilesOutput::failure(format!(
+                    "Invalid input: {}",
+                    e
+                )))
+            }
+        };
+
+        let result = execute_read_many_files(ctx, &tool_input, MAX_TOTAL_BYTES);
+        to_json(&result)
+    }
+}
+
+/// 依次读取每个文件，`budget` 为所有内容合计的字节上限
+fn execute_read_many_files(
+    ctx: &ToolContext,
+    input: &ReadManyFilesInput,
+    budget: usize,
+) -> ReadManyFilesOutput {
+    if input.paths.is_empty() {
+        return ReadManyFilesOutput::failure("paths must not be empty".to_string());
+    }
+    if input.paths.len() > MAX_PATHS {
+        return ReadManyFilesOutput::failure(format!(
+            "Too many paths ({}), at most {} per call",
+            input.paths.len(),
+            MAX_PATHS
+        ));
+    }
+
+    let mut remaining = budget;
+    let files = input
+        .paths
+        .iter()
+        .map(|path| {
+            let output = execute_read_file(
+                ctx,
+                &ReadFileInput {
+                    file_path: path.clone(),
+                    start_line: None,
+                    end_line: None,
+                    with_line_numbers: None,
+                    mode: None,
+                },
+            );
+            let (content, truncated) = match output.content {
+                Some(content) => {
+                    let (content, truncated) = truncate_to(content, remaining);
+                    remaining -= content.len();
+                    (Some(content), truncated)
+                }
+                None => (None, false),
+            };
+            FileResult {
+                path: path.clone(),
+                success: output.success,
+                content,
+                total_lines: output.total_lines,
+                truncated,
+                error: output.error,
+            }
+        })
+        .collect();
+
+    ReadManyFilesOutput {
+        success: true,
+        files,
+        error: None,
+    }
+}
+
+/// 把内容截断到 `limit` 字节以内，尽量在行边界截断
+fn truncate_to(content: String, limit: usize) -> (String, bool) {
+    if content.len() <= limit {
+        return (content, false);
+    }
+    let mut end = limit;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = content[..end].rfind('\n').map_or(end, |i| i + 1);
+    (content[..end].to_string(), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+    use std::fs;
+
+    fn run(input: Value) -> Value {
+        serde_json::from_str(&ReadManyFilesTool.execute(&test_context(), &input)).unwrap()
+    }
+
+    #[test]
+    fn test_mixed_results() {
+        let result = run(serde_json::json!({"paths": ["Cargo.toml", "no_such_file.rs"]}));
+        assert_eq!(result["success"], true);
+
+        let files = result["files"].as_array().unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0]["path"], "Cargo.toml");
+        assert_eq!(files[0]["success"], true);
+        assert!(files[0]["content"].as_str().unwrap().contains("[package]"));
+        assert!(files[0]["total_lines"].as_u64().unwrap() > 0);
+
+        assert_eq!(files[1]["path"], "no_such_file.rs");
+        assert_eq!(files[1]["success"], false);
+        assert!(files[1]["content"].is_null());
+        assert!(files[1]["error"].as_str().is_some());
+    }
+
+    #[test]
+    fn test_total_bytes_cap() {
+        fs::create_dir_all("target/test_read_many").unwrap();
+        let line = "x".repeat(99) + "\n";
+        fs::write("target/test_read_many/a.txt", line.repeat(6)).unwrap();
+        fs::write("target/test_read_many/b.txt", line.repeat(6)).unwrap();
+        let input = ReadManyFilesInput {
+            paths: vec![
+                "target/test_read_many/a.txt".to_string(),
+                "target/test_read_many/b.txt".to_string(),
+                "target/test_read_many/a.txt".to_string(),
+            ],
+        };
+
+        let result = execute_read_many_files(&test_context(), &input, 850);
+        let files = &result.files;
+        assert!(!files[0].truncated);
+        assert_eq!(files[0].content.as_ref().unwrap().len(), 600);
+        // 在行边界截断
+        assert!(files[1].truncated);
+        assert_eq!(files[1].content.as_deref().unwrap(), line.repeat(2));
+        // 剩余预算不足一行时按字节截断
+        assert!(files[2].truncated);
+        assert_eq!(files[2].content.as_deref().unwrap(), "x".repeat(50));
+        assert_eq!(files[2].total_lines, Some(6));
+
+        let _ = fs::remove_dir_all("target/test_read_many");
+    }
+
+    #[test]
+    fn test_invalid_inputs_rejected() {
+        assert_eq!(run(serde_json::json!({"paths": []}))["success"], false);
+        assert_eq!(
+            run(serde_json::json!({"paths": "Cargo.toml"}))["success"],
+            false
+        );
+        let paths = vec!["Cargo.toml"; MAX_PATHS + 1];
+        assert_eq!(run(serde_json::json!({ "paths": paths }))["success"], false);
+    }
+}
@@ -0,0 +1,354 @@
+//! replace_in_files 工具 - 在多个文件中批量替换文本
+
+use super::{to_json, Tool, ToolContext};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+
+/// replace_in_files 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct ReplaceInFilesInput {
+    pub pattern: String,
+    pub replacement: String,
+    #[serde(default)]
+    pub is_regex: bool,
+    pub path: Option<String>,
+    /// 只报告将要进行的替换，不写入文件
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// 一个文件中的替换
+#[derive(Debug, Serialize)]
+pub struct FileReplacement {
+    pub file: String,
+    pub replacements: usize,
+}
+
+/// 含有匹配但未被修改的文件
+#[derive(Debug, Serialize)]
+pub struct SkippedFile {
+    pub file: String,
+    pub reason: String,
+}
+
+/// replace_in_files 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct ReplaceInFilesOutput {
+    pub success: bool,
+    pub files: Vec<FileReplacement>,
+    pub total_replacements: usize,
+    /// 是否只是预览（未写入文件）
+    pub dry_run: bool,
+    pub skipped: Vec<SkippedFile>,
+    pub error: Option<String>,
+}
+
+impl ReplaceInFilesOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            files: Vec::new(),
+            total_replacements: 0,
+            dry_run: false,
+            skipped: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
+
+/// 替换器：正则或普通子串
+enum Replacer {
+    Regex(Regex),
+    Substring(String),
+}
+
+impl Replacer {
+    /// 返回替换后的文本和替换次数
+    fn replace(&self, text: &str, replacement: &str) -> (String, usize) {
+        match self {
+            Replacer::Regex(re) => {
+                let count = re.find_iter(text).count();
+                (re.replace_all(text, replacement).into_owned(), count)
+            }
+            Replacer::Substring(s) => {
+                let count = text.matches(s.as_str()).count();
+                (text.replace(s.as_str(), replacement), count)
+            }
+        }
+    }
+}
+
+/// ReplaceInFiles 工具实现
+pub struct ReplaceInFilesTool;
+
+impl Tool for ReplaceInFilesTool {
+    fn name(&self) -> &'static str {
+        "replace_in_files"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "replace_in_files",
+            "description": "Replace text in every matching file under a directory in one call, e.g. to rename a symbol across the project. Returns the number of replacements per file. Binary files and .git/target/node_modules directories are skipped. Run with dry_run first to check which files would change.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "The text to replace (a regular expression when is_regex is true)"
+                    },
+                    "replacement": {
+                        "type": "string",
+                        "description": "The replacement text. With is_regex, $1 or ${name} refer to capture groups"
+                    },
+                    "is_regex": {
+                        "type": "boolean",
+                        "description": "Treat pattern as a regular expression (default false)"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "A file or directory to limit the replacement to, relative to the workspace (default: the whole workspace)"
+                    },
+                    "dry_run": {
+                        "type": "boolean",
+                        "description": "Only report the replacements that would be made, without writing (default false)"
+                    }
+                },
+                "required": ["pattern", "replacement"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: ReplaceInFilesInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => {
+                return to_json(&ReplaceInFilesOutput::failure(format!(
+                    "Invalid input: {}",
+                    e
+                )))
+            }
+        };
+
+        let result = execute_replace_in_files(ctx, &tool_input);
+        to_json(&result)
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn supports_dry_run(&self) -> bool {
+        true
+    }
+}
+
+/// 执行批量替换
+fn execute_replace_in_files(
+    ctx: &ToolContext,
+    input: &ReplaceInFilesInput,
+) -> ReplaceInFilesOutput {
+    if input.pattern.is_empty() {
+        return ReplaceInFilesOutput::failure("pattern must not be empty".to_string());
+    }
+
+    let replacer = if input.is_regex {
+        match Regex::new(&input.pattern) {
+            Ok(re) => Replacer::Regex(re),
+            Err(e) => return ReplaceInFilesOutput::failure(format!("Invalid regex: {}", e)),
+        }
+    } else {
+        Replacer::Substring(input.pattern.clone())
+    };
+
+    let validator = ctx.validator();
+
+    // 安全检查：验证替换范围
+    let start = input.path.as_deref().unwrap_or(".");
+    let start_path = match validator.validate_for_read(start) {
+        Ok(p) => p,
+        Err(e) => return ReplaceInFilesOutput::failure(e.to_string()),
+    };
+
+    let root = ctx.workspace_root();
+    let files = if start_path.is_dir() {
+        let relative_dir = start.trim_start_matches("./").trim_end_matches('/');
+        let relative_dir = if relative_dir == "." {
+            ""
+        } else {
+            relative_dir
+        };
+
+        let mut files = Vec::new();
+        validator.walk(relative_dir, &mut |file| files.push(file.to_string()));
+        files.sort();
+        files
+    } else {
+        vec![start.to_string()]
+    };
+
+    let dry_run = input.dry_run || ctx.is_dry_run();
+    let mut output = ReplaceInFilesOutput {
+        success: true,
+        files: Vec::new(),
+        total_replacements: 0,
+        dry_run,
+        skipped: Vec::new(),
+        error: None,
+    };
+
+    for file in files {
+        let Ok(bytes) = fs::read(root.join(&file)) else {
+            continue;
+        };
+        // 跳过二进制文件
+        if bytes.contains(&0) {
+            continue;
+        }
+        let Ok(text) = String::from_utf8(bytes) else {
+            continue;
+        };
+
+        let (replaced, count) = replacer.replace(&text, &input.replacement);
+        if count == 0 || replaced == text {
+            continue;
+        }
+
+        // 每个要修改的文件都要通过写入检查（如 writable_roots）
+        let mut skip = |reason: String| {
+            output.skipped.push(SkippedFile {
+                file: file.clone(),
+                reason,
+            })
+        };
+        let path = match validator.validate_for_write(&file) {
+            Ok(p) => p,
+            Err(e) => {
+                skip(e.to_string());
+                continue;
+            }
+        };
+        if !dry_run {
+            if let Err(e) = fs::write(&path, replaced) {
+                skip(format!("Failed to write file: {}", e));
+                continue;
+            }
+        }
+
+        output.total_replacements += count;
+        output.files.push(FileReplacement {
+            file,
+            replacements: count,
+        });
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+
+    fn setup(root: &str) {
+        let _ = fs::remove_dir_all(root);
+        fs::create_dir_all(format!("{}/nested", root)).unwrap();
+        fs::write(
+            format!("{}/a.rs", root),
+            "fn old_name() {}\nfn main() { old_name(); old_name(); }\n",
+        )
+        .unwrap();
+        fs::write(format!("{}/nested/b.rs", root), "use crate::old_name;\n").unwrap();
+        fs::write(format!("{}/c.txt", root), "nothing here\n").unwrap();
+        fs::write(format!("{}/d.bin", root), b"old_name\x00").unwrap();
+    }
+
+    fn replace(input: Value) -> Value {
+        serde_json::from_str(&ReplaceInFilesTool.execute(&test_context(), &input)).unwrap()
+    }
+
+    #[test]
+    fn test_replace_across_files() {
+        let root = "target/test_replace_in_files";
+        setup(root);
+
+        let result = replace(serde_json::json!({
+            "pattern": "old_name",
+            "replacement": "new_name",
+            "path": root
+        }));
+        assert_eq!(result["success"], true);
+        assert_eq!(result["total_replacements"], 4);
+        assert_eq!(
+            result["files"],
+            serde_json::json!([
+                {"file": format!("{}/a.rs", root), "replacements": 3},
+                {"file": format!("{}/nested/b.rs", root), "replacements": 1}
+            ])
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}/a.rs", root)).unwrap(),
+            "fn new_name() {}\nfn main() { new_name(); new_name(); }\n"
+        );
+        assert_eq!(
+            fs::read_to_string(format!("{}/nested/b.rs", root)).unwrap(),
+            "use crate::new_name;\n"
+        );
+        // 二进制文件不被修改
+        assert_eq!(
+            fs::read(format!("{}/d.bin", root)).unwrap(),
+            b"old_name\x00"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_regex_with_capture_groups_and_dry_run() {
+        let root = "target/test_replace_in_files_regex";
+        setup(root);
+
+        let result = replace(serde_json::json!({
+            "pattern": r"fn (\w+)\(\)",
+            "replacement": "pub fn $1()",
+            "is_regex": true,
+            "path": root,
+            "dry_run": true
+        }));
+        assert_eq!(result["dry_run"], true);
+        assert_eq!(result["total_replacements"], 2);
+        assert_eq!(result["files"][0]["replacements"], 2);
+        // dry-run 不写入
+        assert!(fs::read_to_string(format!("{}/a.rs", root))
+            .unwrap()
+            .starts_with("fn old_name()"));
+
+        replace(serde_json::json!({
+            "pattern": r"fn (\w+)\(\)",
+            "replacement": "pub fn $1()",
+            "is_regex": true,
+            "path": format!("{}/a.rs", root)
+        }));
+        assert!(fs::read_to_string(format!("{}/a.rs", root))
+            .unwrap()
+            .starts_with("pub fn old_name() {}\npub fn main()"));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_invalid_input_rejected() {
+        let result = replace(serde_json::json!({"pattern": "", "replacement": "x"}));
+        assert_eq!(result["success"], false);
+
+        let result =
+            replace(serde_json::json!({"pattern": "(", "replacement": "x", "is_regex": true}));
+        assert!(result["error"].as_str().unwrap().contains("Invalid regex"));
+
+        let result =
+            replace(serde_json::json!({"pattern": "a", "replacement": "b", "path": "../"}));
+        assert_eq!(result["success"], false);
+    }
+}
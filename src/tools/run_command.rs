@@ -0,0 +1,402 @@
+//! run_command 工具 - 运行白名单中的命令
+//!
+//! 命令直接以参数列表启动，不经过 shell，因此参数中的 `;`、`|`、`$()` 等
+//! 不会被解释。工作目录固定为工作区根目录。
+//!
+//! 子进程在独立的进程组中运行，超时后终止整个进程组：`cargo test` 启动的测试程序、
+//! rustc 等孙进程也一并结束，不会继续占用输出管道让工具卡住。
+//!
+//! 白名单默认为空，需要在配置的 `allowed_commands` 中显式开启。白名单只限制程序名，
+//! 不限制程序会做什么：`cargo build` 会编译并运行 build.rs 和过程宏，
+//! `git -c core.pager=...`、`core.sshCommand`、别名等配置可以执行任意命令。
+//! 对 git 会拒绝 `-c`、`--config-env`、`--exec-path` 等改写配置或可执行路径的参数，
+//! 但仓库自身的 `.git/config` 仍然生效；加入白名单前应确认信任工作区中的内容。
+
+use super::{to_json, Tool, ToolContext};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Read;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// git 中可以改写配置或可执行文件路径、从而执行任意命令的参数
+const GIT_DENIED_OPTIONS: &[&str] = &["-c", "--config", "--config-env", "--exec-path"];
+
+/// stdout / stderr 各自保留的最大字节数（保留末尾，错误信息通常在最后）
+const MAX_OUTPUT_BYTES: usize = 16 * 1024;
+
+/// 轮询子进程是否退出的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 超时终止后等待读取剩余输出的时间；仍有进程占用管道时不再等待
+const KILL_GRACE: Duration = Duration::from_millis(500);
+
+/// run_command 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct RunCommandInput {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// run_command 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct RunCommandOutput {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+    pub error: Option<String>,
+}
+
+impl RunCommandOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            exit_code: None,
+            stdout: None,
+            stderr: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// RunCommand 工具实现
+pub struct RunCommandTool {
+    /// 允许执行的程序名
+    allowed: Vec<String>,
+    timeout: Duration,
+}
+
+impl RunCommandTool {
+    pub fn new(allowed: Vec<String>, timeout: Duration) -> Self {
+        Self { allowed, timeout }
+    }
+}
+
+impl Tool for RunCommandTool {
+    fn name(&self) -> &'static str {
+        "run_command"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "run_command",
+            "description": format!(
+                "Run a program in the workspace root and return its exit code, stdout and stderr. Use this to build or test your changes. The program is started directly, not through a shell, so pipes, redirects and globs are not available. {}",
+                allowed_summary(&self.allowed)
+            ),
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "command": {
+                        "type": "string",
+                        "description": "The program to run, e.g. \"cargo\""
+                    },
+                    "args": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Arguments passed to the program, e.g. [\"test\", \"--quiet\"]"
+                    }
+                },
+                "required": ["command"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: RunCommandInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&RunCommandOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = self.run(ctx, &tool_input);
+        to_json(&result)
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+}
+
+impl RunCommandTool {
+    /// 校验白名单后运行命令
+    fn run(&self, ctx: &ToolContext, input: &RunCommandInput) -> RunCommandOutput {
+        // 只接受裸程序名，避免通过路径绕过白名单
+        if input.command.contains(['/', '\\']) || !self.allowed.contains(&input.command) {
+            return RunCommandOutput::failure(format!(
+                "Command not allowed: {}. {}",
+                input.command,
+                allowed_summary(&self.allowed)
+            ));
+        }
+        if input.command == "git" {
+            if let Some(arg) = input.args.iter().find(|arg| is_denied_git_option(arg)) {
+                return RunCommandOutput::failure(format!(
+                    "git option not allowed: {} (it can run arbitrary commands)",
+                    arg
+                ));
+            }
+        }
+
+        let mut command = Command::new(&input.command);
+        command
+            .args(&input.args)
+            .current_dir(ctx.workspace_root())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        command.process_group(0);
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                return RunCommandOutput::failure(format!(
+                    "Failed to start {}: {}",
+                    input.command, e
+                ))
+            }
+        };
+
+        // 在线程中读取输出，避免管道写满导致子进程阻塞
+        let stdout = child.stdout.take().map(spawn_reader);
+        let stderr = child.stderr.take().map(spawn_reader);
+
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) if started.elapsed() >= self.timeout => {
+                    kill_process_group(&mut child);
+                    break None;
+                }
+                Ok(None) => thread::sleep(POLL_INTERVAL),
+                Err(e) => {
+                    return RunCommandOutput::failure(format!(
+                        "Failed to wait for {}: {}",
+                        input.command, e
+                    ))
+                }
+            }
+        };
+
+        // 超时终止后只等待一小段时间，脱离进程组的进程仍可能占用管道
+        let deadline = status.is_none().then(|| Instant::now() + KILL_GRACE);
+        let collect = |reader: Option<Receiver<Vec<u8>>>| {
+            let reader = reader?;
+            match deadline {
+                Some(deadline) => reader
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .ok(),
+                None => reader.recv().ok(),
+            }
+        };
+        let stdout = collect(stdout).map(|bytes| tail(&bytes));
+        let stderr = collect(stderr).map(|bytes| tail(&bytes));
+
+        match status {
+            Some(status) => RunCommandOutput {
+                success: status.success(),
+                exit_code: status.code(),
+                stdout,
+                stderr,
+                error: (!status.success()).then(|| format!("Command exited with {}", status)),
+            },
+            None => RunCommandOutput {
+                success: false,
+                exit_code: None,
+                stdout,
+                stderr,
+                error: Some(format!(
+                    "Command timed out after {}s and was killed",
+                    self.timeout.as_secs_f64()
+                )),
+            },
+        }
+    }
+}
+
+/// 终止子进程及其所在进程组中的所有进程
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: 只向 spawn 时创建的进程组发送信号，负的 pid 表示整个进程组
+        unsafe {
+            libc::kill(-pid, libc::SIGKILL);
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// 在后台线程读取管道的全部内容，读完后从返回的通道送出
+fn spawn_reader(mut pipe: impl Read + Send + 'static) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        let _ = sender.send(buf);
+    });
+    receiver
+}
+
+/// 解码输出并只保留末尾 [`MAX_OUTPUT_BYTES`] 字节
+fn tail(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    if text.len() <= MAX_OUTPUT_BYTES {
+        return text.into_owned();
+    }
+    let mut start = text.len() - MAX_OUTPUT_BYTES;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    format!(
+        "[truncated, {} bytes total]...{}",
+        text.len(),
+        &text[start..]
+    )
+}
+
+/// 描述白名单，供工具说明和错误信息使用
+fn allowed_summary(allowed: &[String]) -> String {
+    if allowed.is_empty() {
+        "No programs are allowed; the user must list them in allowed_commands in the config file."
+            .to_string()
+    } else {
+        format!("Allowed programs: {}.", allowed.join(", "))
+    }
+}
+
+/// 是否为 [`GIT_DENIED_OPTIONS`] 中的参数（含 `--exec-path=...` 形式）
+fn is_denied_git_option(arg: &str) -> bool {
+    let name = arg.split_once('=').map_or(arg, |(name, _)| name);
+    GIT_DENIED_OPTIONS.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+
+    fn tool(allowed: &[&str], timeout: Duration) -> RunCommandTool {
+        RunCommandTool::new(allowed.iter().map(|s| s.to_string()).collect(), timeout)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_allowlisted_command_runs() {
+        let input = serde_json::json!({"command": "echo", "args": ["hello", "; rm -rf /"]});
+        let result: Value = serde_json::from_str(
+            &tool(&["echo"], Duration::from_secs(10)).execute(&test_context(), &input),
+        )
+        .unwrap();
+
+        assert_eq!(result["success"], true);
+        assert_eq!(result["exit_code"], 0);
+        // 参数原样传递，不经过 shell 解释
+        assert_eq!(result["stdout"], "hello ; rm -rf /\n");
+    }
+
+    #[test]
+    fn test_command_not_in_allowlist_rejected() {
+        let tool = tool(&["cargo"], Duration::from_secs(10));
+        for command in ["rm", "/bin/echo", "./cargo"] {
+            let input = serde_json::json!({"command": command, "args": ["x"]});
+            let result: Value =
+                serde_json::from_str(&tool.execute(&test_context(), &input)).unwrap();
+            assert_eq!(result["success"], false);
+            assert!(result["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("Command not allowed"));
+        }
+    }
+
+    #[test]
+    fn test_empty_allowlist_rejects_everything() {
+        let tool = tool(&[], Duration::from_secs(10));
+        assert!(tool.definition()["description"]
+            .as_str()
+            .unwrap()
+            .contains("No programs are allowed"));
+        let input = serde_json::json!({"command": "ls"});
+        let result: Value = serde_json::from_str(&tool.execute(&test_context(), &input)).unwrap();
+        assert_eq!(result["success"], false);
+        assert!(result["error"]
+            .as_str()
+            .unwrap()
+            .contains("allowed_commands"));
+    }
+
+    #[test]
+    fn test_git_config_options_rejected() {
+        let tool = tool(&["git"], Duration::from_secs(10));
+        for args in [
+            vec!["-c", "core.pager=touch pwned", "log"],
+            vec!["--config-env=core.sshCommand=X", "fetch"],
+            vec!["--exec-path=/tmp", "status"],
+            vec!["--exec-path", "status"],
+        ] {
+            let input = serde_json::json!({"command": "git", "args": args});
+            let result: Value =
+                serde_json::from_str(&tool.execute(&test_context(), &input)).unwrap();
+            assert_eq!(result["success"], false, "{:?}", args);
+            assert!(result["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("git option not allowed"));
+        }
+        assert!(!is_denied_git_option("--cached"));
+        assert!(!is_denied_git_option("core.pager=less"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_nonzero_exit_and_timeout() {
+        let input = serde_json::json!({"command": "ls", "args": ["no_such_file_12345"]});
+        let result: Value = serde_json::from_str(
+            &tool(&["ls"], Duration::from_secs(10)).execute(&test_context(), &input),
+        )
+        .unwrap();
+        assert_eq!(result["success"], false);
+        assert_ne!(result["exit_code"], 0);
+        assert!(!result["stderr"].as_str().unwrap().is_empty());
+
+        let input = serde_json::json!({"command": "sleep", "args": ["5"]});
+        let started = Instant::now();
+        let result: Value = serde_json::from_str(
+            &tool(&["sleep"], Duration::from_millis(200)).execute(&test_context(), &input),
+        )
+        .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(4));
+        assert_eq!(result["success"], false);
+        assert!(result["error"].as_str().unwrap().contains("timed out"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_timeout_kills_grandchildren() {
+        // sh 启动的 sleep 继承了输出管道，只终止 sh 时读取输出会一直等到 sleep 结束
+        let input = serde_json::json!({"command": "sh", "args": ["-c", "sleep 5 & sleep 5; wait"]});
+        let started = Instant::now();
+        let result: Value = serde_json::from_str(
+            &tool(&["sh"], Duration::from_millis(200)).execute(&test_context(), &input),
+        )
+        .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(result["success"], false);
+        assert!(result["error"].as_str().unwrap().contains("timed out"));
+    }
+
+    #[test]
+    fn test_tail_keeps_end_of_output() {
+        let output = format!("{}END", "x".repeat(MAX_OUTPUT_BYTES));
+        let kept = tail(output.as_bytes());
+        assert!(kept.starts_with("[truncated"));
+        assert!(kept.ends_with("xEND"));
+    }
+}
@@ -0,0 +1,214 @@
+//! 按工具定义中的 input_schema 校验输入
+//!
+//! 模型偶尔会漏掉必填字段、拼错字段名或传错类型。serde 的报错（如
+//! `invalid type: string "10", expected usize`）不指明是哪个字段，模型难以据此修正。
+//! 执行工具前先按 schema 检查，报错中给出出错的字段和该工具接受的全部字段。
+//!
+//! 只支持内置工具用到的子集：`required`、`properties` 中的 `type`、`enum`
+//! 以及数组的 `items.type`。
+
+use serde_json::Value;
+
+/// 按 `schema` 校验 `input`，失败时返回指明字段的错误说明；没有 schema 时不校验
+pub fn validate_input(schema: &Value, input: &Value) -> Result<(), String> {
+    if schema.is_null() {
+        return Ok(());
+    }
+    check_input(schema, input).map_err(|e| format!("{}. {}", e, expected_fields(schema)))
+}
+
+fn check_input(schema: &Value, input: &Value) -> Result<(), String> {
+    let Some(fields) = input.as_object() else {
+        return Err(format!("input must be an object, got {}", type_name(input)));
+    };
+    let properties = schema.get("properties").and_then(Value::as_object);
+
+    for name in required(schema) {
+        if fields.get(name).is_none_or(Value::is_null) {
+            return Err(format!("missing required field '{}'", name));
+        }
+    }
+
+    let Some(properties) = properties else {
+        return Ok(());
+    };
+    for (name, value) in fields {
+        let Some(property) = properties.get(name) else {
+            return Err(format!("unknown field '{}'", name));
+        };
+        // 可选字段传 null 等同于省略
+        if !value.is_null() {
+            check_value(name, property, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// 检查单个字段的类型和取值
+fn check_value(path: &str, property: &Value, value: &Value) -> Result<(), String> {
+    if let Some(expected) = property.get("type").and_then(Value::as_str) {
+        if !matches_type(expected, value) {
+            return Err(format!(
+                "field '{}' must be {}, got {}",
+                path,
+                expected,
+                type_name(value)
+            ));
+        }
+    }
+
+    if let Some(allowed) = property.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let names: Vec<String> = allowed
+                .iter()
+                .map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string))
+                .collect();
+            return Err(format!(
+                "field '{}' must be one of: {}",
+                path,
+                names.join(", ")
+            ));
+        }
+    }
+
+    if let (Some(items), Some(elements)) = (property.get("items"), value.as_array()) {
+        for (i, element) in elements.iter().enumerate() {
+            check_value(&format!("{}[{}]", path, i), items, element)?;
+        }
+    }
+    Ok(())
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn required(schema: &Value) -> impl Iterator<Item = &str> {
+    schema
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+}
+
+/// 列出工具接受的字段，必填字段标注 (required)
+fn expected_fields(schema: &Value) -> String {
+    let required: Vec<&str> = required(schema).collect();
+    let fields: Vec<String> = schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(name, property)| {
+            let ty = property
+                .get("type")
+                .and_then(Value::as_str)
+                .unwrap_or("any");
+            if required.contains(&name.as_str()) {
+                format!("{}: {} (required)", name, ty)
+            } else {
+                format!("{}: {}", name, ty)
+            }
+        })
+        .collect();
+    if fields.is_empty() {
+        "This tool takes no fields".to_string()
+    } else {
+        format!("Expected fields: {}", fields.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "file_path": {"type": "string"},
+                "start_line": {"type": "integer"},
+                "mode": {"type": "string", "enum": ["full", "outline"]},
+                "paths": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["file_path"]
+        })
+    }
+
+    fn error(input: Value) -> String {
+        validate_input(&schema(), &input).unwrap_err()
+    }
+
+    #[test]
+    fn test_valid_input_accepted() {
+        assert!(validate_input(&schema(), &json!({"file_path": "a.rs"})).is_ok());
+        assert!(validate_input(
+            &schema(),
+            &json!({"file_path": "a.rs", "start_line": 3, "mode": "outline", "paths": ["b"]})
+        )
+        .is_ok());
+        // 可选字段为 null 视为省略
+        assert!(
+            validate_input(&schema(), &json!({"file_path": "a.rs", "start_line": null})).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_missing_required_field() {
+        let e = error(json!({"start_line": 1}));
+        assert!(
+            e.starts_with("missing required field 'file_path'."),
+            "{}",
+            e
+        );
+        assert!(e.contains("file_path: string (required)"), "{}", e);
+        assert!(e.contains("start_line: integer"), "{}", e);
+
+        assert!(error(json!({"file_path": null})).starts_with("missing required field"));
+    }
+
+    #[test]
+    fn test_wrong_types_name_the_field() {
+        assert!(error(json!({"file_path": 42}))
+            .starts_with("field 'file_path' must be string, got integer"));
+        assert!(error(json!({"file_path": "a", "start_line": "10"}))
+            .starts_with("field 'start_line' must be integer, got string"));
+        assert!(error(json!({"file_path": "a", "start_line": 1.5}))
+            .starts_with("field 'start_line' must be integer, got number"));
+        assert!(error(json!({"file_path": "a", "paths": ["b", 3]}))
+            .starts_with("field 'paths[1]' must be string, got integer"));
+        assert!(error(json!({"file_path": "a", "mode": "brief"}))
+            .starts_with("field 'mode' must be one of: full, outline"));
+    }
+
+    #[test]
+    fn test_malformed_input_shapes() {
+        assert!(error(json!("a.rs")).starts_with("input must be an object, got string"));
+        assert!(error(json!(null)).starts_with("input must be an object, got null"));
+        assert!(error(json!({"filepath": "a.rs", "file_path": "a.rs"}))
+            .starts_with("unknown field 'filepath'"));
+    }
+}
@@ -0,0 +1,284 @@
+//! search 工具 - 在工作区中按行搜索文本
+
+use super::{to_json, Tool, ToolContext};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+
+/// 默认最多返回的匹配数
+const DEFAULT_MAX_RESULTS: u32 = 100;
+
+/// 单行文本的最大长度（字符），超出部分截断
+const MAX_LINE_CHARS: usize = 300;
+
+/// search 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct SearchInput {
+    pub pattern: String,
+    pub path: Option<String>,
+    #[serde(default)]
+    pub is_regex: bool,
+    pub max_results: Option<u32>,
+}
+
+/// 一处匹配
+#[derive(Debug, Serialize)]
+pub struct SearchMatch {
+    pub file: String,
+    pub line_number: usize,
+    pub line_text: String,
+}
+
+/// search 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct SearchOutput {
+    pub success: bool,
+    pub matches: Vec<SearchMatch>,
+    /// 是否因达到 max_results 而提前停止
+    pub truncated: bool,
+    pub error: Option<String>,
+}
+
+impl SearchOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            matches: Vec::new(),
+            truncated: false,
+            error: Some(error),
+        }
+    }
+}
+
+/// 行匹配器：正则或普通子串
+enum Matcher {
+    Regex(Regex),
+    Substring(String),
+}
+
+impl Matcher {
+    fn is_match(&self, line: &str) -> bool {
+        match self {
+            Matcher::Regex(re) => re.is_match(line),
+            Matcher::Substring(s) => line.contains(s.as_str()),
+        }
+    }
+}
+
+/// Search 工具实现
+pub struct SearchTool;
+
+impl Tool for SearchTool {
+    fn name(&self) -> &'static str {
+        "search"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "search",
+            "description": "Search for text in workspace files, line by line, and return each match as {file, line_number, line_text}. Use this to locate definitions or usages instead of reading files one by one. Binary files and .git/target/node_modules directories are skipped.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "The text to search for (a regular expression when is_regex is true)"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "A file or directory to search, relative to the workspace (default: the whole workspace)"
+                    },
+                    "is_regex": {
+                        "type": "boolean",
+                        "description": "Treat pattern as a regular expression (default false)"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Maximum number of matches to return (default 100)"
+                    }
+                },
+                "required": ["pattern"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: SearchInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&SearchOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = execute_search(ctx, &tool_input);
+        to_json(&result)
+    }
+}
+
+/// 截断过长的行
+fn truncate_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// 执行搜索
+fn execute_search(ctx: &ToolContext, input: &SearchInput) -> SearchOutput {
+    if input.pattern.is_empty() {
+        return SearchOutput::failure("pattern must not be empty".to_string());
+    }
+
+    let matcher = if input.is_regex {
+        match Regex::new(&input.pattern) {
+            Ok(re) => Matcher::Regex(re),
+            Err(e) => return SearchOutput::failure(format!("Invalid regex: {}", e)),
+        }
+    } else {
+        Matcher::Substring(input.pattern.clone())
+    };
+
+    let validator = ctx.validator();
+
+    // 安全检查：验证搜索起点
+    let start = input.path.as_deref().unwrap_or(".");
+    let start_path = match validator.validate_for_read(start) {
+        Ok(p) => p,
+        Err(e) => return SearchOutput::failure(e.to_string()),
+    };
+
+    let root = ctx.workspace_root();
+    let files = if start_path.is_dir() {
+        let relative_dir = start.trim_start_matches("./").trim_end_matches('/');
+        let relative_dir = if relative_dir == "." {
+            ""
+        } else {
+            relative_dir
+        };
+
+        let mut files = Vec::new();
+        validator.walk(relative_dir, &mut |file| files.push(file.to_string()));
+        files.sort();
+        files
+    } else {
+        vec![start.to_string()]
+    };
+
+    let max_results = input.max_results.unwrap_or(DEFAULT_MAX_RESULTS) as usize;
+    let mut matches = Vec::new();
+
+    for file in files {
+        let Ok(bytes) = fs::read(root.join(&file)) else {
+            continue;
+        };
+        // 跳过二进制文件
+        if bytes.contains(&0) {
+            continue;
+        }
+        let text = String::from_utf8_lossy(&bytes);
+
+        for (index, line) in text.lines().enumerate() {
+            if !matcher.is_match(line) {
+                continue;
+            }
+            if matches.len() >= max_results {
+                return SearchOutput {
+                    success: true,
+                    matches,
+                    truncated: true,
+                    error: None,
+                };
+            }
+            matches.push(SearchMatch {
+                file: file.clone(),
+                line_number: index + 1,
+                line_text: truncate_line(line),
+            });
+        }
+    }
+
+    SearchOutput {
+        success: true,
+        matches,
+        truncated: false,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+
+    fn run(input: Value) -> Value {
+        serde_json::from_str(&SearchTool.execute(&test_context(), &input)).unwrap()
+    }
+
+    fn matched_files(result: &Value) -> Vec<String> {
+        result["matches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["file"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_search_substring_across_tool_files() {
+        let result = run(serde_json::json!({"pattern": "fn execute", "path": "src/tools"}));
+        assert_eq!(result["success"], true);
+
+        let files = matched_files(&result);
+        assert!(files.contains(&"src/tools/read_file.rs".to_string()));
+        assert!(files.contains(&"src/tools/write_file.rs".to_string()));
+        assert!(files.contains(&"src/tools/search.rs".to_string()));
+
+        let hit = &result["matches"][0];
+        assert!(hit["line_number"].as_u64().unwrap() > 0);
+        assert!(hit["line_text"].as_str().unwrap().contains("fn execute"));
+    }
+
+    #[test]
+    fn test_search_regex() {
+        let result = run(serde_json::json!({
+            "pattern": r"fn execute_\w+_file\(",
+            "path": "src/tools",
+            "is_regex": true
+        }));
+        let files = matched_files(&result);
+        assert!(files.contains(&"src/tools/read_file.rs".to_string()));
+        assert!(files.contains(&"src/tools/edit_file.rs".to_string()));
+    }
+
+    #[test]
+    fn test_search_respects_max_results() {
+        let result = run(serde_json::json!({
+            "pattern": "fn ",
+            "path": "src",
+            "max_results": 2
+        }));
+        assert_eq!(result["matches"].as_array().unwrap().len(), 2);
+        assert_eq!(result["truncated"], true);
+    }
+
+    #[test]
+    fn test_search_skips_binary_files() {
+        let dir = "target/test_search_binary";
+        fs::create_dir_all(dir).unwrap();
+        fs::write(format!("{}/data.bin", dir), b"needle\x00\x01").unwrap();
+        fs::write(format!("{}/text.txt", dir), "a needle here\n").unwrap();
+
+        let result = run(serde_json::json!({"pattern": "needle", "path": dir}));
+        assert_eq!(matched_files(&result), vec![format!("{}/text.txt", dir)]);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_invalid_regex_and_traversal_rejected() {
+        let result = run(serde_json::json!({"pattern": "(", "is_regex": true}));
+        assert_eq!(result["success"], false);
+
+        let result = run(serde_json::json!({"pattern": "x", "path": "../"}));
+        assert_eq!(result["success"], false);
+    }
+}
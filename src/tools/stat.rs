@@ -0,0 +1,157 @@
+//! stat 工具 - 查询路径是否存在及其类型、大小
+
+use super::{to_json, Tool, ToolContext};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io::ErrorKind;
+
+/// stat 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct StatInput {
+    pub path: String,
+}
+
+/// stat 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct StatOutput {
+    pub success: bool,
+    pub exists: bool,
+    pub is_dir: bool,
+    pub is_file: bool,
+    /// 文件大小（字节），路径不存在时为 `None`
+    pub size_bytes: Option<u64>,
+    /// 是否只读，路径不存在时为 `None`
+    pub readonly: Option<bool>,
+    pub error: Option<String>,
+}
+
+impl StatOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            exists: false,
+            is_dir: false,
+            is_file: false,
+            size_bytes: None,
+            readonly: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Stat 工具实现
+pub struct StatTool;
+
+impl Tool for StatTool {
+    fn name(&self) -> &'static str {
+        "stat"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "stat",
+            "description": "Check whether a path exists without reading it. Returns exists, is_dir, is_file, size_bytes and readonly. Use this before write_file to avoid overwriting an existing file.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The path to check (relative to the workspace)"
+                    }
+                },
+                "required": ["path"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: StatInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&StatOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = execute_stat(ctx, &tool_input);
+        to_json(&result)
+    }
+}
+
+/// 执行路径查询
+fn execute_stat(ctx: &ToolContext, input: &StatInput) -> StatOutput {
+    // 安全检查：路径必须在工作区内，但不要求存在
+    let validated_path = match ctx.validator().validate_for_lookup(&input.path) {
+        Ok(p) => p,
+        Err(e) => return StatOutput::failure(e.to_string()),
+    };
+
+    match fs::metadata(&validated_path) {
+        Ok(metadata) => StatOutput {
+            success: true,
+            exists: true,
+            is_dir: metadata.is_dir(),
+            is_file: metadata.is_file(),
+            size_bytes: Some(metadata.len()),
+            readonly: Some(metadata.permissions().readonly()),
+            error: None,
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => StatOutput {
+            success: true,
+            exists: false,
+            is_dir: false,
+            is_file: false,
+            size_bytes: None,
+            readonly: None,
+            error: None,
+        },
+        Err(e) => StatOutput::failure(format!("Failed to stat path: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+
+    fn stat(path: &str) -> Value {
+        let input = serde_json::json!({ "path": path });
+        serde_json::from_str(&StatTool.execute(&test_context(), &input)).unwrap()
+    }
+
+    #[test]
+    fn test_existing_file() {
+        let result = stat("Cargo.toml");
+        assert_eq!(result["success"], true);
+        assert_eq!(result["exists"], true);
+        assert_eq!(result["is_file"], true);
+        assert_eq!(result["is_dir"], false);
+        assert_eq!(
+            result["size_bytes"],
+            fs::metadata("Cargo.toml").unwrap().len()
+        );
+        assert_eq!(result["readonly"], false);
+    }
+
+    #[test]
+    fn test_directory() {
+        let result = stat("src");
+        assert_eq!(result["exists"], true);
+        assert_eq!(result["is_dir"], true);
+        assert_eq!(result["is_file"], false);
+    }
+
+    #[test]
+    fn test_missing_path() {
+        let result = stat("nonexistent_file_12345.txt");
+        assert_eq!(result["success"], true);
+        assert_eq!(result["exists"], false);
+        assert_eq!(result["is_file"], false);
+        assert!(result["size_bytes"].is_null());
+    }
+
+    #[test]
+    fn test_path_traversal_blocked() {
+        let result = stat("../outside.txt");
+        assert_eq!(result["success"], false);
+        assert!(result["error"].as_str().unwrap().contains("traversal"));
+    }
+}
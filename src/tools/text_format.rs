@@ -0,0 +1,311 @@
+//! text_format 工具 - 检查并规范化换行符、编码和文件末尾换行
+
+use super::glob;
+use super::path_validator::PathValidator;
+use super::{to_json, Tool, ToolContext};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+
+/// UTF-8 BOM
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// 判断二进制文件时检查的字节数
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// text_format 工具的输入参数
+#[derive(Debug, Deserialize)]
+pub struct TextFormatInput {
+    pub path: String,
+    #[serde(default)]
+    pub fix: bool,
+}
+
+/// 单个文件的检查结果
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FileReport {
+    pub file_path: String,
+    /// `lf`、`crlf`、`mixed` 或 `none`（没有换行）
+    pub line_ending: &'static str,
+    /// `utf-8`、`utf-8-bom`、`utf-16le`、`utf-16be` 或 `unknown`
+    pub encoding: &'static str,
+    pub trailing_newline: bool,
+    /// 是否已被规范化（仅 `fix` 时可能为 true）
+    pub fixed: bool,
+}
+
+/// text_format 工具的输出结果
+#[derive(Debug, Serialize)]
+pub struct TextFormatOutput {
+    pub success: bool,
+    pub files: Vec<FileReport>,
+    /// 跳过的二进制文件
+    pub skipped: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl TextFormatOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            files: Vec::new(),
+            skipped: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
+
+/// TextFormat 工具实现
+pub struct TextFormatTool;
+
+impl Tool for TextFormatTool {
+    fn name(&self) -> &'static str {
+        "text_format"
+    }
+
+    fn definition(&self) -> Value {
+        serde_json::json!({
+            "name": "text_format",
+            "description": "Report line-ending style (LF/CRLF/mixed), encoding (UTF-8, UTF-8 with BOM, UTF-16, unknown) and trailing-newline status for a file or glob pattern (e.g. \"src/**/*.rs\"). Binary files are skipped. With fix=true, UTF-8 files are normalized to LF line endings, no BOM, and a final newline.",
+            "input_schema": {
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "A file path or glob pattern relative to the workspace (supports *, ? and **)"
+                    },
+                    "fix": {
+                        "type": "boolean",
+                        "description": "Normalize the files in place (default false)"
+                    }
+                },
+                "required": ["path"]
+            }
+        })
+    }
+
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
+        let tool_input: TextFormatInput = match serde_json::from_value(input.clone()) {
+            Ok(input) => input,
+            Err(e) => return to_json(&TextFormatOutput::failure(format!("Invalid input: {}", e))),
+        };
+
+        let result = execute_text_format(ctx, &tool_input);
+        to_json(&result)
+    }
+
+    // fix 模式会就地改写文件，无法事先区分，统一按修改类工具处理
+    fn is_mutating(&self) -> bool {
+        true
+    }
+}
+
+/// 检测编码
+fn detect_encoding(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(UTF8_BOM) {
+        "utf-8-bom"
+    } else if bytes.starts_with(b"\xFF\xFE") {
+        "utf-16le"
+    } else if bytes.starts_with(b"\xFE\xFF") {
+        "utf-16be"
+    } else if std::str::from_utf8(bytes).is_ok() {
+        "utf-8"
+    } else {
+        "unknown"
+    }
+}
+
+/// 检测换行符风格
+fn detect_line_ending(text: &str) -> &'static str {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+
+    match (lf, crlf) {
+        (0, 0) => "none",
+        (_, 0) => "lf",
+        (0, _) => "crlf",
+        _ => "mixed",
+    }
+}
+
+/// 是否为二进制文件（UTF-16 带 BOM 的文件除外）
+fn is_binary(bytes: &[u8]) -> bool {
+    let encoding = detect_encoding(bytes);
+    if encoding.starts_with("utf-16") {
+        return false;
+    }
+    bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// 规范化文本：LF 换行、去掉 BOM、以换行结尾（空文件保持为空）
+fn normalize(text: &str) -> String {
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    let mut normalized = text.replace("\r\n", "\n");
+    if !normalized.is_empty() && !normalized.ends_with('\n') {
+        normalized.push('\n');
+    }
+    normalized
+}
+
+/// 检查单个文件；`fix` 时对 UTF-8 文件就地规范化
+fn inspect(
+    validator: &PathValidator,
+    file_path: &str,
+    bytes: &[u8],
+    fix: bool,
+) -> Result<FileReport, String> {
+    let encoding = detect_encoding(bytes);
+    let mut report = FileReport {
+        file_path: file_path.to_string(),
+        line_ending: "none",
+        encoding,
+        trailing_newline: bytes.ends_with(b"\n"),
+        fixed: false,
+    };
+
+    // 只对 UTF-8 文件分析换行和规范化，UTF-16 等编码原样报告
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return Ok(report);
+    };
+    report.line_ending = detect_line_ending(text);
+
+    if fix {
+        let normalized = normalize(text);
+        if normalized != text {
+            let path = validator
+                .validate_for_write(file_path)
+                .map_err(|e| e.to_string())?;
+            fs::write(&path, &normalized)
+                .map_err(|e| format!("Failed to write {}: {}", file_path, e))?;
+            report.fixed = true;
+        }
+    }
+
+    Ok(report)
+}
+
+/// 执行检查
+fn execute_text_format(ctx: &ToolContext, input: &TextFormatInput) -> TextFormatOutput {
+    let validator = ctx.validator();
+
+    let candidates = if glob::is_glob(&input.path) {
+        validator.expand(&input.path)
+    } else {
+        vec![input.path.clone()]
+    };
+
+    if candidates.is_empty() {
+        return TextFormatOutput::failure(format!("No files match: {}", input.path));
+    }
+
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+
+    for file_path in candidates {
+        // 安全检查：验证路径
+        let validated_path = match validator.validate_for_read(&file_path) {
+            Ok(p) => p,
+            Err(e) => return TextFormatOutput::failure(format!("{}: {}", file_path, e)),
+        };
+        let bytes = match fs::read(&validated_path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                return TextFormatOutput::failure(format!("Failed to read {}: {}", file_path, e))
+            }
+        };
+
+        if is_binary(&bytes) {
+            skipped.push(file_path);
+            continue;
+        }
+
+        match inspect(validator, &file_path, &bytes, input.fix) {
+            Ok(report) => files.push(report),
+            Err(e) => return TextFormatOutput::failure(e),
+        }
+    }
+
+    TextFormatOutput {
+        success: true,
+        files,
+        skipped,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::test_context;
+
+    const FIXTURE_DIR: &str = "target/test_text_format";
+
+    fn write_fixture(name: &str, bytes: &[u8]) -> String {
+        let dir = format!("{}/{}", FIXTURE_DIR, name);
+        fs::create_dir_all(&dir).unwrap();
+        let path = format!("{}/file.txt", dir);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn run(input: Value) -> Value {
+        serde_json::from_str(&TextFormatTool.execute(&test_context(), &input)).unwrap()
+    }
+
+    #[test]
+    fn test_detect_line_ending() {
+        assert_eq!(detect_line_ending("a\nb\n"), "lf");
+        assert_eq!(detect_line_ending("a\r\nb\r\n"), "crlf");
+        assert_eq!(detect_line_ending("a\r\nb\n"), "mixed");
+        assert_eq!(detect_line_ending("a"), "none");
+    }
+
+    #[test]
+    fn test_report_crlf_with_bom() {
+        let path = write_fixture("report", b"\xEF\xBB\xBFline1\r\nline2");
+
+        let result = run(serde_json::json!({"path": path}));
+        assert_eq!(result["success"], true);
+        let report = &result["files"][0];
+        assert_eq!(report["line_ending"], "crlf");
+        assert_eq!(report["encoding"], "utf-8-bom");
+        assert_eq!(report["trailing_newline"], false);
+        assert_eq!(report["fixed"], false);
+        // 未设置 fix 时文件不变
+        assert_eq!(fs::read(&path).unwrap(), b"\xEF\xBB\xBFline1\r\nline2");
+    }
+
+    #[test]
+    fn test_fix_normalizes_file() {
+        let path = write_fixture("fix", b"\xEF\xBB\xBFline1\r\nline2\nline3");
+
+        let result = run(serde_json::json!({"path": path, "fix": true}));
+        assert_eq!(result["files"][0]["line_ending"], "mixed");
+        assert_eq!(result["files"][0]["fixed"], true);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line1\nline2\nline3\n");
+
+        // 已规范化的文件不会再次写入
+        let result = run(serde_json::json!({"path": path, "fix": true}));
+        assert_eq!(result["files"][0]["fixed"], false);
+    }
+
+    #[test]
+    fn test_glob_skips_binary_files() {
+        write_fixture("glob/text", b"plain\n");
+        write_fixture("glob/binary", b"\x00\x01\x02binary");
+
+        let result = run(serde_json::json!({"path": format!("{}/glob/**/*.txt", FIXTURE_DIR)}));
+        assert_eq!(result["files"].as_array().unwrap().len(), 1);
+        assert_eq!(result["files"][0]["line_ending"], "lf");
+        assert_eq!(
+            result["skipped"],
+            serde_json::json!([format!("{}/glob/binary/file.txt", FIXTURE_DIR)])
+        );
+    }
+
+    #[test]
+    fn test_path_traversal_blocked() {
+        let result = run(serde_json::json!({"path": "../etc/passwd"}));
+        assert_eq!(result["success"], false);
+    }
+}
@@ -1,11 +1,18 @@
 //! write_file 工具 - 写入文件内容
 
-use super::path_validator::PathValidator;
-use super::Tool;
+use super::{to_json, Tool, ToolContext};
+use crate::staging::render_unified_diff;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use similar::{ChangeTag, TextDiff};
 use std::fs;
 
+/// 返回的 diff 的最大字节数，超过时只给出增删行数
+const MAX_DIFF_BYTES: usize = 8 * 1024;
+
+/// 新旧内容合计超过该字节数时不计算 diff，只比较行数
+const MAX_DIFF_INPUT_BYTES: usize = 1024 * 1024;
+
 /// write_file 工具的输入参数
 #[derive(Debug, Deserialize)]
 pub struct WriteFileInput {
@@ -18,9 +25,23 @@ pub struct WriteFileInput {
 pub struct WriteFileOutput {
     pub success: bool,
     pub message: Option<String>,
+    /// 相对原有内容的 unified diff（覆盖已有文件或 dry-run 时提供）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
     pub error: Option<String>,
 }
 
+impl WriteFileOutput {
+    fn failure(error: String) -> Self {
+        Self {
+            success: false,
+            message: None,
+            diff: None,
+            error: Some(error),
+        }
+    }
+}
+
 /// WriteFile 工具实现
 pub struct WriteFileTool;
 
@@ -38,7 +59,7 @@ impl Tool for WriteFileTool {
                 "properties": {
                     "file_path": {
                         "type": "string",
-                        "description": "The path to the file to write (relative or absolute)"
+                        "description": "The path to the file to write (relative to the workspace)"
                     },
                     "content": {
                         "type": "string",
@@ -50,85 +71,204 @@ impl Tool for WriteFileTool {
         })
     }
 
-    fn execute(&self, input: &Value) -> String {
+    fn execute(&self, ctx: &ToolContext, input: &Value) -> String {
         let tool_input: WriteFileInput = match serde_json::from_value(input.clone()) {
             Ok(input) => input,
-            Err(e) => {
-                return serde_json::to_string(&WriteFileOutput {
-                    success: false,
-                    message: None,
-                    error: Some(format!("Invalid input: {}", e)),
-                })
-                .unwrap()
-            }
+            Err(e) => return to_json(&WriteFileOutput::failure(format!("Invalid input: {}", e))),
         };
 
-        let result = execute_write_file(&tool_input);
-        serde_json::to_string(&result).unwrap()
+        let result = execute_write_file(ctx, &tool_input);
+        to_json(&result)
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn supports_dry_run(&self) -> bool {
+        true
     }
 }
 
 /// 执行文件写入
-fn execute_write_file(input: &WriteFileInput) -> WriteFileOutput {
-    // 创建路径验证器
-    let validator = match PathValidator::new() {
-        Ok(v) => v,
-        Err(e) => {
-            return WriteFileOutput {
-                success: false,
-                message: None,
-                error: Some(format!("Failed to initialize path validator: {}", e)),
-            };
-        }
-    };
+fn execute_write_file(ctx: &ToolContext, input: &WriteFileInput) -> WriteFileOutput {
+    let validator = ctx.validator();
 
     // 安全检查：验证路径
     let validated_path = match validator.validate_for_write(&input.file_path) {
         Ok(p) => p,
-        Err(e) => {
-            return WriteFileOutput {
-                success: false,
-                message: None,
-                error: Some(e.to_string()),
-            };
-        }
+        Err(e) => return WriteFileOutput::failure(e.to_string()),
     };
 
+    // 覆盖已有文件时先读取旧内容，用于生成 diff；非文本文件不做比较
+    let original = fs::read_to_string(&validated_path).ok();
+    let change = original
+        .as_deref()
+        .map(|old| describe_change(old, &input.content, &input.file_path));
+
+    // dry-run：只报告将要写入的内容
+    if ctx.is_dry_run() {
+        let (summary, diff) = change.unwrap_or_else(|| {
+            let (_, diff) = describe_change("", &input.content, &input.file_path);
+            ("new file".to_string(), diff)
+        });
+        return WriteFileOutput {
+            success: true,
+            message: Some(format!(
+                "[DRY RUN] Would write {} bytes to {} ({}); nothing was written",
+                input.content.len(),
+                input.file_path,
+                summary
+            )),
+            diff,
+            error: None,
+        };
+    }
+
     // 确保父目录存在
     if let Some(parent) = validated_path.parent() {
         if !parent.as_os_str().is_empty() {
             if let Err(e) = fs::create_dir_all(parent) {
-                return WriteFileOutput {
-                    success: false,
-                    message: None,
-                    error: Some(format!("Failed to create directory: {}", e)),
-                };
+                return WriteFileOutput::failure(format!("Failed to create directory: {}", e));
             }
         }
     }
 
     // 写入文件
     match fs::write(&validated_path, &input.content) {
-        Ok(()) => WriteFileOutput {
-            success: true,
-            message: Some(format!(
-                "Successfully wrote {} bytes to {}",
-                input.content.len(),
-                input.file_path
-            )),
-            error: None,
-        },
-        Err(e) => WriteFileOutput {
-            success: false,
-            message: None,
-            error: Some(format!("Failed to write file: {}", e)),
-        },
+        Ok(()) => {
+            let (message, diff) = match change {
+                Some((summary, diff)) => (
+                    format!(
+                        "Successfully wrote {} bytes to {} (overwrote existing file: {})",
+                        input.content.len(),
+                        input.file_path,
+                        summary
+                    ),
+                    diff,
+                ),
+                None => (
+                    format!(
+                        "Successfully wrote {} bytes to {}",
+                        input.content.len(),
+                        input.file_path
+                    ),
+                    None,
+                ),
+            };
+            WriteFileOutput {
+                success: true,
+                message: Some(message),
+                diff,
+                error: None,
+            }
+        }
+        Err(e) => WriteFileOutput::failure(format!("Failed to write file: {}", e)),
+    }
+}
+
+/// 概括新旧内容的差异，返回 `(摘要, unified diff)`
+///
+/// 摘要形如 `+3/-1 lines`；新旧内容过大时只比较行数，
+/// diff 超过 [`MAX_DIFF_BYTES`] 时省略，只保留摘要。
+fn describe_change(original: &str, modified: &str, file_path: &str) -> (String, Option<String>) {
+    if original.len() + modified.len() > MAX_DIFF_INPUT_BYTES {
+        let summary = format!(
+            "{} -> {} lines, diff skipped for large file",
+            original.lines().count(),
+            modified.lines().count()
+        );
+        return (summary, None);
+    }
+
+    let (mut added, mut removed) = (0, 0);
+    for change in TextDiff::from_lines(original, modified).iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => added += 1,
+            ChangeTag::Delete => removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+
+    let diff = render_unified_diff(original, modified, file_path);
+    if diff.len() > MAX_DIFF_BYTES {
+        let summary = format!("+{}/-{} lines, diff too large to show", added, removed);
+        return (summary, None);
     }
+    (format!("+{}/-{} lines", added, removed), Some(diff))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tools::test_context;
+
+    #[test]
+    fn test_dry_run_reports_diff_without_writing() {
+        let test_path = "target/test_write_dry_run.txt";
+        fs::create_dir_all("target").unwrap();
+        fs::write(test_path, "old line\n").unwrap();
+
+        let mut ctx = test_context();
+        ctx.dry_run = true;
+        let input = serde_json::json!({"file_path": test_path, "content": "new line\n"});
+        let result: Value = serde_json::from_str(&WriteFileTool.execute(&ctx, &input)).unwrap();
+
+        assert_eq!(result["success"], true);
+        let message = result["message"].as_str().unwrap();
+        assert!(message.starts_with("[DRY RUN]"));
+        assert!(message.contains("9 bytes"));
+        let diff = result["diff"].as_str().unwrap();
+        assert!(diff.contains("-old line"));
+        assert!(diff.contains("+new line"));
+        assert_eq!(fs::read_to_string(test_path).unwrap(), "old line\n");
+
+        let _ = fs::remove_file(test_path);
+    }
+
+    #[test]
+    fn test_overwrite_reports_diff() {
+        let test_path = "target/test_write_overwrite_diff.txt";
+        fs::create_dir_all("target").unwrap();
+        fs::write(test_path, "fn main() {\n    old();\n}\n").unwrap();
+
+        let input = serde_json::json!({
+            "file_path": test_path,
+            "content": "fn main() {\n    new();\n}\n"
+        });
+        let result: Value =
+            serde_json::from_str(&WriteFileTool.execute(&test_context(), &input)).unwrap();
+
+        assert_eq!(result["success"], true);
+        assert!(result["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("(overwrote existing file: +1/-1 lines)"));
+        let diff = result["diff"].as_str().unwrap();
+        assert!(diff.contains("-    old();"));
+        assert!(diff.contains("+    new();"));
+        assert!(!diff.contains("-fn main"));
+        assert_eq!(
+            fs::read_to_string(test_path).unwrap(),
+            "fn main() {\n    new();\n}\n"
+        );
+
+        let _ = fs::remove_file(test_path);
+    }
+
+    #[test]
+    fn test_large_diff_falls_back_to_line_counts() {
+        let original: String = (0..2000).map(|i| format!("old {}\n", i)).collect();
+        let modified: String = (0..2000).map(|i| format!("new {}\n", i)).collect();
+        let (summary, diff) = describe_change(&original, &modified, "big.txt");
+        assert_eq!(summary, "+2000/-2000 lines, diff too large to show");
+        assert!(diff.is_none());
+
+        let huge = "x\n".repeat(MAX_DIFF_INPUT_BYTES);
+        let (summary, diff) = describe_change(&huge, "x\n", "huge.txt");
+        assert!(summary.starts_with(&format!("{} -> 1 lines", MAX_DIFF_INPUT_BYTES)));
+        assert!(diff.is_none());
+    }
 
     #[test]
     fn test_write_new_file() {
@@ -138,7 +278,7 @@ mod tests {
             "file_path": test_path,
             "content": "Hello, World!"
         });
-        let result = tool.execute(&input);
+        let result = tool.execute(&test_context(), &input);
         assert!(result.contains("\"success\":true"));
 
         // 验证内容
@@ -149,6 +289,55 @@ mod tests {
         let _ = fs::remove_file(test_path);
     }
 
+    #[test]
+    fn test_write_nested_relative_path() {
+        let tool = WriteFileTool;
+        let test_path = "target/test_write_nested/sub/dir/file.txt";
+        let input = serde_json::json!({
+            "file_path": test_path,
+            "content": "nested"
+        });
+        let result = tool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":true"));
+        assert_eq!(fs::read_to_string(test_path).unwrap(), "nested");
+
+        let _ = fs::remove_dir_all("target/test_write_nested");
+    }
+
+    #[test]
+    fn test_double_dot_in_file_name_allowed() {
+        let tool = WriteFileTool;
+        let test_path = "target/my..notes.txt";
+        let input = serde_json::json!({
+            "file_path": test_path,
+            "content": "notes"
+        });
+        let result = tool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":true"));
+
+        let _ = fs::remove_file(test_path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escape_blocked() {
+        let link = "target/test_write_symlink_escape";
+        let _ = fs::remove_file(link);
+        fs::create_dir_all("target").unwrap();
+        std::os::unix::fs::symlink(std::env::temp_dir(), link).unwrap();
+
+        let tool = WriteFileTool;
+        let input = serde_json::json!({
+            "file_path": format!("{}/evil.txt", link),
+            "content": "malicious"
+        });
+        let result = tool.execute(&test_context(), &input);
+        assert!(result.contains("\"success\":false"));
+        assert!(!std::env::temp_dir().join("evil.txt").exists());
+
+        let _ = fs::remove_file(link);
+    }
+
     #[test]
     fn test_path_traversal_blocked() {
         let tool = WriteFileTool;
@@ -156,7 +345,7 @@ mod tests {
             "file_path": "../etc/test",
             "content": "malicious"
         });
-        let result = tool.execute(&input);
+        let result = tool.execute(&test_context(), &input);
         assert!(result.contains("traversal") || result.contains("not allowed"));
     }
 
@@ -164,10 +353,10 @@ mod tests {
     fn test_absolute_path_blocked() {
         let tool = WriteFileTool;
         let input = serde_json::json!({
-            "file_path": "/etc/test",
+            "file_path": "/etc/passwd",
             "content": "malicious"
         });
-        let result = tool.execute(&input);
+        let result = tool.execute(&test_context(), &input);
         assert!(result.contains("Absolute") || result.contains("not allowed"));
     }
 
@@ -178,7 +367,7 @@ mod tests {
             "file_path": "src/../../../etc/test",
             "content": "malicious"
         });
-        let result = tool.execute(&input);
+        let result = tool.execute(&test_context(), &input);
         assert!(result.contains("traversal") || result.contains("not allowed"));
     }
 }
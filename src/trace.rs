@@ -0,0 +1,298 @@
+//! 工具调用链追踪
+//!
+//! 记录会话中每一轮对话的 API 往返和工具调用（输入/输出摘要、耗时），
+//! 通过 `/trace export <file>` 导出为 JSON，供可视化工具使用。
+//! 记录时即对敏感字段脱敏、对过长字段截断，内存中不保留原始内容。
+
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 字符串字段保留的最大字符数
+const MAX_FIELD_CHARS: usize = 500;
+
+/// 脱敏后的占位符
+const REDACTED: &str = "[REDACTED]";
+
+/// 键名包含这些片段（不区分大小写）时整体脱敏
+const SENSITIVE_KEYS: &[&str] = &[
+    "api_key",
+    "apikey",
+    "token",
+    "password",
+    "secret",
+    "authorization",
+];
+
+/// 一次工具调用
+#[derive(Debug, Serialize)]
+pub struct ToolCallTrace {
+    pub id: String,
+    pub name: String,
+    pub input: Value,
+    pub output: Value,
+    pub duration_ms: u64,
+}
+
+/// 一次 API 往返（模型的一条回复）及其触发的工具调用
+#[derive(Debug, Serialize)]
+pub struct StepTrace {
+    pub api_duration_ms: u64,
+    pub stop_reason: Option<String>,
+    pub tool_calls: Vec<ToolCallTrace>,
+}
+
+/// 一轮对话（一次用户输入）
+#[derive(Debug, Serialize)]
+pub struct TurnTrace {
+    pub index: usize,
+    pub prompt: Value,
+    /// 开始时间（Unix 毫秒）
+    pub started_at_ms: u64,
+    pub duration_ms: u64,
+    pub steps: Vec<StepTrace>,
+    pub error: Option<String>,
+}
+
+/// 追踪记录器
+#[derive(Debug, Default)]
+pub struct TraceRecorder {
+    /// 需要从记录中抹去的敏感字符串（如 API 密钥）
+    secrets: Vec<String>,
+    turns: Vec<TurnTrace>,
+}
+
+/// 导出的文档
+#[derive(Serialize)]
+struct TraceDocument<'a> {
+    version: u32,
+    model: &'a str,
+    turns: &'a [TurnTrace],
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis().min(u64::MAX as u128) as u64
+}
+
+impl TraceRecorder {
+    /// 创建记录器，`secrets` 中的字符串出现在任何字段里都会被替换
+    pub fn new(secrets: Vec<String>) -> Self {
+        Self {
+            secrets: secrets.into_iter().filter(|s| !s.is_empty()).collect(),
+            turns: Vec::new(),
+        }
+    }
+
+    /// 开始新的一轮
+    pub fn begin_turn(&mut self, prompt: &str) {
+        let started_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(millis)
+            .unwrap_or(0);
+        let prompt = self.sanitize(Value::String(prompt.to_string()));
+        self.turns.push(TurnTrace {
+            index: self.turns.len(),
+            prompt,
+            started_at_ms,
+            duration_ms: 0,
+            steps: Vec::new(),
+            error: None,
+        });
+    }
+
+    /// 记录当前轮的一次 API 往返
+    pub fn record_step(&mut self, api_duration: Duration, stop_reason: Option<&str>) {
+        if let Some(turn) = self.turns.last_mut() {
+            turn.steps.push(StepTrace {
+                api_duration_ms: millis(api_duration),
+                stop_reason: stop_reason.map(str::to_string),
+                tool_calls: Vec::new(),
+            });
+        }
+    }
+
+    /// 记录当前步骤中的一次工具调用
+    ///
+    /// `output` 是工具返回的 JSON 字符串，无法解析时按普通字符串记录。
+    pub fn record_tool_call(
+        &mut self,
+        id: &str,
+        name: &str,
+        input: &Value,
+        output: &str,
+        duration: Duration,
+    ) {
+        let input = self.sanitize(input.clone());
+        let output = self.sanitize(
+            serde_json::from_str(output).unwrap_or_else(|_| Value::String(output.to_string())),
+        );
+        if let Some(step) = self.turns.last_mut().and_then(|t| t.steps.last_mut()) {
+            step.tool_calls.push(ToolCallTrace {
+                id: id.to_string(),
+                name: name.to_string(),
+                input,
+                output,
+                duration_ms: millis(duration),
+            });
+        }
+    }
+
+    /// 结束当前轮
+    pub fn end_turn(&mut self, duration: Duration, error: Option<String>) {
+        let error = error.map(|e| match self.sanitize(Value::String(e)) {
+            Value::String(e) => e,
+            other => other.to_string(),
+        });
+        if let Some(turn) = self.turns.last_mut() {
+            turn.duration_ms = millis(duration);
+            turn.error = error;
+        }
+    }
+
+    /// 已记录的轮数
+    pub fn len(&self) -> usize {
+        self.turns.len()
+    }
+
+    /// 清空记录
+    pub fn clear(&mut self) {
+        self.turns.clear();
+    }
+
+    /// 导出为 JSON 文件
+    pub fn export(&self, path: &Path, model: &str) -> std::io::Result<()> {
+        let document = TraceDocument {
+            version: 1,
+            model,
+            turns: &self.turns,
+        };
+        let json = serde_json::to_string_pretty(&document)?;
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, json)
+    }
+
+    /// 脱敏并截断
+    fn sanitize(&self, value: Value) -> Value {
+        match value {
+            Value::String(s) => {
+                let mut s = s;
+                for secret in &self.secrets {
+                    s = s.replace(secret.as_str(), REDACTED);
+                }
+                Value::String(truncate(&s))
+            }
+            Value::Array(items) => {
+                Value::Array(items.into_iter().map(|v| self.sanitize(v)).collect())
+            }
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(key, v)| {
+                        let lower = key.to_lowercase();
+                        if SENSITIVE_KEYS.iter().any(|k| lower.contains(k)) {
+                            (key, Value::String(REDACTED.to_string()))
+                        } else {
+                            (key, self.sanitize(v))
+                        }
+                    })
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+}
+
+/// 截断过长的字符串，并注明原始长度
+fn truncate(s: &str) -> String {
+    match s.char_indices().nth(MAX_FIELD_CHARS) {
+        Some((end, _)) => format!(
+            "{}...[truncated, {} chars total]",
+            &s[..end],
+            s.chars().count()
+        ),
+        None => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_records_turns_steps_and_tool_calls() {
+        let mut trace = TraceRecorder::new(Vec::new());
+        trace.begin_turn("read Cargo.toml");
+        trace.record_step(Duration::from_millis(120), Some("tool_use"));
+        trace.record_tool_call(
+            "t1",
+            "read_file",
+            &json!({"file_path": "Cargo.toml"}),
+            r#"{"success":true,"content":"[package]"}"#,
+            Duration::from_millis(3),
+        );
+        trace.record_step(Duration::from_millis(80), Some("end_turn"));
+        trace.end_turn(Duration::from_millis(210), None);
+
+        assert_eq!(trace.len(), 1);
+        let turn = serde_json::to_value(&trace.turns[0]).unwrap();
+        assert_eq!(turn["duration_ms"], 210);
+        assert_eq!(turn["steps"].as_array().unwrap().len(), 2);
+        let call = &turn["steps"][0]["tool_calls"][0];
+        assert_eq!(call["name"], "read_file");
+        assert_eq!(call["input"]["file_path"], "Cargo.toml");
+        // 工具输出按 JSON 结构记录
+        assert_eq!(call["output"]["success"], true);
+    }
+
+    #[test]
+    fn test_redacts_secrets_and_sensitive_keys() {
+        let mut trace = TraceRecorder::new(vec!["sk-secret-123".to_string()]);
+        trace.begin_turn("my key is sk-secret-123");
+        trace.record_step(Duration::ZERO, None);
+        trace.record_tool_call(
+            "t1",
+            "write_file",
+            &json!({"file_path": ".env", "content": "X=1", "api_token": "abc"}),
+            "{}",
+            Duration::ZERO,
+        );
+
+        let json = serde_json::to_string(&trace.turns).unwrap();
+        assert!(!json.contains("sk-secret-123"));
+        assert!(!json.contains("abc"));
+        assert!(json.contains(REDACTED));
+        assert!(json.contains("X=1"));
+    }
+
+    #[test]
+    fn test_caps_large_fields() {
+        let mut trace = TraceRecorder::new(Vec::new());
+        trace.begin_turn(&"x".repeat(2000));
+
+        let prompt = trace.turns[0].prompt.as_str().unwrap();
+        assert!(prompt.len() < 600);
+        assert!(prompt.ends_with("[truncated, 2000 chars total]"));
+    }
+
+    #[test]
+    fn test_export_writes_document() {
+        let path = std::env::current_dir()
+            .unwrap()
+            .join("target/test_trace_export/trace.json");
+        let mut trace = TraceRecorder::new(Vec::new());
+        trace.begin_turn("hi");
+        trace.end_turn(Duration::from_millis(5), Some("boom".to_string()));
+
+        trace.export(&path, "claude-test").unwrap();
+
+        let document: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(document["model"], "claude-test");
+        assert_eq!(document["turns"][0]["error"], "boom");
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,70 @@
+//! 工作区根目录发现
+//!
+//! 类似 git 查找 `.git` 的方式：从起始目录逐级向上查找标记文件/目录，
+//! 找到的第一个包含标记的目录即为工作区根目录。
+
+use std::path::{Path, PathBuf};
+
+/// 从 `start` 开始向上查找包含任一标记的目录
+///
+/// # Arguments
+/// * `start` - 起始目录（通常是当前工作目录）
+/// * `markers` - 标记文件或目录名，如 `.mentat`、`.git`
+///
+/// # Returns
+/// 找到的工作区根目录；没有任何祖先目录包含标记时返回 `None`
+pub fn discover_root(start: &Path, markers: &[String]) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| markers.iter().any(|marker| dir.join(marker).exists()))
+        .map(Path::to_path_buf)
+}
+
+/// 解析工作区根目录：找到标记则使用标记所在目录，否则回退到 `start`
+pub fn resolve_root(start: &Path, markers: &[String]) -> PathBuf {
+    discover_root(start, markers).unwrap_or_else(|| start.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn create_project(name: &str, marker: &str) -> PathBuf {
+        let root = std::env::current_dir().unwrap().join("target").join(name);
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::write(root.join(marker), "").unwrap();
+        root
+    }
+
+    #[test]
+    fn test_discover_root_from_nested_dir() {
+        let root = create_project("test_workspace_nested", ".test-workspace-marker");
+        let markers = vec![".test-workspace-marker".to_string()];
+
+        let found = discover_root(&root.join("a/b/c"), &markers);
+        assert_eq!(found, Some(root.clone()));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_discover_root_at_start_dir() {
+        let root = create_project("test_workspace_start", ".test-workspace-start");
+        let markers = vec![".test-workspace-start".to_string()];
+
+        assert_eq!(discover_root(&root, &markers), Some(root.clone()));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_resolve_root_falls_back_to_start() {
+        let start = std::env::current_dir().unwrap().join("src");
+        let markers = vec![".no-such-marker-12345".to_string()];
+
+        assert_eq!(discover_root(&start, &markers), None);
+        assert_eq!(resolve_root(&start, &markers), start);
+    }
+}